@@ -0,0 +1,265 @@
+//! A command line interface to construct, search and inspect the indexes.
+//! It's a crate of its own to keep its dependencies out of the library,
+//! install the `zearch` binary with `cargo install --path examples/cli`.
+//!
+//! ```text
+//! zearch build --input names.csv --column name --output names.zearch
+//! zearch search --index names.zearch --query "kefir" --limit 5 --ranking word,typo,exact
+//! zearch stats --index names.zearch
+//! zearch repl --index names.zearch
+//! ```
+
+use std::{
+    error::Error,
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+    time::Instant,
+};
+
+use clap::{Parser, Subcommand, ValueEnum};
+use zearch::{Index, IndexBuilder, RankingRule, Search};
+
+#[derive(Parser)]
+#[command(
+    name = "zearch",
+    about = "Construct, search and inspect zearch indexes"
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Construct an index from a text file with one document per line, a csv or a jsonl file.
+    Build {
+        #[arg(long)]
+        input: PathBuf,
+        #[arg(long)]
+        output: PathBuf,
+        /// Guessed from the extension of the input by default, a text file if it's unknown.
+        #[arg(long)]
+        format: Option<Format>,
+        /// The name or the position of the column of the csv containing the documents, the first one by default.
+        /// For a jsonl file it's the field of the objects, each line can also be a string.
+        #[arg(long)]
+        column: Option<String>,
+        /// Store the positions of the words, required by the proximity ranking rule.
+        #[arg(long)]
+        positions: bool,
+        /// Don't store the text of the documents, the searches only return their ids.
+        #[arg(long)]
+        without_documents: bool,
+    },
+    /// Search an index and print the ids and text of the documents found.
+    Search {
+        #[arg(long)]
+        index: PathBuf,
+        #[arg(long)]
+        query: String,
+        #[arg(long, default_value_t = 10)]
+        limit: usize,
+        /// The ranking rules separated by commas, the default ones if empty.
+        #[arg(long, value_delimiter = ',')]
+        ranking: Vec<Rule>,
+    },
+    /// Print the size of every part of the index and its most frequent words.
+    Stats {
+        #[arg(long)]
+        index: PathBuf,
+    },
+    /// Search an index interactively, one query per line.
+    Repl {
+        #[arg(long)]
+        index: PathBuf,
+        #[arg(long, default_value_t = 10)]
+        limit: usize,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Format {
+    Txt,
+    Csv,
+    Jsonl,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Rule {
+    Word,
+    Typo,
+    Exact,
+    Proximity,
+    /// By payload, the smallest first.
+    Sort,
+    /// By payload, the largest first.
+    SortDesc,
+}
+
+impl From<Rule> for RankingRule {
+    fn from(rule: Rule) -> Self {
+        match rule {
+            Rule::Word => RankingRule::Word,
+            Rule::Typo => RankingRule::Typo,
+            Rule::Exact => RankingRule::Exact,
+            Rule::Proximity => RankingRule::Proximity,
+            Rule::Sort => RankingRule::Sort { descending: false },
+            Rule::SortDesc => RankingRule::Sort { descending: true },
+        }
+    }
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    match Cli::parse().command {
+        Command::Build {
+            input,
+            output,
+            format,
+            column,
+            positions,
+            without_documents,
+        } => {
+            let now = Instant::now();
+            let format = format.unwrap_or_else(|| guess_format(&input));
+            let documents = read_documents(&input, format, column.as_deref())?;
+            let mut writer = BufWriter::new(File::create(&output)?);
+            IndexBuilder::new()
+                .with_positions(positions)
+                .with_documents(!without_documents)
+                .construct(&documents, &mut writer)?;
+            writer.flush()?;
+            println!(
+                "Indexed {} documents in {:.2?}",
+                documents.len(),
+                now.elapsed()
+            );
+        }
+        Command::Search {
+            index,
+            query,
+            limit,
+            ranking,
+        } => {
+            let bytes = std::fs::read(index)?;
+            let index = Index::from_bytes(&bytes)?;
+            let mut search = Search::new(&query);
+            search.with_limit(limit);
+            if !ranking.is_empty() {
+                search.with_ranking_rules(ranking.into_iter().map(RankingRule::from).collect());
+            }
+            print_results(&index, &search);
+        }
+        Command::Stats { index } => {
+            let bytes = std::fs::read(index)?;
+            let index = Index::from_bytes(&bytes)?;
+            println!("{} documents", index.document_count());
+            println!("{} words", index.word_count());
+            println!();
+            print!("{}", index.size_stats());
+            println!();
+
+            let mut words: Vec<(String, u64)> = index.words().collect();
+            words.sort_unstable_by(|(_, left), (_, right)| right.cmp(left));
+            println!("most frequent words:");
+            for (word, frequency) in words.iter().take(10) {
+                println!("{frequency:>10} {word}");
+            }
+        }
+        Command::Repl { index, limit } => {
+            let bytes = std::fs::read(index)?;
+            let index = Index::from_bytes(&bytes)?;
+            let stdin = std::io::stdin();
+            loop {
+                print!("> ");
+                std::io::stdout().flush()?;
+                let mut query = String::new();
+                if stdin.lock().read_line(&mut query)? == 0 {
+                    break;
+                }
+                let mut search = Search::new(query.trim());
+                search.with_limit(limit);
+                print_results(&index, &search);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn guess_format(path: &Path) -> Format {
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some("csv") => Format::Csv,
+        Some("jsonl" | "ndjson") => Format::Jsonl,
+        _ => Format::Txt,
+    }
+}
+
+fn read_documents(
+    path: &Path,
+    format: Format,
+    column: Option<&str>,
+) -> Result<Vec<String>, Box<dyn Error>> {
+    let mut documents = Vec::new();
+    match format {
+        Format::Txt => {
+            for line in BufReader::new(File::open(path)?).lines() {
+                let line = line?;
+                if !line.trim().is_empty() {
+                    documents.push(line);
+                }
+            }
+        }
+        Format::Csv => {
+            let mut reader = csv::Reader::from_path(path)?;
+            let position = match column {
+                None => 0,
+                Some(column) => match column.parse() {
+                    Ok(position) => position,
+                    Err(_) => reader
+                        .headers()?
+                        .iter()
+                        .position(|header| header == column)
+                        .ok_or_else(|| format!("no column named {column:?}"))?,
+                },
+            };
+            for record in reader.records() {
+                let record = record?;
+                documents.push(record.get(position).unwrap_or_default().to_string());
+            }
+        }
+        Format::Jsonl => {
+            for (line, text) in BufReader::new(File::open(path)?).lines().enumerate() {
+                let text = text?;
+                if text.trim().is_empty() {
+                    continue;
+                }
+                let value: serde_json::Value = serde_json::from_str(&text)?;
+                let document = match column {
+                    Some(column) => value.get(column),
+                    None => Some(&value),
+                };
+                let document = document
+                    .and_then(|document| document.as_str())
+                    .ok_or_else(|| format!("line {} has no text to index", line + 1))?;
+                documents.push(document.to_string());
+            }
+        }
+    }
+    Ok(documents)
+}
+
+fn print_results(index: &Index, search: &Search) {
+    let results = search.execute(index);
+    for hit in results.hits(index) {
+        match hit.text {
+            Some(text) => println!("{:>8} {text}", hit.id),
+            None => println!("{:>8}", hit.id),
+        }
+    }
+    println!(
+        "{} results out of {} candidates in {:.2?}",
+        results.ids.len(),
+        results.total_candidates,
+        results.processing_time
+    );
+}
@@ -4,18 +4,25 @@ fn main() {
     let dataset = std::fs::File::open("dataset.csv").unwrap();
     let mut database = std::fs::File::create("database.zearch").unwrap();
 
-    let mut reader = csv::ReaderBuilder::new()
-        .delimiter(b';')
-        .from_reader(dataset);
-    let mut city_names = Vec::new();
-
-    for result in reader.records() {
-        let record = result.unwrap();
-        dbg!(&record);
-        let name = record.get(1).unwrap();
-        city_names.push(name.to_string());
-    }
-
-    zearch::Index::construct(city_names.as_slice(), &mut database).unwrap();
+    zearch::IndexBuilder::new()
+        .with_progress(|progress| {
+            if let Some(total) = progress.total.filter(|total| *total > 0) {
+                let done = progress.processed * 20 / total;
+                eprint!(
+                    "\r{:<16} [{}{}] {}/{}",
+                    format!("{:?}", progress.phase),
+                    "#".repeat(done),
+                    " ".repeat(20 - done),
+                    progress.processed,
+                    total
+                );
+                if progress.processed == total {
+                    eprintln!();
+                }
+            }
+        })
+        // the city names are in the second column
+        .construct_from_csv(dataset, 1, b';', &mut database)
+        .unwrap();
     database.sync_all().unwrap();
 }
@@ -3,7 +3,6 @@ use egui::ScrollArea;
 pub struct TemplateApp {
     index: zearch::Index<'static>,
     query: String,
-    #[cfg(not(target_arch = "wasm32"))]
     processing_time: std::time::Duration,
     limit: usize,
 }
@@ -14,7 +13,6 @@ impl Default for TemplateApp {
         Self {
             index: zearch::Index::from_bytes(database).unwrap(),
             query: String::new(),
-            #[cfg(not(target_arch = "wasm32"))]
             processing_time: std::time::Duration::from_secs(0),
             limit: 10,
         }
@@ -38,7 +36,6 @@ impl eframe::App for TemplateApp {
                 changed |= ui.text_edit_singleline(&mut self.query).changed();
             });
 
-            #[cfg(not(target_arch = "wasm32"))]
             ui.label(format!(
                 "Processed the search in {:?}",
                 self.processing_time
@@ -49,22 +46,22 @@ impl eframe::App for TemplateApp {
 
             ui.separator();
 
-            #[cfg(not(target_arch = "wasm32"))]
-            let now = std::time::Instant::now();
-            let mut search = zearch::Search::new(&self.query);
-            let results = self.index.search(search.with_limit(self.limit));
+            let results = zearch::Search::new(&self.query)
+                .with_limit(self.limit)
+                .execute(&self.index);
 
             // Ideally we shouldn't run the search for every frame
             // but I have other stuff to do before optimizing that
-            #[cfg(not(target_arch = "wasm32"))]
             if changed {
-                self.processing_time = now.elapsed();
+                self.processing_time = results.processing_time;
             }
 
+            if results.exhausted && !self.query.is_empty() {
+                ui.label(format!("Only {} results", results.ids.len()));
+            }
             ScrollArea::vertical().show(ui, |ui| {
-                for result in results {
-                    let result = self.index.get_document(result).unwrap();
-                    ui.label(result);
+                for text in results.hits(&self.index).filter_map(|hit| hit.text) {
+                    ui.label(text);
                 }
             });
         });
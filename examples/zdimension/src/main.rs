@@ -38,12 +38,11 @@ fn main() {
             }
         }
 
-        let now = std::time::Instant::now();
-        let ret = index.search(&Search::new(&input));
+        let results = Search::new(&input).execute(&index);
 
-        println!("Found (in {:?}):", now.elapsed());
-        for id in ret {
-            println!("{}", index.get_document(id).unwrap());
+        println!("Found (in {:?}):", results.processing_time);
+        for hit in results.hits(&index) {
+            println!("{}", hit.text.unwrap());
         }
     }
 }
@@ -0,0 +1,20 @@
+//! Run with `cargo +nightly fuzz run from_bytes corpus/from_bytes seeds/from_bytes`,
+//! the seed is the index of the corpus of the `search` target.
+//! Loading arbitrary bytes must return an error, never panic, loop or allocate for nothing.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use zearch::Index;
+
+fuzz_target!(|data: &[u8]| {
+    // a random input is almost never going to have a valid checksum
+    // thus we also skip it to explore the rest of the parsing
+    if let Ok(index) = Index::from_bytes_unverified(data) {
+        let mut id = 0;
+        while index.get_document(id).is_some() {
+            index.get_payload(id);
+            id += 1;
+        }
+    }
+    let _ = Index::from_bytes(data);
+});
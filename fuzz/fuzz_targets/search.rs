@@ -0,0 +1,64 @@
+//! Run with `cargo +nightly fuzz run search corpus/search seeds/search`.
+//! Searching a small index with any query, limit and ranking rules must never panic
+//! nor return a document outside of the index.
+//!
+//! The first byte of the input is the limit, the second one the number of ranking rules,
+//! followed by a byte per ranking rule and the query itself.
+#![no_main]
+
+use std::sync::OnceLock;
+
+use libfuzzer_sys::fuzz_target;
+use zearch::{Index, RankingRule, Search};
+
+const CORPUS: &[&str] = &[
+    "Tamo le plus beau",
+    "kefir le bon petit chien",
+    "kefir le beau chien",
+    "tamo est très beau aussi",
+    "le plus beau c'est kefir",
+    "mais il est un peu con",
+    "le petit kefir",
+    "kefirounet se prends pour un poney",
+    "kefirounet a un gros nez",
+    "kefir est un demi poney",
+    "le double kef",
+    "les keftas c'est bon aussi",
+];
+
+static INDEX: OnceLock<Index<'static>> = OnceLock::new();
+
+fn ranking_rule(byte: u8) -> RankingRule {
+    match byte % 10 {
+        0 => RankingRule::Word,
+        1 => RankingRule::Typo,
+        2 => RankingRule::Exact,
+        3 => RankingRule::Proximity,
+        4 => RankingRule::Attribute,
+        5 => RankingRule::Boost,
+        6 => RankingRule::Sort { descending: false },
+        7 => RankingRule::Sort { descending: true },
+        8 => RankingRule::Position,
+        _ => RankingRule::WordCount,
+    }
+}
+
+fuzz_target!(|data: &[u8]| {
+    let [limit, rules, rest @ ..] = data else {
+        return;
+    };
+    let (rules, query) = rest.split_at((*rules as usize % 8).min(rest.len()));
+    let Ok(query) = std::str::from_utf8(query) else {
+        return;
+    };
+    let index = INDEX.get_or_init(|| Index::new_in_memory(CORPUS).unwrap());
+
+    let mut search = Search::new(query);
+    search
+        .with_limit(*limit as usize)
+        .with_ranking_rules(rules.iter().copied().map(ranking_rule).collect());
+    let ids = index.search(&search);
+    assert!(ids.len() <= *limit as usize);
+    assert!(ids.iter().all(|id| index.get_document(*id).is_some()));
+    assert_eq!(index.execute(&search).ids, ids);
+});
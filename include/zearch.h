@@ -0,0 +1,107 @@
+#ifndef ZEARCH_H
+#define ZEARCH_H
+
+/* Generated by cbindgen from src/ffi.rs with cbindgen.toml, don't edit it by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * Returned by every function, the variants after `InvalidPattern` are specific to the C interface.
+ * The other ones mirror [`Error`] and keep their value whatever the features.
+ */
+typedef enum ZearchError {
+  ZEARCH_ERROR_OK = 0,
+  ZEARCH_ERROR_IO,
+  ZEARCH_ERROR_FST_BUILD,
+  ZEARCH_ERROR_TOO_MANY_DOCUMENTS,
+  ZEARCH_ERROR_TOO_LARGE,
+  ZEARCH_ERROR_THREAD_POOL,
+  ZEARCH_ERROR_CANCELLED,
+  ZEARCH_ERROR_INCOMPATIBLE_INDEXES,
+  ZEARCH_ERROR_MISSING_DOCUMENTS,
+  ZEARCH_ERROR_INVALID_RECORD,
+  ZEARCH_ERROR_NOT_AN_INDEX,
+  ZEARCH_ERROR_UNSUPPORTED_VERSION,
+  ZEARCH_ERROR_UNSUPPORTED_NORMALIZER,
+  ZEARCH_ERROR_UNSUPPORTED_STEMMER,
+  ZEARCH_ERROR_UNSUPPORTED_COMPRESSION,
+  ZEARCH_ERROR_CHECKSUM_MISMATCH,
+  ZEARCH_ERROR_CORRUPTED,
+  ZEARCH_ERROR_CUSTOM_RANKING_RULE,
+  ZEARCH_ERROR_INVALID_WORD_WEIGHTS,
+  ZEARCH_ERROR_INVALID_PATTERN,
+  /**
+   * A pointer given to the function was null.
+   */
+  ZEARCH_ERROR_NULL_POINTER,
+  /**
+   * The query is not valid UTF-8.
+   */
+  ZEARCH_ERROR_INVALID_UTF8,
+  /**
+   * The document doesn't exist or was deleted.
+   */
+  ZEARCH_ERROR_NOT_FOUND,
+  /**
+   * The function panicked, it's a bug.
+   */
+  ZEARCH_ERROR_PANIC,
+} ZearchError;
+
+/**
+ * An index owning its bytes, created by [`zearch_index_from_bytes`] and freed by [`zearch_index_free`].
+ */
+typedef struct ZearchIndex ZearchIndex;
+
+/**
+ * Load an index written by `Index::construct` and verify its checksum.
+ * The bytes are copied, they can be freed once the function returns.
+ *
+ * # Safety
+ *
+ * `bytes` must point to `len` readable bytes. On success `*out` is an index that must be
+ * freed with [`zearch_index_free`], it's left untouched otherwise.
+ */
+ZearchError zearch_index_from_bytes(const uint8_t *bytes, size_t len, struct ZearchIndex **out);
+
+/**
+ * Free an index, the documents returned by [`zearch_get_document`] are freed with it.
+ *
+ * # Safety
+ *
+ * `index` must come from [`zearch_index_from_bytes`] and not be used afterward. It can be null.
+ */
+void zearch_index_free(struct ZearchIndex *index);
+
+/**
+ * Search the index and write the ids of up to `limit` documents in `out_ids`, the best ones first.
+ * `*out_len` is set to the number of ids written.
+ *
+ * # Safety
+ *
+ * `index` must be a valid index, `query` a null terminated string
+ * and `out_ids` must have room for `limit` ids.
+ */
+ZearchError zearch_search(const struct ZearchIndex *index,
+                          const char *query,
+                          uint32_t limit,
+                          uint32_t *out_ids,
+                          size_t *out_len);
+
+/**
+ * Set `*out_ptr` and `*out_len` to the UTF-8 text of the document, it's not null terminated.
+ * It's borrowed from the index and stays valid until the index is freed.
+ *
+ * # Safety
+ *
+ * `index` must be a valid index.
+ */
+ZearchError zearch_get_document(const struct ZearchIndex *index,
+                                uint32_t id,
+                                const char **out_ptr,
+                                size_t *out_len);
+
+#endif /* ZEARCH_H */
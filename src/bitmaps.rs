@@ -0,0 +1,264 @@
+//! The bitmaps of the index are only decoded when a word of the query matches them.
+//! Decoding all of them when loading a large index would take hundreds of MB and
+//! several seconds while a query only touches a handful of words.
+//!
+//! Ideally we would use the frozen format of CRoaring and view the bitmaps directly
+//! from the bytes without any allocation, but the `roaring` crate can neither write
+//! nor read this format. Until it does, decoding on demand is the best we can do.
+//!
+//! Most of the words of the name-like corpora appear in a single document. Instead of a bitmap,
+//! the id of this document is stored in the value of the word in the fst, see [`word_value`].
+
+use std::{
+    collections::HashMap,
+    ops::Deref,
+    sync::{Arc, Mutex},
+};
+
+use roaring::RoaringBitmap;
+
+use crate::Error;
+
+/// How many decoded bitmaps we keep around between the searches.
+const CACHE_SIZE: usize = 256;
+
+/// Returns the value of the word in the fst. Its lower 32 bits are the id of the word, which is also
+/// the position of its bitmap. If the word appears in a single document, the id of this document
+/// plus one is stored in the upper 32 bits and the bitmap is cleared.
+pub(crate) fn word_value(word_id: u32, bitmap: &mut RoaringBitmap) -> u64 {
+    if bitmap.len() != 1 {
+        return word_id as u64;
+    }
+    let document = bitmap.min().unwrap();
+    bitmap.clear();
+    word_id as u64 | (document as u64 + 1) << 32
+}
+
+/// Returns the document stored in the value of a word appearing in a single document, see [`word_value`].
+fn single_document(value: u64) -> Option<u32> {
+    match value >> 32 {
+        0 => None,
+        document => Some(document as u32 - 1),
+    }
+}
+
+/// Returns the whole bitmap of the word, taking it out of the bitmaps if it's not in its value.
+pub(crate) fn take_bitmap(bitmaps: &mut [RoaringBitmap], value: u64) -> RoaringBitmap {
+    match single_document(value) {
+        Some(document) => RoaringBitmap::from_iter([document]),
+        None => bitmaps
+            .get_mut(value as u32 as usize)
+            .map(std::mem::take)
+            .unwrap_or_default(),
+    }
+}
+
+pub(crate) enum Bitmaps<'a> {
+    /// The bitmaps are still serialized in the bytes we've been given.
+    Serialized {
+        bitmaps: Vec<&'a [u8]>,
+        cache: Mutex<Cache>,
+    },
+    /// Everything has already been decoded, used when the index is moved in memory.
+    Decoded(Vec<RoaringBitmap>),
+}
+
+impl<'a> Bitmaps<'a> {
+    pub fn serialized(bitmaps: Vec<&'a [u8]>) -> Self {
+        Self::Serialized {
+            bitmaps,
+            cache: Mutex::default(),
+        }
+    }
+
+    /// Returns the bitmap of a word from its value in the fst, decoding it if needed.
+    /// A word without bitmap or whose bitmap can't be decoded matches no document, the ids of the documents
+    /// aren't checked either, see [`crate::Index::restrict`].
+    pub fn get(&self, value: u64) -> BitmapRef<'_> {
+        if let Some(document) = single_document(value) {
+            return BitmapRef::Owned(RoaringBitmap::from_iter([document]));
+        }
+        let id = value as u32;
+        match self {
+            Self::Decoded(bitmaps) => match bitmaps.get(id as usize) {
+                Some(bitmap) => BitmapRef::Borrowed(bitmap),
+                None => BitmapRef::Owned(RoaringBitmap::new()),
+            },
+            Self::Serialized { bitmaps, cache } => {
+                let mut cache = cache
+                    .lock()
+                    .unwrap_or_else(|poisoned| poisoned.into_inner());
+                if let Some(bitmap) = cache.get(id) {
+                    return BitmapRef::Shared(bitmap);
+                }
+                let Some(bytes) = bitmaps.get(id as usize) else {
+                    return BitmapRef::Owned(RoaringBitmap::new());
+                };
+                let bitmap = Arc::new(decode(bytes).unwrap_or_default());
+                cache.insert(id, bitmap.clone());
+                BitmapRef::Shared(bitmap)
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            Self::Serialized { bitmaps, .. } => bitmaps.len(),
+            Self::Decoded(bitmaps) => bitmaps.len(),
+        }
+    }
+
+    /// Read a byte of every page of the serialized bitmaps, see [`touch_pages`].
+    pub fn touch_pages(&self) -> u8 {
+        match self {
+            Self::Serialized { bitmaps, .. } => bitmaps
+                .iter()
+                .fold(0, |acc, bitmap| acc ^ touch_pages(bitmap)),
+            Self::Decoded(_) => 0,
+        }
+    }
+
+    /// Returns the size of all the bitmaps once serialized, without the size prefixing each of them.
+    pub fn serialized_size(&self) -> usize {
+        match self {
+            Self::Serialized { bitmaps, .. } => bitmaps.iter().map(|bitmap| bitmap.len()).sum(),
+            Self::Decoded(bitmaps) => bitmaps.iter().map(serialized_size).sum(),
+        }
+    }
+
+    /// Decode all the bitmaps that weren't decoded yet, the ones that can't be decoded are empty like in [`Bitmaps::get`].
+    pub fn into_decoded(self) -> Vec<RoaringBitmap> {
+        match self {
+            Self::Decoded(bitmaps) => bitmaps,
+            Self::Serialized { bitmaps, .. } => bitmaps
+                .into_iter()
+                .map(|bytes| decode(bytes).unwrap_or_default())
+                .collect(),
+        }
+    }
+}
+
+/// Read a byte of every page of `bytes` so the pages of a memory-mapped index are loaded from the disk.
+/// The result must be used or the reads could be optimized away.
+pub(crate) fn touch_pages(bytes: &[u8]) -> u8 {
+    const PAGE_SIZE: usize = 4096;
+    bytes
+        .iter()
+        .step_by(PAGE_SIZE)
+        .fold(0, |acc, byte| acc ^ byte)
+}
+
+/// Serialize the bitmap of a word in the buffer, nothing is written for the empty bitmaps
+/// of the words appearing in a single document, see [`word_value`].
+pub(crate) fn serialize(bitmap: &RoaringBitmap, buffer: &mut Vec<u8>) -> std::io::Result<()> {
+    match bitmap.is_empty() {
+        true => Ok(()),
+        false => bitmap.serialize_into(buffer),
+    }
+}
+
+fn serialized_size(bitmap: &RoaringBitmap) -> usize {
+    match bitmap.is_empty() {
+        true => 0,
+        false => bitmap.serialized_size(),
+    }
+}
+
+/// Only the checksum of the whole index can tell if the bitmap is the one we serialized,
+/// an index loaded without verifying it may contain anything, see [`crate::Index::from_bytes_unverified`].
+fn decode(bytes: &[u8]) -> Result<RoaringBitmap, Error> {
+    if bytes.is_empty() {
+        return Ok(RoaringBitmap::new());
+    }
+    RoaringBitmap::deserialize_from(bytes).map_err(|_| Error::Corrupted)
+}
+
+/// A bitmap that's either owned by the index, shared with the cache or built from the value of the word.
+pub(crate) enum BitmapRef<'b> {
+    Borrowed(&'b RoaringBitmap),
+    Shared(Arc<RoaringBitmap>),
+    Owned(RoaringBitmap),
+}
+
+impl Deref for BitmapRef<'_> {
+    type Target = RoaringBitmap;
+
+    fn deref(&self) -> &RoaringBitmap {
+        match self {
+            Self::Borrowed(bitmap) => bitmap,
+            Self::Shared(bitmap) => bitmap,
+            Self::Owned(bitmap) => bitmap,
+        }
+    }
+}
+
+/// A very small LRU, evicting the least recently used bitmap once it's full.
+#[derive(Default)]
+pub(crate) struct Cache {
+    entries: HashMap<u32, (Arc<RoaringBitmap>, u64)>,
+    tick: u64,
+}
+
+impl Cache {
+    fn get(&mut self, id: u32) -> Option<Arc<RoaringBitmap>> {
+        self.tick += 1;
+        let (bitmap, last_used) = self.entries.get_mut(&id)?;
+        *last_used = self.tick;
+        Some(bitmap.clone())
+    }
+
+    fn insert(&mut self, id: u32, bitmap: Arc<RoaringBitmap>) {
+        if self.entries.len() >= CACHE_SIZE {
+            let oldest = self
+                .entries
+                .iter()
+                .min_by_key(|(_, (_, last_used))| *last_used)
+                .map(|(id, _)| *id);
+            if let Some(oldest) = oldest {
+                self.entries.remove(&oldest);
+            }
+        }
+        self.tick += 1;
+        self.entries.insert(id, (bitmap, self.tick));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_cache_evicts_the_least_recently_used() {
+        let mut cache = Cache::default();
+        for id in 0..CACHE_SIZE as u32 {
+            cache.insert(id, Arc::new(RoaringBitmap::from_iter([id])));
+        }
+        // 0 is now the most recently used
+        assert!(cache.get(0).is_some());
+        cache.insert(1000, Arc::default());
+
+        assert_eq!(cache.entries.len(), CACHE_SIZE);
+        assert!(cache.get(0).is_some());
+        assert!(cache.get(1).is_none());
+        assert!(cache.get(1000).is_some());
+    }
+
+    #[test]
+    fn test_corrupted_bitmaps_match_nothing() {
+        let mut valid = Vec::new();
+        serialize(&RoaringBitmap::from_iter([1, 2]), &mut valid).unwrap();
+        let bitmaps = Bitmaps::serialized(vec![&valid, b"not a bitmap"]);
+        assert!(matches!(decode(b"not a bitmap"), Err(Error::Corrupted)));
+        assert_eq!(*bitmaps.get(0), RoaringBitmap::from_iter([1, 2]));
+        assert!(bitmaps.get(1).is_empty());
+        // there is no third word
+        assert!(bitmaps.get(2).is_empty());
+
+        let bitmaps = bitmaps.into_decoded();
+        assert_eq!(
+            bitmaps,
+            [RoaringBitmap::from_iter([1, 2]), RoaringBitmap::new()]
+        );
+        assert!(Bitmaps::Decoded(bitmaps).get(2).is_empty());
+    }
+}
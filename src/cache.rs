@@ -0,0 +1,340 @@
+//! Remember the results of the last searches to return them immediately when the same search is ran again,
+//! like a demo running the search on every frame or a server receiving the same popular queries.
+
+use std::{
+    collections::HashMap,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use roaring::RoaringBitmap;
+
+use crate::{Index, QueryItem, RankingRule, Scoring, Search, TieBreak, TypoTolerance};
+
+/// Every index gets a new generation when it's created and every time its documents change.
+/// It's global so two indexes never share a generation, even if one replaced the other.
+static GENERATION: AtomicU64 = AtomicU64::new(1);
+
+pub(crate) fn next_generation() -> u64 {
+    GENERATION.fetch_add(1, Ordering::Relaxed)
+}
+
+/// The least recently used results are evicted once the cache reaches its capacity, see [`Index::search_cached`].
+/// The cache is emptied when it's used with another index or once the documents of its index change.
+#[derive(Debug, Default)]
+pub struct SearchCache {
+    capacity: usize,
+    entries: HashMap<Key, Entry>,
+    // the generation of the index the results come from, `0` is never used by an index
+    generation: u64,
+    clock: u64,
+    hits: u64,
+    misses: u64,
+}
+
+#[derive(Debug)]
+struct Entry {
+    ids: Vec<u32>,
+    last_used: u64,
+}
+
+/// Everything that changes the results of a search.
+#[derive(Debug, PartialEq, Eq, Hash)]
+struct Key {
+    // the words of the query separated by a single space, the case matters to the exact ranking rule
+    query: String,
+    // `None` when the defaults of the index are used, the cache is emptied when the index changes
+    limit: Option<usize>,
+    ranking_rules: Option<Vec<RankingRule>>,
+    excluded: Vec<String>,
+    fuzzy_exclusion: bool,
+    filter: Option<FilterKey>,
+    max_candidates_per_word: usize,
+    max_exact_documents: usize,
+    max_candidate_documents: usize,
+    max_query_words: usize,
+    min_prefix_len: usize,
+    distinct: bool,
+    // the bits of the latitude and longitude since floats are not `Eq`
+    sort_by_distance: Option<(u32, u32)>,
+    minimum_words_matched: usize,
+    // the bits of the weights, like the coordinates
+    word_weights: Vec<u32>,
+    tie_break: TieBreak,
+    exact_words: Option<Vec<String>>,
+    typo_tolerance: TypoTolerance,
+    pretokenized: bool,
+    // the bits of the parameters of BM25, like the coordinates
+    scoring: Option<(u32, u32)>,
+}
+
+/// A bitmap is hashed by its length and compared entirely.
+#[derive(Debug, PartialEq, Eq)]
+struct FilterKey(RoaringBitmap);
+
+impl std::hash::Hash for FilterKey {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.0.len().hash(state);
+    }
+}
+
+impl Key {
+    /// Returns `None` if the search can't be cached since it uses synonyms or a custom ranking rule.
+    fn new(search: &Search) -> Option<Self> {
+        let custom = |rule: &RankingRule| matches!(rule, RankingRule::Custom(_));
+        if search.synonyms.is_some() || search.ranking_rules.iter().flatten().any(custom) {
+            return None;
+        }
+        let query = match search.pretokenized {
+            // a token can contain whitespaces
+            true => search
+                .query
+                .positives()
+                .filter_map(|item| match item {
+                    QueryItem::Term(term) => Some(term.text.as_str()),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+                .join("\0"),
+            false => search
+                .input
+                .split_whitespace()
+                .collect::<Vec<_>>()
+                .join(" "),
+        };
+        Some(Self {
+            query,
+            limit: search.limit,
+            ranking_rules: search.ranking_rules.clone(),
+            excluded: search.excluded.clone(),
+            fuzzy_exclusion: search.fuzzy_exclusion,
+            filter: search
+                .filter
+                .as_ref()
+                .map(|filter| FilterKey(filter.as_ref().clone())),
+            max_candidates_per_word: search.max_candidates_per_word,
+            max_exact_documents: search.max_exact_documents,
+            max_candidate_documents: search.max_candidate_documents,
+            max_query_words: search.max_query_words,
+            min_prefix_len: search.min_prefix_len,
+            distinct: search.distinct,
+            sort_by_distance: search
+                .sort_by_distance
+                .map(|(lat, lng)| (lat.to_bits(), lng.to_bits())),
+            minimum_words_matched: search.minimum_words_matched,
+            word_weights: search
+                .word_weights
+                .iter()
+                .map(|weight| weight.to_bits())
+                .collect(),
+            tie_break: search.tie_break,
+            exact_words: search.exact_words.clone(),
+            typo_tolerance: search.typo_tolerance,
+            pretokenized: search.pretokenized,
+            scoring: match search.scoring {
+                Scoring::BucketSort => None,
+                Scoring::Bm25 { k1, b } => Some((k1.to_bits(), b.to_bits())),
+            },
+        })
+    }
+}
+
+impl SearchCache {
+    /// Keeps the results of up to `capacity` searches, a capacity of 0 disables the cache.
+    /// Finding the search to evict is linear in the capacity, it's meant to stay small.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            ..Self::default()
+        }
+    }
+
+    /// Returns the number of searches whose results were in the cache.
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+
+    /// Returns the number of searches that had to be ran, the searches that can't be cached are not counted.
+    pub fn misses(&self) -> u64 {
+        self.misses
+    }
+
+    /// Returns the number of searches whose results are in the cache.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Forget all the results, the counters are kept.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    fn insert(&mut self, key: Key, ids: Vec<u32>) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.entries.len() >= self.capacity {
+            // the clock never goes back thus only the least recently used entry has this value
+            let oldest = self.entries.values().map(|entry| entry.last_used).min();
+            self.entries
+                .retain(|_, entry| Some(entry.last_used) != oldest);
+        }
+        let last_used = self.tick();
+        self.entries.insert(key, Entry { ids, last_used });
+    }
+
+    fn tick(&mut self) -> u64 {
+        self.clock += 1;
+        self.clock
+    }
+}
+
+impl Index<'_> {
+    /// Same as [`Index::search`] but returns the results from the cache if the same search was already ran.
+    /// Two queries are the same if they contain the same words, whatever the whitespaces between them.
+    /// The searches with synonyms or a custom ranking rule are never cached,
+    /// nor the results of a search stopped by its time budget or interrupted.
+    ///
+    /// ```
+    /// use zearch::{Index, Search, SearchCache};
+    ///
+    /// let index = Index::new_in_memory(&["kefir", "kefir le chien", "le chat"]).unwrap();
+    /// let mut cache = SearchCache::new(100);
+    /// assert_eq!(index.search_cached(&Search::new("kefir"), &mut cache), [0, 1]);
+    /// assert_eq!(index.search_cached(&Search::new(" kefir "), &mut cache), [0, 1]);
+    /// assert_eq!((cache.hits(), cache.misses()), (1, 1));
+    /// ```
+    pub fn search_cached(&self, search: &Search, cache: &mut SearchCache) -> Vec<u32> {
+        if cache.generation != self.generation {
+            cache.entries.clear();
+            cache.generation = self.generation;
+        }
+        let Some(key) = Key::new(search) else {
+            return self.search(search);
+        };
+        let now = cache.tick();
+        if let Some(entry) = cache.entries.get_mut(&key) {
+            entry.last_used = now;
+            cache.hits += 1;
+            return entry.ids.clone();
+        }
+
+        cache.misses += 1;
+        let results = self.execute(search);
+        if !results.degraded {
+            cache.insert(key, results.ids.clone());
+        }
+        results.ids
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test::create_small_index;
+
+    #[test]
+    fn test_hits_and_misses() {
+        let index = create_small_index();
+        let mut cache = SearchCache::new(2);
+
+        let search = Search::new("kefir chien");
+        let expected = index.search(&search);
+        assert_eq!(index.search_cached(&search, &mut cache), expected);
+        assert_eq!(index.search_cached(&search, &mut cache), expected);
+        // the same words with other whitespaces
+        let same = Search::new("  kefir\tchien ");
+        assert_eq!(index.search_cached(&same, &mut cache), expected);
+        assert_eq!((cache.hits(), cache.misses(), cache.len()), (2, 1, 1));
+
+        // any setting changes the results
+        let mut limited = Search::new("kefir chien");
+        limited.with_limit(1);
+        assert_eq!(index.search_cached(&limited, &mut cache), &expected[..1]);
+        let filter = RoaringBitmap::from_iter([2]);
+        let mut filtered = Search::new("kefir chien");
+        filtered.with_filter(&filter);
+        assert_eq!(index.search_cached(&filtered, &mut cache), [2]);
+        assert_eq!((cache.hits(), cache.misses(), cache.len()), (2, 3, 2));
+
+        // the least recently used search was evicted
+        index.search_cached(&filtered, &mut cache);
+        index.search_cached(&limited, &mut cache);
+        index.search_cached(&search, &mut cache);
+        assert_eq!((cache.hits(), cache.misses(), cache.len()), (4, 4, 2));
+
+        // the searches with synonyms are not counted
+        let synonyms = HashMap::from([(String::from("toutou"), vec![String::from("chien")])]);
+        let mut with_synonyms = Search::new("toutou");
+        with_synonyms.with_synonyms(&synonyms);
+        index.search_cached(&with_synonyms, &mut cache);
+        index.search_cached(&with_synonyms, &mut cache);
+        assert_eq!((cache.hits(), cache.misses(), cache.len()), (4, 4, 2));
+
+        // the case breaks the ties of the exact ranking rule
+        let mut cache = SearchCache::new(2);
+        let capitalized = Search::new("Kefir chien");
+        index.search_cached(&search, &mut cache);
+        assert_eq!(
+            index.search_cached(&capitalized, &mut cache),
+            index.search(&capitalized)
+        );
+        assert_eq!((cache.hits(), cache.misses(), cache.len()), (0, 2, 2));
+
+        let mut disabled = SearchCache::new(0);
+        index.search_cached(&search, &mut disabled);
+        index.search_cached(&search, &mut disabled);
+        assert_eq!((disabled.hits(), disabled.misses()), (0, 2));
+    }
+
+    #[test]
+    fn test_invalidation() {
+        let mut index = create_small_index();
+        let mut cache = SearchCache::new(10);
+        let search = Search::new("chien");
+        insta::assert_debug_snapshot!(index.search_cached(&search, &mut cache), @r###"
+        [
+            1,
+            2,
+        ]
+        "###);
+
+        index.add_documents(&["le chien de tamo"]).unwrap();
+        insta::assert_debug_snapshot!(index.search_cached(&search, &mut cache), @r###"
+        [
+            1,
+            2,
+            12,
+        ]
+        "###);
+        index.delete_documents(&RoaringBitmap::from_iter([1]));
+        insta::assert_debug_snapshot!(index.search_cached(&search, &mut cache), @r###"
+        [
+            2,
+            12,
+        ]
+        "###);
+        index.compact().unwrap();
+        insta::assert_debug_snapshot!(index.search_cached(&search, &mut cache), @r###"
+        [
+            1,
+            11,
+        ]
+        "###);
+        assert_eq!(cache.hits(), 0);
+        index.search_cached(&search, &mut cache);
+        assert_eq!(cache.hits(), 1);
+
+        // an index with the same documents is still another index
+        let other = create_small_index();
+        other.search_cached(&search, &mut cache);
+        assert_eq!((cache.hits(), cache.len()), (1, 1));
+        // but loading it in memory doesn't change it
+        let other = other.move_in_memory();
+        other.search_cached(&search, &mut cache);
+        assert_eq!(cache.hits(), 2);
+    }
+}
@@ -0,0 +1,321 @@
+//! The settings of a search without its query, to store them in a configuration file
+//! with the `serde` feature and create the searches from them.
+//! The limit and the ranking rules can also be stored in the index, see [`SearchDefaults`].
+
+use std::{collections::HashMap, time::Duration};
+
+use crate::{Error, Index, RankingRule, Scoring, Search, TieBreak, TypoTolerance};
+
+/// The owned settings of a [`Search`], see the methods of [`Search`] for their meaning.
+/// With the `serde` feature the missing fields take their default value when it's deserialized.
+///
+/// ```
+/// use zearch::{Index, RankingRule, Search, SearchConfig};
+///
+/// let index = Index::new_in_memory(&["kefir le chien", "tamo le chat"]).unwrap();
+/// let config = SearchConfig {
+///     limit: Some(1),
+///     ranking_rules: Some(vec![RankingRule::Typo, RankingRule::Word]),
+///     ..SearchConfig::default()
+/// };
+/// assert_eq!(index.search(&config.to_search("le")), [0]);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default)
+)]
+pub struct SearchConfig {
+    /// `None` to use the limit of the index, see [`Index::default_search`].
+    pub limit: Option<usize>,
+    /// `None` to use the ranking rules of the index, see [`Index::default_search`].
+    pub ranking_rules: Option<Vec<RankingRule>>,
+    pub excluded_terms: Vec<String>,
+    pub fuzzy_exclusion: bool,
+    pub max_candidates_per_word: usize,
+    pub max_exact_documents: usize,
+    pub max_candidate_documents: usize,
+    pub max_query_words: usize,
+    pub min_prefix_len: usize,
+    pub distinct: bool,
+    pub minimum_words_matched: usize,
+    pub tie_break: TieBreak,
+    /// `None` to use the exact words of the index.
+    pub exact_words: Option<Vec<String>>,
+    pub typo_tolerance: TypoTolerance,
+    pub synonyms: HashMap<String, Vec<String>>,
+    pub time_budget: Option<Duration>,
+    pub scoring: Scoring,
+}
+
+impl SearchConfig {
+    /// Create a search of `input` with these settings, the filter and the interrupt can then be set on the search.
+    pub fn to_search<'a>(&'a self, input: &'a str) -> Search<'a> {
+        let mut search = Search::new(input);
+        search
+            .with_excluded_terms(&self.excluded_terms)
+            .with_fuzzy_exclusion(self.fuzzy_exclusion)
+            .with_max_candidates_per_word(self.max_candidates_per_word)
+            .with_max_exact_documents(self.max_exact_documents)
+            .with_max_candidate_documents(self.max_candidate_documents)
+            .with_max_query_words(self.max_query_words)
+            .with_min_prefix_len(self.min_prefix_len)
+            .with_distinct(self.distinct)
+            .with_minimum_words_matched(self.minimum_words_matched)
+            .with_tie_break(self.tie_break)
+            .with_typo_tolerance(self.typo_tolerance)
+            .with_scoring(self.scoring);
+        if let Some(limit) = self.limit {
+            search.with_limit(limit);
+        }
+        if let Some(ranking_rules) = &self.ranking_rules {
+            search.with_ranking_rules(ranking_rules.clone());
+        }
+        if let Some(exact_words) = &self.exact_words {
+            let exact_words: Vec<&str> = exact_words.iter().map(String::as_str).collect();
+            search.with_exact_words(&exact_words);
+        }
+        if !self.synonyms.is_empty() {
+            search.with_synonyms(&self.synonyms);
+        }
+        if let Some(budget) = self.time_budget {
+            search.with_time_budget(budget);
+        }
+        search
+    }
+}
+
+impl Default for SearchConfig {
+    /// The same settings as [`Search::new`].
+    fn default() -> Self {
+        let search = Search::new("");
+        Self {
+            limit: search.limit,
+            ranking_rules: search.ranking_rules,
+            excluded_terms: Vec::new(),
+            fuzzy_exclusion: search.fuzzy_exclusion,
+            max_candidates_per_word: search.max_candidates_per_word,
+            max_exact_documents: search.max_exact_documents,
+            max_candidate_documents: search.max_candidate_documents,
+            max_query_words: search.max_query_words,
+            min_prefix_len: search.min_prefix_len,
+            distinct: search.distinct,
+            minimum_words_matched: search.minimum_words_matched,
+            tie_break: search.tie_break,
+            exact_words: search.exact_words,
+            typo_tolerance: search.typo_tolerance,
+            synonyms: HashMap::new(),
+            time_budget: search.time_budget,
+            scoring: search.scoring,
+        }
+    }
+}
+
+/// The limit and ranking rules of the searches that don't set them, see [`Index::default_search`].
+/// They can be stored in the index with [`crate::IndexBuilder::with_default_limit`]
+/// and [`crate::IndexBuilder::with_default_ranking_rules`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchDefaults {
+    pub limit: usize,
+    pub ranking_rules: Vec<RankingRule>,
+}
+
+impl Default for SearchDefaults {
+    /// The settings of the indexes that don't store them.
+    fn default() -> Self {
+        Self {
+            limit: 10,
+            ranking_rules: vec![RankingRule::Word, RankingRule::Typo, RankingRule::Exact],
+        }
+    }
+}
+
+impl SearchDefaults {
+    /// Write the limit followed by the ranking rules, a byte each.
+    /// The custom ranking rules are code, they can't be stored.
+    pub(crate) fn write(&self, writer: &mut impl std::io::Write) -> Result<(), Error> {
+        Index::write_size(writer, self.limit)?;
+        let rules = self
+            .ranking_rules
+            .iter()
+            .map(|rule| match rule {
+                RankingRule::Word => Ok(0),
+                RankingRule::Typo => Ok(1),
+                RankingRule::Exact => Ok(2),
+                RankingRule::Proximity => Ok(3),
+                RankingRule::Attribute => Ok(4),
+                RankingRule::Boost => Ok(5),
+                RankingRule::Sort { descending: false } => Ok(6),
+                RankingRule::Sort { descending: true } => Ok(7),
+                RankingRule::Position => Ok(8),
+                RankingRule::WordCount => Ok(9),
+                RankingRule::Custom(_) => Err(Error::CustomRankingRule),
+            })
+            .collect::<Result<Vec<u8>, Error>>()?;
+        Index::write_slice(writer, &rules)
+    }
+
+    /// Returns the size of the section written by [`SearchDefaults::write`].
+    pub(crate) fn serialized_size(&self) -> usize {
+        2 * std::mem::size_of::<u32>() + self.ranking_rules.len()
+    }
+
+    pub(crate) fn read(mut section: &[u8]) -> Option<Self> {
+        let limit = Index::read_size_from_bytes(&mut section)? as usize;
+        let ranking_rules = Index::read_slice_from_bytes(&mut section)?
+            .iter()
+            .map(|rule| match rule {
+                0 => Some(RankingRule::Word),
+                1 => Some(RankingRule::Typo),
+                2 => Some(RankingRule::Exact),
+                3 => Some(RankingRule::Proximity),
+                4 => Some(RankingRule::Attribute),
+                5 => Some(RankingRule::Boost),
+                6 => Some(RankingRule::Sort { descending: false }),
+                7 => Some(RankingRule::Sort { descending: true }),
+                8 => Some(RankingRule::Position),
+                9 => Some(RankingRule::WordCount),
+                _ => None,
+            })
+            .collect::<Option<Vec<RankingRule>>>()?;
+        section.is_empty().then_some(Self {
+            limit,
+            ranking_rules,
+        })
+    }
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod test {
+    use std::ops::ControlFlow;
+
+    use roaring::RoaringBitmap;
+
+    use super::*;
+    use crate::{Index, RankingRuleImpl, WordCandidate};
+
+    #[test]
+    fn test_round_trip() {
+        let config = SearchConfig {
+            limit: Some(3),
+            ranking_rules: Some(vec![
+                RankingRule::Sort { descending: true },
+                RankingRule::Word,
+                RankingRule::Proximity,
+            ]),
+            excluded_terms: vec![String::from("chat")],
+            synonyms: HashMap::from([(String::from("st"), vec![String::from("saint")])]),
+            time_budget: Some(Duration::from_millis(20)),
+            ..SearchConfig::default()
+        };
+        let json = serde_json::to_string_pretty(&config).unwrap();
+        insta::assert_snapshot!(json, @r###"
+        {
+          "limit": 3,
+          "ranking_rules": [
+            {
+              "sort": {
+                "descending": true
+              }
+            },
+            "word",
+            "proximity"
+          ],
+          "excluded_terms": [
+            "chat"
+          ],
+          "fuzzy_exclusion": false,
+          "max_candidates_per_word": 10000,
+          "max_exact_documents": 1000,
+          "max_candidate_documents": 1000000,
+          "max_query_words": 10,
+          "min_prefix_len": 1,
+          "distinct": false,
+          "minimum_words_matched": 0,
+          "tie_break": "doc_id_asc",
+          "exact_words": null,
+          "typo_tolerance": {
+            "first_letter_penalty": true
+          },
+          "synonyms": {
+            "st": [
+              "saint"
+            ]
+          },
+          "time_budget": {
+            "secs": 0,
+            "nanos": 20000000
+          },
+          "scoring": "bucket_sort"
+        }
+        "###);
+        assert_eq!(serde_json::from_str::<SearchConfig>(&json).unwrap(), config);
+
+        // the missing fields take their default value
+        let config: SearchConfig = serde_json::from_str(r#"{ "limit": 2 }"#).unwrap();
+        assert_eq!(
+            config,
+            SearchConfig {
+                limit: Some(2),
+                ..SearchConfig::default()
+            }
+        );
+
+        struct Noop;
+        impl RankingRuleImpl for Noop {
+            fn name(&self) -> &'static str {
+                "noop"
+            }
+            fn next(
+                &mut self,
+                _prev: Option<&dyn RankingRuleImpl>,
+                _words: &mut Vec<WordCandidate>,
+                _index: &Index,
+            ) -> ControlFlow<RoaringBitmap, ()> {
+                ControlFlow::Break(RoaringBitmap::new())
+            }
+            fn current_results(&self, _words: &[WordCandidate]) -> RoaringBitmap {
+                RoaringBitmap::new()
+            }
+        }
+        // the custom ranking rules are code, they can't be part of a configuration
+        let custom = RankingRule::custom(|| Noop);
+        insta::assert_snapshot!(serde_json::to_string(&custom).unwrap_err(), @"the enum variant RankingRule::Custom cannot be serialized");
+    }
+
+    #[test]
+    fn test_same_results() {
+        let index = Index::new_in_memory(&[
+            "kefir le chien",
+            "tamo le chat",
+            "kefir et tamo",
+            "le chien kefir",
+        ])
+        .unwrap();
+        let config: SearchConfig = serde_json::from_str(
+            r#"{
+                "limit": 2,
+                "ranking_rules": ["exact", "word"],
+                "excluded_terms": ["tamo"],
+                "min_prefix_len": 3
+            }"#,
+        )
+        .unwrap();
+
+        let excluded = ["tamo"];
+        for query in ["kefir le", "le ch", "chien kef"] {
+            let mut search = Search::new(query);
+            search
+                .with_limit(2)
+                .with_ranking_rules(vec![RankingRule::Exact, RankingRule::Word])
+                .with_excluded_terms(&excluded)
+                .with_min_prefix_len(3);
+            assert_eq!(
+                index.search(&config.to_search(query)),
+                index.search(&search),
+                "{query}"
+            );
+        }
+    }
+}
@@ -0,0 +1,56 @@
+//! Stops a search once its time budget is spent or it was interrupted,
+//! see [`Search::with_time_budget`] and [`Search::with_interrupt`].
+
+use std::{
+    cell::Cell,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use crate::{Instant, Search};
+
+/// Created at the beginning of every search since the time budget starts when the search is ran.
+pub(crate) struct Deadline<'s> {
+    end: Option<Instant>,
+    interrupt: Option<&'s AtomicBool>,
+    // once reached we stop checking so the rest of the search stays consistent
+    reached: Cell<bool>,
+}
+
+impl<'s> Deadline<'s> {
+    pub fn new(search: &'s Search) -> Self {
+        Self {
+            end: search
+                .time_budget
+                .and_then(|budget| Instant::now().checked_add(budget)),
+            interrupt: search.interrupt.as_deref(),
+            reached: Cell::new(false),
+        }
+    }
+
+    /// A deadline that's never reached.
+    pub fn never() -> Self {
+        Self {
+            end: None,
+            interrupt: None,
+            reached: Cell::new(false),
+        }
+    }
+
+    /// Returns `true` if the search must stop now.
+    pub fn is_reached(&self) -> bool {
+        if self.reached.get() {
+            return true;
+        }
+        let reached = self
+            .interrupt
+            .is_some_and(|flag| flag.load(Ordering::Relaxed))
+            || self.end.is_some_and(|end| Instant::now() >= end);
+        self.reached.set(reached);
+        reached
+    }
+
+    /// Returns `true` if the search was stopped before its end, without checking the deadline again.
+    pub fn was_reached(&self) -> bool {
+        self.reached.get()
+    }
+}
@@ -0,0 +1,67 @@
+//! Building the Levenshtein automaton of a word costs more than streaming the fst with it for short words.
+//! When the user is typing, every query repeats the words of the previous one,
+//! thus the automata are kept in the [`SearchContext`](crate::SearchContext) between the searches.
+
+use std::collections::HashMap;
+
+use levenshtein_automata::DFA;
+
+use crate::{levenshtein_builders, typo_budget};
+
+/// How many automata we keep before starting over.
+const MAX_DFAS: usize = 64;
+
+/// The automata of the normalized words, with and without a prefix search.
+/// The typo budget only depends on the word thus it's not part of the key.
+#[derive(Default)]
+pub(crate) struct Dfas {
+    dfas: HashMap<(String, bool), DFA>,
+}
+
+impl Dfas {
+    /// Returns the automaton matching the word with typos, or the words starting with it if `prefix` is set.
+    pub fn get(&mut self, normalized: &str, prefix: bool) -> &DFA {
+        let key = (normalized.to_string(), prefix);
+        if self.dfas.len() >= MAX_DFAS && !self.dfas.contains_key(&key) {
+            self.dfas.clear();
+        }
+        self.dfas.entry(key).or_insert_with(|| {
+            let lev = &levenshtein_builders()[typo_budget(normalized)];
+            if prefix {
+                lev.build_prefix_dfa(normalized)
+            } else {
+                lev.build_dfa(normalized)
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use fst::Automaton;
+
+    use super::*;
+
+    fn matches(dfa: &DFA, word: &str) -> bool {
+        let state = word
+            .bytes()
+            .fold(dfa.start(), |state, byte| dfa.accept(&state, byte));
+        dfa.is_match(&state)
+    }
+
+    #[test]
+    fn test_reuse() {
+        let mut dfas = Dfas::default();
+        assert!(matches(dfas.get("kefir", false), "kefri"));
+        assert!(!matches(dfas.get("kefir", false), "kefirss"));
+        assert!(matches(dfas.get("kefir", true), "kefirss"));
+        dfas.get("kefir", true);
+        assert_eq!(dfas.dfas.len(), 2);
+
+        for i in 0..MAX_DFAS {
+            dfas.get(&format!("word{i}"), true);
+        }
+        // the cache was emptied once full instead of growing
+        assert_eq!(dfas.dfas.len(), 2);
+    }
+}
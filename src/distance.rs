@@ -0,0 +1,182 @@
+//! The restricted Damerau-Levenshtein distance, also called optimal string alignment.
+//! It counts the insertions, deletions, substitutions and transpositions of two adjacent
+//! characters required to go from one string to the other, without editing a substring twice.
+//! The buffers are kept between calls since we're going to compute a lot of small distances.
+//! The number of typos of a matched word is its distance to the word of the query, see [`TypoTolerance`].
+
+/// How the typos between a word of the query and a word of the index are counted, see [`crate::Search::with_typo_tolerance`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default)
+)]
+pub struct TypoTolerance {
+    /// A different first letter counts as two typos, `tamo` is closer to `tamp` than to `samo`. `true` by default.
+    /// The words are still only matched within the typo budget of the word of the query.
+    /// The extra typo is not added to a word that already has two typos: the typo ranking rule
+    /// never returns the words with three typos, the penalty would remove the word from the results instead of ranking it down.
+    pub first_letter_penalty: bool,
+}
+
+impl Default for TypoTolerance {
+    fn default() -> Self {
+        Self {
+            first_letter_penalty: true,
+        }
+    }
+}
+
+/// The typo ranking rule never returns the words matched with more typos than this.
+const MAX_RETURNED_TYPOS: usize = 2;
+
+#[derive(Debug, Default, Clone)]
+pub(crate) struct DamerauLevenshtein {
+    left: Vec<char>,
+    right: Vec<char>,
+    // the last three rows of the matrix
+    rows: Vec<usize>,
+}
+
+impl DamerauLevenshtein {
+    pub fn distance(&mut self, left: &str, right: &str) -> usize {
+        self.left.clear();
+        self.left.extend(left.chars());
+        self.right.clear();
+        self.right.extend(right.chars());
+        let (left, right) = (&self.left, &self.right);
+
+        if left.is_empty() || right.is_empty() {
+            return left.len().max(right.len());
+        }
+
+        let width = right.len() + 1;
+        self.rows.clear();
+        self.rows.resize(width * 3, 0);
+        let (mut before, mut previous, mut current) = (0, width, width * 2);
+        for (j, cell) in self.rows[previous..current].iter_mut().enumerate() {
+            *cell = j;
+        }
+
+        for i in 1..=left.len() {
+            self.rows[current] = i;
+            for j in 1..=right.len() {
+                let cost = (left[i - 1] != right[j - 1]) as usize;
+                let mut distance = (self.rows[previous + j] + 1)
+                    .min(self.rows[current + j - 1] + 1)
+                    .min(self.rows[previous + j - 1] + cost);
+                if i > 1 && j > 1 && left[i - 1] == right[j - 2] && left[i - 2] == right[j - 1] {
+                    distance = distance.min(self.rows[before + j - 2] + 1);
+                }
+                self.rows[current + j] = distance;
+            }
+            (before, previous, current) = (previous, current, before);
+        }
+
+        self.rows[previous + right.len()]
+    }
+
+    /// The number of typos between the word of the query and a word of the index cut to its length,
+    /// at most 3.
+    pub fn typos(&mut self, query: &str, word: &str, tolerance: TypoTolerance) -> usize {
+        let mut typos = self.distance(query, word);
+        // the penalty ranks the word down but must not push it out of the results
+        if tolerance.first_letter_penalty
+            && typos < MAX_RETURNED_TYPOS
+            && query.chars().next() != word.chars().next()
+        {
+            typos += 1;
+        }
+        typos.min(3)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_distances() {
+        // these are the distances that were returned by `text_distance::DamerauLevenshtein { restricted: true }`
+        let pairs = [
+            ("", "", 0),
+            ("", "kefir", 5),
+            ("kefir", "", 5),
+            ("kefir", "kefir", 0),
+            ("kefir", "kefri", 1),
+            ("kefir", "ekfir", 1),
+            ("kefir", "kfir", 1),
+            ("kefir", "keffir", 1),
+            ("kefir", "tamo", 5),
+            ("tamo", "samo", 1),
+            ("ca", "abc", 3),
+            ("abcdef", "badcfe", 3),
+            ("été", "ete", 2),
+            ("Épinal", "epinal", 1),
+            ("très", "tres", 1),
+            ("müller", "mulelr", 2),
+        ];
+
+        let mut distance = DamerauLevenshtein::default();
+        for (left, right, expected) in pairs {
+            assert_eq!(
+                distance.distance(left, right),
+                expected,
+                "distance between {left:?} and {right:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_typos() {
+        let mut distance = DamerauLevenshtein::default();
+        let penalty = TypoTolerance::default();
+        let none = TypoTolerance {
+            first_letter_penalty: false,
+        };
+        assert_eq!(distance.typos("tamo", "tamp", penalty), 1);
+        assert_eq!(distance.typos("tamo", "samo", penalty), 2);
+        assert_eq!(distance.typos("tamo", "samo", none), 1);
+        // a transposition of the first letters changes the first letter
+        assert_eq!(distance.typos("kefir", "ekfir", penalty), 2);
+        assert_eq!(distance.typos("kefir", "kefir", penalty), 0);
+        // a word with two typos is never given a third one
+        assert_eq!(distance.typos("kefirounet", "sefirounez", penalty), 2);
+        assert_eq!(distance.typos("kefirounet", "sefiroumez", penalty), 3);
+    }
+
+    #[test]
+    #[ignore]
+    fn bench_distances() {
+        let words = [
+            "kefir",
+            "kefirounet",
+            "keftas",
+            "kef",
+            "tamo",
+            "beau",
+            "chien",
+        ];
+        // the distances computed with the same buffers against new buffers every time
+        let time = |reuse: bool| {
+            let mut distance = DamerauLevenshtein::default();
+            let now = std::time::Instant::now();
+            let mut total = 0;
+            for _ in 0..100_000 {
+                for left in words {
+                    for right in words {
+                        if !reuse {
+                            distance = DamerauLevenshtein::default();
+                        }
+                        total += distance.distance(left, right);
+                    }
+                }
+            }
+            (total, now.elapsed())
+        };
+        let (total, reused) = time(true);
+        let (expected, allocated) = time(false);
+        assert_eq!(total, expected);
+        assert!(reused < allocated, "{reused:?} {allocated:?}");
+    }
+}
@@ -0,0 +1,363 @@
+//! The text of the documents is optional, an index can be constructed without it when
+//! the documents are already stored elsewhere, see [`crate::IndexBuilder::with_documents`].
+//! In this case we only remember how many documents there are so the ids stay valid.
+//!
+//! With the `compression` feature the documents can also be compressed by blocks of about 64KiB,
+//! see [`crate::IndexBuilder::with_compression`]. A block is only decompressed when one of its
+//! documents is accessed, and then kept since [`crate::Index::get_document`] borrows from the index.
+
+use std::{borrow::Cow, sync::OnceLock};
+
+use crate::{Error, Index};
+
+/// The size of a block of documents before its compression, a larger document is alone in its block.
+#[cfg(feature = "compression")]
+const BLOCK_SIZE: usize = 64 * 1024;
+
+pub(crate) enum Documents<'a> {
+    /// The text of every document, in the order of their ids.
+    Stored(Vec<Cow<'a, str>>),
+    /// The number of documents, their text was not stored.
+    Missing(u32),
+    /// The documents are compressed by blocks of consecutive ids.
+    Compressed {
+        count: u32,
+        // the id of the first document of every block
+        first_ids: Vec<u32>,
+        blocks: Vec<Cow<'a, [u8]>>,
+        decompressed: Vec<OnceLock<Vec<String>>>,
+    },
+}
+
+impl<'a> Documents<'a> {
+    pub fn len(&self) -> usize {
+        match self {
+            Self::Stored(documents) => documents.len(),
+            Self::Missing(count) | Self::Compressed { count, .. } => *count as usize,
+        }
+    }
+
+    /// Returns `true` if the text of the documents can be retrieved.
+    pub fn is_stored(&self) -> bool {
+        !matches!(self, Self::Missing(_))
+    }
+
+    #[cfg(feature = "compression")]
+    pub fn is_compressed(&self) -> bool {
+        matches!(self, Self::Compressed { .. })
+    }
+
+    /// Returns the text of the document, `None` if it doesn't exist or wasn't stored.
+    pub fn get(&self, id: u32) -> Option<&str> {
+        match self {
+            Self::Stored(documents) => documents.get(id as usize).map(|s| s.as_ref()),
+            Self::Missing(_) => None,
+            Self::Compressed {
+                count,
+                first_ids,
+                blocks,
+                decompressed,
+            } => {
+                if id >= *count {
+                    return None;
+                }
+                let block = first_ids
+                    .partition_point(|first| *first <= id)
+                    .checked_sub(1)?;
+                let documents = decompressed[block].get_or_init(|| decompress(&blocks[block]));
+                documents
+                    .get((id - first_ids[block]) as usize)
+                    .map(|s| s.as_str())
+            }
+        }
+    }
+
+    /// Returns the stored documents with their id, nothing if they weren't stored.
+    pub fn iter(&self) -> impl Iterator<Item = (u32, &str)> + '_ {
+        let len = match self.is_stored() {
+            true => self.len() as u32,
+            false => 0,
+        };
+        (0..len).filter_map(|id| Some((id, self.get(id)?)))
+    }
+
+    /// Append the documents, only their number is kept if the others weren't stored.
+    /// The caller must check the total number of documents fits in an id.
+    pub fn extend(&mut self, new: &[impl AsRef<str>]) -> Result<(), Error> {
+        match self {
+            Self::Stored(documents) => documents.extend(
+                new.iter()
+                    .map(|document| Cow::Owned(document.as_ref().to_string())),
+            ),
+            Self::Missing(count) => *count += new.len() as u32,
+            #[cfg(feature = "compression")]
+            Self::Compressed {
+                count,
+                first_ids,
+                blocks,
+                decompressed,
+            } => {
+                // the last block is not filled again, the new documents start a new one
+                let start = *count;
+                let mut compressor = Compressor::default();
+                let mut push = |(first, block)| {
+                    first_ids.push(start + first);
+                    blocks.push(Cow::Owned(block));
+                    decompressed.push(OnceLock::new());
+                };
+                for document in new {
+                    compressor.push(document.as_ref())?.map(&mut push);
+                }
+                compressor.finish().map(push);
+                *count += new.len() as u32;
+            }
+            // such an index can't be loaded without the feature, see `Documents::check_supported`
+            #[cfg(not(feature = "compression"))]
+            Self::Compressed { .. } => unreachable!(),
+        }
+        Ok(())
+    }
+
+    pub fn into_owned(self) -> Documents<'static> {
+        match self {
+            Self::Stored(documents) => Documents::Stored(
+                documents
+                    .into_iter()
+                    .map(|document| Cow::Owned(document.into_owned()))
+                    .collect(),
+            ),
+            Self::Missing(count) => Documents::Missing(count),
+            Self::Compressed {
+                count,
+                first_ids,
+                blocks,
+                decompressed,
+            } => Documents::Compressed {
+                count,
+                first_ids,
+                blocks: blocks
+                    .into_iter()
+                    .map(|block| Cow::Owned(block.into_owned()))
+                    .collect(),
+                decompressed,
+            },
+        }
+    }
+
+    /// Compress the documents in memory.
+    #[cfg(feature = "compression")]
+    pub fn compressed(documents: &[impl AsRef<str>]) -> Result<Documents<'a>, Error> {
+        let mut compressed = Documents::Compressed {
+            count: 0,
+            first_ids: Vec::new(),
+            blocks: Vec::new(),
+            decompressed: Vec::new(),
+        };
+        compressed.extend(documents)?;
+        Ok(compressed)
+    }
+
+    /// The compressed documents can be read without the `compression` feature but not decompressed.
+    pub fn check_supported(&self) -> Result<(), Error> {
+        match self {
+            Self::Compressed { .. } if cfg!(not(feature = "compression")) => {
+                Err(Error::UnsupportedCompression)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Write the beginning of the section of the compressed documents, made of their count
+    /// and the id of the first document of every block. The blocks follow, each of them prefixed by its size.
+    pub fn write_compressed_header(
+        writer: &mut impl std::io::Write,
+        count: usize,
+        first_ids: &[u32],
+    ) -> Result<(), Error> {
+        Index::write_size(writer, count)?;
+        Index::write_size(writer, first_ids.len())?;
+        for first in first_ids {
+            writer.write_all(&first.to_be_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// Read the section of the compressed documents, the blocks are not decompressed.
+    pub fn read_compressed(mut section: &'a [u8]) -> Option<Self> {
+        let count = Index::read_size_from_bytes(&mut section)?;
+        let nb_blocks = Index::read_count_from_bytes(&mut section, 2 * std::mem::size_of::<u32>())?;
+        let first_ids = (0..nb_blocks)
+            .map(|_| Index::read_size_from_bytes(&mut section))
+            .collect::<Option<Vec<u32>>>()?;
+        let blocks = (0..nb_blocks)
+            .map(|_| Index::read_slice_from_bytes(&mut section).map(Cow::Borrowed))
+            .collect::<Option<Vec<_>>>()?;
+        // the blocks must cover every document in order
+        if !section.is_empty()
+            || first_ids.first().is_some_and(|first| *first != 0)
+            || first_ids.windows(2).any(|ids| ids[0] >= ids[1])
+            || first_ids.last().is_some_and(|last| *last >= count)
+            || (count > 0 && first_ids.is_empty())
+        {
+            return None;
+        }
+        Some(Self::Compressed {
+            count,
+            first_ids,
+            decompressed: blocks.iter().map(|_| OnceLock::new()).collect(),
+            blocks,
+        })
+    }
+}
+
+/// Split the documents in blocks of about [`BLOCK_SIZE`] bytes and compress them.
+/// A block contains the documents one after the other, each of them prefixed by its size.
+#[cfg(feature = "compression")]
+#[derive(Default)]
+pub(crate) struct Compressor {
+    block: Vec<u8>,
+    // the position of the first document of the current block and of the next document
+    first: u32,
+    next: u32,
+}
+
+#[cfg(feature = "compression")]
+impl Compressor {
+    /// Add the next document, returns the previous block with the position of its first document once it's full.
+    pub fn push(&mut self, document: &str) -> Result<Option<(u32, Vec<u8>)>, Error> {
+        let mut full = None;
+        if !self.block.is_empty() && self.block.len() + document.len() > BLOCK_SIZE {
+            full = Some((self.first, lz4_flex::compress_prepend_size(&self.block)));
+            self.block.clear();
+            self.first = self.next;
+        }
+        Index::write_slice(&mut self.block, document.as_bytes())?;
+        self.next += 1;
+        Ok(full)
+    }
+
+    /// Returns the last block, if any.
+    pub fn finish(self) -> Option<(u32, Vec<u8>)> {
+        (!self.block.is_empty()).then(|| (self.first, lz4_flex::compress_prepend_size(&self.block)))
+    }
+}
+
+/// Returns the documents of the block, none of them if it's corrupted.
+/// The checksum of the index makes it very unlikely.
+#[cfg(feature = "compression")]
+fn decompress(block: &[u8]) -> Vec<String> {
+    let Ok(block) = lz4_flex::decompress_size_prepended(block) else {
+        return Vec::new();
+    };
+    let mut bytes = block.as_slice();
+    let mut documents = Vec::new();
+    while !bytes.is_empty() {
+        let Some(document) = Index::read_slice_from_bytes(&mut bytes) else {
+            break;
+        };
+        documents.push(String::from_utf8_lossy(document).into_owned());
+    }
+    documents
+}
+
+/// Such an index can't be loaded without the feature, see [`Documents::check_supported`].
+#[cfg(not(feature = "compression"))]
+fn decompress(_block: &[u8]) -> Vec<String> {
+    Vec::new()
+}
+
+#[cfg(all(test, feature = "compression"))]
+mod test {
+    use crate::{Index, IndexBuilder, Search};
+
+    #[test]
+    fn test_compression() {
+        // long and repetitive documents spanning multiple blocks
+        let documents: Vec<String> = (0..2000)
+            .map(|i| {
+                format!(
+                    "Document {i} about kefir{} the dog and tamo the cat. ",
+                    i % 7
+                )
+                .repeat(4)
+            })
+            .collect();
+
+        let mut raw = Vec::new();
+        Index::construct(&documents, &mut raw).unwrap();
+        let mut compressed = Vec::new();
+        IndexBuilder::new()
+            .with_compression(true)
+            .construct(&documents, &mut compressed)
+            .unwrap();
+        assert!(
+            compressed.len() * 2 < raw.len(),
+            "{} {}",
+            compressed.len(),
+            raw.len()
+        );
+
+        let raw = Index::from_bytes(&raw).unwrap();
+        let mut index = Index::from_bytes(&compressed).unwrap();
+        assert!(matches!(
+            &index.documents,
+            super::Documents::Compressed { first_ids, .. } if first_ids.len() > 3
+        ));
+        assert_eq!(index.size_stats().total, compressed.len());
+        // every document is retrieved, including the first and last ones of the blocks
+        for id in (0..documents.len() as u32).rev() {
+            assert_eq!(
+                index.get_document(id),
+                Some(documents[id as usize].as_str())
+            );
+        }
+        assert_eq!(index.get_document(documents.len() as u32), None);
+        assert!(index
+            .documents()
+            .map(|(_, document)| document)
+            .eq(&documents));
+        let search = Search::new("kefir3 tamo");
+        assert_eq!(index.search(&search), raw.search(&search));
+        assert_eq!(
+            index.search_with_highlights(&search),
+            raw.search_with_highlights(&search)
+        );
+
+        // the blocks are written back as they are
+        let mut bytes = Vec::new();
+        index.write_to(&mut bytes).unwrap();
+        assert_eq!(bytes, compressed);
+        let mut bytes = Vec::new();
+        IndexBuilder::new()
+            .with_compression(true)
+            .with_memory_budget(4096)
+            .construct_to(&mut bytes, &documents)
+            .unwrap();
+        assert_eq!(bytes, compressed);
+
+        let mut merged = Vec::new();
+        Index::merge(&[&compressed, &compressed], &mut merged).unwrap();
+        let merged = Index::from_bytes(&merged).unwrap();
+        assert!(merged.documents.is_compressed());
+        assert_eq!(merged.get_document(2005), Some(documents[5].as_str()));
+
+        let ids = index.add_documents(&["kefir le chien"]).unwrap();
+        assert_eq!(index.get_document(ids[0]), Some("kefir le chien"));
+        index.delete_documents(&roaring::RoaringBitmap::from_iter(0..1999));
+        let mapping = index.compact().unwrap();
+        assert_eq!(mapping[2000], Some(1));
+        assert!(index.documents.is_compressed());
+        insta::assert_debug_snapshot!(index.documents().collect::<Vec<_>>(), @r###"
+        [
+            (
+                0,
+                "Document 1999 about kefir4 the dog and tamo the cat. Document 1999 about kefir4 the dog and tamo the cat. Document 1999 about kefir4 the dog and tamo the cat. Document 1999 about kefir4 the dog and tamo the cat. ",
+            ),
+            (
+                1,
+                "kefir le chien",
+            ),
+        ]
+        "###);
+    }
+}
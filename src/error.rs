@@ -0,0 +1,58 @@
+/// Everything that can go wrong while constructing, reading or validating an index.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error("Failed to build the fst: {0}")]
+    FstBuild(#[from] fst::Error),
+    #[error("Cannot index {0} documents, the maximum is {}.", u32::MAX)]
+    TooManyDocuments(usize),
+    #[error(
+        "Cannot store a size of {0} in the index, the maximum is {}.",
+        u32::MAX
+    )]
+    TooLarge(usize),
+    #[cfg(feature = "parallel")]
+    #[error(transparent)]
+    ThreadPool(#[from] rayon::ThreadPoolBuildError),
+    #[error("The construction of the index was cancelled.")]
+    Cancelled,
+    #[error(
+        "Cannot merge indexes constructed with different tokenizers, normalizers or stemmers."
+    )]
+    IncompatibleIndexes,
+    #[error("The documents are not stored in the index, see `IndexBuilder::with_documents`.")]
+    MissingDocuments,
+    #[cfg(feature = "ingest")]
+    #[error("Invalid record on line {line}: {reason}.")]
+    InvalidRecord { line: usize, reason: String },
+    #[error("Not a zearch index, the magic bytes are missing.")]
+    NotAnIndex,
+    #[error(
+        "Unsupported index version {0}, only the version {} is supported.",
+        crate::VERSION
+    )]
+    UnsupportedVersion(u16),
+    #[error(
+        "Unsupported normalizer {0}, the `unicode` feature may be required to load this index."
+    )]
+    UnsupportedNormalizer(u8),
+    #[error("Unsupported stemmer {0}, the `stemming` feature may be required to load this index.")]
+    UnsupportedStemmer(u8),
+    #[error(
+        "The documents are compressed, the `compression` feature is required to load this index."
+    )]
+    UnsupportedCompression,
+    #[error("The checksum doesn't match, the index was truncated or modified.")]
+    ChecksumMismatch,
+    #[error("The index is corrupted.")]
+    Corrupted,
+    #[error("The custom ranking rules can't be stored in the index, they're code.")]
+    CustomRankingRule,
+    #[error(
+        "Expected a positive weight for each of the {words} words of the query, got {weights:?}."
+    )]
+    InvalidWordWeights { words: usize, weights: Vec<f32> },
+    #[error("Invalid pattern {pattern:?}: {reason}.")]
+    InvalidPattern { pattern: String, reason: String },
+}
@@ -0,0 +1,119 @@
+//! Trace the decisions of the ranking rules to understand why a document is ranked before another one.
+
+use std::fmt;
+
+use crate::{deadline::Deadline, Index, Search, SearchContext};
+
+/// The buckets returned by the bucket sort in the order of the results, see [`Index::search_explain`].
+#[derive(Default, Clone, PartialEq, Eq)]
+pub struct Explain {
+    pub buckets: Vec<ExplainedBucket>,
+}
+
+/// A bucket of documents with the state of every ranking rule that was called to return it,
+/// the first ranking rule first.
+#[derive(Clone, PartialEq, Eq)]
+pub struct ExplainedBucket {
+    /// The name of the ranking rule and the description of its current bucket, see [`crate::RankingRuleImpl::explain`].
+    pub rules: Vec<(&'static str, String)>,
+    pub ids: Vec<u32>,
+}
+
+impl fmt::Debug for Explain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(&self.buckets).finish()
+    }
+}
+
+/// Written on a single line like `word: tamo est > typo: 0 typos => [0, 3]`.
+impl fmt::Debug for ExplainedBucket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, (name, explain)) in self.rules.iter().enumerate() {
+            if i > 0 {
+                f.write_str(" > ")?;
+            }
+            match explain.is_empty() {
+                true => write!(f, "{name}")?,
+                false => write!(f, "{name}: {explain}")?,
+            }
+        }
+        write!(f, " => {:?}", self.ids)
+    }
+}
+
+impl Index<'_> {
+    /// Run the search like [`Index::search`] but returns the buckets of documents with the reason they were ranked
+    /// this way. The ids of the buckets put together are the results of the search.
+    ///
+    /// ```
+    /// use zearch::{Index, RankingRule, Search};
+    ///
+    /// let index = Index::new_in_memory(&["le beau chein", "le beau chien"]).unwrap();
+    /// let mut search = Search::new("beau chien");
+    /// search.with_ranking_rules(vec![RankingRule::Word, RankingRule::Typo]);
+    /// assert_eq!(
+    ///     format!("{:?}", index.search_explain(&search)),
+    ///     "[word: beau chien > typo: 0 typos => [1], word: beau chien > typo: 1 typos => [0]]"
+    /// );
+    /// ```
+    pub fn search_explain(&self, search: &Search) -> Explain {
+        let mut ctx = SearchContext::default();
+        let deadline = Deadline::new(search);
+        let (mut candidates, _) = self.get_candidates(search, &mut ctx, &deadline);
+        let mut explain = Explain::default();
+        self.bucket_sort(
+            search,
+            &mut candidates,
+            &mut ctx.buckets,
+            &deadline,
+            Some(&mut explain.buckets),
+            None,
+        );
+
+        // like the search, the last bucket is cut at the limit
+        let mut remaining = self.limit(search);
+        explain.buckets.retain_mut(|bucket| {
+            bucket.ids.truncate(remaining);
+            remaining -= bucket.ids.len();
+            !bucket.ids.is_empty()
+        });
+        explain
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{test::create_small_index, RankingRule};
+
+    #[test]
+    fn test_explain() {
+        let index = create_small_index();
+        let mut search = Search::new("kefir chien");
+        search.with_limit(4);
+        insta::assert_debug_snapshot!(index.search_explain(&search), @r###"
+        [
+            word: kefir chien > typo: 0 typos > exact: byte exact => [1, 2],
+        ]
+        "###);
+
+        // the ids match the results of the search
+        let ids: Vec<u32> = index
+            .search_explain(&search)
+            .buckets
+            .into_iter()
+            .flat_map(|bucket| bucket.ids)
+            .collect();
+        assert_eq!(ids, index.search(&search));
+
+        let mut search = Search::new("kefir");
+        search.with_ranking_rules(vec![RankingRule::Sort { descending: true }]);
+        insta::assert_debug_snapshot!(index.search_explain(&search), @r###"
+        [
+            sort: no payloads => [1, 2, 4, 6, 7, 8, 9],
+        ]
+        "###);
+
+        insta::assert_debug_snapshot!(index.search_explain(&Search::new("xyzzy")), @"[]");
+    }
+}
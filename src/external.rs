@@ -0,0 +1,582 @@
+//! Construct an index from more documents than we can hold in memory.
+//! The documents are written in a temporary file as they come, and their words are
+//! sorted in runs that are spilled to temporary files once they exceed the memory budget.
+//! The runs are then merged back to build the fst and the bitmaps.
+
+use std::{
+    cell::Cell,
+    collections::HashSet,
+    fs::File,
+    io::{BufReader, BufWriter, Read, Seek, Write},
+    path::PathBuf,
+    rc::Rc,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use fst::Map;
+
+#[cfg(feature = "compression")]
+use crate::documents::{Compressor, Documents};
+use crate::{
+    bitmaps, build_words, runs::KWayMerge, sort_words, term_frequencies, trace, unique_words,
+    word_count, word_ids, ChecksumWriter, Error, Id, Index, IndexBuilder, Phase, Section,
+    NO_COORDINATES,
+};
+#[cfg(feature = "parallel")]
+use crate::{progress::Reporter, Analyzer};
+
+/// The memory we can use to sort the words before spilling them to disk.
+const DEFAULT_MEMORY_BUDGET: usize = 1024 * 1024 * 1024;
+
+/// The number of documents tokenized at once on the thread pool, see [`IndexBuilder::with_threads`].
+#[cfg(feature = "parallel")]
+const BATCH_SIZE: usize = 4096;
+
+impl IndexBuilder {
+    fn compressed(&self) -> bool {
+        #[cfg(feature = "compression")]
+        return self.compression;
+        #[cfg(not(feature = "compression"))]
+        false
+    }
+
+    /// The memory that can be used to sort the words in [`IndexBuilder::construct_to`], 1GiB by default.
+    /// It doesn't account for the fst and bitmaps.
+    pub fn with_memory_budget(&mut self, bytes: usize) -> &mut Self {
+        self.memory_budget = Some(bytes);
+        self
+    }
+
+    /// Construct the index from an iterator of documents without keeping them in memory.
+    /// The words are spilled to temporary files once they exceed the memory budget,
+    /// see [`IndexBuilder::with_memory_budget`]. With [`IndexBuilder::with_dedup_documents`]
+    /// the normalized words of every distinct document are kept in memory.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(skip_all, fields(documents, elapsed_us))
+    )]
+    pub fn construct_to(
+        &self,
+        writer: &mut impl Write,
+        documents: impl IntoIterator<Item = impl AsRef<str>>,
+    ) -> Result<(), Error> {
+        let _elapsed = trace::Elapsed::start();
+        let documents = documents.into_iter();
+        let reporter = self.reporter();
+        let stop_words = self.normalized_stop_words();
+        let analyzer = self.analyzer(&stop_words);
+
+        // 1. Store the documents and sort their words in runs
+        let total = match documents.size_hint() {
+            (lower, Some(upper)) if lower == upper => Some(lower),
+            _ => None,
+        };
+        reporter.phase(Phase::Tokenizing, total)?;
+        let documents_file = TempFile::new()?;
+        let mut documents_writer = BufWriter::new(&documents_file.file);
+        let mut nb_documents: usize = 0;
+        let mut runs = Runs::new(self.memory_budget.unwrap_or(DEFAULT_MEMORY_BUDGET));
+        let mut buffer = Vec::new();
+        let mut word_counts = Vec::new();
+        // the words of the documents kept and their positions, see `IndexBuilder::with_dedup_documents`
+        let mut seen = self.dedup_documents.then(HashSet::new);
+        let mut kept = Vec::new();
+        #[cfg(feature = "parallel")]
+        let pool = match self.threads {
+            1 => None,
+            threads => Some(
+                rayon::ThreadPoolBuilder::new()
+                    .num_threads(threads)
+                    .build()?,
+            ),
+        };
+        #[cfg(feature = "parallel")]
+        let mut batch = Vec::new();
+
+        for (position, document) in documents.enumerate() {
+            let document = document.as_ref();
+            if let Some(seen) = seen.as_mut() {
+                let words: Vec<String> = analyzer
+                    .tokens(document)
+                    .map(|token| token.normalized)
+                    .collect();
+                // the documents without words are never skipped
+                if !words.is_empty() && !seen.insert(words) {
+                    reporter.advance(1)?;
+                    continue;
+                }
+                kept.push(position);
+            }
+            let id = Id::try_from(nb_documents)
+                .map_err(|_| Error::TooManyDocuments(nb_documents + 1))?;
+            Index::write_slice(&mut documents_writer, document.as_bytes())?;
+            if self.word_counts {
+                word_counts.push(word_count(analyzer, document));
+            }
+            nb_documents += 1;
+
+            #[cfg(feature = "parallel")]
+            if let Some(pool) = &pool {
+                batch.push(document.to_string());
+                if batch.len() == BATCH_SIZE {
+                    let first_id = nb_documents - batch.len();
+                    tokenize_batch(pool, &mut batch, first_id, analyzer, &reporter, &mut runs)?;
+                }
+                continue;
+            }
+            unique_words(document, analyzer, &mut buffer);
+            runs.push(id, buffer.drain(..))?;
+            reporter.advance(1)?;
+        }
+        #[cfg(feature = "parallel")]
+        if let Some(pool) = &pool {
+            let first_id = nb_documents - batch.len();
+            tokenize_batch(pool, &mut batch, first_id, analyzer, &reporter, &mut runs)?;
+        }
+        documents_writer.flush()?;
+        drop(documents_writer);
+        #[cfg(feature = "tracing")]
+        tracing::Span::current().record("documents", nb_documents);
+
+        // 2. Merge the runs to build the fst and the bitmaps
+        let Runs {
+            mut run,
+            words: nb_words,
+            spilled,
+            ..
+        } = runs;
+        reporter.phase(Phase::Sorting, Some(run.len()))?;
+        #[cfg(feature = "parallel")]
+        match &pool {
+            Some(pool) => pool.install(|| {
+                use rayon::prelude::*;
+                run.par_sort_unstable_by(|(_, left), (_, right)| left.cmp(right))
+            }),
+            None => sort_words(&mut run),
+        }
+        #[cfg(not(feature = "parallel"))]
+        sort_words(&mut run);
+        reporter.advance(run.len())?;
+        let error = Rc::new(Cell::new(None));
+        let mut runs: Vec<Box<dyn Iterator<Item = (Id, String)>>> = Vec::new();
+        for file in spilled {
+            runs.push(Box::new(RunReader::new(file, error.clone())?));
+        }
+        runs.push(Box::new(run.into_iter()));
+        let (fst, bitmaps) = build_words(KWayMerge::new(runs), nb_words, &reporter)?;
+        if let Some(error) = error.take() {
+            return Err(error.into());
+        }
+        let common_words = self.common_words(&bitmaps, nb_documents);
+
+        // 3. Write everything in the same format as `Index::write_to`
+        let writer = &mut ChecksumWriter::new(writer);
+        Index::write_header(writer, self.tokenizer, self.normalizer, self.stemmer)?;
+
+        reporter.phase(Phase::WritingDocuments, Some(nb_documents))?;
+        if self.without_documents || self.compressed() {
+            Index::write_size(writer, 0)?;
+        } else {
+            Index::write_size(writer, nb_documents)?;
+            (&documents_file.file).rewind()?;
+            std::io::copy(&mut BufReader::new(&documents_file.file), writer)?;
+        }
+        reporter.advance(nb_documents)?;
+
+        reporter.phase(Phase::WritingBitmaps, Some(bitmaps.len()))?;
+        Index::write_size(writer, bitmaps.len())?;
+        let mut buffer = Vec::new();
+        for bitmap in bitmaps {
+            buffer.clear();
+            bitmaps::serialize(&bitmap, &mut buffer)?;
+            Index::write_slice(writer, &buffer)?;
+            reporter.advance(1)?;
+        }
+
+        Index::write_slice(writer, &fst)?;
+
+        if self.without_documents {
+            Index::write_document_count(writer, nb_documents)?;
+        } else if self.compressed() {
+            #[cfg(feature = "compression")]
+            write_compressed_documents(writer, &documents_file, nb_documents)?;
+        }
+
+        let fst = Map::new(fst)?;
+        if self.positions {
+            // we can't hold the section in memory, thus we compute its size first
+            let mut size = 0;
+            for_each_document(&documents_file, nb_documents, |document| {
+                size += (1 + word_ids(&fst, analyzer, document).len()) * std::mem::size_of::<u32>();
+                Ok(())
+            })?;
+
+            writer.write_all(&[Section::Positions as u8])?;
+            Index::write_size(writer, size)?;
+            for_each_document(&documents_file, nb_documents, |document| {
+                let ids = word_ids(&fst, analyzer, document);
+                Index::write_size(writer, ids.len())?;
+                for id in ids {
+                    writer.write_all(&id.to_be_bytes())?;
+                }
+                Ok(())
+            })?;
+        }
+        if self.word_counts {
+            Index::write_word_counts(writer, &word_counts)?;
+        }
+        if self.term_frequencies {
+            // like the positions, the size of the section is computed first
+            let entry = std::mem::size_of::<u32>() + std::mem::size_of::<u16>();
+            let mut size = 0;
+            for_each_document(&documents_file, nb_documents, |document| {
+                let frequencies = term_frequencies(word_ids(&fst, analyzer, document));
+                size += std::mem::size_of::<u32>() + frequencies.len() * entry;
+                Ok(())
+            })?;
+
+            writer.write_all(&[Section::TermFrequencies as u8])?;
+            Index::write_size(writer, size)?;
+            for_each_document(&documents_file, nb_documents, |document| {
+                let frequencies = term_frequencies(word_ids(&fst, analyzer, document));
+                Index::write_term_frequencies(writer, &frequencies)
+            })?;
+        }
+
+        let kept = seen.map(|_| kept);
+        if !self.boosts.is_empty() {
+            let mut boosts: Vec<f32> = keep(&self.boosts, kept.as_deref());
+            boosts.resize(nb_documents, 1.0);
+            Index::write_boosts(writer, &boosts)?;
+        }
+        if !self.coordinates.is_empty() {
+            let mut coordinates: Vec<(f32, f32)> = keep(&self.coordinates, kept.as_deref());
+            coordinates.resize(nb_documents, NO_COORDINATES);
+            Index::write_coordinates(writer, &coordinates)?;
+        }
+        Index::write_search_defaults(writer, self.search_defaults().as_ref())?;
+
+        Index::write_words(writer, Section::StopWords, &stop_words)?;
+        Index::write_words(writer, Section::ExactWords, &self.normalized_exact_words())?;
+        Index::write_common_words(writer, &common_words)?;
+
+        writer.finish()?;
+
+        Ok(())
+    }
+}
+
+/// Compress the documents stored in the file to another temporary file,
+/// and then write the section with the size of the blocks we now know.
+#[cfg(feature = "compression")]
+fn write_compressed_documents(
+    writer: &mut impl Write,
+    documents_file: &TempFile,
+    nb_documents: usize,
+) -> Result<(), Error> {
+    let blocks_file = TempFile::new()?;
+    let mut blocks_writer = BufWriter::new(&blocks_file.file);
+    let mut first_ids = Vec::new();
+    let mut size = 0;
+    let mut write = |(first, block): (u32, Vec<u8>)| {
+        first_ids.push(first);
+        size += std::mem::size_of::<u32>() + block.len();
+        Index::write_slice(&mut blocks_writer, &block)
+    };
+    let mut compressor = Compressor::default();
+    for_each_document(documents_file, nb_documents, |document| {
+        compressor.push(document)?.map_or(Ok(()), &mut write)
+    })?;
+    compressor.finish().map_or(Ok(()), &mut write)?;
+    blocks_writer.flush()?;
+    drop(blocks_writer);
+
+    let mut header = Vec::new();
+    Documents::write_compressed_header(&mut header, nb_documents, &first_ids)?;
+    writer.write_all(&[Section::CompressedDocuments as u8])?;
+    Index::write_size(writer, header.len() + size)?;
+    writer.write_all(&header)?;
+    (&blocks_file.file).rewind()?;
+    std::io::copy(&mut BufReader::new(&blocks_file.file), writer)?;
+    Ok(())
+}
+
+/// The words of the documents in the order they come, sorted and spilled to a temporary file
+/// once they exceed the memory budget.
+struct Runs {
+    memory_budget: usize,
+    run: Vec<(Id, String)>,
+    // the memory used by the run
+    size: usize,
+    // the number of words of all the runs
+    words: usize,
+    spilled: Vec<TempFile>,
+}
+
+impl Runs {
+    fn new(memory_budget: usize) -> Self {
+        Self {
+            memory_budget,
+            run: Vec::new(),
+            size: 0,
+            words: 0,
+            spilled: Vec::new(),
+        }
+    }
+
+    fn push(&mut self, id: Id, words: impl IntoIterator<Item = String>) -> Result<(), Error> {
+        for word in words {
+            self.size += word.len() + std::mem::size_of::<(Id, String)>();
+            self.run.push((id, word));
+            self.words += 1;
+        }
+        if self.size > self.memory_budget {
+            self.spilled.push(spill(&mut self.run)?);
+            self.size = 0;
+        }
+        Ok(())
+    }
+}
+
+/// Tokenize the documents of the batch on the thread pool and push their words in the runs,
+/// the first document gets the id `first_id`. The batch is left empty.
+#[cfg(feature = "parallel")]
+fn tokenize_batch(
+    pool: &rayon::ThreadPool,
+    batch: &mut Vec<String>,
+    first_id: usize,
+    analyzer: Analyzer,
+    reporter: &Reporter,
+    runs: &mut Runs,
+) -> Result<(), Error> {
+    use rayon::prelude::*;
+
+    let words = pool.install(|| {
+        batch
+            .par_iter()
+            .map(|document| {
+                let mut words = Vec::new();
+                unique_words(document, analyzer, &mut words);
+                reporter.advance(1)?;
+                Ok(words)
+            })
+            .collect::<Result<Vec<Vec<String>>, Error>>()
+    })?;
+    for (id, words) in (first_id as Id..).zip(words) {
+        runs.push(id, words)?;
+    }
+    batch.clear();
+    Ok(())
+}
+
+/// The items of the documents kept by the deduplication, in the order of their new ids.
+fn keep<T: Copy>(items: &[T], kept: Option<&[usize]>) -> Vec<T> {
+    match kept {
+        Some(kept) => kept
+            .iter()
+            .map_while(|position| items.get(*position))
+            .copied()
+            .collect(),
+        None => items.to_vec(),
+    }
+}
+
+/// Sort the run and write it to a temporary file.
+fn spill(run: &mut Vec<(Id, String)>) -> Result<TempFile, Error> {
+    sort_words(run);
+
+    let file = TempFile::new()?;
+    let mut writer = BufWriter::new(&file.file);
+    for (id, word) in run.drain(..) {
+        writer.write_all(&id.to_be_bytes())?;
+        Index::write_slice(&mut writer, word.as_bytes())?;
+    }
+    writer.flush()?;
+    drop(writer);
+
+    Ok(file)
+}
+
+/// Calls `f` on every document stored in the file.
+fn for_each_document(
+    file: &TempFile,
+    nb_documents: usize,
+    mut f: impl FnMut(&str) -> Result<(), Error>,
+) -> Result<(), Error> {
+    (&file.file).rewind()?;
+    let mut reader = BufReader::new(&file.file);
+    let mut document = Vec::new();
+    for _ in 0..nb_documents {
+        read_slice(&mut reader, &mut document)?;
+        f(std::str::from_utf8(&document).map_err(|_| Error::Corrupted)?)?;
+    }
+    Ok(())
+}
+
+fn read_slice(reader: &mut impl Read, buffer: &mut Vec<u8>) -> std::io::Result<()> {
+    let mut size = [0; std::mem::size_of::<u32>()];
+    reader.read_exact(&mut size)?;
+    buffer.resize(u32::from_be_bytes(size) as usize, 0);
+    reader.read_exact(buffer)
+}
+
+/// Read back a run that was spilled to disk.
+/// Since it's used as an iterator, the first error is stored in `error` and stops the iteration.
+struct RunReader {
+    reader: BufReader<File>,
+    error: Rc<Cell<Option<std::io::Error>>>,
+    // deletes the file once we're done
+    _file: TempFile,
+}
+
+impl RunReader {
+    fn new(file: TempFile, error: Rc<Cell<Option<std::io::Error>>>) -> std::io::Result<Self> {
+        (&file.file).rewind()?;
+        Ok(Self {
+            reader: BufReader::new(file.file.try_clone()?),
+            error,
+            _file: file,
+        })
+    }
+
+    fn read(&mut self) -> std::io::Result<Option<(Id, String)>> {
+        let mut id = [0; std::mem::size_of::<Id>()];
+        match self.reader.read_exact(&mut id) {
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+            result => result?,
+        }
+        let mut word = Vec::new();
+        read_slice(&mut self.reader, &mut word)?;
+        let word = String::from_utf8(word)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        Ok(Some((Id::from_be_bytes(id), word)))
+    }
+}
+
+impl Iterator for RunReader {
+    type Item = (Id, String);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read().unwrap_or_else(|error| {
+            self.error.set(Some(error));
+            None
+        })
+    }
+}
+
+/// A file in the temporary directory that's removed once dropped.
+struct TempFile {
+    path: PathBuf,
+    file: File,
+}
+
+impl TempFile {
+    fn new() -> std::io::Result<Self> {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let path = std::env::temp_dir().join(format!(
+            "zearch-{}-{}.tmp",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        let file = File::options()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(&path)?;
+        Ok(Self { path, file })
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_same_output_as_in_memory() {
+        let documents: Vec<String> = (0..300)
+            .map(|i| format!("Document {} about {} and kefir{}", i, i * 7919 % 301, i % 7))
+            .collect();
+
+        for (positions, store) in [(false, true), (true, true), (true, false)] {
+            let mut builder = IndexBuilder::new();
+            builder
+                .with_positions(positions)
+                .with_documents(store)
+                .with_stop_words(&["and"])
+                .with_max_document_frequency(0.2);
+            let expected = in_memory(&builder, &documents);
+
+            // everything fits in memory
+            let mut bytes = Vec::new();
+            builder.construct_to(&mut bytes, &documents).unwrap();
+            assert_eq!(bytes, expected);
+
+            // we spill a run for every document
+            let mut bytes = Vec::new();
+            builder
+                .with_memory_budget(1)
+                .construct_to(&mut bytes, &documents)
+                .unwrap();
+            assert_eq!(bytes, expected);
+
+            // and sometimes
+            let mut bytes = Vec::new();
+            builder
+                .with_memory_budget(4096)
+                .construct_to(&mut bytes, documents.iter().map(|doc| doc.as_str()))
+                .unwrap();
+            assert_eq!(bytes, expected);
+        }
+    }
+
+    #[test]
+    fn test_dedup_same_output_as_in_memory() {
+        let documents: Vec<String> = (0..5000)
+            .map(|i| match i % 3 {
+                0 => String::new(),
+                _ => format!("Kefir {}", i % 11),
+            })
+            .collect();
+        let boosts: Vec<f32> = (0..20).map(|i| i as f32).collect();
+        let coordinates: Vec<(f32, f32)> = (0..30).map(|i| (i as f32, 0.0)).collect();
+
+        let mut builder = IndexBuilder::new();
+        builder
+            .with_dedup_documents(true)
+            .with_word_counts(true)
+            .with_boosts(&boosts)
+            .with_coordinates(&coordinates);
+        let expected = in_memory(&builder, &documents);
+
+        let mut bytes = Vec::new();
+        builder.construct(&documents, &mut bytes).unwrap();
+        assert_eq!(bytes, expected);
+
+        // the documents are tokenized by batches on the thread pool
+        #[cfg(feature = "parallel")]
+        builder.with_threads(4);
+        let mut bytes = Vec::new();
+        builder
+            .with_memory_budget(64)
+            .construct_to(&mut bytes, &documents)
+            .unwrap();
+        assert_eq!(bytes, expected);
+    }
+
+    /// The bytes of the index built in memory, like [`IndexBuilder::construct`] used to.
+    fn in_memory(builder: &IndexBuilder, documents: &[String]) -> Vec<u8> {
+        let documents = documents.iter().map(|doc| doc.into()).collect();
+        let index = builder
+            .build(documents, None, false, &crate::progress::Reporter::silent())
+            .unwrap();
+        let mut bytes = Vec::new();
+        index.write_to(&mut bytes).unwrap();
+        bytes
+    }
+}
@@ -0,0 +1,136 @@
+//! Search multiple indexes at once and merge their results in a single list, like one index per country.
+//! The scores of two indexes can't be compared thus the buckets are interleaved by their rank:
+//! the first bucket of every index comes before the second bucket of any index.
+
+use roaring::RoaringBitmap;
+
+use crate::{Index, Search, SearchContext, SearchDefaults};
+
+/// A document returned by [`MultiIndex::search`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FederatedHit {
+    /// The position of the index in the [`MultiIndex`].
+    pub index: usize,
+    /// The id of the document in its index.
+    pub id: u32,
+}
+
+/// Indexes searched together, each of them with a name.
+///
+/// ```
+/// use zearch::{FederatedHit, Index, MultiIndex, Search};
+///
+/// let france = Index::new_in_memory(&["Paris", "Parisot"]).unwrap();
+/// let usa = Index::new_in_memory(&["Paris, Texas"]).unwrap();
+/// let multi = MultiIndex::new(vec![("fr", &france), ("us", &usa)]);
+/// let hits = multi.search(&Search::new("paris"));
+/// assert_eq!(hits[0], FederatedHit { index: 0, id: 0 });
+/// assert_eq!(multi.name(hits[1].index), Some("us"));
+/// ```
+pub struct MultiIndex<'a> {
+    indexes: Vec<(&'a str, &'a Index<'a>)>,
+}
+
+impl<'a> MultiIndex<'a> {
+    pub fn new(indexes: Vec<(&'a str, &'a Index<'a>)>) -> Self {
+        Self { indexes }
+    }
+
+    /// Returns the name of the index at this position, see [`FederatedHit::index`].
+    pub fn name(&self, index: usize) -> Option<&'a str> {
+        self.indexes.get(index).map(|(name, _)| *name)
+    }
+
+    /// Returns the index at this position, to retrieve the documents of the hits.
+    pub fn index(&self, index: usize) -> Option<&'a Index<'a>> {
+        self.indexes.get(index).map(|(_, index)| *index)
+    }
+
+    /// Search every index and interleave their buckets, the ties are broken by the order of the indexes.
+    /// At most [`Search::with_limit`] documents are returned in total.
+    /// With the `parallel` feature the indexes are searched on the rayon thread pool.
+    pub fn search(&self, search: &Search) -> Vec<FederatedHit> {
+        #[cfg(feature = "parallel")]
+        let buckets: Vec<Vec<RoaringBitmap>> = {
+            use rayon::prelude::*;
+
+            self.indexes
+                .par_iter()
+                .map_init(SearchContext::default, |ctx, (_, index)| {
+                    index.search_buckets(search, ctx)
+                })
+                .collect()
+        };
+        #[cfg(not(feature = "parallel"))]
+        let buckets: Vec<Vec<RoaringBitmap>> = {
+            let mut ctx = SearchContext::default();
+            self.indexes
+                .iter()
+                .map(|(_, index)| index.search_buckets(search, &mut ctx))
+                .collect()
+        };
+
+        // the indexes may store different limits, the first one is used
+        let limit = self
+            .indexes
+            .first()
+            .map_or(SearchDefaults::default().limit, |(_, index)| {
+                index.limit(search)
+            });
+        let ranks = buckets.iter().map(Vec::len).max().unwrap_or(0);
+        (0..ranks)
+            .flat_map(|rank| {
+                buckets
+                    .iter()
+                    .enumerate()
+                    .flat_map(move |(index, buckets)| {
+                        buckets.get(rank).into_iter().flat_map(move |bucket| {
+                            search
+                                .tie_break
+                                .iter(bucket)
+                                .map(move |id| FederatedHit { index, id })
+                        })
+                    })
+            })
+            .take(limit)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::RankingRule;
+
+    fn hits(hits: &[FederatedHit]) -> Vec<(usize, u32)> {
+        hits.iter().map(|hit| (hit.index, hit.id)).collect()
+    }
+
+    #[test]
+    fn test_interleave() {
+        let france = Index::new_in_memory(&["le chien", "le chein", "le chat"]).unwrap();
+        let belgium = Index::new_in_memory(&["un chein", "un chien"]).unwrap();
+        let multi = MultiIndex::new(vec![("fr", &france), ("be", &belgium)]);
+        assert_eq!(multi.name(1), Some("be"));
+        assert_eq!(multi.name(2), None);
+
+        let mut search = Search::new("chien");
+        search.with_ranking_rules(vec![RankingRule::Word, RankingRule::Typo]);
+        // the exact matches of both indexes come before the typos
+        assert_eq!(
+            hits(&multi.search(&search)),
+            [(0, 0), (1, 1), (0, 1), (1, 0)]
+        );
+
+        search.with_limit(3);
+        assert_eq!(hits(&multi.search(&search)), [(0, 0), (1, 1), (0, 1)]);
+
+        // an index without results doesn't shift the others
+        let empty = Index::new_in_memory(&["le chat"]).unwrap();
+        let multi = MultiIndex::new(vec![("ch", &empty), ("be", &belgium)]);
+        search.with_limit(10);
+        assert_eq!(hits(&multi.search(&search)), [(1, 1), (1, 0)]);
+
+        assert_eq!(MultiIndex::new(Vec::new()).search(&search), []);
+    }
+}
@@ -0,0 +1,182 @@
+//! A C interface to load and search an index, with the `ffi` feature.
+//! The header is `include/zearch.h`, generated by `cbindgen` with the `cbindgen.toml` of the repository.
+//! Every function catches the panics instead of unwinding into C and returns a [`ZearchError`].
+
+use std::{
+    ffi::{c_char, CStr},
+    panic::{catch_unwind, AssertUnwindSafe},
+};
+
+use crate::{Error, Index, Search};
+
+/// An index owning its bytes, created by [`zearch_index_from_bytes`] and freed by [`zearch_index_free`].
+pub struct ZearchIndex {
+    index: Index<'static>,
+}
+
+/// Returned by every function, the variants after `InvalidPattern` are specific to the C interface.
+/// The other ones mirror [`Error`] and keep their value whatever the features.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ZearchError {
+    Ok = 0,
+    Io,
+    FstBuild,
+    TooManyDocuments,
+    TooLarge,
+    ThreadPool,
+    Cancelled,
+    IncompatibleIndexes,
+    MissingDocuments,
+    InvalidRecord,
+    NotAnIndex,
+    UnsupportedVersion,
+    UnsupportedNormalizer,
+    UnsupportedStemmer,
+    UnsupportedCompression,
+    ChecksumMismatch,
+    Corrupted,
+    CustomRankingRule,
+    InvalidWordWeights,
+    InvalidPattern,
+    /// A pointer given to the function was null.
+    NullPointer,
+    /// The query is not valid UTF-8.
+    InvalidUtf8,
+    /// The document doesn't exist or was deleted.
+    NotFound,
+    /// The function panicked, it's a bug.
+    Panic,
+}
+
+impl From<Error> for ZearchError {
+    fn from(error: Error) -> Self {
+        match error {
+            Error::Io(_) => Self::Io,
+            Error::FstBuild(_) => Self::FstBuild,
+            Error::TooManyDocuments(_) => Self::TooManyDocuments,
+            Error::TooLarge(_) => Self::TooLarge,
+            #[cfg(feature = "parallel")]
+            Error::ThreadPool(_) => Self::ThreadPool,
+            Error::Cancelled => Self::Cancelled,
+            Error::IncompatibleIndexes => Self::IncompatibleIndexes,
+            Error::MissingDocuments => Self::MissingDocuments,
+            #[cfg(feature = "ingest")]
+            Error::InvalidRecord { .. } => Self::InvalidRecord,
+            Error::NotAnIndex => Self::NotAnIndex,
+            Error::UnsupportedVersion(_) => Self::UnsupportedVersion,
+            Error::UnsupportedNormalizer(_) => Self::UnsupportedNormalizer,
+            Error::UnsupportedStemmer(_) => Self::UnsupportedStemmer,
+            Error::UnsupportedCompression => Self::UnsupportedCompression,
+            Error::ChecksumMismatch => Self::ChecksumMismatch,
+            Error::Corrupted => Self::Corrupted,
+            Error::CustomRankingRule => Self::CustomRankingRule,
+            Error::InvalidWordWeights { .. } => Self::InvalidWordWeights,
+            Error::InvalidPattern { .. } => Self::InvalidPattern,
+        }
+    }
+}
+
+fn guard(f: impl FnOnce() -> Result<(), ZearchError>) -> ZearchError {
+    match catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(())) => ZearchError::Ok,
+        Ok(Err(error)) => error,
+        Err(_) => ZearchError::Panic,
+    }
+}
+
+/// Load an index written by `Index::construct` and verify its checksum.
+/// The bytes are copied, they can be freed once the function returns.
+///
+/// # Safety
+///
+/// `bytes` must point to `len` readable bytes. On success `*out` is an index that must be
+/// freed with [`zearch_index_free`], it's left untouched otherwise.
+#[no_mangle]
+pub unsafe extern "C" fn zearch_index_from_bytes(
+    bytes: *const u8,
+    len: usize,
+    out: *mut *mut ZearchIndex,
+) -> ZearchError {
+    guard(|| {
+        if bytes.is_null() || out.is_null() {
+            return Err(ZearchError::NullPointer);
+        }
+        let bytes = std::slice::from_raw_parts(bytes, len);
+        let index = Index::from_reader(bytes)?;
+        *out = Box::into_raw(Box::new(ZearchIndex { index }));
+        Ok(())
+    })
+}
+
+/// Free an index, the documents returned by [`zearch_get_document`] are freed with it.
+///
+/// # Safety
+///
+/// `index` must come from [`zearch_index_from_bytes`] and not be used afterward. It can be null.
+#[no_mangle]
+pub unsafe extern "C" fn zearch_index_free(index: *mut ZearchIndex) {
+    if !index.is_null() {
+        // there is nothing to report if dropping the index panics
+        let _ = catch_unwind(AssertUnwindSafe(|| drop(Box::from_raw(index))));
+    }
+}
+
+/// Search the index and write the ids of up to `limit` documents in `out_ids`, the best ones first.
+/// `*out_len` is set to the number of ids written.
+///
+/// # Safety
+///
+/// `index` must be a valid index, `query` a null terminated string
+/// and `out_ids` must have room for `limit` ids.
+#[no_mangle]
+pub unsafe extern "C" fn zearch_search(
+    index: *const ZearchIndex,
+    query: *const c_char,
+    limit: u32,
+    out_ids: *mut u32,
+    out_len: *mut usize,
+) -> ZearchError {
+    guard(|| {
+        if index.is_null() || query.is_null() || out_ids.is_null() || out_len.is_null() {
+            return Err(ZearchError::NullPointer);
+        }
+        let query = CStr::from_ptr(query)
+            .to_str()
+            .map_err(|_| ZearchError::InvalidUtf8)?;
+        let mut search = Search::new(query);
+        search.with_limit(limit as usize);
+        let ids = (*index).index.search(&search);
+        std::ptr::copy_nonoverlapping(ids.as_ptr(), out_ids, ids.len());
+        *out_len = ids.len();
+        Ok(())
+    })
+}
+
+/// Set `*out_ptr` and `*out_len` to the UTF-8 text of the document, it's not null terminated.
+/// It's borrowed from the index and stays valid until the index is freed.
+///
+/// # Safety
+///
+/// `index` must be a valid index.
+#[no_mangle]
+pub unsafe extern "C" fn zearch_get_document(
+    index: *const ZearchIndex,
+    id: u32,
+    out_ptr: *mut *const c_char,
+    out_len: *mut usize,
+) -> ZearchError {
+    guard(|| {
+        if index.is_null() || out_ptr.is_null() || out_len.is_null() {
+            return Err(ZearchError::NullPointer);
+        }
+        let index = &(*index).index;
+        if !index.documents.is_stored() {
+            return Err(ZearchError::MissingDocuments);
+        }
+        let document = index.get_document(id).ok_or(ZearchError::NotFound)?;
+        *out_ptr = document.as_ptr().cast();
+        *out_len = document.len();
+        Ok(())
+    })
+}
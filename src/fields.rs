@@ -0,0 +1,141 @@
+//! A document can be made of multiple fields, like a name and its aliases, see [`crate::IndexBuilder::construct_with_fields`].
+//! The fields of a document are stored joined by newlines, which every tokenizer splits on,
+//! thus the text of a document is split on its newlines to find its fields again.
+//! For every word we keep the documents containing it in each field so the attribute ranking rule
+//! can rank up the documents matching the query in their first fields, see [`crate::RankingRule::Attribute`].
+
+use fst::Map;
+use roaring::RoaringBitmap;
+
+use crate::{Analyzer, Error, Id, Index};
+
+/// Separates the fields in the text of a document.
+pub(crate) const FIELD_SEPARATOR: char = '\n';
+
+#[derive(Debug, Default)]
+pub(crate) struct Fields {
+    /// The number of fields of the document with the most fields.
+    pub count: usize,
+    /// For every word, the documents containing it in each field.
+    /// The fields after the last one containing the word may be missing.
+    pub words: Vec<Vec<RoaringBitmap>>,
+}
+
+impl Fields {
+    /// Returns the text of a document made of these fields.
+    /// A newline in a field is replaced by a space since it separates the fields.
+    pub fn join(fields: &[impl AsRef<str>]) -> String {
+        let fields: Vec<String> = fields
+            .iter()
+            .map(|field| field.as_ref().replace(FIELD_SEPARATOR, " "))
+            .collect();
+        fields.join(&FIELD_SEPARATOR.to_string())
+    }
+
+    /// Returns the fields of the text of a document.
+    pub fn split(document: &str) -> impl Iterator<Item = &str> {
+        document.split(FIELD_SEPARATOR)
+    }
+
+    /// Record the fields of the words of the documents, the first document gets the id `first_id`.
+    /// The words must all be in the fst, which may contain words we don't know yet.
+    pub fn insert(
+        &mut self,
+        fst: &Map<impl AsRef<[u8]>>,
+        analyzer: Analyzer,
+        first_id: Id,
+        documents: &[impl AsRef<str>],
+    ) {
+        self.words.resize_with(fst.len(), Vec::new);
+        for (id, document) in documents.iter().enumerate() {
+            for (field, text) in Self::split(document.as_ref()).enumerate() {
+                self.count = self.count.max(field + 1);
+                for (_, word) in analyzer.words(text) {
+                    let Some(word_id) = fst.get(word) else {
+                        continue;
+                    };
+                    let fields = &mut self.words[word_id as u32 as usize];
+                    if fields.len() <= field {
+                        fields.resize_with(field + 1, RoaringBitmap::new);
+                    }
+                    fields[field].insert(first_id + id as Id);
+                }
+            }
+        }
+    }
+
+    /// Returns the documents containing the word in the field.
+    pub fn get(&self, word_id: u32, field: usize) -> Option<&RoaringBitmap> {
+        self.words.get(word_id as usize)?.get(field)
+    }
+
+    /// Append the fields of another index whose words got the ids `word_ids` and documents are offset by `offset`.
+    pub fn append(&mut self, other: &Fields, word_ids: &[u32], offset: u32) {
+        self.count = self.count.max(other.count);
+        for (old_id, fields) in other.words.iter().enumerate() {
+            let ids = &mut self.words[word_ids[old_id] as usize];
+            if ids.len() < fields.len() {
+                ids.resize_with(fields.len(), RoaringBitmap::new);
+            }
+            for (field, documents) in fields.iter().enumerate() {
+                ids[field].extend(documents.iter().map(|id| id + offset));
+            }
+        }
+    }
+
+    /// Write the number of fields followed by the bitmap of every field of every word.
+    pub fn write(&self, writer: &mut impl std::io::Write) -> Result<(), Error> {
+        Index::write_size(writer, self.count)?;
+        let mut buffer = Vec::new();
+        for fields in self.words.iter() {
+            for field in 0..self.count {
+                buffer.clear();
+                match fields.get(field) {
+                    Some(documents) if !documents.is_empty() => {
+                        documents.serialize_into(&mut buffer)?
+                    }
+                    _ => (),
+                }
+                Index::write_slice(writer, &buffer)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the size of the section written by [`Fields::write`].
+    pub fn serialized_size(&self) -> usize {
+        const SIZE: usize = std::mem::size_of::<u32>();
+        let bitmaps: usize = self
+            .words
+            .iter()
+            .flatten()
+            .filter(|documents| !documents.is_empty())
+            .map(RoaringBitmap::serialized_size)
+            .sum();
+        SIZE + self.words.len() * self.count * SIZE + bitmaps
+    }
+
+    pub fn read(mut section: &[u8], nb_words: u32, nb_documents: u32) -> Option<Self> {
+        let count = Index::read_size_from_bytes(&mut section)? as usize;
+        // every field of every word is prefixed by its size
+        if (nb_words as usize).checked_mul(count)?.checked_mul(4)? > section.len() {
+            return None;
+        }
+        let mut words = Vec::with_capacity(nb_words as usize);
+        for _ in 0..nb_words {
+            let mut fields = Vec::with_capacity(count);
+            for _ in 0..count {
+                let documents = match Index::read_slice_from_bytes(&mut section)? {
+                    [] => RoaringBitmap::new(),
+                    bytes => RoaringBitmap::deserialize_from(bytes).ok()?,
+                };
+                if documents.max().is_some_and(|max| max >= nb_documents) {
+                    return None;
+                }
+                fields.push(documents);
+            }
+            words.push(fields);
+        }
+        section.is_empty().then_some(Self { count, words })
+    }
+}
@@ -0,0 +1,123 @@
+//! Share an index between threads and replace it while it's being searched,
+//! for example with an index rebuilt in the background.
+
+use std::sync::{Arc, RwLock};
+
+use crate::{Index, Search};
+
+// the index is shared between the threads searching it
+const _: () = {
+    const fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Index<'static>>();
+};
+
+/// An index that can be searched from multiple threads and swapped for another one at any time.
+/// The searches that already started keep using the index they started with.
+///
+/// ```
+/// use std::sync::Arc;
+/// use zearch::{Index, IndexHandle, Search};
+///
+/// let handle = Arc::new(IndexHandle::new(Index::new_in_memory(&["kefir"]).unwrap()));
+/// assert_eq!(handle.search(&Search::new("kefir")), [0]);
+///
+/// let old = handle.swap(Index::new_in_memory(&["tamo", "kefir"]).unwrap());
+/// assert_eq!(old.len(), 1);
+/// assert_eq!(handle.search(&Search::new("kefir")), [1]);
+/// ```
+pub struct IndexHandle {
+    // the lock is only held to clone or replace the `Arc`, never during a search
+    current: RwLock<Arc<Index<'static>>>,
+}
+
+impl IndexHandle {
+    pub fn new(index: Index<'static>) -> Self {
+        Self {
+            current: RwLock::new(Arc::new(index)),
+        }
+    }
+
+    /// Returns the current index, it stays valid even if it's swapped in the meantime.
+    /// Use it to run multiple operations on the same index, like a search and then [`Index::get_documents`].
+    pub fn current(&self) -> Arc<Index<'static>> {
+        self.current
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone()
+    }
+
+    /// Search the current index, see [`Index::search`].
+    pub fn search(&self, search: &Search) -> Vec<u32> {
+        self.current().search(search)
+    }
+
+    /// Replace the index and returns the previous one.
+    /// It's dropped once the last search using it is done, unless the returned `Arc` is kept.
+    pub fn swap(&self, index: Index<'static>) -> Arc<Index<'static>> {
+        let mut current = self
+            .current
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        std::mem::replace(&mut *current, Arc::new(index))
+    }
+}
+
+impl From<Index<'static>> for IndexHandle {
+    fn from(index: Index<'static>) -> Self {
+        Self::new(index)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    use super::*;
+
+    #[test]
+    fn test_swap_while_searching() {
+        let small: Vec<String> = (0..10).map(|i| format!("kefir {i}")).collect();
+        let large: Vec<String> = (0..50).map(|i| format!("kefir chien {i}")).collect();
+        let construct = |documents: &[String]| {
+            let mut bytes = Vec::new();
+            Index::construct(documents, &mut bytes).unwrap();
+            Index::from_bytes(&bytes).unwrap().move_in_memory()
+        };
+        let handle = IndexHandle::new(construct(&small));
+        let stop = AtomicBool::new(false);
+
+        std::thread::scope(|s| {
+            let readers: Vec<_> = (0..4)
+                .map(|_| {
+                    s.spawn(|| {
+                        let mut search = Search::new("kefir");
+                        search.with_limit(100);
+                        while !stop.load(Ordering::Relaxed) {
+                            let index = handle.current();
+                            let ids = index.search(&search);
+                            // all the results come from the same index
+                            let large = match index.len() {
+                                10 => false,
+                                50 => true,
+                                len => panic!("unexpected index of {len} documents"),
+                            };
+                            assert_eq!(ids, (0..index.len() as u32).collect::<Vec<_>>());
+                            let document = index.get_document(ids[ids.len() - 1]).unwrap();
+                            assert_eq!(document.contains("chien"), large);
+                        }
+                    })
+                })
+                .collect();
+
+            for i in 0..200 {
+                let documents = if i % 2 == 0 { &large } else { &small };
+                handle.swap(construct(documents));
+            }
+            stop.store(true, Ordering::Relaxed);
+            for reader in readers {
+                reader.join().unwrap();
+            }
+        });
+        assert_eq!(handle.search(&Search::new("chien")), Vec::<u32>::new());
+    }
+}
@@ -0,0 +1,369 @@
+//! Find where the words of the query matched in the documents returned by a search,
+//! to show them in bold for example, and crop the long documents around their matches.
+
+use roaring::RoaringBitmap;
+
+use crate::{deadline::Deadline, Index, Search, SearchContext, Tokenizer, Whitespace};
+
+/// A word of a document matching a word of the query.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MatchRange {
+    /// The offset of the first byte of the word in the document.
+    pub start: usize,
+    /// The offset right after the last byte of the word in the document.
+    pub end: usize,
+    /// The position of the word in the query, see [`crate::WordCandidate::query_index`].
+    pub query_word: usize,
+}
+
+impl Index<'_> {
+    /// Same as [`Index::search`] but also returns where the words of the query matched in every document.
+    /// A word of the document matched by a prefix or with typos is highlighted entirely,
+    /// and it's only attributed to the first word of the query it matched.
+    ///
+    /// ```
+    /// use zearch::{Index, MatchRange, Search};
+    ///
+    /// let index = Index::new_in_memory(&["Le beau chien"]).unwrap();
+    /// let results = index.search_with_highlights(&Search::new("baeu ch"));
+    /// assert_eq!(
+    ///     results[0].1,
+    ///     [
+    ///         MatchRange { start: 3, end: 7, query_word: 0 },
+    ///         MatchRange { start: 8, end: 13, query_word: 1 },
+    ///     ]
+    /// );
+    /// ```
+    pub fn search_with_highlights(&self, search: &Search) -> Vec<(u32, Vec<MatchRange>)> {
+        let mut ctx = SearchContext::default();
+        let deadline = Deadline::new(search);
+        let (mut candidates, _) = self.get_candidates(search, &mut ctx, &deadline);
+        // the ranking rules can drop the candidates
+        let matched: Vec<(usize, RoaringBitmap)> = candidates
+            .iter()
+            .map(|candidate| (candidate.index, candidate.word_ids.clone()))
+            .collect();
+        let ids = self.search_with_candidates(search, &mut candidates, &mut ctx.buckets, &deadline);
+        ids.into_iter()
+            .map(|id| (id, self.highlights(id, &matched)))
+            .collect()
+    }
+
+    /// Tokenize the document again to find its words that were matched by the words of the query.
+    /// `matched` contains the position of every word of the query with the id of the words of the fst it matched.
+    /// There is nothing to highlight if the documents are not stored.
+    fn highlights(&self, id: u32, matched: &[(usize, RoaringBitmap)]) -> Vec<MatchRange> {
+        let Some(document) = self.documents.get(id) else {
+            return Vec::new();
+        };
+        self.analyzer()
+            .words(document)
+            .filter_map(|(token, normalized)| {
+                let word_id = self.fst.get(normalized)? as u32;
+                let (query_word, _) = matched
+                    .iter()
+                    .find(|(_, word_ids)| word_ids.contains(word_id))?;
+                // the punctuation is part of the token but it's not worth highlighting
+                let text = token
+                    .text
+                    .trim_start_matches(|c: char| !c.is_alphanumeric());
+                let start = token.offset + token.text.len() - text.len();
+                let text = text.trim_end_matches(|c: char| !c.is_alphanumeric());
+                Some(MatchRange {
+                    start,
+                    end: start + text.len(),
+                    query_word: *query_word,
+                })
+            })
+            .collect()
+    }
+}
+
+/// An excerpt of a document, see [`Highlighter::snippet`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snippet {
+    pub text: String,
+    /// The matches contained in the excerpt, their offsets are in the excerpt.
+    pub ranges: Vec<MatchRange>,
+}
+
+/// Crop the documents around their first match.
+///
+/// ```
+/// use zearch::{Highlighter, Index, Search};
+///
+/// let index = Index::new_in_memory(&["the quick brown fox jumps over the lazy dog"]).unwrap();
+/// let (id, ranges) = index.search_with_highlights(&Search::new("fox")).remove(0);
+/// let snippet = Highlighter::new()
+///     .with_crop_length(3)
+///     .snippet(index.get_document(id).unwrap(), &ranges);
+/// assert_eq!(snippet.text, "… brown fox jumps …");
+/// assert_eq!(&snippet.text[snippet.ranges[0].start..snippet.ranges[0].end], "fox");
+/// ```
+#[derive(Debug, Clone)]
+pub struct Highlighter {
+    crop_length: usize,
+    ellipsis: String,
+}
+
+impl Default for Highlighter {
+    fn default() -> Self {
+        Self {
+            crop_length: 10,
+            ellipsis: String::from("…"),
+        }
+    }
+}
+
+impl Highlighter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The number of words kept in the excerpt, 10 by default.
+    pub fn with_crop_length(&mut self, words: usize) -> &mut Self {
+        self.crop_length = words;
+        self
+    }
+
+    /// Written with a space in place of the words that were cropped at the start and the end of the document, `…` by default.
+    pub fn with_ellipsis(&mut self, ellipsis: &str) -> &mut Self {
+        self.ellipsis = ellipsis.to_string();
+        self
+    }
+
+    /// Keep `crop_length` words around the first match of the document, or its first words if nothing matched.
+    /// The document is only cut on whitespaces and the ranges are the ones returned by [`Index::search_with_highlights`].
+    pub fn snippet(&self, document: &str, ranges: &[MatchRange]) -> Snippet {
+        let words: Vec<(usize, usize)> = Whitespace
+            .tokenize(document)
+            .map(|token| (token.offset, token.offset + token.text.len()))
+            .collect();
+        let crop_length = self.crop_length.min(words.len());
+        if crop_length == 0 {
+            return Snippet {
+                text: String::new(),
+                ranges: Vec::new(),
+            };
+        }
+
+        // the first match is in the middle of the excerpt unless it's too close to the start or the end
+        let first_match = ranges.first().map_or(0, |range| {
+            words.partition_point(|(_, end)| *end <= range.start)
+        });
+        let first_word = first_match
+            .saturating_sub(crop_length / 2)
+            .min(words.len() - crop_length);
+        let last_word = first_word + crop_length - 1;
+        let (start, end) = (words[first_word].0, words[last_word].1);
+
+        let mut text = String::new();
+        if first_word > 0 {
+            text.push_str(&self.ellipsis);
+            text.push(' ');
+        }
+        let shift = text.len();
+        text.push_str(&document[start..end]);
+        if last_word < words.len() - 1 {
+            text.push(' ');
+            text.push_str(&self.ellipsis);
+        }
+
+        let ranges = ranges
+            .iter()
+            .filter(|range| start <= range.start && range.end <= end)
+            .map(|range| MatchRange {
+                start: range.start - start + shift,
+                end: range.end - start + shift,
+                query_word: range.query_word,
+            })
+            .collect();
+        Snippet { text, ranges }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test::create_small_index;
+
+    #[test]
+    fn test_highlights() {
+        let index = create_small_index();
+        let highlight = |query| {
+            index
+                .search_with_highlights(Search::new(query).with_limit(3))
+                .into_iter()
+                .map(|(id, ranges)| {
+                    let document = index.get_document(id).unwrap();
+                    let words: Vec<_> = ranges
+                        .iter()
+                        .map(|range| (&document[range.start..range.end], range.query_word))
+                        .collect();
+                    (document, words)
+                })
+                .collect::<Vec<_>>()
+        };
+
+        // the prefix and typos highlight the whole word
+        insta::assert_debug_snapshot!(highlight("kafir be"), @r###"
+        [
+            (
+                "kefir le beau chien",
+                [
+                    (
+                        "kefir",
+                        0,
+                    ),
+                    (
+                        "beau",
+                        1,
+                    ),
+                ],
+            ),
+            (
+                "le plus beau c'est kefir",
+                [
+                    (
+                        "beau",
+                        1,
+                    ),
+                    (
+                        "kefir",
+                        0,
+                    ),
+                ],
+            ),
+            (
+                "Tamo le plus beau",
+                [
+                    (
+                        "beau",
+                        1,
+                    ),
+                ],
+            ),
+        ]
+        "###);
+        insta::assert_debug_snapshot!(highlight("c'est kef"), @r###"
+        [
+            (
+                "le plus beau c'est kefir",
+                [
+                    (
+                        "c",
+                        0,
+                    ),
+                    (
+                        "est",
+                        1,
+                    ),
+                    (
+                        "kefir",
+                        2,
+                    ),
+                ],
+            ),
+            (
+                "les keftas c'est bon aussi",
+                [
+                    (
+                        "keftas",
+                        2,
+                    ),
+                    (
+                        "c",
+                        0,
+                    ),
+                    (
+                        "est",
+                        1,
+                    ),
+                ],
+            ),
+        ]
+        "###);
+
+        // every occurrence is highlighted, the offsets are in bytes of the original document
+        let index = Index::new_in_memory(&["Kéfir, le kefir de (Kéfir)", "rien"]).unwrap();
+        insta::assert_debug_snapshot!(index.search_with_highlights(&Search::new("kefir")), @r###"
+        [
+            (
+                0,
+                [
+                    MatchRange {
+                        start: 0,
+                        end: 6,
+                        query_word: 0,
+                    },
+                    MatchRange {
+                        start: 11,
+                        end: 16,
+                        query_word: 0,
+                    },
+                    MatchRange {
+                        start: 21,
+                        end: 27,
+                        query_word: 0,
+                    },
+                ],
+            ),
+        ]
+        "###);
+    }
+
+    #[test]
+    fn test_snippets() {
+        let documents: Vec<String> = [0, 50, 99]
+            .iter()
+            .map(|position| {
+                (0..100)
+                    .map(|i| match i {
+                        i if i == *position => String::from("kéfir"),
+                        i => format!("w{i}"),
+                    })
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            })
+            .collect();
+        let index = Index::new_in_memory(&documents.iter().map(String::as_str).collect::<Vec<_>>())
+            .unwrap();
+        let mut highlighter = Highlighter::new();
+        highlighter.with_crop_length(6).with_ellipsis("[...]");
+
+        let snippets: Vec<_> = index
+            .search_with_highlights(&Search::new("kefir"))
+            .into_iter()
+            .map(|(id, ranges)| {
+                let snippet = highlighter.snippet(index.get_document(id).unwrap(), &ranges);
+                let range = snippet.ranges[0];
+                format!(
+                    "{} ({:?})",
+                    snippet.text,
+                    &snippet.text[range.start..range.end]
+                )
+            })
+            .collect();
+        insta::assert_debug_snapshot!(snippets, @r###"
+        [
+            "kéfir w1 w2 w3 w4 w5 [...] (\"kéfir\")",
+            "[...] w47 w48 w49 kéfir w51 w52 [...] (\"kéfir\")",
+            "[...] w94 w95 w96 w97 w98 kéfir (\"kéfir\")",
+        ]
+        "###);
+
+        // without matches we keep the start of the document
+        insta::assert_debug_snapshot!(highlighter.snippet(&documents[0], &[]), @r###"
+        Snippet {
+            text: "kéfir w1 w2 w3 w4 w5 [...]",
+            ranges: [],
+        }
+        "###);
+        insta::assert_debug_snapshot!(highlighter.with_crop_length(200).snippet("le  petit kéfir", &[]), @r###"
+        Snippet {
+            text: "le  petit kéfir",
+            ranges: [],
+        }
+        "###);
+    }
+}
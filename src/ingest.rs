@@ -0,0 +1,196 @@
+//! Construct an index directly from a JSON Lines or csv file, with the `ingest` feature.
+//! Only one field or column is indexed, the ids of the documents follow the order of the lines.
+
+use std::{borrow::Cow, io::BufRead};
+
+use crate::{Error, IndexBuilder};
+
+/// Construct the index from the string `field` of every object of a JSON Lines file,
+/// see [`IndexBuilder::construct_from_jsonl`]. Returns the number of documents indexed.
+pub fn construct_from_jsonl(
+    reader: impl BufRead,
+    field: &str,
+    writer: &mut impl std::io::Write,
+) -> Result<u32, Error> {
+    IndexBuilder::new().construct_from_jsonl(reader, field, writer)
+}
+
+/// Construct the index from the `column` of a csv file, see [`IndexBuilder::construct_from_csv`].
+/// Returns the number of documents indexed.
+pub fn construct_from_csv(
+    reader: impl std::io::Read,
+    column: usize,
+    delimiter: u8,
+    writer: &mut impl std::io::Write,
+) -> Result<u32, Error> {
+    IndexBuilder::new().construct_from_csv(reader, column, delimiter, writer)
+}
+
+impl IndexBuilder {
+    /// Construct the index from the string `field` of every object of a JSON Lines file.
+    /// The empty lines are skipped, any other line that isn't an object with such a field is an error.
+    ///
+    /// ```
+    /// use zearch::{Index, IndexBuilder};
+    ///
+    /// let jsonl = "{\"id\": 1, \"name\": \"kefir\"}\n{\"id\": 2, \"name\": \"tamo\"}\n";
+    /// let mut bytes = Vec::new();
+    /// let count = IndexBuilder::new().construct_from_jsonl(jsonl.as_bytes(), "name", &mut bytes).unwrap();
+    /// assert_eq!(count, 2);
+    /// assert_eq!(Index::from_bytes(&bytes).unwrap().get_document(1), Some("tamo"));
+    /// ```
+    pub fn construct_from_jsonl(
+        &self,
+        reader: impl BufRead,
+        field: &str,
+        writer: &mut impl std::io::Write,
+    ) -> Result<u32, Error> {
+        let mut documents = Vec::new();
+        for (line, text) in reader.lines().enumerate() {
+            let text = text?;
+            if text.trim().is_empty() {
+                continue;
+            }
+            let invalid = |reason: String| Error::InvalidRecord {
+                line: line + 1,
+                reason,
+            };
+            let mut value: serde_json::Value =
+                serde_json::from_str(&text).map_err(|e| invalid(e.to_string()))?;
+            match value.get_mut(field).map(serde_json::Value::take) {
+                Some(serde_json::Value::String(document)) => documents.push(Cow::Owned(document)),
+                Some(_) => return Err(invalid(format!("the field `{field}` is not a string"))),
+                None => return Err(invalid(format!("missing field `{field}`"))),
+            }
+        }
+        self.construct_documents(documents, writer)
+    }
+
+    /// Construct the index from the `column` of a csv file, the first one being `0`.
+    /// The first line is the header and isn't indexed.
+    pub fn construct_from_csv(
+        &self,
+        reader: impl std::io::Read,
+        column: usize,
+        delimiter: u8,
+        writer: &mut impl std::io::Write,
+    ) -> Result<u32, Error> {
+        let mut reader = csv::ReaderBuilder::new()
+            .delimiter(delimiter)
+            .from_reader(reader);
+        let mut documents = Vec::new();
+        let mut record = csv::StringRecord::new();
+        loop {
+            let read = reader.read_record(&mut record).map_err(|error| {
+                let line = error.position().map_or(0, |position| position.line());
+                let reason = error.to_string();
+                match error.into_kind() {
+                    csv::ErrorKind::Io(error) => Error::Io(error),
+                    _ => Error::InvalidRecord {
+                        line: line as usize,
+                        reason,
+                    },
+                }
+            })?;
+            if !read {
+                break;
+            }
+            let line = record.position().map_or(0, |position| position.line());
+            let document = record.get(column).ok_or_else(|| Error::InvalidRecord {
+                line: line as usize,
+                reason: format!("missing column {column}"),
+            })?;
+            documents.push(Cow::Owned(document.to_string()));
+        }
+        self.construct_documents(documents, writer)
+    }
+
+    fn construct_documents(
+        &self,
+        documents: Vec<Cow<'_, str>>,
+        writer: &mut impl std::io::Write,
+    ) -> Result<u32, Error> {
+        let count = documents.len();
+        let reporter = self.reporter();
+        self.build(documents, None, false, &reporter)?
+            .write_with(writer, &reporter)?;
+        // the construction fails with more documents than an id can hold
+        Ok(count as u32)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Index;
+
+    const JSONL: &str = r#"{"id": 0, "name": "kefir le chien"}
+
+{"id": 1, "name": "tamo le chat", "age": 5}
+{"name": "Kéfir"}
+"#;
+
+    const CSV: &str = "id;name;age
+0;kefir le chien;3
+1;tamo le chat;5
+2;Kéfir;
+";
+
+    #[test]
+    fn test_construct_from_jsonl() {
+        let mut bytes = Vec::new();
+        let count = construct_from_jsonl(JSONL.as_bytes(), "name", &mut bytes).unwrap();
+        assert_eq!(count, 3);
+        let index = Index::from_bytes(&bytes).unwrap();
+        insta::assert_debug_snapshot!(index.documents().collect::<Vec<_>>(), @r###"
+        [
+            (
+                0,
+                "kefir le chien",
+            ),
+            (
+                1,
+                "tamo le chat",
+            ),
+            (
+                2,
+                "Kéfir",
+            ),
+        ]
+        "###);
+        assert_eq!(index.search(&crate::Search::new("kefir")), [0, 2]);
+
+        let malformed = format!("{JSONL}{{\"name\": \"trunc");
+        insta::assert_snapshot!(construct_from_jsonl(malformed.as_bytes(), "name", &mut Vec::new()).unwrap_err(), @"Invalid record on line 5: EOF while parsing a string at line 1 column 15.");
+        insta::assert_snapshot!(construct_from_jsonl(JSONL.as_bytes(), "age", &mut Vec::new()).unwrap_err(), @"Invalid record on line 1: missing field `age`.");
+        insta::assert_snapshot!(construct_from_jsonl(JSONL.as_bytes(), "id", &mut Vec::new()).unwrap_err(), @"Invalid record on line 1: the field `id` is not a string.");
+    }
+
+    #[test]
+    fn test_construct_from_csv() {
+        let mut bytes = Vec::new();
+        let count = construct_from_csv(CSV.as_bytes(), 1, b';', &mut bytes).unwrap();
+        assert_eq!(count, 3);
+        let index = Index::from_bytes(&bytes).unwrap();
+        insta::assert_debug_snapshot!(index.documents().collect::<Vec<_>>(), @r###"
+        [
+            (
+                0,
+                "kefir le chien",
+            ),
+            (
+                1,
+                "tamo le chat",
+            ),
+            (
+                2,
+                "Kéfir",
+            ),
+        ]
+        "###);
+
+        let malformed = format!("{CSV}3;mochi;1;too many\n");
+        insta::assert_snapshot!(construct_from_csv(malformed.as_bytes(), 1, b';', &mut Vec::new()).unwrap_err(), @"Invalid record on line 5: CSV error: record 4 (line: 5, byte: 58): found record with 4 fields, but the previous record has 3 fields.");
+        insta::assert_snapshot!(construct_from_csv(CSV.as_bytes(), 3, b';', &mut Vec::new()).unwrap_err(), @"Invalid record on line 2: missing column 3.");
+    }
+}
@@ -1,426 +1,7976 @@
+mod bitmaps;
+mod cache;
+mod config;
+mod deadline;
+mod dfas;
+mod distance;
+mod documents;
+mod error;
+mod explain;
+mod external;
+mod federated;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+mod fields;
+mod handle;
+mod highlight;
+#[cfg(feature = "ingest")]
+pub mod ingest;
+mod normalizer;
+mod pattern;
+mod progress;
+mod query;
 mod ranking_rules;
+mod runs;
+mod scoring;
+mod sharded;
+mod stats;
+mod stemmer;
+mod suggestions;
+mod tokenizer;
+mod trace;
+mod typeahead;
+#[cfg(feature = "wasm")]
+mod wasm;
 
-use std::{borrow::Cow, ops::ControlFlow, sync::OnceLock};
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+    io::Write,
+    ops::{ControlFlow, RangeBounds},
+    sync::{atomic::AtomicBool, Arc, OnceLock},
+    time::Duration,
+};
 
-use fst::{IntoStreamer, Map, MapBuilder, Streamer};
-use levenshtein_automata::LevenshteinAutomatonBuilder;
-use ranking_rules::{typo::Typo, word::Word, RankingRule, RankingRuleImpl};
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+use std::time::Instant;
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+use web_time::Instant;
+
+use fst::{automaton::Str, Automaton, IntoStreamer, Map, MapBuilder, Streamer};
+use levenshtein_automata::{LevenshteinAutomatonBuilder, DFA};
+use ranking_rules::{typo::Typo, word::Word};
 use roaring::RoaringBitmap;
-use text_distance::DamerauLevenshtein;
 
-use crate::ranking_rules::exact::Exact;
+use crate::bitmaps::{take_bitmap, word_value, Bitmaps};
+use crate::deadline::Deadline;
+use crate::dfas::Dfas;
+use crate::distance::DamerauLevenshtein;
+use crate::documents::Documents;
+use crate::fields::Fields;
+use crate::progress::{ProgressCallback, Reporter};
+use crate::ranking_rules::{
+    attribute::Attribute, boost::Boost, exact::Exact, geo::Geo, position::Position,
+    proximity::Proximity, sort::Sort, word_count::WordCount,
+};
+use crate::runs::KWayMerge;
+use crate::scoring::CorpusStats;
+pub use cache::SearchCache;
+pub use config::{SearchConfig, SearchDefaults};
+pub use distance::TypoTolerance;
+pub use error::Error;
+pub use explain::{Explain, ExplainedBucket};
+pub use federated::{FederatedHit, MultiIndex};
+pub use handle::IndexHandle;
+pub use highlight::{Highlighter, MatchRange, Snippet};
+#[cfg(feature = "unicode")]
+pub use normalizer::Unicode;
+pub use normalizer::{Ascii, Normalizer, NormalizerKind};
+pub use pattern::{PatternMatches, MAX_PATTERN_LEN, MAX_PATTERN_WORDS};
+pub use progress::{Phase, Progress};
+pub use query::{ParseError, ParseErrorKind, Query, QueryItem, Term};
+pub use ranking_rules::{CustomRankingRule, RankingRule, RankingRuleImpl, TieBreak};
+pub use scoring::Scoring;
+pub use sharded::ShardedIndex;
+pub use stats::{IndexStats, Words};
+pub use stemmer::Stemmer;
+pub use suggestions::MAX_COMPLETION_WORDS;
+pub use tokenizer::{
+    AnalyzedToken, Apostrophes, Separators, Token, Tokenizer, TokenizerKind, Whitespace,
+};
+pub use typeahead::TypeaheadSession;
+#[cfg(feature = "wasm")]
+pub use wasm::WasmIndex;
 
 pub struct Index<'a> {
-    documents: Vec<Cow<'a, str>>,
-    // we cannot work on serialized bitmap yet thus we're going to load everything in RAM
-    bitmaps: Vec<RoaringBitmap>,
+    // may only be a count, see `IndexBuilder::with_documents`
+    documents: Documents<'a>,
+    // decoded lazily when a word matches, see the bitmaps module
+    bitmaps: Bitmaps<'a>,
     fst: Map<Cow<'a, [u8]>>,
+    // for every document, the id of each of its words in the order they appear
+    positions: Option<Vec<Vec<u32>>>,
+    // for every document, the number of words it was indexed with, see `IndexBuilder::with_word_counts`
+    word_counts: Option<Vec<u16>>,
+    // for every document, the id of each of its words with its number of occurrences, sorted by id
+    term_frequencies: Option<Vec<Vec<(u32, u16)>>>,
+    // for every document, the value it was associated with at construction
+    payloads: Option<Vec<u64>>,
+    // the fields of every word, only if the documents were made of fields
+    fields: Option<Fields>,
+    // for every document, the boost it was given at construction
+    boosts: Option<Vec<f32>>,
+    // for every document, its latitude and longitude, NaN if it has none
+    coordinates: Option<Vec<(f32, f32)>>,
+    // the settings of the searches that don't set them, `None` if they weren't given at construction
+    search_defaults: Option<SearchDefaults>,
+    // the documents that were deleted but are still in the fst and bitmaps
+    deleted: RoaringBitmap,
+    // the normalized words that were not indexed, sorted
+    stop_words: Vec<String>,
+    // the normalized words that are never matched with a typo, sorted
+    exact_words: Vec<String>,
+    // the id of the words appearing in too many documents, see `IndexBuilder::with_max_document_frequency`
+    common_words: RoaringBitmap,
+    tokenizer: TokenizerKind,
+    normalizer: NormalizerKind,
+    stemmer: Stemmer,
+    // changes every time the documents change, see `SearchCache`
+    generation: u64,
 }
 
 type Id = u32;
 
-impl<'a> Index<'a> {
-    pub fn construct(
-        documents: &[impl AsRef<str>],
-        writer: &mut impl std::io::Write,
-    ) -> std::io::Result<()> {
-        let mut words = documents
-            .iter()
-            .enumerate()
-            .flat_map(|(id, document)| {
-                document
-                    .as_ref()
-                    .split_whitespace()
-                    .map(move |word| (id as Id, normalize(word)))
-            })
-            .collect::<Vec<(Id, String)>>();
-        words.sort_unstable_by(|(_, left), (_, right)| left.cmp(right));
+/// Every index starts with these bytes followed by the version of its format
+/// and the ids of its tokenizer, normalizer and stemmer.
+const MAGIC: &[u8; 4] = b"ZRCH";
+/// Must be bumped every time the format changes in a way older versions can't read.
+const VERSION: u16 = 6;
 
-        let mut build = MapBuilder::memory();
+/// The optional sections that can be written after the fst.
+/// Each of them is prefixed by its kind and its size.
+#[repr(u8)]
+enum Section {
+    Positions = 1,
+    Payloads = 2,
+    Tombstones = 3,
+    StopWords = 4,
+    CommonWords = 5,
+    /// Written first when the documents are not stored, it replaces their count of `0`.
+    DocumentCount = 6,
+    /// Written first when the documents are compressed, it replaces them and their count of `0`.
+    CompressedDocuments = 7,
+    /// Only written if the documents are made of multiple fields.
+    Fields = 8,
+    Boosts = 9,
+    /// The latitude and longitude of every document, NaN for the documents without coordinates.
+    Coordinates = 10,
+    /// The limit and ranking rules of the searches that don't set them.
+    SearchDefaults = 11,
+    /// The normalized words that can't be matched with a typo, sorted.
+    ExactWords = 12,
+    /// The number of words of every document, see [`IndexBuilder::with_word_counts`].
+    WordCounts = 13,
+    /// The number of occurrences of every word of every document, see [`IndexBuilder::with_term_frequencies`].
+    TermFrequencies = 14,
+}
 
-        let mut last_word = None;
-        let mut bitmaps = Vec::new();
+/// The coordinates of the documents that have none, see [`IndexBuilder::with_coordinates`].
+const NO_COORDINATES: (f32, f32) = (f32::NAN, f32::NAN);
 
-        for (id, word) in words.iter() {
-            if Some(word) != last_word {
-                bitmaps.push(RoaringBitmap::from_sorted_iter(Some(*id)).unwrap());
-                build.insert(word, (bitmaps.len() - 1) as u64).unwrap();
-            } else {
-                bitmaps.last_mut().unwrap().insert(*id);
-            }
+/// Only the first words of the query are split and concatenated, see [`Index::lookup_compounds`],
+/// so the long queries don't explode the number of lookups in the fst.
+const MAX_COMPOUND_WORDS: usize = 8;
 
-            last_word = Some(word);
-        }
-        writer.write_all((documents.len() as u32).to_be_bytes().as_slice())?;
-        for document in documents {
-            Self::write_slice(writer, document.as_ref().as_bytes())?;
-        }
+/// The words of the query up to this length in bytes are too short to be given a typo, see [`typo_budget`].
+/// They're looked up directly in the fst instead of building an automaton.
+const SHORT_WORD_LEN: usize = 2;
 
-        writer.write_all((bitmaps.len() as u32).to_be_bytes().as_slice())?;
-        for bitmap in bitmaps {
-            bitmap.serialize_into(&mut *writer)?;
-        }
+/// A word of the query remembers this many words of the fst it matched, see [`WordCandidate::matched_words`].
+const MAX_RECORDED_MATCHES: usize = 100;
 
-        // cannot fail since we were writing in memory
-        let fst = build.into_inner().unwrap();
-        Self::write_slice(writer, &fst)?;
+/// A word must appear in at least this many documents to be a common word,
+/// otherwise every word of a tiny corpus would be one.
+const MIN_COMMON_WORD_DOCUMENTS: u64 = 10;
 
-        Ok(())
+/// Let you choose what should be stored in the index before constructing it.
+#[derive(Debug, Default, Clone)]
+pub struct IndexBuilder {
+    positions: bool,
+    word_counts: bool,
+    term_frequencies: bool,
+    // the flag is inverted so the documents are stored by default
+    without_documents: bool,
+    #[cfg(feature = "compression")]
+    compression: bool,
+    // `None` means the default budget
+    memory_budget: Option<usize>,
+    #[cfg(feature = "parallel")]
+    threads: usize,
+    progress: Option<ProgressCallback>,
+    cancel: Option<Arc<AtomicBool>>,
+    // as they were given, they're normalized when constructing the index
+    stop_words: Vec<String>,
+    exact_words: Vec<String>,
+    max_document_frequency: Option<f32>,
+    // in the order of the documents, empty if they have no boost
+    boosts: Vec<f32>,
+    // in the order of the documents, empty if they have no coordinates
+    coordinates: Vec<(f32, f32)>,
+    dedup_documents: bool,
+    default_limit: Option<usize>,
+    default_ranking_rules: Option<Vec<RankingRule>>,
+    tokenizer: TokenizerKind,
+    normalizer: NormalizerKind,
+    stemmer: Stemmer,
+}
+
+impl IndexBuilder {
+    pub fn new() -> Self {
+        Self::default()
     }
 
-    fn write_slice(writer: &mut impl std::io::Write, slice: &[u8]) -> std::io::Result<()> {
-        writer.write_all((slice.len() as u32).to_be_bytes().as_slice())?;
-        writer.write_all(slice)?;
-        Ok(())
+    /// Store the position of every word in the documents.
+    /// It's required by the proximity ranking rule but makes the index bigger.
+    pub fn with_positions(&mut self, positions: bool) -> &mut Self {
+        self.positions = positions;
+        self
     }
 
-    fn read_size_from_bytes(bytes: &mut &[u8]) -> Option<u32> {
-        const U32SIZE: usize = std::mem::size_of::<u32>();
-        let (size, b) = bytes.split_first_chunk::<U32SIZE>()?;
-        *bytes = b;
-        Some(u32::from_be_bytes(*size))
+    /// Store the number of words of every document, without the stop words and capped to 65535.
+    /// It's required by [`RankingRule::WordCount`] and takes two bytes per document.
+    pub fn with_word_counts(&mut self, word_counts: bool) -> &mut Self {
+        self.word_counts = word_counts;
+        self
     }
 
-    fn read_slice_from_bytes<'b>(bytes: &mut &'b [u8]) -> Option<&'b [u8]> {
-        let size = Self::read_size_from_bytes(bytes)?;
-        if bytes.len() < size as usize {
-            return None;
-        }
-        let ret = &bytes[..size as usize];
-        *bytes = &bytes[size as usize..];
+    /// Store how many times every word appears in every document, capped to 65535.
+    /// It's used by [`Scoring::Bm25`], along with the word counts, and takes six bytes per distinct word of every document.
+    pub fn with_term_frequencies(&mut self, term_frequencies: bool) -> &mut Self {
+        self.term_frequencies = term_frequencies;
+        self
+    }
 
-        Some(ret)
+    /// Store the text of the documents, `true` by default.
+    /// Without it the index only returns ids, [`Index::get_document`] always returns `None`
+    /// and the highlights and [`Search::with_distinct`] are not available.
+    /// It's useful when the documents are already stored elsewhere and makes the index a lot smaller.
+    pub fn with_documents(&mut self, documents: bool) -> &mut Self {
+        self.without_documents = !documents;
+        self
     }
 
-    pub fn from_bytes(mut bytes: &'a [u8]) -> Option<Self> {
-        // 1. Read the documents
-        let mut documents = Vec::new();
-        let nb_documents = Self::read_size_from_bytes(&mut bytes)?;
-        for _ in 0..nb_documents {
-            let document = Self::read_slice_from_bytes(&mut bytes)?;
-            documents.push(Cow::Borrowed(std::str::from_utf8(document).ok()?));
-        }
+    /// Compress the text of the documents by blocks with LZ4, `false` by default.
+    /// It only slows down [`Index::get_document`] and the highlights, which decompress the block
+    /// of the document the first time it's accessed. The searches themselves are not affected.
+    #[cfg(feature = "compression")]
+    pub fn with_compression(&mut self, compression: bool) -> &mut Self {
+        self.compression = compression;
+        self
+    }
 
-        // 2. Read the bitmap
-        let nb_bitmaps = Self::read_size_from_bytes(&mut bytes)?;
-        let mut bitmaps = Vec::new();
-        for _ in 0..nb_bitmaps {
-            let bitmap = RoaringBitmap::deserialize_from(&mut bytes).unwrap();
-            bitmaps.push(bitmap);
-        }
+    /// Words so common they're useless for the search, like `le` or `the`.
+    /// They're not indexed and they're removed from the queries unless they're the last word of the query,
+    /// since it can be the beginning of another word.
+    pub fn with_stop_words(&mut self, stop_words: &[&str]) -> &mut Self {
+        self.stop_words = stop_words.iter().map(|word| word.to_string()).collect();
+        self
+    }
 
-        // 3. Read the fst
-        let fst = Self::read_slice_from_bytes(&mut bytes)?;
-        let fst = Map::new(Cow::Borrowed(fst)).ok()?;
+    /// Words that must never be matched with a typo, like product codes or postal codes: `a38` must not match `a39`.
+    /// A word of the query that is one of them only matches itself, and the other words of the query never match them with a typo.
+    /// They can be replaced for a single search with [`Search::with_exact_words`].
+    ///
+    /// ```
+    /// use zearch::{Index, IndexBuilder, Search};
+    ///
+    /// let mut bytes = Vec::new();
+    /// IndexBuilder::new()
+    ///     .with_exact_words(&["A38"])
+    ///     .construct(&["a38", "a39"], &mut bytes)
+    ///     .unwrap();
+    /// let index = Index::from_bytes(&bytes).unwrap();
+    /// assert_eq!(index.search(&Search::new("a38")), [0]);
+    /// assert_eq!(index.search(&Search::new("a39")), [1]);
+    /// ```
+    pub fn with_exact_words(&mut self, exact_words: &[&str]) -> &mut Self {
+        self.exact_words = exact_words.iter().map(|word| word.to_string()).collect();
+        self
+    }
 
-        Some(Self {
-            documents,
-            bitmaps,
-            fst,
-        })
+    /// How the documents and the queries are split in words, [`TokenizerKind::Apostrophes`] by default.
+    pub fn with_tokenizer(&mut self, tokenizer: TokenizerKind) -> &mut Self {
+        self.tokenizer = tokenizer;
+        self
     }
 
-    pub fn move_in_memory(self) -> Index<'static> {
-        Index {
-            documents: self
-                .documents
-                .into_iter()
-                .map(|document| Cow::Owned(document.into_owned()))
-                .collect(),
-            bitmaps: self.bitmaps,
-            fst: self
-                .fst
-                .map_data(|data| Cow::Owned(data.into_owned()))
-                .unwrap(),
-        }
+    /// How the words are normalized before being stored, [`NormalizerKind::Ascii`] by default.
+    pub fn with_normalizer(&mut self, normalizer: NormalizerKind) -> &mut Self {
+        self.normalizer = normalizer;
+        self
     }
 
-    pub fn new_in_memory(documents: &[&str]) -> Option<Index<'static>> {
-        let mut index = Vec::new();
-        Self::construct(documents, &mut index).ok()?;
-        let index = Index::from_bytes(&index)?;
-        Some(index.move_in_memory())
+    /// Reduce the normalized words to their stem in this language, [`Stemmer::None`] by default.
+    /// A query word then matches all the words sharing its stem, like `chien` and `chiens`,
+    /// and since only the stem is stored the ranking rules can't tell them apart.
+    pub fn with_stemmer(&mut self, stemmer: Stemmer) -> &mut Self {
+        self.stemmer = stemmer;
+        self
     }
 
-    pub fn get_document(&self, id: u32) -> Option<&str> {
-        self.documents.get(id as usize).map(|s| s.as_ref())
+    /// Flag the words appearing in more than `frequency` of the documents, `0.5` meaning half of them.
+    /// They're still searchable but the [`RankingRule::Word`] drops them first, see [`Index::common_words`].
+    /// A word must also appear in at least ten documents to be flagged.
+    pub fn with_max_document_frequency(&mut self, frequency: f32) -> &mut Self {
+        self.max_document_frequency = Some(frequency);
+        self
     }
 
-    pub fn search(&self, search: &Search) -> Vec<u32> {
-        // contains all the buckets
-        let mut res: Vec<RoaringBitmap> = Vec::new();
-        let mut candidates = self.get_candidates(search);
+    /// Give a boost to every document, in the order of the documents, to rank them up with [`RankingRule::Boost`].
+    /// The documents after the last boost get a boost of `1.0`, like the documents of an index constructed without boosts.
+    pub fn with_boosts(&mut self, boosts: &[f32]) -> &mut Self {
+        self.boosts = boosts.to_vec();
+        self
+    }
 
-        // TODO: returns random results maybe?
-        if candidates.is_empty() {
-            return Vec::new();
+    /// Give a latitude and a longitude in degrees to every document, in the order of the documents,
+    /// to sort them by their distance to a point with [`Search::with_sort_by_distance`].
+    /// The documents after the last coordinates have none.
+    pub fn with_coordinates(&mut self, coordinates: &[(f32, f32)]) -> &mut Self {
+        self.coordinates = coordinates.to_vec();
+        self
+    }
+
+    /// Skip the documents whose normalized words are the same as a previous document, `false` by default.
+    /// The ids then no longer follow the positions of the documents, see [`IndexBuilder::document_ids`].
+    /// The payload, boost and coordinates of the first occurrence are kept. The documents without words are never skipped.
+    /// [`IndexBuilder::construct`] and [`IndexBuilder::construct_to`] keep the normalized words of every distinct document in memory.
+    ///
+    /// ```
+    /// use zearch::{Index, IndexBuilder, Search};
+    ///
+    /// let documents = ["Kefir le chien", "Tamo", "kefir, le chien !"];
+    /// let mut builder = IndexBuilder::new();
+    /// builder.with_dedup_documents(true);
+    /// let mut bytes = Vec::new();
+    /// builder.construct(&documents, &mut bytes).unwrap();
+    /// let index = Index::from_bytes(&bytes).unwrap();
+    /// assert_eq!(index.search(&Search::new("kefir")), [0]);
+    /// assert_eq!(builder.document_ids(&documents), [0, 1, 0]);
+    /// ```
+    pub fn with_dedup_documents(&mut self, dedup: bool) -> &mut Self {
+        self.dedup_documents = dedup;
+        self
+    }
+
+    /// Returns the id every document gets once constructed, in the order of the documents:
+    /// a skipped duplicate gets the id of its first occurrence, see [`IndexBuilder::with_dedup_documents`].
+    pub fn document_ids(&self, documents: &[impl AsRef<str>]) -> Vec<u32> {
+        let documents: Vec<&str> = documents.iter().map(AsRef::as_ref).collect();
+        self.dedup(&documents).0
+    }
+
+    /// Returns the documents and payloads that are kept, with a builder whose boosts and coordinates follow them.
+    pub(crate) fn remove_duplicates<D: AsRef<str>>(
+        &self,
+        documents: Vec<D>,
+        payloads: Option<Vec<u64>>,
+    ) -> (IndexBuilder, Vec<D>, Option<Vec<u64>>) {
+        fn keep<T>(items: impl IntoIterator<Item = T>, kept: &[usize]) -> Vec<T> {
+            items
+                .into_iter()
+                .enumerate()
+                .filter(|(position, _)| kept.binary_search(position).is_ok())
+                .map(|(_, item)| item)
+                .collect()
         }
 
-        let mut ranking_rules: Vec<Box<dyn RankingRuleImpl>> = search
-            .ranking_rules
+        let (_, kept) = self.dedup(&documents);
+        let mut builder = self.clone();
+        builder.dedup_documents = false;
+        builder.boosts = keep(self.boosts.iter().copied(), &kept);
+        builder.coordinates = keep(self.coordinates.iter().copied(), &kept);
+        let payloads = payloads.map(|payloads| keep(payloads, &kept));
+        (builder, keep(documents, &kept), payloads)
+    }
+
+    /// Returns the id of every document and the position of the documents that are kept.
+    fn dedup(&self, documents: &[impl AsRef<str>]) -> (Vec<u32>, Vec<usize>) {
+        if !self.dedup_documents {
+            let ids = (0..documents.len() as u32).collect();
+            return (ids, (0..documents.len()).collect());
+        }
+        let stop_words = self.normalized_stop_words();
+        let analyzer = self.analyzer(&stop_words);
+        let mut seen = HashMap::new();
+        let mut kept = Vec::new();
+        let ids = documents
             .iter()
-            .map(|ranking_rule| match ranking_rule {
-                RankingRule::Word => {
-                    Box::new(Word::new(&mut candidates)) as Box<dyn RankingRuleImpl>
+            .enumerate()
+            .map(|(position, document)| {
+                let words: Vec<String> = analyzer
+                    .tokens(document.as_ref())
+                    .map(|token| token.normalized)
+                    .collect();
+                let id = kept.len() as u32;
+                let id = match words.is_empty() {
+                    true => id,
+                    false => *seen.entry(words).or_insert(id),
+                };
+                if id == kept.len() as u32 {
+                    kept.push(position);
                 }
-                RankingRule::Typo => Box::new(Typo::new(&candidates)) as Box<dyn RankingRuleImpl>,
-                RankingRule::Exact => Box::new(Exact::new()) as Box<dyn RankingRuleImpl>,
+                id
             })
             .collect();
-        let ranking_rules_len = ranking_rules.len();
-
-        let mut current_ranking_rule = 0;
+        (ids, kept)
+    }
 
-        macro_rules! next {
-            () => {
-                {
-                // we cannot borrow twice the list of ranking rules thus we'll cheat a little
-                let current = &mut ranking_rules[current_ranking_rule];
-                // we detach the lifetime from the vec, this allow us to borrow the previous element safely
-                let current: &'static mut Box<dyn RankingRuleImpl> = unsafe { std::mem::transmute(current) };
-                current.next(
-                    current_ranking_rule.checked_sub(1).and_then(|prev| ranking_rules.get(prev)).map(|rr| &**rr),
-                    &mut candidates,
-                    self
-                )
-                }
-            };
-        }
+    /// Store the limit of the searches that don't set one, see [`Search::with_limit`] and [`Index::default_search`].
+    pub fn with_default_limit(&mut self, limit: usize) -> &mut Self {
+        self.default_limit = Some(limit);
+        self
+    }
 
-        while res.iter().map(|bucket| bucket.len()).sum::<u64>() < search.limit as u64 {
-            let next = next!();
-            let ranking_rule = &mut ranking_rules[current_ranking_rule];
+    /// Store the ranking rules of the searches that don't set them, see [`Search::with_ranking_rules`].
+    /// The construction fails with [`Error::CustomRankingRule`] if one of them is a custom ranking rule.
+    ///
+    /// ```
+    /// use zearch::{Index, IndexBuilder, RankingRule, Search};
+    ///
+    /// let mut bytes = Vec::new();
+    /// IndexBuilder::new()
+    ///     .with_default_limit(1)
+    ///     .with_default_ranking_rules(vec![RankingRule::Typo, RankingRule::Word])
+    ///     .construct(&["kefir", "le chien", "le chat"], &mut bytes)
+    ///     .unwrap();
+    /// let index = Index::from_bytes(&bytes).unwrap();
+    /// assert_eq!(index.default_search().limit, 1);
+    /// assert_eq!(index.search(&Search::new("le")), [1]);
+    /// assert_eq!(index.search(Search::new("le").with_limit(2)), [1, 2]);
+    /// ```
+    pub fn with_default_ranking_rules(&mut self, ranking_rules: Vec<RankingRule>) -> &mut Self {
+        self.default_ranking_rules = Some(ranking_rules);
+        self
+    }
 
-            match next {
-                // We want to advance
-                ControlFlow::Continue(()) => {
-                    if current_ranking_rule == ranking_rules_len - 1 {
-                        // there is no ranking rule to continue, get the bucket of the current one and call it again
-                        let bucket = ranking_rule.current_results(&candidates);
-                        Self::cleanup(&bucket, &mut candidates);
-                        ranking_rules.iter_mut().for_each(|rr| rr.cleanup(&bucket));
-                        res.push(bucket);
-                    } else {
-                        // we advance and do nothing
-                        current_ranking_rule += 1;
-                    }
-                }
-                // We want to get back one ranking rule behind
-                ControlFlow::Break(bucket) if bucket.is_empty() => {
-                    // if we're at the first ranking rule and there is nothing left to sort, exit
-                    if current_ranking_rule == 0 {
-                        break;
-                    }
-                    current_ranking_rule -= 1;
-                    res.push(bucket);
-                }
-                // We want to push that bucket and continue our life with the next ranking rule if there is one
-                ControlFlow::Break(bucket) => {
-                    Self::cleanup(&bucket, &mut candidates);
-                    ranking_rules.iter_mut().for_each(|rr| rr.cleanup(&bucket));
-                    res.push(bucket);
-                }
-            }
+    /// `None` if neither the limit nor the ranking rules were set.
+    fn search_defaults(&self) -> Option<SearchDefaults> {
+        if self.default_limit.is_none() && self.default_ranking_rules.is_none() {
+            return None;
         }
+        let defaults = SearchDefaults::default();
+        Some(SearchDefaults {
+            limit: self.default_limit.unwrap_or(defaults.limit),
+            ranking_rules: self
+                .default_ranking_rules
+                .clone()
+                .unwrap_or(defaults.ranking_rules),
+        })
+    }
 
-        res.iter()
-            .flat_map(|bitmap| bitmap.iter())
-            .take(search.limit)
-            .collect()
+    /// The number of threads used to tokenize and sort the documents.
+    /// `0`, the default, lets rayon decide and `1` constructs the index on the current thread.
+    #[cfg(feature = "parallel")]
+    pub fn with_threads(&mut self, threads: usize) -> &mut Self {
+        self.threads = threads;
+        self
     }
 
-    fn cleanup(used: &RoaringBitmap, candidates: &mut [WordCandidate]) {
-        for candidate in candidates.iter_mut() {
-            for typo in candidate.typos.iter_mut() {
-                *typo -= used;
-            }
-        }
+    /// Called when a phase of the construction starts and then regularly while it's running,
+    /// about a hundred times per phase when we know how many items it has to process.
+    /// The counts of a phase only increase, even when the documents are tokenized on multiple threads.
+    pub fn with_progress(
+        &mut self,
+        progress: impl Fn(Progress) + Send + Sync + 'static,
+    ) -> &mut Self {
+        self.progress = Some(ProgressCallback(Arc::new(progress)));
+        self
     }
 
-    fn get_candidates(&self, search: &Search) -> Vec<WordCandidate> {
-        static LEVENSHTEINS: OnceLock<[LevenshteinAutomatonBuilder; 4]> = OnceLock::new();
-        let levenshtein = LEVENSHTEINS.get_or_init(|| {
-            core::array::from_fn(|nb_typo| LevenshteinAutomatonBuilder::new(nb_typo as u8, true))
-        });
+    /// Once `cancel` is set to `true` the construction stops as soon as possible and returns [`Error::Cancelled`].
+    /// Nothing is written to the writer after that, but it may contain the beginning of the index.
+    pub fn with_cancellation(&mut self, cancel: Arc<AtomicBool>) -> &mut Self {
+        self.cancel = Some(cancel);
+        self
+    }
+
+    /// Construct the index, the id of a document is its position in `documents`.
+    /// A document without words, like an empty one or one made of punctuation, keeps its id and is stored
+    /// but is never returned by a search. An index can be constructed from no document at all.
+    /// Same as [`IndexBuilder::construct_from_iter`].
+    pub fn construct(
+        &self,
+        documents: &[impl AsRef<str>],
+        writer: &mut impl std::io::Write,
+    ) -> Result<(), Error> {
+        self.construct_from_iter(documents.iter().map(AsRef::as_ref), writer)
+    }
 
-        let words: Vec<_> = search
-            .input
-            .split_whitespace()
-            .map(|word| (word, normalize(word)))
-            .filter(|(_word, normalized)| !normalized.is_empty())
+    /// Construct the index from documents made of multiple fields, like a name and its aliases.
+    /// The [`RankingRule::Attribute`] then ranks up the documents matching the query in their first fields.
+    /// The text of a document is its fields separated by newlines, a newline in a field is replaced by a space.
+    pub fn construct_with_fields<S: AsRef<str>>(
+        &self,
+        documents: &[impl AsRef<[S]>],
+        writer: &mut impl std::io::Write,
+    ) -> Result<(), Error> {
+        let documents = documents
+            .iter()
+            .map(|fields| Cow::Owned(Fields::join(fields.as_ref())))
             .collect();
-        let mut ret = Vec::with_capacity(words.len());
+        let reporter = self.reporter();
+        self.build(documents, None, true, &reporter)?
+            .write_with(writer, &reporter)
+    }
 
-        for (index, (word, normalized)) in words.iter().enumerate() {
-            let mut candidates =
-                WordCandidate::new(word.to_string(), normalized.to_string(), index);
-
-            // enable 1 typo every 3 letters maxed at 3 typos
-            let typo = (normalized.len() / 3).min(3);
-            let lev = &levenshtein[typo];
-
-            // if we're at the last word we should also run a prefix search
-            if index == words.len() - 1 {
-                let lev = lev.build_prefix_dfa(normalized);
-                let mut stream = self.fst.search(lev).into_stream();
-                while let Some((matched, id)) = stream.next() {
-                    candidates.insert_with_maybe_typo(
-                        std::str::from_utf8(matched).unwrap(),
-                        &self.bitmaps[id as usize],
-                    );
-                }
-            } else {
-                let lev = lev.build_dfa(normalized);
-                let mut stream = self.fst.search(lev).into_stream();
-                while let Some((matched, id)) = stream.next() {
-                    candidates.insert_with_maybe_typo(
-                        std::str::from_utf8(matched).unwrap(),
-                        &self.bitmaps[id as usize],
-                    );
-                }
-            }
+    /// Construct the index from any iterator of documents, their ids are assigned in the iteration order.
+    /// The documents are streamed to a temporary file instead of being kept in memory,
+    /// see [`IndexBuilder::construct_to`].
+    pub fn construct_from_iter(
+        &self,
+        documents: impl IntoIterator<Item = impl AsRef<str>>,
+        writer: &mut impl std::io::Write,
+    ) -> Result<(), Error> {
+        self.construct_to(writer, documents)
+    }
 
-            ret.push(candidates);
+    /// Construct the index while associating a payload to every document.
+    /// It can then be used to sort the results, see [`RankingRule::Sort`].
+    pub fn construct_with_payloads(
+        &self,
+        documents: &[(impl AsRef<str>, u64)],
+        writer: &mut impl std::io::Write,
+    ) -> Result<(), Error> {
+        let (documents, payloads) = documents
+            .iter()
+            .map(|(document, payload)| (Cow::Borrowed(document.as_ref()), *payload))
+            .unzip();
+        let reporter = self.reporter();
+        self.build(documents, Some(payloads), false, &reporter)?
+            .write_with(writer, &reporter)
+    }
+
+    /// Construct the index from documents tokenized beforehand, like the lemmas of an external pipeline:
+    /// a document is the text stored and returned by [`Index::get_document`] along with the tokens indexed in its place.
+    /// The tokens are only lowercased, neither the normalizer nor the stemmer of the builder is applied to them,
+    /// and the empty ones and the stop words are skipped. Search them with [`Search::with_pretokenized_query`].
+    ///
+    /// The stored text still goes through the tokenizer of the builder when it's read back: by the highlights,
+    /// by the byte exact bucket of [`RankingRule::Exact`], and by [`Index::add_documents`] and [`Index::compact`]
+    /// which index it like any other document. [`IndexBuilder::with_dedup_documents`] is ignored.
+    ///
+    /// ```
+    /// use zearch::{IndexBuilder, Index, Search};
+    ///
+    /// let documents = [
+    ///     ("The mice were running", &["the", "mouse", "be", "run"][..]),
+    ///     ("A mouse runs", &["a", "mouse", "run"][..]),
+    /// ];
+    /// let mut bytes = Vec::new();
+    /// IndexBuilder::new().construct_pretokenized(&documents, &mut bytes).unwrap();
+    /// let index = Index::from_bytes(&bytes).unwrap();
+    ///
+    /// let mut search = Search::new("");
+    /// search.with_pretokenized_query(&["mouse", "be"]);
+    /// assert_eq!(index.search(&search), [0]);
+    /// assert_eq!(index.get_document(0), Some("The mice were running"));
+    /// // the stored text isn't indexed
+    /// assert!(index.search(&Search::new("mice")).is_empty());
+    /// ```
+    pub fn construct_pretokenized<S: AsRef<str>>(
+        &self,
+        documents: &[(impl AsRef<str>, impl AsRef<[S]>)],
+        writer: &mut impl std::io::Write,
+    ) -> Result<(), Error> {
+        let (documents, tokens) = documents
+            .iter()
+            .map(|(text, tokens)| {
+                let tokens = tokens.as_ref().iter();
+                let tokens = tokens.map(|token| pretokenized(token.as_ref())).collect();
+                (Cow::Borrowed(text.as_ref()), tokens)
+            })
+            .unzip();
+        let reporter = self.reporter();
+        self.build_with(documents, None, false, Some(tokens), &reporter)?
+            .write_with(writer, &reporter)
+    }
+
+    /// Build the index in memory, with `fields` the documents are split in fields on their newlines.
+    fn build<'d>(
+        &self,
+        documents: Vec<Cow<'d, str>>,
+        payloads: Option<Vec<u64>>,
+        fields: bool,
+        reporter: &Reporter,
+    ) -> Result<Index<'d>, Error> {
+        self.build_with(documents, payloads, fields, None, reporter)
+    }
+
+    /// Same as [`IndexBuilder::build`] but the words of every document are `tokens` when they're given,
+    /// see [`IndexBuilder::construct_pretokenized`].
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(name = "construct", skip_all, fields(documents = documents.len(), elapsed_us))
+    )]
+    fn build_with<'d>(
+        &self,
+        documents: Vec<Cow<'d, str>>,
+        payloads: Option<Vec<u64>>,
+        fields: bool,
+        tokens: Option<Vec<Vec<String>>>,
+        reporter: &Reporter,
+    ) -> Result<Index<'d>, Error> {
+        let _elapsed = trace::Elapsed::start();
+        if u32::try_from(documents.len()).is_err() {
+            return Err(Error::TooManyDocuments(documents.len()));
+        }
+        if self.dedup_documents && tokens.is_none() {
+            let (builder, documents, payloads) = self.remove_duplicates(documents, payloads);
+            return builder.build(documents, payloads, fields, reporter);
         }
 
-        ret
+        let stop_words = self.normalized_stop_words();
+        let analyzer = self.analyzer(&stop_words);
+        let runs = match &tokens {
+            Some(tokens) => pretokenized_runs(tokens, analyzer, reporter)?,
+            None => self.word_runs(&documents, analyzer, reporter)?,
+        };
+        let total = runs.iter().map(Vec::len).sum();
+        let words = KWayMerge::new(runs.into_iter().map(Vec::into_iter).collect());
+        let (fst, bitmaps) = build_words(words, total, reporter)?;
+        let fst = Map::new(Cow::Owned(fst))?;
+        let common_words = self.common_words(&bitmaps, documents.len());
+
+        // we need the final id of every word, thus we have to wait for the fst to be built
+        let document_word_ids = |id: usize| match &tokens {
+            Some(tokens) => {
+                let ids = tokens[id].iter().filter_map(|token| fst.get(token));
+                ids.map(|id| id as u32).collect()
+            }
+            None => word_ids(&fst, analyzer, documents[id].as_ref()),
+        };
+        let positions = self
+            .positions
+            .then(|| (0..documents.len()).map(document_word_ids).collect());
+        let term_frequencies = self.term_frequencies.then(|| {
+            (0..documents.len())
+                .map(|id| term_frequencies(document_word_ids(id)))
+                .collect()
+        });
+        let word_counts = self.word_counts.then(|| match &tokens {
+            Some(tokens) => tokens
+                .iter()
+                .map(|tokens| {
+                    let words = tokens
+                        .iter()
+                        .filter(|token| !token.is_empty() && !analyzer.is_stop_word(token));
+                    words.count().min(u16::MAX as usize) as u16
+                })
+                .collect(),
+            None => documents
+                .iter()
+                .map(|document| word_count(analyzer, document.as_ref()))
+                .collect(),
+        });
+        let fields = fields.then(|| {
+            let mut fields = Fields::default();
+            fields.insert(&fst, analyzer, 0, &documents);
+            fields
+        });
+        let boosts = (!self.boosts.is_empty()).then(|| {
+            let mut boosts = self.boosts.clone();
+            boosts.resize(documents.len(), 1.0);
+            boosts
+        });
+        let coordinates = (!self.coordinates.is_empty()).then(|| {
+            let mut coordinates = self.coordinates.clone();
+            coordinates.resize(documents.len(), NO_COORDINATES);
+            coordinates
+        });
+
+        let documents = match self.without_documents {
+            true => Documents::Missing(documents.len() as u32),
+            #[cfg(feature = "compression")]
+            false if self.compression => Documents::compressed(&documents)?,
+            false => Documents::Stored(documents),
+        };
+
+        Ok(Index {
+            documents,
+            bitmaps: Bitmaps::Decoded(bitmaps),
+            fst,
+            positions,
+            word_counts,
+            term_frequencies,
+            payloads,
+            fields,
+            boosts,
+            coordinates,
+            search_defaults: self.search_defaults(),
+            deleted: RoaringBitmap::new(),
+            stop_words,
+            exact_words: self.normalized_exact_words(),
+            common_words,
+            tokenizer: self.tokenizer,
+            normalizer: self.normalizer,
+            stemmer: self.stemmer,
+            generation: cache::next_generation(),
+        })
     }
 }
 
-#[derive(Debug)]
-pub(crate) struct WordCandidate {
-    // the original string
-    original: String,
-    // normalized string
-    normalized: String,
-    // its index in the phrase
-    index: usize,
-    // the number of documuents its contained in
-    typos: Vec<RoaringBitmap>,
-}
+impl IndexBuilder {
+    fn reporter(&self) -> Reporter<'_> {
+        Reporter::new(self.progress.as_ref(), self.cancel.as_deref())
+    }
 
-impl WordCandidate {
-    pub fn new(original: String, normalized: String, index: usize) -> Self {
-        Self {
-            original,
-            normalized,
-            index,
-            // we have a maximum of 3 typos
-            typos: vec![RoaringBitmap::new(); 4],
+    /// Returns the stop words normalized and stemmed, sorted and deduplicated.
+    fn normalized_stop_words(&self) -> Vec<String> {
+        self.analyzer(&[]).normalize_all(&self.stop_words)
+    }
+
+    /// Returns the exact words normalized and stemmed, sorted and deduplicated.
+    fn normalized_exact_words(&self) -> Vec<String> {
+        self.analyzer(&[]).normalize_all(&self.exact_words)
+    }
+
+    fn analyzer<'s>(&self, stop_words: &'s [String]) -> Analyzer<'s> {
+        Analyzer {
+            tokenizer: self.tokenizer,
+            normalizer: self.normalizer,
+            stemmer: self.stemmer,
+            stop_words,
         }
     }
 
-    // Since the fst::Automaton doesn't tells us which automaton matched and with how many typos or prefixes
-    // we need to recompute the stuff ourselves and insert our shit in the right cell
-    pub fn insert_with_maybe_typo(&mut self, other: &str, bitmap: &RoaringBitmap) {
-        // TODO: why is this crate taking ownership of my value to do a read only operation :(
-        let distance = DamerauLevenshtein {
-            src: self.normalized.clone(),
-            // if we did a prefix query we shouldn't count the extra letters as typo
-            tar: other[0..other.len().min(self.normalized.len())].to_string(),
-            restricted: true,
-        }
-        .distance();
-
-        // distance shouldn't be able to go over 3 but we don't want any crash so let's ensure that
-        let distance = distance.min(3);
-        self.typos[distance] |= bitmap;
+    /// Returns the id of the words whose document frequency is over the maximum.
+    fn common_words(&self, bitmaps: &[RoaringBitmap], nb_documents: usize) -> RoaringBitmap {
+        let Some(max_frequency) = self.max_document_frequency else {
+            return RoaringBitmap::new();
+        };
+        let max_documents = max_frequency as f64 * nb_documents as f64;
+        bitmaps
+            .iter()
+            .enumerate()
+            .filter(|(_, bitmap)| {
+                bitmap.len() >= MIN_COMMON_WORD_DOCUMENTS && bitmap.len() as f64 > max_documents
+            })
+            .map(|(id, _)| id as u32)
+            .collect()
     }
-}
 
-pub struct Search<'a> {
-    input: &'a str,
-    limit: usize,
-    ranking_rules: Vec<RankingRule>,
-}
+    /// Returns the words of the documents in multiple runs sorted by word.
+    /// The ids of the documents are always their position in the slice.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(name = "tokenize", skip_all, fields(elapsed_us))
+    )]
+    fn word_runs(
+        &self,
+        documents: &[impl AsRef<str>],
+        analyzer: Analyzer,
+        reporter: &Reporter,
+    ) -> Result<Vec<Vec<(Id, String)>>, Error> {
+        let _elapsed = trace::Elapsed::start();
+        reporter.phase(Phase::Tokenizing, Some(documents.len()))?;
 
-impl<'a> Search<'a> {
-    /// Create a new search requests from an input
-    pub fn new(input: &'a str) -> Self {
-        Self {
-            input,
-            limit: 10,
-            ranking_rules: vec![RankingRule::Word, RankingRule::Typo, RankingRule::Exact],
+        #[cfg(feature = "parallel")]
+        if self.threads != 1 {
+            use rayon::prelude::*;
+
+            let documents: Vec<&str> = documents.iter().map(|document| document.as_ref()).collect();
+            let pool = rayon::ThreadPoolBuilder::new()
+                .num_threads(self.threads)
+                .build()?;
+            let chunk_size = documents.len().div_ceil(pool.current_num_threads()).max(1);
+            return pool.install(|| {
+                let mut runs = documents
+                    .par_chunks(chunk_size)
+                    .enumerate()
+                    .map(|(chunk, documents)| {
+                        let first_id = (chunk * chunk_size) as Id;
+                        tokenize(documents, first_id, analyzer, reporter)
+                    })
+                    .collect::<Result<Vec<_>, Error>>()?;
+
+                reporter.phase(Phase::Sorting, Some(runs.iter().map(Vec::len).sum()))?;
+                runs.par_iter_mut().try_for_each(|run| {
+                    sort_words(run);
+                    reporter.advance(run.len())
+                })?;
+                Ok(runs)
+            });
         }
-    }
 
-    /// Customize the number of results you want to get back
-    pub fn with_limit(&mut self, limit: usize) -> &mut Self {
-        self.limit = limit;
-        self
+        let mut words = tokenize(documents, 0, analyzer, reporter)?;
+        reporter.phase(Phase::Sorting, Some(words.len()))?;
+        sort_words(&mut words);
+        reporter.advance(words.len())?;
+        Ok(vec![words])
     }
 }
 
-fn normalize(s: &str) -> String {
-    s.chars()
-        .filter_map(|c| match c.to_ascii_lowercase() {
-            'á' | 'â' | 'à' | 'ä' => Some('a'),
-            'é' | 'ê' | 'è' | 'ë' => Some('e'),
-            'í' | 'î' | 'ì' | 'ï' => Some('i'),
-            'ó' | 'ô' | 'ò' | 'ö' => Some('o'),
-            'ú' | 'û' | 'ù' | 'ü' => Some('u'),
-            c if c.is_ascii_punctuation() || !c.is_ascii_graphic() || c.is_ascii_control() => None,
-            c => Some(c),
-        })
-        .collect()
+/// How a text is turned in the words stored in the fst, the documents and the queries must go through the same one.
+#[derive(Debug, Clone, Copy)]
+struct Analyzer<'s> {
+    tokenizer: TokenizerKind,
+    normalizer: NormalizerKind,
+    stemmer: Stemmer,
+    // normalized, stemmed and sorted
+    stop_words: &'s [String],
 }
 
-#[cfg(test)]
-mod test {
-    use super::*;
+impl Analyzer<'_> {
+    /// Returns every word of the text with its normalized and stemmed version, including the stop words.
+    fn words<'a, 't: 'a>(
+        &'a self,
+        text: &'t str,
+    ) -> impl Iterator<Item = (Token<'t>, String)> + 'a {
+        self.tokenizer
+            .tokenize(text)
+            .map(|token| (token, self.normalize(token.text)))
+    }
 
-    fn create_small_index() -> Index<'static> {
+    /// Returns the words of the text that can be indexed and searched, the ones with an empty normalized version
+    /// like the punctuation are skipped. Both the documents and the queries go through it.
+    fn tokens<'a, 't: 'a>(&'a self, text: &'t str) -> impl Iterator<Item = AnalyzedToken<'t>> + 'a {
+        self.words(text)
+            .filter(|(_, normalized)| !normalized.is_empty())
+            .enumerate()
+            .map(|(index, (token, normalized))| AnalyzedToken {
+                original: token.text,
+                normalized,
+                byte_range: token.offset..token.offset + token.text.len(),
+                index,
+            })
+    }
+
+    /// Normalize the words like [`Analyzer::normalize`], sorted and deduplicated.
+    fn normalize_all(&self, words: &[impl AsRef<str>]) -> Vec<String> {
+        let mut words: Vec<String> = words
+            .iter()
+            .map(|word| self.normalize(word.as_ref()))
+            .filter(|word| !word.is_empty())
+            .collect();
+        words.sort_unstable();
+        words.dedup();
+        words
+    }
+
+    /// Normalize the word and then reduce it to its stem.
+    fn normalize(&self, word: &str) -> String {
+        let normalized = self.normalizer.normalize(word);
+        match self.stemmer.stem(&normalized) {
+            Cow::Borrowed(_) => normalized,
+            Cow::Owned(stem) => stem,
+        }
+    }
+
+    fn is_stop_word(&self, normalized: &str) -> bool {
+        self.stop_words
+            .binary_search_by(|word| word.as_str().cmp(normalized))
+            .is_ok()
+    }
+}
+
+/// Returns all the normalized words of the documents with the id of their document.
+/// The first document gets the id `first_id`.
+fn tokenize(
+    documents: &[impl AsRef<str>],
+    first_id: Id,
+    analyzer: Analyzer,
+    reporter: &Reporter,
+) -> Result<Vec<(Id, String)>, Error> {
+    let mut words = Vec::new();
+    let mut buffer = Vec::new();
+    for (id, document) in documents.iter().enumerate() {
+        let id = first_id + id as Id;
+        unique_words(document.as_ref(), analyzer, &mut buffer);
+        words.extend(buffer.drain(..).map(|word| (id, word)));
+        reporter.advance(1)?;
+    }
+    Ok(words)
+}
+
+/// Fills `buffer` with the normalized words of the document, without duplicates and stop words.
+/// A document only needs to be inserted once in the bitmap of its words,
+/// sorting the duplicates with all the other words would be a waste of memory.
+fn unique_words(document: &str, analyzer: Analyzer, buffer: &mut Vec<String>) {
+    buffer.clear();
+    buffer.extend(
+        analyzer
+            .tokens(document)
+            .map(|token| token.normalized)
+            .filter(|word| !analyzer.is_stop_word(word)),
+    );
+    buffer.sort_unstable();
+    buffer.dedup();
+}
+
+/// Same as [`IndexBuilder::word_runs`] for the documents tokenized beforehand, their tokens are already normalized.
+fn pretokenized_runs(
+    documents: &[Vec<String>],
+    analyzer: Analyzer,
+    reporter: &Reporter,
+) -> Result<Vec<Vec<(Id, String)>>, Error> {
+    reporter.phase(Phase::Tokenizing, Some(documents.len()))?;
+    let mut words = Vec::new();
+    let mut buffer = Vec::new();
+    for (id, tokens) in documents.iter().enumerate() {
+        buffer.clear();
+        buffer.extend(
+            tokens
+                .iter()
+                .filter(|token| !token.is_empty() && !analyzer.is_stop_word(token)),
+        );
+        buffer.sort_unstable();
+        buffer.dedup();
+        words.extend(buffer.drain(..).map(|token| (id as Id, token.clone())));
+        reporter.advance(1)?;
+    }
+    reporter.phase(Phase::Sorting, Some(words.len()))?;
+    sort_words(&mut words);
+    reporter.advance(words.len())?;
+    Ok(vec![words])
+}
+
+/// How a token given by [`IndexBuilder::construct_pretokenized`] or [`Search::with_pretokenized_query`] is normalized.
+fn pretokenized(token: &str) -> String {
+    token.to_lowercase()
+}
+
+fn sort_words(words: &mut [(Id, String)]) {
+    words.sort_unstable_by(|(_, left), (_, right)| left.cmp(right));
+}
+
+/// Build the fst and the bitmap of every word from the `total` words sorted by word.
+/// The words get their id in the order they come.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(name = "build_fst", skip_all, fields(words = total, elapsed_us))
+)]
+fn build_words(
+    words: impl IntoIterator<Item = (Id, String)>,
+    total: usize,
+    reporter: &Reporter,
+) -> Result<(Vec<u8>, Vec<RoaringBitmap>), Error> {
+    let _elapsed = trace::Elapsed::start();
+    reporter.phase(Phase::BuildingFst, Some(total))?;
+    let mut build = MapBuilder::memory();
+    let mut last_word = None;
+    let mut bitmaps = Vec::new();
+
+    // the value of a word can only be inserted once we've seen all its documents
+    let mut insert = |word: String, bitmaps: &mut Vec<RoaringBitmap>| {
+        let id = (bitmaps.len() - 1) as u32;
+        let bitmap = bitmaps.last_mut().unwrap();
+        // the words appearing in most of the documents are a lot smaller as runs
+        bitmap.optimize();
+        build.insert(word, word_value(id, bitmap))
+    };
+    for (id, word) in words {
+        reporter.advance(1)?;
+        if last_word.as_ref() != Some(&word) {
+            if let Some(last_word) = last_word.take() {
+                insert(last_word, &mut bitmaps)?;
+            }
+            bitmaps.push(RoaringBitmap::from_iter([id]));
+            last_word = Some(word);
+        } else {
+            bitmaps.last_mut().unwrap().insert(id);
+        }
+    }
+    if let Some(last_word) = last_word {
+        insert(last_word, &mut bitmaps)?;
+    }
+
+    Ok((build.into_inner()?, bitmaps))
+}
+
+/// Returns the number of words of the document that are indexed, see [`IndexBuilder::with_word_counts`].
+fn word_count(analyzer: Analyzer, document: &str) -> u16 {
+    let words = analyzer
+        .tokens(document)
+        .filter(|token| !analyzer.is_stop_word(&token.normalized));
+    words.count().min(u16::MAX as usize) as u16
+}
+
+/// Returns every word id of the document once with its number of occurrences, sorted by id,
+/// see [`IndexBuilder::with_term_frequencies`].
+fn term_frequencies(mut ids: Vec<u32>) -> Vec<(u32, u16)> {
+    ids.sort_unstable();
+    let mut frequencies: Vec<(u32, u16)> = Vec::new();
+    for id in ids {
+        match frequencies.last_mut() {
+            Some((last, count)) if *last == id => *count = count.saturating_add(1),
+            _ => frequencies.push((id, 1)),
+        }
+    }
+    frequencies
+}
+
+/// Returns the id of every word of the document in the order they appear.
+fn word_ids(fst: &Map<impl AsRef<[u8]>>, analyzer: Analyzer, document: &str) -> Vec<u32> {
+    analyzer
+        .words(document)
+        .filter_map(|(_, word)| fst.get(word))
+        .map(|id| id as u32)
+        .collect()
+}
+
+/// Computes the checksum of everything written through it.
+struct ChecksumWriter<W> {
+    inner: W,
+    hasher: crc32fast::Hasher,
+}
+
+impl<W: std::io::Write> ChecksumWriter<W> {
+    fn new(inner: W) -> Self {
+        Self {
+            inner,
+            hasher: crc32fast::Hasher::new(),
+        }
+    }
+
+    /// Write the checksum of everything that was written before.
+    fn finish(&mut self) -> std::io::Result<()> {
+        let checksum = self.hasher.clone().finalize();
+        self.inner.write_all(&checksum.to_be_bytes())
+    }
+}
+
+impl<W: std::io::Write> std::io::Write for ChecksumWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.hasher.update(&buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<'a> Index<'a> {
+    pub fn construct(
+        documents: &[impl AsRef<str>],
+        writer: &mut impl std::io::Write,
+    ) -> Result<(), Error> {
+        IndexBuilder::new().construct(documents, writer)
+    }
+
+    pub fn construct_with_payloads(
+        documents: &[(impl AsRef<str>, u64)],
+        writer: &mut impl std::io::Write,
+    ) -> Result<(), Error> {
+        IndexBuilder::new().construct_with_payloads(documents, writer)
+    }
+
+    pub fn construct_from_iter(
+        documents: impl IntoIterator<Item = impl AsRef<str>>,
+        writer: &mut impl std::io::Write,
+    ) -> Result<(), Error> {
+        IndexBuilder::new().construct_from_iter(documents, writer)
+    }
+
+    /// See [`IndexBuilder::construct_with_fields`].
+    ///
+    /// ```
+    /// use zearch::{Index, RankingRule, Search};
+    ///
+    /// // a name and an alias
+    /// let documents = [vec!["Tamo", "Kefir"], vec!["Kefir", "Tamo"]];
+    /// let mut bytes = Vec::new();
+    /// Index::construct_with_fields(&documents, &mut bytes).unwrap();
+    /// let index = Index::from_bytes(&bytes).unwrap();
+    ///
+    /// let mut search = Search::new("kefir");
+    /// assert_eq!(index.search(&search), [0, 1]);
+    /// search.with_ranking_rules(vec![RankingRule::Word, RankingRule::Attribute]);
+    /// assert_eq!(index.search(&search), [1, 0]);
+    /// ```
+    pub fn construct_with_fields<S: AsRef<str>>(
+        documents: &[impl AsRef<[S]>],
+        writer: &mut impl std::io::Write,
+    ) -> Result<(), Error> {
+        IndexBuilder::new().construct_with_fields(documents, writer)
+    }
+
+    /// Write the index in the same format as [`Index::construct`], it can be loaded back with [`Index::from_bytes`].
+    pub fn write_to(&self, writer: &mut impl std::io::Write) -> Result<(), Error> {
+        self.write_with(writer, &Reporter::silent())
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(name = "write", skip_all, fields(elapsed_us))
+    )]
+    fn write_with(
+        &self,
+        writer: &mut impl std::io::Write,
+        reporter: &Reporter,
+    ) -> Result<(), Error> {
+        let _elapsed = trace::Elapsed::start();
+        let writer = &mut ChecksumWriter::new(writer);
+        Self::write_header(writer, self.tokenizer, self.normalizer, self.stemmer)?;
+
+        reporter.phase(Phase::WritingDocuments, Some(self.documents.len()))?;
+        match &self.documents {
+            Documents::Stored(documents) => {
+                Self::write_size(writer, documents.len())?;
+                for document in documents {
+                    Self::write_slice(writer, document.as_bytes())?;
+                    reporter.advance(1)?;
+                }
+            }
+            Documents::Missing(_) | Documents::Compressed { .. } => Self::write_size(writer, 0)?,
+        }
+
+        // every bitmap is prefixed by its size so we can find them without decoding them
+        reporter.phase(Phase::WritingBitmaps, Some(self.bitmaps.len()))?;
+        Self::write_size(writer, self.bitmaps.len())?;
+        match &self.bitmaps {
+            Bitmaps::Serialized { bitmaps, .. } => {
+                for bitmap in bitmaps {
+                    Self::write_slice(writer, bitmap)?;
+                    reporter.advance(1)?;
+                }
+            }
+            Bitmaps::Decoded(bitmaps) => {
+                let mut buffer = Vec::new();
+                for bitmap in bitmaps {
+                    buffer.clear();
+                    bitmaps::serialize(bitmap, &mut buffer)?;
+                    Self::write_slice(writer, &buffer)?;
+                    reporter.advance(1)?;
+                }
+            }
+        }
+
+        Self::write_slice(writer, self.fst.as_fst().as_bytes())?;
+
+        match &self.documents {
+            Documents::Stored(_) => (),
+            Documents::Missing(count) => Self::write_document_count(writer, *count as usize)?,
+            Documents::Compressed {
+                count,
+                first_ids,
+                blocks,
+                ..
+            } => {
+                let mut section = Vec::new();
+                Documents::write_compressed_header(&mut section, *count as usize, first_ids)?;
+                for block in blocks {
+                    Self::write_slice(&mut section, block)?;
+                }
+                writer.write_all(&[Section::CompressedDocuments as u8])?;
+                Self::write_slice(writer, &section)?;
+            }
+        }
+        if let Some(positions) = &self.positions {
+            let mut section = Vec::new();
+            for ids in positions {
+                Self::write_size(&mut section, ids.len())?;
+                for id in ids {
+                    section.extend_from_slice(&id.to_be_bytes());
+                }
+            }
+            writer.write_all(&[Section::Positions as u8])?;
+            Self::write_slice(writer, &section)?;
+        }
+        if let Some(word_counts) = &self.word_counts {
+            Self::write_word_counts(writer, word_counts)?;
+        }
+        if let Some(term_frequencies) = &self.term_frequencies {
+            let mut section = Vec::new();
+            for frequencies in term_frequencies {
+                Self::write_term_frequencies(&mut section, frequencies)?;
+            }
+            writer.write_all(&[Section::TermFrequencies as u8])?;
+            Self::write_slice(writer, &section)?;
+        }
+
+        if let Some(payloads) = &self.payloads {
+            let section: Vec<u8> = payloads
+                .iter()
+                .flat_map(|payload| payload.to_be_bytes())
+                .collect();
+            writer.write_all(&[Section::Payloads as u8])?;
+            Self::write_slice(writer, &section)?;
+        }
+
+        if let Some(fields) = &self.fields {
+            let mut section = Vec::new();
+            fields.write(&mut section)?;
+            writer.write_all(&[Section::Fields as u8])?;
+            Self::write_slice(writer, &section)?;
+        }
+        if let Some(boosts) = &self.boosts {
+            Self::write_boosts(writer, boosts)?;
+        }
+        if let Some(coordinates) = &self.coordinates {
+            Self::write_coordinates(writer, coordinates)?;
+        }
+        Self::write_search_defaults(writer, self.search_defaults.as_ref())?;
+
+        if !self.deleted.is_empty() {
+            let mut section = Vec::new();
+            self.deleted.serialize_into(&mut section)?;
+            writer.write_all(&[Section::Tombstones as u8])?;
+            Self::write_slice(writer, &section)?;
+        }
+
+        Self::write_words(writer, Section::StopWords, &self.stop_words)?;
+        Self::write_words(writer, Section::ExactWords, &self.exact_words)?;
+        Self::write_common_words(writer, &self.common_words)?;
+
+        writer.finish()?;
+
+        Ok(())
+    }
+
+    // The sections are written by associated functions since `IndexBuilder::construct_to`
+    // writes them without building an `Index`.
+    fn write_header(
+        writer: &mut impl std::io::Write,
+        tokenizer: TokenizerKind,
+        normalizer: NormalizerKind,
+        stemmer: Stemmer,
+    ) -> Result<(), Error> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&VERSION.to_be_bytes())?;
+        writer.write_all(&[tokenizer as u8, normalizer as u8, stemmer as u8])?;
+        Ok(())
+    }
+
+    fn write_common_words(
+        writer: &mut impl std::io::Write,
+        common_words: &RoaringBitmap,
+    ) -> Result<(), Error> {
+        if common_words.is_empty() {
+            return Ok(());
+        }
+        let mut section = Vec::new();
+        common_words.serialize_into(&mut section)?;
+        writer.write_all(&[Section::CommonWords as u8])?;
+        Self::write_slice(writer, &section)
+    }
+
+    fn write_boosts(writer: &mut impl std::io::Write, boosts: &[f32]) -> Result<(), Error> {
+        let section: Vec<u8> = boosts
+            .iter()
+            .flat_map(|boost| boost.to_be_bytes())
+            .collect();
+        writer.write_all(&[Section::Boosts as u8])?;
+        Self::write_slice(writer, &section)
+    }
+
+    fn write_word_counts(writer: &mut impl std::io::Write, counts: &[u16]) -> Result<(), Error> {
+        let section: Vec<u8> = counts
+            .iter()
+            .flat_map(|count| count.to_be_bytes())
+            .collect();
+        writer.write_all(&[Section::WordCounts as u8])?;
+        Self::write_slice(writer, &section)
+    }
+
+    /// Write the term frequencies of a single document, the section is made of those of every document.
+    fn write_term_frequencies(
+        writer: &mut impl std::io::Write,
+        frequencies: &[(u32, u16)],
+    ) -> Result<(), Error> {
+        Self::write_size(writer, frequencies.len())?;
+        for (id, count) in frequencies {
+            writer.write_all(&id.to_be_bytes())?;
+            writer.write_all(&count.to_be_bytes())?;
+        }
+        Ok(())
+    }
+
+    fn write_search_defaults(
+        writer: &mut impl std::io::Write,
+        defaults: Option<&SearchDefaults>,
+    ) -> Result<(), Error> {
+        let Some(defaults) = defaults else {
+            return Ok(());
+        };
+        let mut section = Vec::new();
+        defaults.write(&mut section)?;
+        writer.write_all(&[Section::SearchDefaults as u8])?;
+        Self::write_slice(writer, &section)
+    }
+
+    fn write_coordinates(
+        writer: &mut impl std::io::Write,
+        coordinates: &[(f32, f32)],
+    ) -> Result<(), Error> {
+        let section: Vec<u8> = coordinates
+            .iter()
+            .flat_map(|(lat, lng)| [lat.to_be_bytes(), lng.to_be_bytes()])
+            .flatten()
+            .collect();
+        writer.write_all(&[Section::Coordinates as u8])?;
+        Self::write_slice(writer, &section)
+    }
+
+    fn write_document_count(writer: &mut impl std::io::Write, count: usize) -> Result<(), Error> {
+        writer.write_all(&[Section::DocumentCount as u8])?;
+        Self::write_slice(writer, &(count as u32).to_be_bytes())
+    }
+
+    /// Write a list of words, like the stop words, in the section `kind` if it's not empty.
+    fn write_words(
+        writer: &mut impl std::io::Write,
+        kind: Section,
+        words: &[String],
+    ) -> Result<(), Error> {
+        if words.is_empty() {
+            return Ok(());
+        }
+        let mut section = Vec::new();
+        for word in words {
+            Self::write_slice(&mut section, word.as_bytes())?;
+        }
+        writer.write_all(&[kind as u8])?;
+        Self::write_slice(writer, &section)
+    }
+
+    /// Read a section written by [`Index::write_words`], `None` if the words are not sorted.
+    fn read_words(mut section: &[u8]) -> Option<Vec<String>> {
+        let mut words = Vec::new();
+        while !section.is_empty() {
+            let word = Self::read_slice_from_bytes(&mut section)?;
+            words.push(std::str::from_utf8(word).ok()?.to_string());
+        }
+        // we're looking for them with a binary search
+        words.is_sorted().then_some(words)
+    }
+
+    /// Serialize the index in memory, see [`Index::write_to`].
+    pub fn to_bytes(&self) -> Result<Vec<u8>, Error> {
+        let mut bytes = Vec::new();
+        self.write_to(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    /// All the sizes are stored on a `u32`, we must fail instead of writing a wrapped size.
+    fn write_size(writer: &mut impl std::io::Write, size: usize) -> Result<(), Error> {
+        let size = u32::try_from(size).map_err(|_| Error::TooLarge(size))?;
+        writer.write_all(size.to_be_bytes().as_slice())?;
+        Ok(())
+    }
+
+    fn write_slice(writer: &mut impl std::io::Write, slice: &[u8]) -> Result<(), Error> {
+        Self::write_size(writer, slice.len())?;
+        writer.write_all(slice)?;
+        Ok(())
+    }
+
+    fn read_size_from_bytes(bytes: &mut &[u8]) -> Option<u32> {
+        const U32SIZE: usize = std::mem::size_of::<u32>();
+        let (size, b) = bytes.split_first_chunk::<U32SIZE>()?;
+        *bytes = b;
+        Some(u32::from_be_bytes(*size))
+    }
+
+    /// Read the number of elements that follows, each of them taking at least `min_size` bytes.
+    /// Returns `None` if there isn't enough bytes left to store them so we never loop or allocate for nothing.
+    fn read_count_from_bytes(bytes: &mut &[u8], min_size: usize) -> Option<u32> {
+        let count = Self::read_size_from_bytes(bytes)?;
+        if (count as usize).checked_mul(min_size)? > bytes.len() {
+            return None;
+        }
+        Some(count)
+    }
+
+    fn read_slice_from_bytes<'b>(bytes: &mut &'b [u8]) -> Option<&'b [u8]> {
+        let size = Self::read_size_from_bytes(bytes)?;
+        if bytes.len() < size as usize {
+            return None;
+        }
+        let ret = &bytes[..size as usize];
+        *bytes = &bytes[size as usize..];
+
+        Some(ret)
+    }
+
+    /// Load an index without copying the bytes, the bitmaps are only decoded when needed.
+    pub fn from_bytes(bytes: &'a [u8]) -> Result<Self, Error> {
+        let payload = Self::split_payload(bytes, true)?;
+        let index = Self::read_index(payload).ok_or(Error::Corrupted)?;
+        index.documents.check_supported()?;
+        Ok(index)
+    }
+
+    /// Same as [`Index::from_bytes`] but doesn't verify the checksum of the index.
+    /// Checking the checksum requires reading all the bytes once, which can be slow on very large mmap'd indexes.
+    /// Only the structure of the index is validated thus the searches on a corrupted index can panic,
+    /// the bytes must come from a trusted source.
+    pub fn from_bytes_unverified(bytes: &'a [u8]) -> Result<Self, Error> {
+        let payload = Self::split_payload(bytes, false)?;
+        let index = Self::read_index(payload).ok_or(Error::Corrupted)?;
+        index.documents.check_supported()?;
+        Ok(index)
+    }
+
+    /// Check the header and the checksum of an index without loading it.
+    pub fn validate(bytes: &[u8]) -> Result<(), Error> {
+        Self::split_payload(bytes, true).map(|_| ())
+    }
+
+    /// Check the header and returns the bytes between it and the checksum.
+    fn split_payload(bytes: &[u8], verify: bool) -> Result<&[u8], Error> {
+        let rest = bytes.strip_prefix(MAGIC).ok_or(Error::NotAnIndex)?;
+        let (version, rest) = rest.split_first_chunk().ok_or(Error::Corrupted)?;
+        let version = u16::from_be_bytes(*version);
+        if version != VERSION {
+            return Err(Error::UnsupportedVersion(version));
+        }
+        let (payload, checksum) = rest.split_last_chunk().ok_or(Error::Corrupted)?;
+        // the checksum covers everything that was written before it, header included
+        let hashed = &bytes[..bytes.len() - checksum.len()];
+        if verify && crc32fast::hash(hashed) != u32::from_be_bytes(*checksum) {
+            return Err(Error::ChecksumMismatch);
+        }
+        // the normalizer and stemmer may exist but not be enabled, the tokenizer is checked with the rest
+        if let Some(&[_tokenizer, normalizer, stemmer]) = payload.first_chunk() {
+            if NormalizerKind::from_id(normalizer).is_none() {
+                return Err(Error::UnsupportedNormalizer(normalizer));
+            }
+            if Stemmer::from_id(stemmer).is_none() {
+                return Err(Error::UnsupportedStemmer(stemmer));
+            }
+        }
+        Ok(payload)
+    }
+
+    /// Read the whole index in memory.
+    pub fn from_reader(mut reader: impl std::io::Read) -> Result<Index<'static>, Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        Ok(Index::from_bytes(&bytes)?.move_in_memory())
+    }
+
+    fn read_index(bytes: &'a [u8]) -> Option<Self> {
+        // 0. Read the end of the header
+        let (&[tokenizer, normalizer, stemmer], mut bytes) = bytes.split_first_chunk()?;
+        let tokenizer = TokenizerKind::from_id(tokenizer)?;
+        let normalizer = NormalizerKind::from_id(normalizer)?;
+        let stemmer = Stemmer::from_id(stemmer)?;
+
+        // 1. Read the documents
+        let mut documents = Vec::new();
+        // every document is prefixed by its size
+        let mut nb_documents = Self::read_count_from_bytes(&mut bytes, std::mem::size_of::<u32>())?;
+        for _ in 0..nb_documents {
+            let document = Self::read_slice_from_bytes(&mut bytes)?;
+            documents.push(Cow::Borrowed(std::str::from_utf8(document).ok()?));
+        }
+
+        // 2. Read the bitmap
+        let nb_bitmaps = Self::read_count_from_bytes(&mut bytes, std::mem::size_of::<u32>())?;
+        let mut bitmaps = Vec::with_capacity(nb_bitmaps as usize);
+        for _ in 0..nb_bitmaps {
+            bitmaps.push(Self::read_slice_from_bytes(&mut bytes)?);
+        }
+        let bitmaps = Bitmaps::serialized(bitmaps);
+
+        // 3. Read the fst
+        let fst = Self::read_slice_from_bytes(&mut bytes)?;
+        let fst = Map::new(Cow::Borrowed(fst)).ok()?;
+        // there is exactly one bitmap per word
+        if fst.len() != nb_bitmaps as usize {
+            return None;
+        }
+
+        // 4. Read the optional sections, the count of documents must come first since the others depend on it
+        let mut documents = Documents::Stored(documents);
+        if let Some((&kind, mut b)) = bytes.split_first() {
+            if kind == Section::DocumentCount as u8 && nb_documents == 0 {
+                let mut section = Self::read_slice_from_bytes(&mut b)?;
+                nb_documents = Self::read_size_from_bytes(&mut section)?;
+                if !section.is_empty() {
+                    return None;
+                }
+                documents = Documents::Missing(nb_documents);
+                bytes = b;
+            } else if kind == Section::CompressedDocuments as u8 && nb_documents == 0 {
+                let section = Self::read_slice_from_bytes(&mut b)?;
+                documents = Documents::read_compressed(section)?;
+                nb_documents = documents.len() as u32;
+                bytes = b;
+            }
+        }
+        let mut positions = None;
+        let mut word_counts = None;
+        let mut term_frequencies = None;
+        let mut payloads = None;
+        let mut fields = None;
+        let mut boosts = None;
+        let mut coordinates = None;
+        let mut search_defaults = None;
+        let mut deleted = RoaringBitmap::new();
+        let mut stop_words = Vec::new();
+        let mut exact_words = Vec::new();
+        let mut common_words = RoaringBitmap::new();
+        while let Some((&kind, b)) = bytes.split_first() {
+            bytes = b;
+            let mut section = Self::read_slice_from_bytes(&mut bytes)?;
+            match kind {
+                k if k == Section::Positions as u8 => {
+                    let mut all = Vec::with_capacity(nb_documents as usize);
+                    for _ in 0..nb_documents {
+                        let nb_words =
+                            Self::read_count_from_bytes(&mut section, std::mem::size_of::<u32>())?;
+                        let words = (0..nb_words)
+                            .map(|_| Self::read_size_from_bytes(&mut section))
+                            .collect::<Option<Vec<u32>>>()?;
+                        all.push(words);
+                    }
+                    positions = Some(all);
+                }
+                k if k == Section::WordCounts as u8 => {
+                    if section.len() != nb_documents as usize * std::mem::size_of::<u16>() {
+                        return None;
+                    }
+                    let values = section
+                        .chunks_exact(std::mem::size_of::<u16>())
+                        .map(|chunk| u16::from_be_bytes(chunk.try_into().unwrap()))
+                        .collect();
+                    word_counts = Some(values);
+                }
+                k if k == Section::TermFrequencies as u8 => {
+                    const ENTRY: usize = std::mem::size_of::<u32>() + std::mem::size_of::<u16>();
+                    let mut all = Vec::with_capacity(nb_documents as usize);
+                    for _ in 0..nb_documents {
+                        let nb_words = Self::read_count_from_bytes(&mut section, ENTRY)?;
+                        let (entries, rest) = section.split_at(nb_words as usize * ENTRY);
+                        section = rest;
+                        let frequencies = entries
+                            .chunks_exact(ENTRY)
+                            .map(|chunk| {
+                                let (id, count) = chunk.split_at(std::mem::size_of::<u32>());
+                                let id = u32::from_be_bytes(id.try_into().unwrap());
+                                let count = u16::from_be_bytes(count.try_into().unwrap());
+                                (id < nb_bitmaps).then_some((id, count))
+                            })
+                            .collect::<Option<Vec<_>>>()?;
+                        all.push(frequencies);
+                    }
+                    if !section.is_empty() {
+                        return None;
+                    }
+                    term_frequencies = Some(all);
+                }
+                k if k == Section::Payloads as u8 => {
+                    if section.len() != nb_documents as usize * std::mem::size_of::<u64>() {
+                        return None;
+                    }
+                    let values = section
+                        .chunks_exact(std::mem::size_of::<u64>())
+                        .map(|chunk| u64::from_be_bytes(chunk.try_into().unwrap()))
+                        .collect();
+                    payloads = Some(values);
+                }
+                k if k == Section::Fields as u8 => {
+                    fields = Some(Fields::read(section, nb_bitmaps, nb_documents)?);
+                }
+                k if k == Section::Boosts as u8 => {
+                    if section.len() != nb_documents as usize * std::mem::size_of::<f32>() {
+                        return None;
+                    }
+                    let values = section
+                        .chunks_exact(std::mem::size_of::<f32>())
+                        .map(|chunk| f32::from_be_bytes(chunk.try_into().unwrap()))
+                        .collect();
+                    boosts = Some(values);
+                }
+                k if k == Section::Coordinates as u8 => {
+                    if section.len() != nb_documents as usize * 2 * std::mem::size_of::<f32>() {
+                        return None;
+                    }
+                    let values = section
+                        .chunks_exact(2 * std::mem::size_of::<f32>())
+                        .map(|chunk| {
+                            let (lat, lng) = chunk.split_at(std::mem::size_of::<f32>());
+                            (
+                                f32::from_be_bytes(lat.try_into().unwrap()),
+                                f32::from_be_bytes(lng.try_into().unwrap()),
+                            )
+                        })
+                        .collect();
+                    coordinates = Some(values);
+                }
+                k if k == Section::SearchDefaults as u8 => {
+                    search_defaults = Some(SearchDefaults::read(section)?);
+                }
+                k if k == Section::Tombstones as u8 => {
+                    deleted = RoaringBitmap::deserialize_from(section).ok()?;
+                    if deleted.max().is_some_and(|max| max >= nb_documents) {
+                        return None;
+                    }
+                }
+                k if k == Section::StopWords as u8 => stop_words = Self::read_words(section)?,
+                k if k == Section::ExactWords as u8 => exact_words = Self::read_words(section)?,
+                k if k == Section::CommonWords as u8 => {
+                    common_words = RoaringBitmap::deserialize_from(section).ok()?;
+                    if common_words.max().is_some_and(|max| max >= nb_bitmaps) {
+                        return None;
+                    }
+                }
+                // we don't know how to interpret this section
+                _ => return None,
+            }
+        }
+
+        Some(Self {
+            documents,
+            bitmaps,
+            fst,
+            positions,
+            word_counts,
+            term_frequencies,
+            payloads,
+            fields,
+            boosts,
+            coordinates,
+            search_defaults,
+            deleted,
+            stop_words,
+            exact_words,
+            common_words,
+            tokenizer,
+            normalizer,
+            stemmer,
+            generation: cache::next_generation(),
+        })
+    }
+
+    /// Do the work the first search would otherwise pay for: build the Levenshtein automaton builders
+    /// shared by all the indexes, and read a byte of every page of the fst and of the serialized bitmaps
+    /// so a memory-mapped index is loaded from the disk. Nothing is decoded, the memory used doesn't change.
+    ///
+    /// ```
+    /// use zearch::Index;
+    ///
+    /// let index = Index::new_in_memory(&["kefir le chien"]).unwrap();
+    /// index.warm_up();
+    /// assert!(Index::is_warm());
+    /// ```
+    pub fn warm_up(&self) {
+        levenshtein_builders();
+        let fst = bitmaps::touch_pages(self.fst.as_fst().as_bytes());
+        std::hint::black_box(fst ^ self.bitmaps.touch_pages());
+    }
+
+    /// Returns `true` once the Levenshtein automaton builders were built, by [`Index::warm_up`] or a search.
+    pub fn is_warm() -> bool {
+        LEVENSHTEINS.get().is_some()
+    }
+
+    /// Also optimize the index, see [`Index::optimize`].
+    pub fn move_in_memory(self) -> Index<'static> {
+        let mut index = Index {
+            documents: self.documents.into_owned(),
+            bitmaps: Bitmaps::Decoded(self.bitmaps.into_decoded()),
+            fst: self
+                .fst
+                .map_data(|data| Cow::Owned(data.into_owned()))
+                .unwrap(),
+            positions: self.positions,
+            word_counts: self.word_counts,
+            term_frequencies: self.term_frequencies,
+            payloads: self.payloads,
+            fields: self.fields,
+            boosts: self.boosts,
+            coordinates: self.coordinates,
+            search_defaults: self.search_defaults,
+            deleted: self.deleted,
+            stop_words: self.stop_words,
+            exact_words: self.exact_words,
+            common_words: self.common_words,
+            tokenizer: self.tokenizer,
+            normalizer: self.normalizer,
+            stemmer: self.stemmer,
+            generation: self.generation,
+        };
+        index.optimize();
+        index
+    }
+
+    /// Run-compress the bitmaps and release the memory that was allocated but isn't used.
+    /// Returns an estimate of the number of bytes saved.
+    /// The bitmaps that are still serialized in the bytes of the index are left as they are.
+    pub fn optimize(&mut self) -> usize {
+        fn shrink<T>(vec: &mut Vec<T>) -> usize {
+            let saved = (vec.capacity() - vec.len()) * std::mem::size_of::<T>();
+            vec.shrink_to_fit();
+            saved
+        }
+
+        let mut saved = 0;
+        if let Bitmaps::Decoded(bitmaps) = &mut self.bitmaps {
+            for bitmap in bitmaps.iter_mut() {
+                let before = bitmap.serialized_size();
+                if bitmap.optimize() {
+                    saved += before.saturating_sub(bitmap.serialized_size());
+                }
+            }
+            saved += shrink(bitmaps);
+        }
+        if let Documents::Stored(documents) = &mut self.documents {
+            saved += shrink(documents);
+        }
+        if let Some(positions) = &mut self.positions {
+            saved += positions.iter_mut().map(shrink).sum::<usize>();
+            saved += shrink(positions);
+        }
+        if let Some(word_counts) = &mut self.word_counts {
+            saved += shrink(word_counts);
+        }
+        if let Some(term_frequencies) = &mut self.term_frequencies {
+            saved += term_frequencies.iter_mut().map(shrink).sum::<usize>();
+            saved += shrink(term_frequencies);
+        }
+        if let Some(payloads) = &mut self.payloads {
+            saved += shrink(payloads);
+        }
+        saved
+    }
+
+    pub fn new_in_memory(documents: &[&str]) -> Result<Index<'static>, Error> {
+        let mut index = Vec::new();
+        Self::construct(documents, &mut index)?;
+        let index = Index::from_bytes(&index)?;
+        Ok(index.move_in_memory())
+    }
+
+    /// Add new documents to the index and returns their ids.
+    /// The new words are merged in the fst and every bitmap gets decoded in memory.
+    /// If the index was constructed with payloads, the new documents gets a payload of `0`, and a boost of `1.0` with boosts.
+    /// They have no coordinates.
+    /// If it was constructed without the documents, their text is not kept either.
+    /// If it was constructed with fields, the fields of the new documents are separated by newlines.
+    /// The common words are not computed again.
+    pub fn add_documents(&mut self, documents: &[impl AsRef<str>]) -> Result<Vec<u32>, Error> {
+        let first_id = self.documents.len();
+        let total = first_id + documents.len();
+        if u32::try_from(total).is_err() {
+            return Err(Error::TooManyDocuments(total));
+        }
+        let first_id = first_id as Id;
+
+        // build a small fst of the new words pointing to their bitmap in `new_bitmaps`
+        let reporter = Reporter::silent();
+        let mut words = tokenize(documents, first_id, self.analyzer(), &reporter)?;
+        sort_words(&mut words);
+        let nb_words = words.len();
+        let (new_fst, mut new_bitmaps) = build_words(words, nb_words, &reporter)?;
+        let new_fst = Map::new(new_fst)?;
+
+        // the index is modified from here, even if it fails halfway the cached results are wrong
+        self.generation = cache::next_generation();
+
+        // the existing words keep their id, the new ones are appended after them
+        let mut bitmaps =
+            std::mem::replace(&mut self.bitmaps, Bitmaps::Decoded(Vec::new())).into_decoded();
+        let mut build = MapBuilder::memory();
+        let mut stream = self.fst.op().add(&new_fst).union();
+        while let Some((word, values)) = stream.next() {
+            let existing = values.iter().find(|value| value.index == 0);
+            let new = values.iter().find(|value| value.index == 1);
+            let value = match (existing, new) {
+                (Some(existing), Some(new)) => {
+                    let id = existing.value as u32;
+                    let mut bitmap = take_bitmap(&mut bitmaps, existing.value);
+                    bitmap |= take_bitmap(&mut new_bitmaps, new.value);
+                    bitmap.optimize();
+                    let value = word_value(id, &mut bitmap);
+                    bitmaps[id as usize] = bitmap;
+                    value
+                }
+                (Some(existing), None) => existing.value,
+                (None, Some(new)) => {
+                    let id = bitmaps.len() as u32;
+                    let mut bitmap = take_bitmap(&mut new_bitmaps, new.value);
+                    let value = word_value(id, &mut bitmap);
+                    bitmaps.push(bitmap);
+                    value
+                }
+                (None, None) => unreachable!("a word of the union must come from one of the fst"),
+            };
+            build.insert(word, value)?;
+        }
+        drop(stream);
+        self.fst = Map::new(Cow::Owned(build.into_inner()?))?;
+        self.bitmaps = Bitmaps::Decoded(bitmaps);
+
+        // `self.analyzer()` would borrow the whole index
+        let analyzer = Analyzer {
+            tokenizer: self.tokenizer,
+            normalizer: self.normalizer,
+            stemmer: self.stemmer,
+            stop_words: &self.stop_words,
+        };
+        if let Some(positions) = &mut self.positions {
+            positions.extend(
+                documents
+                    .iter()
+                    .map(|document| word_ids(&self.fst, analyzer, document.as_ref())),
+            );
+        }
+        if let Some(word_counts) = &mut self.word_counts {
+            word_counts.extend(
+                documents
+                    .iter()
+                    .map(|document| word_count(analyzer, document.as_ref())),
+            );
+        }
+        if let Some(frequencies) = &mut self.term_frequencies {
+            frequencies.extend(documents.iter().map(|document| {
+                term_frequencies(word_ids(&self.fst, analyzer, document.as_ref()))
+            }));
+        }
+        if let Some(fields) = &mut self.fields {
+            fields.insert(&self.fst, analyzer, first_id, documents);
+        }
+        if let Some(payloads) = &mut self.payloads {
+            payloads.resize(total, 0);
+        }
+        if let Some(boosts) = &mut self.boosts {
+            boosts.resize(total, 1.0);
+        }
+        if let Some(coordinates) = &mut self.coordinates {
+            coordinates.resize(total, NO_COORDINATES);
+        }
+        self.documents.extend(documents)?;
+
+        Ok((first_id..total as Id).collect())
+    }
+
+    /// Merge multiple serialized indexes into a single one.
+    /// The documents of every index are appended one after the other, the ids
+    /// of the documents of an index are thus offset by the number of documents before it.
+    /// The documents, positions, payloads and fields are only kept if all the indexes have them,
+    /// while the stop words of all the indexes are kept.
+    pub fn merge(indexes: &[&[u8]], writer: &mut impl std::io::Write) -> Result<(), Error> {
+        let indexes = indexes
+            .iter()
+            .map(|bytes| Index::from_bytes(bytes))
+            .collect::<Result<Vec<_>, _>>()?;
+        // the words of the indexes must have been split, normalized and stemmed the same way
+        let (tokenizer, normalizer, stemmer) =
+            indexes.first().map_or_else(Default::default, |index| {
+                (index.tokenizer, index.normalizer, index.stemmer)
+            });
+        if indexes.iter().any(|index| {
+            (index.tokenizer, index.normalizer, index.stemmer) != (tokenizer, normalizer, stemmer)
+        }) {
+            return Err(Error::IncompatibleIndexes);
+        }
+
+        let mut offsets = Vec::with_capacity(indexes.len());
+        let mut nb_documents = 0;
+        let mut deleted = RoaringBitmap::new();
+        for index in indexes.iter() {
+            let offset = nb_documents as u32;
+            offsets.push(offset);
+            nb_documents += index.documents.len();
+            if u32::try_from(nb_documents).is_err() {
+                return Err(Error::TooManyDocuments(nb_documents));
+            }
+            deleted.extend(index.deleted.iter().map(|id| id + offset));
+        }
+        let documents = match indexes.iter().all(|index| index.documents.is_stored()) {
+            true => {
+                let documents: Vec<Cow<str>> = indexes
+                    .iter()
+                    .flat_map(|index| index.documents.iter())
+                    .map(|(_, document)| Cow::Borrowed(document))
+                    .collect();
+                // the documents are compressed if they all were
+                #[cfg(feature = "compression")]
+                if indexes.iter().all(|index| index.documents.is_compressed()) {
+                    Documents::compressed(&documents)?
+                } else {
+                    Documents::Stored(documents)
+                }
+                #[cfg(not(feature = "compression"))]
+                Documents::Stored(documents)
+            }
+            false => Documents::Missing(nb_documents as u32),
+        };
+
+        // the words are renumbered in the order of the union, like in a fresh construction
+        let mut word_ids: Vec<Vec<u32>> = indexes
+            .iter()
+            .map(|index| vec![0; index.fst.len()])
+            .collect();
+        let mut bitmaps = Vec::new();
+        let mut build = MapBuilder::memory();
+        let mut union = fst::map::OpBuilder::new();
+        for index in indexes.iter() {
+            union = union.add(&index.fst);
+        }
+        let mut stream = union.union();
+        while let Some((word, values)) = stream.next() {
+            let id = bitmaps.len() as u32;
+            let mut bitmap = RoaringBitmap::new();
+            for value in values {
+                let offset = offsets[value.index];
+                let docids = indexes[value.index].bitmaps.get(value.value);
+                bitmap.extend(docids.iter().map(|docid| docid + offset));
+                word_ids[value.index][value.value as u32 as usize] = id;
+            }
+            bitmap.optimize();
+            build.insert(word, word_value(id, &mut bitmap))?;
+            bitmaps.push(bitmap);
+        }
+        drop(stream);
+        let fst = Map::new(Cow::Owned(build.into_inner()?))?;
+
+        let positions = indexes
+            .iter()
+            .zip(&word_ids)
+            .map(|(index, word_ids)| {
+                let positions = index.positions.as_ref()?;
+                Some(positions.iter().map(|ids| {
+                    ids.iter()
+                        .map(|id| word_ids[*id as usize])
+                        .collect::<Vec<u32>>()
+                }))
+            })
+            .collect::<Option<Vec<_>>>()
+            .map(|positions| positions.into_iter().flatten().collect());
+        let word_counts = indexes
+            .iter()
+            .map(|index| index.word_counts.as_deref())
+            .collect::<Option<Vec<_>>>()
+            .map(|word_counts| word_counts.concat());
+        // the words are renumbered thus the frequencies must be sorted again
+        let term_frequencies = indexes
+            .iter()
+            .zip(&word_ids)
+            .map(|(index, word_ids)| {
+                let term_frequencies = index.term_frequencies.as_ref()?;
+                Some(term_frequencies.iter().map(|frequencies| {
+                    let mut frequencies: Vec<(u32, u16)> = frequencies
+                        .iter()
+                        .map(|(id, count)| (word_ids[*id as usize], *count))
+                        .collect();
+                    frequencies.sort_unstable();
+                    frequencies
+                }))
+            })
+            .collect::<Option<Vec<_>>>()
+            .map(|term_frequencies| term_frequencies.into_iter().flatten().collect());
+        let payloads = indexes
+            .iter()
+            .map(|index| index.payloads.as_deref())
+            .collect::<Option<Vec<_>>>()
+            .map(|payloads| payloads.concat());
+        let fields = indexes.iter().all(|index| index.fields.is_some()).then(|| {
+            let mut fields = Fields {
+                count: 0,
+                words: vec![Vec::new(); bitmaps.len()],
+            };
+            for ((index, word_ids), offset) in indexes.iter().zip(&word_ids).zip(&offsets) {
+                fields.append(index.fields.as_ref().unwrap(), word_ids, *offset);
+            }
+            fields
+        });
+        // an index without boosts is the same as one where every document has a boost of 1
+        let boosts = indexes.iter().any(|index| index.boosts.is_some()).then(|| {
+            indexes
+                .iter()
+                .flat_map(|index| match &index.boosts {
+                    Some(boosts) => boosts.clone(),
+                    None => vec![1.0; index.documents.len()],
+                })
+                .collect()
+        });
+        let coordinates = indexes
+            .iter()
+            .any(|index| index.coordinates.is_some())
+            .then(|| {
+                indexes
+                    .iter()
+                    .flat_map(|index| match &index.coordinates {
+                        Some(coordinates) => coordinates.clone(),
+                        None => vec![NO_COORDINATES; index.documents.len()],
+                    })
+                    .collect()
+            });
+        // the first index storing them wins, like its position in the merged index
+        let search_defaults = indexes
+            .iter()
+            .find_map(|index| index.search_defaults.clone());
+        let mut stop_words: Vec<String> = indexes
+            .iter()
+            .flat_map(|index| index.stop_words.iter().cloned())
+            .collect();
+        stop_words.sort_unstable();
+        stop_words.dedup();
+        let mut exact_words: Vec<String> = indexes
+            .iter()
+            .flat_map(|index| index.exact_words.iter().cloned())
+            .collect();
+        exact_words.sort_unstable();
+        exact_words.dedup();
+        let common_words = indexes
+            .iter()
+            .zip(&word_ids)
+            .flat_map(|(index, word_ids)| index.common_words.iter().map(|id| word_ids[id as usize]))
+            .collect();
+
+        Index {
+            documents,
+            bitmaps: Bitmaps::Decoded(bitmaps),
+            fst,
+            positions,
+            word_counts,
+            term_frequencies,
+            payloads,
+            fields,
+            boosts,
+            coordinates,
+            search_defaults,
+            deleted,
+            stop_words,
+            exact_words,
+            common_words,
+            tokenizer,
+            normalizer,
+            stemmer,
+            generation: cache::next_generation(),
+        }
+        .write_to(writer)
+    }
+
+    /// Mark the documents as deleted, they won't be returned by the searches anymore.
+    /// They're still taking space in the index until it's compacted, see [`Index::compact`].
+    pub fn delete_documents(&mut self, ids: &RoaringBitmap) {
+        self.deleted |= ids;
+        self.deleted.remove_range(self.documents.len() as u32..);
+        self.generation = cache::next_generation();
+    }
+
+    /// Rebuild the index without the deleted documents.
+    /// The ids of the remaining documents change, the returned vector gives
+    /// the new id of every old id, or `None` if the document was deleted.
+    /// It returns [`Error::MissingDocuments`] if the index doesn't store its documents since it can't tokenize them again.
+    pub fn compact(&mut self) -> Result<Vec<Option<u32>>, Error> {
+        if !self.documents.is_stored() {
+            return Err(Error::MissingDocuments);
+        }
+        let mut mapping = Vec::with_capacity(self.documents.len());
+        let mut documents = Vec::new();
+        let mut payloads = self.payloads.as_ref().map(|_| Vec::new());
+        let mut boosts = Vec::new();
+        let mut coordinates = Vec::new();
+        for (id, document) in self.documents.iter() {
+            if self.deleted.contains(id) {
+                mapping.push(None);
+                continue;
+            }
+            mapping.push(Some(documents.len() as u32));
+            documents.push(Cow::Owned(document.to_string()));
+            if let (Some(payloads), Some(old)) = (&mut payloads, &self.payloads) {
+                payloads.push(old[id as usize]);
+            }
+            if let Some(old) = &self.boosts {
+                boosts.push(old[id as usize]);
+            }
+            if let Some(old) = &self.coordinates {
+                coordinates.push(old[id as usize]);
+            }
+        }
+
+        let common_words = self.common_words();
+        let mut builder = IndexBuilder::new();
+        builder
+            .with_positions(self.positions.is_some())
+            .with_word_counts(self.word_counts.is_some())
+            .with_term_frequencies(self.term_frequencies.is_some())
+            .with_tokenizer(self.tokenizer)
+            .with_normalizer(self.normalizer)
+            .with_stemmer(self.stemmer);
+        #[cfg(feature = "compression")]
+        builder.with_compression(self.documents.is_compressed());
+        // the index is left as it was if the construction fails
+        builder.stop_words = self.stop_words.clone();
+        builder.exact_words = self.exact_words.clone();
+        builder.boosts = boosts;
+        builder.coordinates = coordinates;
+        if let Some(defaults) = &self.search_defaults {
+            builder.default_limit = Some(defaults.limit);
+            builder.default_ranking_rules = Some(defaults.ranking_rules.clone());
+        }
+        *self = builder
+            .build(
+                documents,
+                payloads,
+                self.fields.is_some(),
+                &Reporter::silent(),
+            )?
+            .move_in_memory();
+        // the words keep their flag even if they're not as common anymore
+        self.common_words = common_words
+            .iter()
+            .filter_map(|word| self.fst.get(word))
+            .map(|id| id as u32)
+            .collect();
+        Ok(mapping)
+    }
+
+    /// Returns the words that were flagged as common during the construction,
+    /// see [`IndexBuilder::with_max_document_frequency`].
+    pub fn common_words(&self) -> Vec<String> {
+        let mut words = Vec::new();
+        let mut stream = self.fst.stream();
+        while let Some((word, id)) = stream.next() {
+            if self.common_words.contains(id as u32) {
+                words.push(String::from_utf8_lossy(word).into_owned());
+            }
+        }
+        words
+    }
+
+    /// Returns the text of the document, `None` if it was deleted or the index doesn't store the documents.
+    pub fn get_document(&self, id: u32) -> Option<&str> {
+        if self.deleted.contains(id) {
+            return None;
+        }
+        self.documents.get(id)
+    }
+
+    /// Returns the fields of the document, a single one if the index was constructed without fields.
+    /// `None` if it was deleted or the index doesn't store the documents.
+    ///
+    /// ```
+    /// use zearch::Index;
+    ///
+    /// let mut bytes = Vec::new();
+    /// Index::construct_with_fields(&[["Lutèce", "Paris"]], &mut bytes).unwrap();
+    /// let index = Index::from_bytes(&bytes).unwrap();
+    /// assert_eq!(index.get_document(0), Some("Lutèce\nParis"));
+    /// assert_eq!(index.get_fields(0), Some(vec!["Lutèce", "Paris"]));
+    /// ```
+    pub fn get_fields(&self, id: u32) -> Option<Vec<&str>> {
+        let document = self.get_document(id)?;
+        match self.fields {
+            Some(_) => Some(Fields::split(document).collect()),
+            None => Some(vec![document]),
+        }
+    }
+
+    /// Same as [`Index::get_document`] for multiple documents, in the same order as the ids.
+    pub fn get_documents(&self, ids: &[u32]) -> Vec<Option<&str>> {
+        ids.iter().map(|id| self.get_document(*id)).collect()
+    }
+
+    /// Returns all the documents that weren't deleted with their id, in the order of their ids.
+    /// Nothing is returned if the index doesn't store the documents.
+    pub fn documents(&self) -> impl Iterator<Item = (u32, &str)> + '_ {
+        self.documents
+            .iter()
+            .filter(|(id, _)| !self.deleted.contains(*id))
+    }
+
+    /// The number of documents that weren't deleted, same as [`Index::document_count`].
+    pub fn len(&self) -> usize {
+        self.document_count() as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the payload associated to the document, if the index was constructed with payloads.
+    pub fn get_payload(&self, id: u32) -> Option<u64> {
+        self.payloads.as_ref()?.get(id as usize).copied()
+    }
+
+    /// Returns the boost given to the document at construction, `1.0` if the index was constructed without boosts.
+    pub fn get_boost(&self, id: u32) -> f32 {
+        match &self.boosts {
+            Some(boosts) => boosts.get(id as usize).copied().unwrap_or(1.0),
+            None => 1.0,
+        }
+    }
+
+    /// Returns the latitude and longitude given to the document at construction, see [`IndexBuilder::with_coordinates`].
+    pub fn get_coordinates(&self, id: u32) -> Option<(f32, f32)> {
+        let (lat, lng) = *self.coordinates.as_ref()?.get(id as usize)?;
+        (!lat.is_nan() && !lng.is_nan()).then_some((lat, lng))
+    }
+
+    /// Returns the number of words the document was indexed with, if the index was constructed with the word counts,
+    /// see [`IndexBuilder::with_word_counts`].
+    ///
+    /// ```
+    /// use zearch::{Index, IndexBuilder};
+    ///
+    /// let mut bytes = Vec::new();
+    /// IndexBuilder::new()
+    ///     .with_word_counts(true)
+    ///     .with_stop_words(&["le"])
+    ///     .construct(&["le petit kefir", "tamo", "!!!"], &mut bytes)
+    ///     .unwrap();
+    /// let index = Index::from_bytes(&bytes).unwrap();
+    /// assert_eq!(index.word_count_of(0), Some(2));
+    /// assert_eq!(index.word_count_of(2), Some(0));
+    /// assert_eq!(index.word_count_of(3), None);
+    /// assert_eq!(index.average_document_length(), 1.0);
+    /// ```
+    pub fn word_count_of(&self, id: u32) -> Option<u16> {
+        self.word_counts.as_ref()?.get(id as usize).copied()
+    }
+
+    /// Returns the average number of words of the documents that weren't deleted, see [`Index::word_count_of`].
+    /// It's `NaN` if the index was constructed without the word counts or if it has no documents.
+    pub fn average_document_length(&self) -> f32 {
+        match self.document_lengths() {
+            Some((total, documents)) => (total as f64 / documents as f64) as f32,
+            None => f32::NAN,
+        }
+    }
+
+    /// Returns the number of words of all the documents that weren't deleted and the number of these documents,
+    /// `None` without the word counts.
+    pub(crate) fn document_lengths(&self) -> Option<(u64, u64)> {
+        let word_counts = self.word_counts.as_ref()?;
+        Some(
+            word_counts
+                .iter()
+                .enumerate()
+                .filter(|(id, _)| !self.deleted.contains(*id as u32))
+                .fold((0, 0), |(total, documents), (_, count)| {
+                    (total + *count as u64, documents + 1)
+                }),
+        )
+    }
+
+    /// Returns the limit and ranking rules used by the searches that don't set them,
+    /// see [`IndexBuilder::with_default_limit`] and [`IndexBuilder::with_default_ranking_rules`].
+    pub fn default_search(&self) -> SearchDefaults {
+        self.search_defaults.clone().unwrap_or_default()
+    }
+
+    /// Split the text in words and normalize them exactly like the documents and the queries,
+    /// to align your own processing or highlighting with the search. The stop words are included.
+    ///
+    /// ```
+    /// use zearch::Index;
+    ///
+    /// let index = Index::new_in_memory(&["kefir"]).unwrap();
+    /// let tokens = index.tokenize("  L'Avion,  vite !");
+    /// assert_eq!(tokens[1].original, "Avion,");
+    /// assert_eq!(tokens[1].normalized, "avion");
+    /// assert_eq!(tokens[1].byte_range, 4..10);
+    /// assert_eq!(tokens.len(), 3);
+    /// ```
+    pub fn tokenize<'t>(&self, text: &'t str) -> Vec<AnalyzedToken<'t>> {
+        self.analyzer().tokens(text).collect()
+    }
+
+    /// Returns `true` if a document that wasn't deleted contains the word, see [`Index::documents_containing`].
+    pub fn contains_word(&self, word: &str) -> bool {
+        self.documents_containing(word).is_some()
+    }
+
+    /// Returns the documents containing exactly the word, without typos nor prefix.
+    /// The word is normalized like the queries, `None` if it isn't a single word once normalized,
+    /// if it's a stop word or if no document that wasn't deleted contains it.
+    ///
+    /// ```
+    /// use zearch::Index;
+    ///
+    /// let index = Index::new_in_memory(&["le chien", "le chat", "les chiens"]).unwrap();
+    /// assert_eq!(index.documents_containing("Chien!").unwrap().iter().collect::<Vec<_>>(), [0]);
+    /// assert!(!index.contains_word("chi"));
+    /// ```
+    pub fn documents_containing(&self, word: &str) -> Option<RoaringBitmap> {
+        let analyzer = self.analyzer();
+        let mut tokens = analyzer.tokens(word);
+        let (token, None) = (tokens.next()?, tokens.next()) else {
+            return None;
+        };
+        let id = self.fst.get(&token.normalized)?;
+        let documents = &*self.bitmaps.get(id) - &self.deleted;
+        Some(documents).filter(|documents| !documents.is_empty())
+    }
+
+    /// The exact words of the search or of the index, normalized and sorted.
+    pub(crate) fn exact_words<'s>(&'s self, search: &Search) -> Cow<'s, [String]> {
+        match &search.exact_words {
+            Some(words) => Cow::Owned(self.analyzer().normalize_all(words)),
+            None => Cow::Borrowed(&self.exact_words),
+        }
+    }
+
+    /// The limit of the search or the default limit of the index.
+    pub(crate) fn limit(&self, search: &Search) -> usize {
+        search.limit.unwrap_or_else(|| match &self.search_defaults {
+            Some(defaults) => defaults.limit,
+            None => SearchDefaults::default().limit,
+        })
+    }
+
+    /// The ranking rules of the search or the default ranking rules of the index.
+    pub(crate) fn ranking_rules<'s>(&'s self, search: &'s Search) -> Cow<'s, [RankingRule]> {
+        match (&search.ranking_rules, &self.search_defaults) {
+            (Some(ranking_rules), _) => Cow::Borrowed(ranking_rules),
+            (None, Some(defaults)) => Cow::Borrowed(&defaults.ranking_rules),
+            (None, None) => Cow::Owned(SearchDefaults::default().ranking_rules),
+        }
+    }
+
+    /// Returns the ids of all the documents whose payload is contained in the range.
+    /// It can be used as a filter for the search, see [`Search::with_filter`].
+    pub fn filter_by_payload(&self, range: impl RangeBounds<u64>) -> RoaringBitmap {
+        self.payloads
+            .iter()
+            .flatten()
+            .enumerate()
+            .filter(|(_, payload)| range.contains(payload))
+            .map(|(id, _)| id as Id)
+            .collect()
+    }
+
+    /// Returns the ids of the best documents for the search, at most [`Search::with_limit`] of them.
+    ///
+    /// The ranking rules split the documents into buckets of equally relevant documents, the best bucket first.
+    /// Inside a bucket the documents are always returned in the order given by [`Search::with_tie_break`],
+    /// the smallest id first by default, thus the same search on the same index always returns the same results.
+    pub fn search(&self, search: &Search) -> Vec<u32> {
+        self.search_with_context(search, &mut SearchContext::default())
+    }
+
+    /// Run multiple searches on the index, the results are the same as calling [`Index::search`] on every search.
+    /// The buffers of the searches are reused and, with the `parallel` feature, the searches run on the rayon thread pool.
+    ///
+    /// ```
+    /// use zearch::{Index, Search};
+    ///
+    /// let index = Index::new_in_memory(&["kefir", "le chien", "le chat"]).unwrap();
+    /// let results = index.multi_search(&[Search::new("kefir"), Search::new("chat")]);
+    /// assert_eq!(results, [vec![0], vec![2]]);
+    /// ```
+    pub fn multi_search(&self, searches: &[Search]) -> Vec<Vec<u32>> {
+        #[cfg(feature = "parallel")]
+        {
+            use rayon::prelude::*;
+
+            searches
+                .par_iter()
+                .map_init(SearchContext::default, |ctx, search| {
+                    self.search_with_context(search, ctx)
+                })
+                .collect()
+        }
+        #[cfg(not(feature = "parallel"))]
+        {
+            let mut ctx = SearchContext::default();
+            searches
+                .iter()
+                .map(|search| self.search_with_context(search, &mut ctx))
+                .collect()
+        }
+    }
+
+    /// Same as [`Index::search`] but also returns how long the search took and how many documents matched.
+    ///
+    /// ```
+    /// use zearch::{Index, Search};
+    ///
+    /// let index = Index::new_in_memory(&["kefir", "kefir le chien", "le chat"]).unwrap();
+    /// let results = index.execute(&Search::new("kefir"));
+    /// assert_eq!(results.ids, [0, 1]);
+    /// assert_eq!(results.total_candidates, 2);
+    /// // we asked for 10 documents but only 2 matched
+    /// assert!(results.exhausted);
+    /// ```
+    pub fn execute(&self, search: &Search) -> SearchResults {
+        let start = Instant::now();
+        let deadline = Deadline::new(search);
+        let mut ctx = SearchContext::default();
+        let (mut candidates, processed_query) = self.get_candidates(search, &mut ctx, &deadline);
+        let mut ranks = Vec::new();
+        let universe = self.bucket_sort(
+            search,
+            &mut candidates,
+            &mut ctx.buckets,
+            &deadline,
+            None,
+            Some(&mut ranks),
+        );
+        // a ranking rule without rank, like the sort, can return a better rank after a worse one
+        let mut min = f32::INFINITY;
+        let (ids, scores): (Vec<u32>, Vec<f32>) = ctx
+            .buckets
+            .iter()
+            .zip(ranks)
+            .filter(|(bitmap, _)| !bitmap.is_empty())
+            .flat_map(|(bitmap, rank)| {
+                min = min.min(rank.score);
+                let score = min;
+                search.tie_break.iter(bitmap).map(move |id| (id, score))
+            })
+            .take(self.limit(search))
+            .unzip();
+
+        SearchResults {
+            exhausted: ids.len() < self.limit(search) && !deadline.was_reached(),
+            degraded: deadline.was_reached()
+                || processed_query
+                    .iter()
+                    .any(|word| word.reason == Some(DropReason::TooFrequent)),
+            ids,
+            scores,
+            processing_time: start.elapsed(),
+            total_candidates: universe.len(),
+            query_truncated: processed_query
+                .iter()
+                .any(|word| word.reason == Some(DropReason::TooManyWords)),
+            processed_query,
+            universe,
+        }
+    }
+
+    /// Returns every word of the query with the documents it matched, grouped by their number of typos.
+    /// The documents that were filtered out, deleted or excluded are already removed,
+    /// nothing is ranked thus it can be used to rank the documents yourself.
+    ///
+    /// ```
+    /// use zearch::{Index, Search};
+    ///
+    /// let index = Index::new_in_memory(&["kefir", "kefir le chien", "le chein"]).unwrap();
+    /// let candidates = index.candidates(&Search::new("kefir chien"));
+    /// assert_eq!(candidates[1].normalized(), "chien");
+    /// assert_eq!(candidates[1].docs_with_n_typos(0).unwrap().iter().collect::<Vec<_>>(), [1]);
+    /// assert_eq!(candidates[1].docs_with_n_typos(1).unwrap().iter().collect::<Vec<_>>(), [2]);
+    /// assert_eq!(index.candidate_universe(&Search::new("kefir chien")).len(), 3);
+    /// ```
+    pub fn candidates(&self, search: &Search) -> Vec<WordCandidate> {
+        let deadline = Deadline::new(search);
+        let (mut candidates, _) =
+            self.get_candidates(search, &mut SearchContext::default(), &deadline);
+        Self::cleanup(&self.get_excluded(search), &mut candidates);
+        candidates
+    }
+
+    /// Returns all the documents matching at least one word of the query, see [`Index::candidates`].
+    /// It's the documents the ranking rules would sort, [`SearchResults::total_candidates`] is its length.
+    pub fn candidate_universe(&self, search: &Search) -> RoaringBitmap {
+        roaring::MultiOps::union(
+            self.candidates(search)
+                .iter()
+                .flat_map(|candidate| &candidate.typos),
+        )
+    }
+
+    /// Same as [`Index::search`] but the documents are grouped by the number of words of the query they matched,
+    /// the documents matching the most words first. The limit applies to all the groups together.
+    /// The words are only dropped by the [`RankingRule::Word`], without it there is a single group.
+    ///
+    /// ```
+    /// use zearch::{Index, MatchGroup, Search};
+    ///
+    /// let index = Index::new_in_memory(&["kefir le chien", "kefir le chat", "kefir", "chien"]).unwrap();
+    /// let groups = index.search_grouped(&Search::new("kefir chien"));
+    /// assert_eq!(
+    ///     groups,
+    ///     [
+    ///         MatchGroup { words_matched: 2, ids: vec![0] },
+    ///         MatchGroup { words_matched: 1, ids: vec![3] },
+    ///     ]
+    /// );
+    /// ```
+    pub fn search_grouped(&self, search: &Search) -> Vec<MatchGroup> {
+        let deadline = Deadline::new(search);
+        let mut ctx = SearchContext::default();
+        let (mut candidates, _) = self.get_candidates(search, &mut ctx, &deadline);
+        let mut ranks = Vec::new();
+        self.bucket_sort(
+            search,
+            &mut candidates,
+            &mut ctx.buckets,
+            &deadline,
+            None,
+            Some(&mut ranks),
+        );
+
+        let mut groups: Vec<MatchGroup> = Vec::new();
+        let mut remaining = self.limit(search);
+        for (bucket, rank) in ctx.buckets.iter().zip(ranks) {
+            if bucket.is_empty() || remaining == 0 {
+                continue;
+            }
+            let group = match groups.last_mut() {
+                Some(group) if group.words_matched == rank.words => group,
+                _ => {
+                    groups.push(MatchGroup {
+                        words_matched: rank.words,
+                        ids: Vec::new(),
+                    });
+                    groups.last_mut().unwrap()
+                }
+            };
+            let ids: Vec<u32> = search.tie_break.iter(bucket).take(remaining).collect();
+            remaining -= ids.len();
+            group.ids.extend(ids);
+        }
+        groups
+    }
+
+    /// Same as [`Index::search`] but reuses the buffers of the context instead of allocating new ones.
+    /// Useful if you're running a lot of searches in a row, like on every keystroke.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(name = "search", skip_all, fields(query_len = search.input.len(), limit = self.limit(search), results, elapsed_us))
+    )]
+    pub fn search_with_context(&self, search: &Search, ctx: &mut SearchContext) -> Vec<u32> {
+        let _elapsed = trace::Elapsed::start();
+        let deadline = Deadline::new(search);
+        let (mut candidates, _) = self.get_candidates(search, ctx, &deadline);
+        let ret = self.search_with_candidates(search, &mut candidates, &mut ctx.buckets, &deadline);
+        ctx.candidates.extend(candidates);
+        #[cfg(feature = "tracing")]
+        tracing::Span::current().record("results", ret.len());
+        ret
+    }
+
+    /// Run the ranking rules on candidates that were already computed.
+    /// `res` is the buffer used to store the buckets, it's left empty.
+    pub(crate) fn search_with_candidates(
+        &self,
+        search: &Search,
+        candidates: &mut Vec<WordCandidate>,
+        res: &mut Vec<RoaringBitmap>,
+        deadline: &Deadline,
+    ) -> Vec<u32> {
+        if let Some(ret) = self.single_word_search(search, candidates, deadline) {
+            return ret;
+        }
+        self.bucket_sort(search, candidates, res, deadline, None, None);
+
+        let ret = res
+            .iter()
+            .flat_map(|bitmap| search.tie_break.iter(bitmap))
+            .take(self.limit(search))
+            .collect();
+        res.clear();
+        ret
+    }
+
+    /// Most queries of a search as you type are made of a single word, their buckets are the documents
+    /// matching the word with 0, 1 and 2 typos, each of them split by the exact ranking rule.
+    /// They're returned without building the ranking rules, the results are the same as [`Index::bucket_sort`].
+    /// Returns `None` if the search doesn't use the default ranking rules, or a subset of them, in their order
+    /// or if it needs more than the typos, like the distinct, the sort by distance or another scoring.
+    fn single_word_search(
+        &self,
+        search: &Search,
+        candidates: &mut [WordCandidate],
+        deadline: &Deadline,
+    ) -> Option<Vec<u32>> {
+        let [word] = candidates else {
+            return None;
+        };
+        // the word ranking rule can't drop the only word of the query
+        let mut exact = match &*self.ranking_rules(search) {
+            [RankingRule::Word, RankingRule::Typo] | [RankingRule::Typo] => None,
+            [RankingRule::Word, RankingRule::Typo, RankingRule::Exact]
+            | [RankingRule::Typo, RankingRule::Exact] => {
+                Some(Exact::new(search.max_exact_documents))
+            }
+            _ => return None,
+        };
+        if search.distinct
+            || search.sort_by_distance.is_some()
+            || search.scoring != Scoring::BucketSort
+        {
+            return None;
+        }
+
+        let limit = self.limit(search);
+        let mut used = self.get_excluded(search);
+        let mut ret = Vec::new();
+        // like the typo ranking rule, the documents matching the word with 3 typos are never returned
+        for typos in 0..word.typos.len() - 1 {
+            if ret.len() >= limit || deadline.is_reached() {
+                break;
+            }
+            let bucket = &word.typos[typos] - &used;
+            used |= &bucket;
+            let buckets = match exact.as_mut() {
+                Some(exact) => exact.split(std::slice::from_ref(word), bucket, self),
+                None => vec![bucket],
+            };
+            for bucket in buckets {
+                if ret.len() >= limit || deadline.is_reached() {
+                    break;
+                }
+                ret.extend(search.tie_break.iter(&bucket).take(limit - ret.len()));
+            }
+        }
+        Some(ret)
+    }
+
+    /// Run the search like [`Index::search_with_context`] but returns the non-empty buckets of documents
+    /// in the order of the results, the last one is cut at the limit.
+    pub(crate) fn search_buckets(
+        &self,
+        search: &Search,
+        ctx: &mut SearchContext,
+    ) -> Vec<RoaringBitmap> {
+        let deadline = Deadline::new(search);
+        let (mut candidates, _) = self.get_candidates(search, ctx, &deadline);
+        let mut buckets = Vec::new();
+        self.bucket_sort(search, &mut candidates, &mut buckets, &deadline, None, None);
+        ctx.candidates.extend(candidates);
+
+        let mut remaining = self.limit(search) as u64;
+        buckets.retain_mut(|bucket| {
+            search.tie_break.truncate(bucket, remaining);
+            remaining -= bucket.len();
+            !bucket.is_empty()
+        });
+        buckets
+    }
+
+    /// When `explain` is set, every bucket pushed in `res` is also recorded with the state of the ranking rules that returned it.
+    /// When `ranks` is set, the rank of every bucket pushed in `res` is pushed in it.
+    /// Once the deadline is reached the buckets already in `res` are kept and nothing else is sorted.
+    /// Returns the documents matching at least one word of the query that weren't excluded.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(skip_all, fields(universe, buckets, elapsed_us))
+    )]
+    pub(crate) fn bucket_sort(
+        &self,
+        search: &Search,
+        candidates: &mut Vec<WordCandidate>,
+        res: &mut Vec<RoaringBitmap>,
+        deadline: &Deadline,
+        mut explain: Option<&mut Vec<ExplainedBucket>>,
+        mut ranks: Option<&mut Vec<BucketRank>>,
+    ) -> RoaringBitmap {
+        let _elapsed = trace::Elapsed::start();
+        let excluded = self.get_excluded(search);
+        Self::cleanup(&excluded, candidates);
+        let universe =
+            roaring::MultiOps::union(candidates.iter().flat_map(|candidate| &candidate.typos));
+        #[cfg(feature = "tracing")]
+        tracing::Span::current().record("universe", universe.len());
+
+        // TODO: returns random results maybe?
+        // nothing matched, or everything was filtered or excluded
+        if universe.is_empty() {
+            return universe;
+        }
+        if search.scoring != Scoring::BucketSort {
+            if !deadline.is_reached() {
+                self.bm25_sort(search, candidates, &universe, res, explain, ranks);
+            }
+            return universe;
+        }
+        // only the word ranking rule removes words from the candidates
+        let minimum_words = search.minimum_words_matched.min(candidates.len());
+
+        let configured = self.ranking_rules(search);
+        // a built-in ranking rule used twice would split the buckets it already returned, only its first occurrence is kept
+        let rules = configured.iter().enumerate().filter(|(i, rule)| {
+            matches!(rule, RankingRule::Custom(_)) || !configured[..*i].contains(rule)
+        });
+        let mut ranking_rules: Vec<Box<dyn RankingRuleImpl>> = rules
+            .map(|(_, ranking_rule)| match ranking_rule {
+                RankingRule::Word => {
+                    Box::new(Word::new(candidates, &self.common_words)) as Box<dyn RankingRuleImpl>
+                }
+                RankingRule::Typo => Box::new(Typo::new(candidates)) as Box<dyn RankingRuleImpl>,
+                RankingRule::Exact => {
+                    Box::new(Exact::new(search.max_exact_documents)) as Box<dyn RankingRuleImpl>
+                }
+                RankingRule::Proximity => Box::new(Proximity::new()) as Box<dyn RankingRuleImpl>,
+                RankingRule::Attribute => Box::new(Attribute::new()) as Box<dyn RankingRuleImpl>,
+                RankingRule::Boost => Box::new(Boost::new()) as Box<dyn RankingRuleImpl>,
+                RankingRule::Position => Box::new(Position::new()) as Box<dyn RankingRuleImpl>,
+                RankingRule::WordCount => Box::new(WordCount::new()) as Box<dyn RankingRuleImpl>,
+                RankingRule::Sort { descending } => {
+                    Box::new(Sort::new(*descending)) as Box<dyn RankingRuleImpl>
+                }
+                RankingRule::Custom(custom) => custom.build(),
+            })
+            .collect();
+        if let Some(point) = search.sort_by_distance {
+            ranking_rules.push(Box::new(Geo::new(point)));
+        }
+        let ranking_rules_len = ranking_rules.len();
+
+        // without ranking rules every document matching a word of the query is in a single bucket
+        if ranking_rules.is_empty() {
+            let mut bucket = universe.clone();
+            if search.distinct {
+                self.remove_duplicates(&mut bucket, &mut HashSet::new(), search.tie_break);
+            }
+            if let Some(explain) = explain {
+                explain.push(ExplainedBucket {
+                    rules: Vec::new(),
+                    ids: search.tie_break.iter(&bucket).collect(),
+                });
+            }
+            if let Some(ranks) = ranks {
+                ranks.push(BucketRank {
+                    score: ranking_rules::score(&ranking_rules, candidates, self),
+                    words: candidates.len(),
+                    values: Vec::new(),
+                });
+            }
+            res.push(bucket);
+            return universe;
+        }
+
+        let mut current_ranking_rule = 0;
+
+        macro_rules! explain {
+            ($bucket:expr) => {
+                if let Some(explain) = explain.as_mut() {
+                    explain.push(ExplainedBucket {
+                        rules: ranking_rules[..=current_ranking_rule]
+                            .iter()
+                            .map(|rr| (rr.name(), rr.explain(candidates, self)))
+                            .collect(),
+                        ids: search.tie_break.iter(&$bucket).collect(),
+                    });
+                }
+            };
+        }
+
+        macro_rules! next {
+            () => {
+                {
+                // we cannot borrow twice the list of ranking rules thus we'll cheat a little
+                let current = &mut ranking_rules[current_ranking_rule];
+                // we detach the lifetime from the vec, this allow us to borrow the previous element safely
+                let current: &'static mut Box<dyn RankingRuleImpl> = unsafe { std::mem::transmute(current) };
+                current.next(
+                    current_ranking_rule.checked_sub(1).and_then(|prev| ranking_rules.get(prev)).map(|rr| &**rr),
+                    candidates,
+                    self
+                )
+                }
+            };
+        }
+
+        // the normalized text of the documents returned so far, see `Search::with_distinct`
+        let mut distinct = search.distinct.then(HashSet::new);
+
+        // the bucket is used by every ranking rule and it's removed from the candidates
+        macro_rules! push {
+            ($bucket:expr) => {{
+                let mut bucket = $bucket;
+                let duplicates = distinct
+                    .as_mut()
+                    .map(|seen| self.remove_duplicates(&mut bucket, seen, search.tie_break));
+                explain!(bucket);
+                if let Some(ranks) = ranks.as_mut() {
+                    ranks.push(BucketRank {
+                        score: ranking_rules::score(
+                            &ranking_rules[..=current_ranking_rule],
+                            candidates,
+                            self,
+                        ),
+                        words: candidates.len(),
+                        values: ranking_rules[..=current_ranking_rule]
+                            .iter()
+                            .map(|rr| rr.sort_value(candidates, self))
+                            .collect(),
+                    });
+                }
+                for used in std::iter::once(&bucket).chain(duplicates.as_ref()) {
+                    Self::cleanup(used, candidates);
+                    ranking_rules.iter_mut().for_each(|rr| rr.cleanup(used));
+                }
+                res.push(bucket);
+            }};
+        }
+
+        while res.iter().map(|bucket| bucket.len()).sum::<u64>() < self.limit(search) as u64 {
+            if deadline.is_reached() || candidates.len() < minimum_words {
+                break;
+            }
+            let next = next!();
+            #[cfg(feature = "tracing")]
+            tracing::trace!(
+                rule = ranking_rules[current_ranking_rule].name(),
+                bucket = match &next {
+                    ControlFlow::Break(bucket) => Some(bucket.len()),
+                    ControlFlow::Continue(()) => None,
+                },
+                "next"
+            );
+            let ranking_rule = &mut ranking_rules[current_ranking_rule];
+
+            match next {
+                // We want to advance
+                ControlFlow::Continue(()) => {
+                    if current_ranking_rule == ranking_rules_len - 1 {
+                        // there is no ranking rule to continue, get the bucket of the current one and call it again
+                        let bucket = ranking_rule.current_results(candidates);
+                        push!(bucket);
+                    } else {
+                        // we advance and do nothing
+                        current_ranking_rule += 1;
+                    }
+                }
+                // We want to get back one ranking rule behind
+                ControlFlow::Break(bucket) if bucket.is_empty() => {
+                    // if we're at the first ranking rule and there is nothing left to sort, exit
+                    if current_ranking_rule == 0 {
+                        break;
+                    }
+                    current_ranking_rule -= 1;
+                    if let Some(ranks) = ranks.as_mut() {
+                        ranks.push(BucketRank {
+                            score: 0.0,
+                            words: candidates.len(),
+                            values: Vec::new(),
+                        });
+                    }
+                    res.push(bucket);
+                }
+                // We want to push that bucket and continue our life with the next ranking rule if there is one
+                ControlFlow::Break(bucket) => push!(bucket),
+            }
+        }
+        #[cfg(feature = "tracing")]
+        tracing::Span::current().record(
+            "buckets",
+            res.iter().filter(|bucket| !bucket.is_empty()).count(),
+        );
+        universe
+    }
+
+    /// Removes the documents of the bucket whose normalized text is in `seen` and inserts the text of the others.
+    /// Returns the documents that were removed, none of them if the documents are not stored.
+    fn remove_duplicates(
+        &self,
+        bucket: &mut RoaringBitmap,
+        seen: &mut HashSet<String>,
+        tie_break: TieBreak,
+    ) -> RoaringBitmap {
+        // the first document returned is kept
+        let duplicates: RoaringBitmap = tie_break
+            .iter(bucket)
+            .filter(|id| {
+                self.distinct_text(*id)
+                    .is_some_and(|text| !seen.insert(text))
+            })
+            .collect();
+        *bucket -= &duplicates;
+        duplicates
+    }
+
+    /// Returns the normalized words of the document joined by spaces, two documents sharing it are duplicates.
+    /// `None` if the documents are not stored.
+    pub(crate) fn distinct_text(&self, id: u32) -> Option<String> {
+        let document = self.documents.get(id)?;
+        let words: Vec<String> = self
+            .analyzer()
+            .words(document)
+            .map(|(_, word)| word)
+            .filter(|word| !word.is_empty())
+            .collect();
+        Some(words.join(" "))
+    }
+
+    fn cleanup(used: &RoaringBitmap, candidates: &mut [WordCandidate]) {
+        for candidate in candidates.iter_mut() {
+            for typo in candidate.typos.iter_mut() {
+                *typo -= used;
+            }
+        }
+    }
+
+    /// The candidates are taken from the `pool` when possible instead of being allocated.
+    /// The boolean is set if the query had too many words, see [`Search::with_max_query_words`].
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(skip_all, fields(words, elapsed_us))
+    )]
+    fn get_candidates(
+        &self,
+        search: &Search,
+        ctx: &mut SearchContext,
+        deadline: &Deadline,
+    ) -> (Vec<WordCandidate>, Vec<ProcessedWord>) {
+        let _elapsed = trace::Elapsed::start();
+        let QueryWords {
+            words,
+            prefix,
+            mut processed,
+        } = self.query_words(search);
+        let mut ret = Vec::with_capacity(words.len());
+
+        for (index, word) in words.iter().enumerate() {
+            let normalized = &word.normalized;
+            let mut candidates = match ctx.candidates.pop() {
+                Some(mut candidates) => {
+                    candidates.reset(word.original, normalized, index);
+                    candidates
+                }
+                None => WordCandidate::new(word.original.to_string(), normalized.clone(), index),
+            };
+            candidates.weight = word.weight;
+
+            let prefix = prefix == Some(index) && normalized.len() >= search.min_prefix_len;
+            // the short words and the words of a phrase are matched without typos,
+            // the first keystrokes only match the words starting with them
+            let typos = (normalized.len() > SHORT_WORD_LEN && !word.exact)
+                .then(|| ctx.dfas.get(normalized, prefix));
+            self.lookup_word(
+                search,
+                &mut candidates,
+                normalized,
+                Matching { prefix, typos },
+                deadline,
+                |_, _| (),
+            );
+            self.lookup_synonyms(search, &mut candidates, normalized);
+            ret.push(candidates);
+        }
+
+        self.lookup_compounds(&mut ret);
+        for candidates in ret.iter_mut() {
+            self.restrict(search, candidates);
+            #[cfg(feature = "tracing")]
+            tracing::debug!(
+                word = candidates.normalized(),
+                candidates = roaring::MultiOps::union(&candidates.typos).len(),
+                "candidates"
+            );
+        }
+        self.drop_frequent_words(search, &mut ret, &mut processed);
+        #[cfg(feature = "tracing")]
+        tracing::Span::current().record("words", ret.len());
+
+        (ret, processed)
+    }
+
+    /// Drop the words matching the most documents until the words match at most [`Search::with_max_candidate_documents`]
+    /// documents in total, the word matching the fewest documents is always kept.
+    fn drop_frequent_words(
+        &self,
+        search: &Search,
+        candidates: &mut Vec<WordCandidate>,
+        processed: &mut [ProcessedWord],
+    ) {
+        let mut counts: Vec<u64> = candidates
+            .iter()
+            .map(|candidate| candidate.typos.iter().map(RoaringBitmap::len).sum())
+            .collect();
+        let mut total: u64 = counts.iter().sum();
+        while total > search.max_candidate_documents as u64 && candidates.len() > 1 {
+            let (position, count) = counts
+                .iter()
+                .copied()
+                .enumerate()
+                .max_by_key(|(_, count)| *count)
+                .unwrap();
+            total -= count;
+            counts.remove(position);
+            let dropped = candidates.remove(position);
+            #[cfg(feature = "tracing")]
+            tracing::debug!(
+                word = dropped.normalized(),
+                documents = count,
+                "too frequent"
+            );
+            // the words looked for are unique, only their first occurrence wasn't dropped
+            if let Some(word) = processed
+                .iter_mut()
+                .find(|word| !word.dropped && word.normalized == dropped.normalized)
+            {
+                word.dropped = true;
+                word.reason = Some(DropReason::TooFrequent);
+            }
+        }
+    }
+
+    /// Remove the documents that were filtered out, deleted or that don't exist from the candidates.
+    pub(crate) fn restrict(&self, search: &Search, candidates: &mut WordCandidate) {
+        for typo in candidates.typos.iter_mut() {
+            if let Some(filter) = &search.filter {
+                *typo &= filter.as_ref();
+            }
+            *typo -= &self.deleted;
+            // the bitmaps are decoded lazily and can't be checked on load, the ranking rules
+            // index the sections of the documents with these ids
+            typo.remove_range(self.documents.len() as u32..);
+        }
+    }
+
+    /// Insert the words of the fst matching the normalized word without typos, and then the ones matched with typos
+    /// until the cap is reached, see [`Matching`].
+    /// `on_match` is called on every word inserted with its value in the fst.
+    /// Returns `false` if we stopped early because of [`Search::with_max_candidates_per_word`] or the deadline.
+    pub(crate) fn lookup_word(
+        &self,
+        search: &Search,
+        candidates: &mut WordCandidate,
+        normalized: &str,
+        Matching { prefix, typos }: Matching,
+        deadline: &Deadline,
+        mut on_match: impl FnMut(&str, u64),
+    ) -> bool {
+        // the words without typos are inserted first so they can't be evicted by the cap
+        let exact = self.fst.get(normalized);
+        if let Some(id) = exact {
+            candidates.insert_with_maybe_typo(
+                normalized,
+                id,
+                &self.bitmaps.get(id),
+                search.typo_tolerance,
+            );
+            on_match(normalized, id);
+        }
+        let mut nb_matches = exact.is_some() as usize;
+
+        let exact_words = self.exact_words(search);
+        let is_exact_word = |word: &str| {
+            exact_words
+                .binary_search_by(|exact| exact.as_str().cmp(word))
+                .is_ok()
+        };
+        if is_exact_word(normalized) {
+            return true;
+        }
+
+        if prefix {
+            let mut stream = self
+                .fst
+                .search(Str::new(normalized).starts_with())
+                .into_stream();
+            while let Some((matched, id)) = stream.next() {
+                if nb_matches >= search.max_candidates_per_word || deadline.is_reached() {
+                    return false;
+                }
+                if Some(id) == exact {
+                    continue;
+                }
+                let matched = std::str::from_utf8(matched).unwrap();
+                candidates.insert_with_maybe_typo(
+                    matched,
+                    id,
+                    &self.bitmaps.get(id),
+                    search.typo_tolerance,
+                );
+                on_match(matched, id);
+                nb_matches += 1;
+            }
+        }
+
+        let Some(typos) = typos else {
+            return true;
+        };
+        let mut stream = self.fst.search(typos).into_stream();
+        while let Some((matched, id)) = stream.next() {
+            if nb_matches >= search.max_candidates_per_word || deadline.is_reached() {
+                return false;
+            }
+            let matched = std::str::from_utf8(matched).unwrap();
+            // the words without typos were inserted by the first pass
+            if Some(id) == exact || (prefix && matched.starts_with(normalized)) {
+                continue;
+            }
+            // without the extra letters of a prefix, a word matched with a typo differs from the word of the query
+            if !matched.starts_with(normalized) && is_exact_word(matched) {
+                continue;
+            }
+            candidates.insert_with_maybe_typo(
+                matched,
+                id,
+                &self.bitmaps.get(id),
+                search.typo_tolerance,
+            );
+            on_match(matched, id);
+            nb_matches += 1;
+        }
+
+        true
+    }
+
+    /// Insert the documents containing a synonym of the word as if they had one typo,
+    /// a synonym made of multiple words matches the documents containing all of them.
+    /// The synonyms only match exactly, without typos nor prefix.
+    pub(crate) fn lookup_synonyms(
+        &self,
+        search: &Search,
+        candidates: &mut WordCandidate,
+        normalized: &str,
+    ) {
+        let Some(synonyms) = search
+            .synonyms
+            .as_ref()
+            .and_then(|synonyms| synonyms.get(normalized))
+        else {
+            return;
+        };
+        let analyzer = self.analyzer();
+        'synonyms: for synonym in synonyms {
+            let mut word_ids = Vec::new();
+            let mut documents: Option<RoaringBitmap> = None;
+            for (_, word) in analyzer.words(synonym) {
+                if word.is_empty() || analyzer.is_stop_word(&word) {
+                    continue;
+                }
+                let Some(id) = self.fst.get(&word) else {
+                    continue 'synonyms;
+                };
+                let bitmap = self.bitmaps.get(id);
+                documents = Some(match documents {
+                    Some(documents) => documents & &*bitmap,
+                    None => bitmap.clone(),
+                });
+                word_ids.push(id as u32);
+            }
+            if let Some(documents) = documents {
+                candidates.insert_with_one_typo(&word_ids, &documents);
+            }
+        }
+    }
+
+    /// Match the words of the query that were glued together like `newyork` or split like `base ball`.
+    /// Every word is split at every position and matches the documents containing both halves,
+    /// while two adjacent words match the documents containing their concatenation.
+    /// Both are counted as one typo and only the first words of the query are tried.
+    pub(crate) fn lookup_compounds(&self, candidates: &mut [WordCandidate]) {
+        let len = candidates.len().min(MAX_COMPOUND_WORDS);
+        let candidates = &mut candidates[..len];
+
+        for candidate in candidates.iter_mut() {
+            let word = candidate.normalized.clone();
+            for (position, _) in word.char_indices().skip(1) {
+                let (left, right) = word.split_at(position);
+                let (Some(left), Some(right)) = (self.fst.get(left), self.fst.get(right)) else {
+                    continue;
+                };
+                let documents = &*self.bitmaps.get(left) & &*self.bitmaps.get(right);
+                candidate.insert_with_one_typo(&[left as u32, right as u32], &documents);
+            }
+        }
+
+        for second in 1..candidates.len() {
+            let first = second - 1;
+            let concatenated = format!(
+                "{}{}",
+                candidates[first].normalized, candidates[second].normalized
+            );
+            let Some(id) = self.fst.get(concatenated) else {
+                continue;
+            };
+            let documents = self.bitmaps.get(id);
+            candidates[first].insert_with_one_typo(&[id as u32], &documents);
+            candidates[second].insert_with_one_typo(&[id as u32], &documents);
+        }
+    }
+
+    /// Returns all the documents containing one of the excluded terms of the search.
+    fn get_excluded(&self, search: &Search) -> RoaringBitmap {
+        let levenshtein = levenshtein_builders();
+        let mut excluded = RoaringBitmap::new();
+
+        let terms = search
+            .query
+            .negations()
+            .chain(search.excluded.iter().map(String::as_str));
+
+        for term in terms {
+            // a term split in multiple words only excludes the documents containing all of them
+            let mut term_excluded: Option<RoaringBitmap> = None;
+            for (_, normalized) in self.analyzer().words(term) {
+                if normalized.is_empty() {
+                    continue;
+                }
+                let mut documents = RoaringBitmap::new();
+                if search.fuzzy_exclusion {
+                    // we're as tolerant as with the last word of the query
+                    let lev = levenshtein[typo_budget(&normalized)].build_prefix_dfa(&normalized);
+                    let mut stream = self.fst.search(lev).into_stream();
+                    while let Some((_, id)) = stream.next() {
+                        documents |= &*self.bitmaps.get(id);
+                    }
+                } else if let Some(id) = self.fst.get(normalized) {
+                    documents |= &*self.bitmaps.get(id);
+                }
+                term_excluded = Some(match term_excluded {
+                    Some(term_excluded) => term_excluded & documents,
+                    None => documents,
+                });
+            }
+            excluded |= term_excluded.unwrap_or_default();
+        }
+
+        excluded
+    }
+
+    /// Returns the words of the query we need to look for in the fst with their normalized version.
+    /// The stop words are skipped, except the last word which can be the beginning of another word.
+    /// A word appearing multiple times is only kept once at its first position, and only the first words are kept,
+    /// see [`Search::with_max_query_words`].
+    pub(crate) fn query_words<'i>(&self, search: &'i Search) -> QueryWords<'i> {
+        let analyzer = self.analyzer();
+        let mut processed = Vec::new();
+        // the words to look for with the position of their description in `processed`
+        let mut words: Vec<(QueryWord, usize)> = Vec::new();
+        let mut position = 0;
+        for item in &search.query.items {
+            let (texts, weight, exact) = match item {
+                QueryItem::Term(term) => (std::slice::from_ref(&term.text), term.weight, false),
+                QueryItem::Phrase { words, weight } => (words.as_slice(), *weight, true),
+                QueryItem::Negation(text) => {
+                    processed.extend(analyzer.words(text).map(|(token, normalized)| {
+                        ProcessedWord::dropped(token.text, normalized, DropReason::Negated)
+                    }));
+                    continue;
+                }
+            };
+            // the weight of a term or a phrase is given to all its words
+            let weight = search
+                .word_weights
+                .get(position)
+                .copied()
+                .or(weight)
+                .unwrap_or(1.0);
+            position += 1;
+            let analyzed: Vec<(&str, String)> = match search.pretokenized {
+                true => texts
+                    .iter()
+                    .map(|text| (text.as_str(), pretokenized(text)))
+                    .collect(),
+                false => texts
+                    .iter()
+                    .flat_map(|text| analyzer.words(text))
+                    .map(|(token, normalized)| (token.text, normalized))
+                    .collect(),
+            };
+            // same as `Analyzer::tokens` but the empty words are described
+            for (original, normalized) in analyzed {
+                if normalized.is_empty() {
+                    processed.push(ProcessedWord::dropped(
+                        original,
+                        normalized,
+                        DropReason::Empty,
+                    ));
+                    continue;
+                }
+                processed.push(ProcessedWord {
+                    original: original.to_string(),
+                    normalized: normalized.clone(),
+                    dropped: false,
+                    reason: None,
+                });
+                let word = QueryWord {
+                    original,
+                    normalized,
+                    weight,
+                    exact,
+                };
+                words.push((word, processed.len() - 1));
+            }
+        }
+        let drop = |processed: &mut Vec<ProcessedWord>, at: usize, reason| {
+            processed[at].dropped = true;
+            processed[at].reason = Some(reason);
+        };
+
+        let Some(last) = words.pop() else {
+            return QueryWords {
+                words: Vec::new(),
+                prefix: None,
+                processed,
+            };
+        };
+        let mut seen = HashSet::new();
+        words.retain(|(word, at)| {
+            let reason = if analyzer.is_stop_word(&word.normalized) {
+                DropReason::StopWord
+            } else if !seen.insert(word.normalized.clone()) {
+                DropReason::Duplicate
+            } else {
+                return true;
+            };
+            drop(&mut processed, *at, reason);
+            false
+        });
+        let max = search.max_query_words.saturating_sub(1);
+        if words.len() > max {
+            for (_, at) in words.drain(max..) {
+                drop(&mut processed, at, DropReason::TooManyWords);
+            }
+        }
+
+        // when the last word was already in the query its first occurrence becomes the prefix
+        let prefix = match words
+            .iter()
+            .position(|(word, _)| word.normalized == last.0.normalized)
+        {
+            Some(position) => {
+                drop(&mut processed, last.1, DropReason::Duplicate);
+                position
+            }
+            None => {
+                words.push(last);
+                words.len() - 1
+            }
+        };
+        let words: Vec<QueryWord> = words.into_iter().map(|(word, _)| word).collect();
+        QueryWords {
+            // the words of a phrase are never a prefix
+            prefix: Some(prefix).filter(|prefix| !words[*prefix].exact),
+            words,
+            processed,
+        }
+    }
+
+    fn analyzer(&self) -> Analyzer<'_> {
+        Analyzer {
+            tokenizer: self.tokenizer,
+            normalizer: self.normalizer,
+            stemmer: self.stemmer,
+            stop_words: &self.stop_words,
+        }
+    }
+}
+
+/// Shared by all the indexes, building them takes a few ms, see [`Index::warm_up`].
+static LEVENSHTEINS: OnceLock<[LevenshteinAutomatonBuilder; 4]> = OnceLock::new();
+
+fn levenshtein_builders() -> &'static [LevenshteinAutomatonBuilder; 4] {
+    LEVENSHTEINS.get_or_init(|| {
+        core::array::from_fn(|nb_typo| LevenshteinAutomatonBuilder::new(nb_typo as u8, true))
+    })
+}
+
+/// Enable 1 typo every 3 letters maxed at 3 typos.
+fn typo_budget(normalized: &str) -> usize {
+    (normalized.len() / 3).min(3)
+}
+
+/// A word of the query and all the documents it matched.
+#[derive(Debug, Clone)]
+pub struct WordCandidate {
+    // the original string
+    original: String,
+    // normalized string
+    normalized: String,
+    // its index in the phrase
+    index: usize,
+    // see `Search::with_word_weights`
+    weight: f32,
+    // the number of documuents its contained in
+    typos: Vec<RoaringBitmap>,
+    // the id of all the words of the fst it matched, used to find it in the positions
+    word_ids: RoaringBitmap,
+    // the first words of the fst it matched with their number of typos and value, see `MAX_RECORDED_MATCHES`
+    matches: Vec<(String, u8, u64)>,
+    // scratch buffers to compute the typos of the matched words
+    distance: DamerauLevenshtein,
+}
+
+impl WordCandidate {
+    pub(crate) fn new(original: String, normalized: String, index: usize) -> Self {
+        Self {
+            original,
+            normalized,
+            index,
+            weight: 1.0,
+            // we have a maximum of 3 typos
+            typos: vec![RoaringBitmap::new(); 4],
+            word_ids: RoaringBitmap::new(),
+            matches: Vec::new(),
+            distance: DamerauLevenshtein::default(),
+        }
+    }
+
+    /// Reuse the allocations of the candidate for another word.
+    pub(crate) fn reset(&mut self, original: &str, normalized: &str, index: usize) {
+        self.original.clear();
+        self.original.push_str(original);
+        self.normalized.clear();
+        self.normalized.push_str(normalized);
+        self.index = index;
+        self.weight = 1.0;
+        self.typos.iter_mut().for_each(|typo| typo.clear());
+        self.word_ids.clear();
+        self.matches.clear();
+    }
+
+    // Since the fst::Automaton doesn't tells us which automaton matched and with how many typos or prefixes
+    // we need to recompute the stuff ourselves and insert our shit in the right cell
+    pub(crate) fn insert_with_maybe_typo(
+        &mut self,
+        other: &str,
+        value: u64,
+        bitmap: &RoaringBitmap,
+        tolerance: TypoTolerance,
+    ) {
+        // if we did a prefix query we shouldn't count the extra letters as typo,
+        // the words that aren't ASCII are cut on the previous character
+        let mut end = other.len().min(self.normalized.len());
+        while !other.is_char_boundary(end) {
+            end -= 1;
+        }
+        // distance shouldn't be able to go over 3 but we don't want any crash so it's clamped
+        let distance = self
+            .distance
+            .typos(&self.normalized, &other[..end], tolerance);
+        self.typos[distance] |= bitmap;
+        self.word_ids.insert(value as u32);
+        if self.matches.len() < MAX_RECORDED_MATCHES {
+            self.matches
+                .push((other.to_string(), distance as u8, value));
+        }
+    }
+
+    /// The documents matched by a synonym or a compound of the word are counted as having one typo,
+    /// so the documents containing the word itself are ranked first by the typo ranking rule.
+    pub(crate) fn insert_with_one_typo(&mut self, word_ids: &[u32], documents: &RoaringBitmap) {
+        self.typos[1] |= documents;
+        self.word_ids.extend(word_ids.iter().copied());
+    }
+
+    /// The word as it was written in the query
+    pub fn original(&self) -> &str {
+        &self.original
+    }
+
+    /// The word once normalized, that's the one we look for in the index
+    pub fn normalized(&self) -> &str {
+        &self.normalized
+    }
+
+    /// The weight of the word, see [`Search::with_word_weights`]
+    pub fn weight(&self) -> f32 {
+        self.weight
+    }
+
+    /// The position of the word in the query
+    pub fn query_index(&self) -> usize {
+        self.index
+    }
+
+    /// The words of the index matched by the word with their number of typos, the exact match first
+    /// and the words matched without typos before the others. Only the first matches are kept, a short prefix can match thousands of words.
+    /// The words matched through a synonym or a compound are not included.
+    pub fn matched_words(&self) -> impl Iterator<Item = (&str, u8)> {
+        self.matches
+            .iter()
+            .map(|(word, typos, _)| (word.as_str(), *typos))
+    }
+
+    /// The documents containing the word with exactly `n` typos, `None` if more typos than that are never allowed.
+    pub fn docs_with_n_typos(&self, n: usize) -> Option<&RoaringBitmap> {
+        self.typos.get(n)
+    }
+}
+
+/// How a word of the query matches the words of the fst, see [`Index::lookup_word`].
+#[derive(Clone, Copy)]
+pub(crate) struct Matching<'d> {
+    /// Also match the words starting with the word of the query.
+    pub prefix: bool,
+    /// The automaton matching the word with typos from [`Dfas::get`], `None` to match it without typos.
+    pub typos: Option<&'d DFA>,
+}
+
+/// The words of a query, see [`Index::query_words`].
+pub(crate) struct QueryWords<'i> {
+    pub words: Vec<QueryWord<'i>>,
+    /// The position of the word that can be the beginning of another word, it's usually the last one.
+    pub prefix: Option<usize>,
+    /// What happened to every word of the query, see [`SearchResults::processed_query`].
+    pub processed: Vec<ProcessedWord>,
+}
+
+/// A word of the query to look for in the fst.
+#[derive(Debug, Clone)]
+pub(crate) struct QueryWord<'i> {
+    /// The word as it was typed.
+    pub original: &'i str,
+    pub normalized: String,
+    /// See [`Search::with_word_weights`].
+    pub weight: f32,
+    /// The word belongs to a phrase, it's matched without typos and never as a prefix.
+    pub exact: bool,
+}
+
+/// A word of the query and what happened to it, see [`SearchResults::processed_query`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProcessedWord {
+    /// The word as it was typed.
+    pub original: String,
+    /// The word as it's looked for in the index, it can be empty like the punctuation.
+    pub normalized: String,
+    /// `true` if the word wasn't looked for in the index, the reason is then set.
+    pub dropped: bool,
+    pub reason: Option<DropReason>,
+}
+
+impl ProcessedWord {
+    fn dropped(original: &str, normalized: String, reason: DropReason) -> Self {
+        Self {
+            original: original.to_string(),
+            normalized,
+            dropped: true,
+            reason: Some(reason),
+        }
+    }
+}
+
+/// Why a word of the query wasn't looked for in the index.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DropReason {
+    /// Nothing was left after the normalization, like the punctuation.
+    Empty,
+    /// See [`IndexBuilder::with_stop_words`], the last word is never dropped since it can be a prefix.
+    StopWord,
+    /// The word is already in the query, only its first occurrence is kept.
+    Duplicate,
+    /// See [`Search::with_max_query_words`].
+    TooManyWords,
+    /// The word matched too many documents, see [`Search::with_max_candidate_documents`].
+    TooFrequent,
+    /// The word belongs to an excluded term, the documents containing it are removed instead.
+    Negated,
+}
+
+/// Returned by [`Index::execute`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchResults {
+    /// The same ids as [`Index::search`].
+    pub ids: Vec<u32>,
+    /// The score between 0 and 1 of every document, in the same order as the ids and never increasing.
+    /// It's computed from the rank of the buckets of the word, typo, exact and attribute ranking rules,
+    /// the first ranking rule weighting the most, see [`RankingRuleImpl::rank`].
+    /// A document matching all the words as they were typed has a score of 1.
+    /// With [`Scoring::Bm25`] it's the BM25 score of the document instead, which has no upper bound.
+    pub scores: Vec<f32>,
+    pub processing_time: Duration,
+    /// `true` if there were not enough matching documents to reach the limit of the search.
+    pub exhausted: bool,
+    /// `true` if the search was stopped by its time budget or interrupted, the results are then incomplete.
+    /// See [`Search::with_time_budget`] and [`Search::with_interrupt`].
+    /// Also `true` if the most frequent words of the query were dropped, see [`Search::with_max_candidate_documents`].
+    pub degraded: bool,
+    /// The number of documents matching at least one word of the query, including the ones that weren't returned.
+    pub total_candidates: u64,
+    /// `true` if some words of the query were ignored, see [`Search::with_max_query_words`].
+    pub query_truncated: bool,
+    /// Every word of the query in its order, with the reason it wasn't looked for.
+    ///
+    /// ```
+    /// use zearch::{DropReason, IndexBuilder, Index, Search};
+    ///
+    /// let mut bytes = Vec::new();
+    /// IndexBuilder::new().with_stop_words(&["le"]).construct(&["le beau kefir"], &mut bytes).unwrap();
+    /// let index = Index::from_bytes(&bytes).unwrap();
+    /// let results = index.execute(&Search::new("Le Kefir, beau"));
+    /// let searched: Vec<&str> = results
+    ///     .processed_query
+    ///     .iter()
+    ///     .filter(|word| !word.dropped)
+    ///     .map(|word| word.normalized.as_str())
+    ///     .collect();
+    /// assert_eq!(searched.join(" "), "kefir beau");
+    /// assert_eq!(results.processed_query[0].reason, Some(DropReason::StopWord));
+    /// ```
+    pub processed_query: Vec<ProcessedWord>,
+    /// The documents matching at least one word of the query, see [`Index::candidate_universe`].
+    pub universe: RoaringBitmap,
+}
+
+impl SearchResults {
+    /// Returns the documents found with their text, in the same order as the ids.
+    /// The text is borrowed from the index, the results can be dropped while the hits are still used.
+    ///
+    /// ```
+    /// use zearch::{Hit, Index, Search};
+    ///
+    /// let index = Index::new_in_memory(&["kefir", "kefir le chien", "le chat"]).unwrap();
+    /// let hits: Vec<Hit> = Search::new("chien").execute(&index).hits(&index).collect();
+    /// assert_eq!(hits, [Hit { id: 1, text: Some("kefir le chien") }]);
+    /// ```
+    pub fn hits<'s, 'i: 's>(&'s self, index: &'i Index<'i>) -> impl Iterator<Item = Hit<'i>> + 's {
+        self.ids.iter().map(|&id| Hit {
+            id,
+            text: index.get_document(id),
+        })
+    }
+
+    /// Restrict the search to the documents of these results, see [`Search::within`].
+    /// The documents that didn't match this search are never returned by the refined search,
+    /// even if they match its new words.
+    ///
+    /// ```
+    /// use zearch::{Index, Search};
+    ///
+    /// let index = Index::new_in_memory(&["kefir le beau", "tamo le beau", "kefir"]).unwrap();
+    /// let results = Search::new("kefir").execute(&index);
+    /// let refined = results.refine(Search::new("beau")).execute(&index);
+    /// assert_eq!(refined.ids, [0]);
+    /// ```
+    pub fn refine<'s>(&'s self, mut search: Search<'s>) -> Search<'s> {
+        search.within(&self.universe);
+        search
+    }
+}
+
+/// The documents matching the same number of words of the query, see [`Index::search_grouped`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MatchGroup {
+    pub words_matched: usize,
+    pub ids: Vec<u32>,
+}
+
+/// The rank of a bucket returned by the bucket sort.
+struct BucketRank {
+    /// See [`SearchResults::scores`].
+    score: f32,
+    /// The number of words of the query still required when the bucket was returned.
+    words: usize,
+    /// The sort value of every ranking rule that returned the bucket, see [`RankingRuleImpl::sort_value`].
+    values: Vec<Option<f64>>,
+}
+
+/// A document found by a search, see [`SearchResults::hits`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Hit<'i> {
+    pub id: u32,
+    /// `None` if the index doesn't store the documents, see [`IndexBuilder::with_documents`].
+    pub text: Option<&'i str>,
+}
+
+/// Keeps the buffers allocated by a search so they can be reused by the next one,
+/// see [`Index::search_with_context`].
+#[derive(Default)]
+pub struct SearchContext {
+    candidates: Vec<WordCandidate>,
+    buckets: Vec<RoaringBitmap>,
+    // the automata of the words of the last queries, the next ones are likely to repeat them
+    dfas: Dfas,
+}
+
+#[derive(Clone)]
+pub struct Search<'a> {
+    input: Cow<'a, str>,
+    // the input once parsed, or the query it was written from
+    query: Query,
+    // `None` to use the defaults of the index, see [`Index::default_search`]
+    limit: Option<usize>,
+    ranking_rules: Option<Vec<RankingRule>>,
+    excluded: Vec<String>,
+    fuzzy_exclusion: bool,
+    filter: Option<Cow<'a, RoaringBitmap>>,
+    max_candidates_per_word: usize,
+    max_exact_documents: usize,
+    max_candidate_documents: usize,
+    max_query_words: usize,
+    min_prefix_len: usize,
+    distinct: bool,
+    synonyms: Option<Cow<'a, HashMap<String, Vec<String>>>>,
+    time_budget: Option<Duration>,
+    interrupt: Option<Arc<AtomicBool>>,
+    sort_by_distance: Option<(f32, f32)>,
+    minimum_words_matched: usize,
+    // one for every word of the input, empty when they all weigh 1
+    word_weights: Vec<f32>,
+    tie_break: TieBreak,
+    // replaces the exact words of the index when set
+    exact_words: Option<Vec<String>>,
+    typo_tolerance: TypoTolerance,
+    // the terms of the query skip the tokenizer, see [`Search::with_pretokenized_query`]
+    pretokenized: bool,
+    scoring: Scoring,
+    // replaces the statistics of the index in BM25 when it's a shard, see [`ShardedIndex`]
+    corpus: Option<CorpusStats>,
+}
+
+impl<'a> Search<'a> {
+    /// Create a new search requests from an input, see [`Query`] for its syntax.
+    /// The invalid syntax is read as plain text.
+    pub fn new(input: &'a str) -> Self {
+        Self::from_cow(Cow::Borrowed(input))
+    }
+
+    /// Same as [`Search::new`] but returns the first invalid syntax of the input instead of reading it as plain text.
+    ///
+    /// ```
+    /// use zearch::{ParseErrorKind, Search};
+    ///
+    /// let error = Search::parse_strict("le \"petit chien").err().unwrap();
+    /// assert_eq!((error.position, error.kind), (3, ParseErrorKind::UnclosedQuote));
+    /// assert!(Search::parse_strict("le \"petit chien\"").is_ok());
+    /// ```
+    pub fn parse_strict(input: &'a str) -> Result<Self, ParseError> {
+        let query = Query::parse_strict(input)?;
+        Ok(Self::from_parts(Cow::Borrowed(input), query))
+    }
+
+    /// Create a search from a query built without its syntax, the input becomes the text of the query.
+    ///
+    /// ```
+    /// use zearch::{Index, Query, QueryItem, Search, Term};
+    ///
+    /// let index = Index::new_in_memory(&["kefir", "tamo", "kefir tamo"]).unwrap();
+    /// let query = Query {
+    ///     items: vec![
+    ///         QueryItem::Term(Term::new("kefir")),
+    ///         QueryItem::Negation(String::from("tamo")),
+    ///     ],
+    /// };
+    /// assert_eq!(index.search(&Search::from_query(query)), [0]);
+    /// ```
+    pub fn from_query(query: Query) -> Search<'static> {
+        Search::from_parts(Cow::Owned(query.to_string()), query)
+    }
+
+    /// The parsed input, see [`Query`].
+    pub fn query(&self) -> &Query {
+        &self.query
+    }
+
+    /// Create a search owning its input, it can be stored or sent to another thread
+    /// without keeping the query alive. See [`Search::with_owned_filter`] and [`Search::with_owned_synonyms`].
+    ///
+    /// ```
+    /// use zearch::{Index, Search};
+    ///
+    /// fn prepare(query: &str) -> Search<'static> {
+    ///     let mut search = Search::owned(query.trim().to_lowercase());
+    ///     search.with_limit(1);
+    ///     search
+    /// }
+    ///
+    /// let index = Index::new_in_memory(&["kefir", "tamo"]).unwrap();
+    /// let search = prepare("  TAMO ");
+    /// assert_eq!(index.search(&search), [1]);
+    /// ```
+    pub fn owned(input: String) -> Search<'static> {
+        Search::from_cow(Cow::Owned(input))
+    }
+
+    fn from_cow(input: Cow<'a, str>) -> Self {
+        let query = Query::parse(&input);
+        Self::from_parts(input, query)
+    }
+
+    fn from_parts(input: Cow<'a, str>, query: Query) -> Self {
+        Self {
+            input,
+            query,
+            limit: None,
+            ranking_rules: None,
+            excluded: Vec::new(),
+            fuzzy_exclusion: false,
+            filter: None,
+            max_candidates_per_word: 10_000,
+            max_exact_documents: 1000,
+            max_candidate_documents: 1_000_000,
+            max_query_words: 10,
+            distinct: false,
+            min_prefix_len: 1,
+            synonyms: None,
+            time_budget: None,
+            interrupt: None,
+            sort_by_distance: None,
+            minimum_words_matched: 0,
+            word_weights: Vec::new(),
+            tie_break: TieBreak::DocIdAsc,
+            exact_words: None,
+            typo_tolerance: TypoTolerance::default(),
+            pretokenized: false,
+            scoring: Scoring::BucketSort,
+            corpus: None,
+        }
+    }
+
+    /// Only search the documents contained in the bitmap, like the results of a previous search, see [`SearchResults::refine`].
+    /// Unlike [`Search::with_filter`] it's combined with the filter instead of replacing it.
+    pub fn within(&mut self, documents: &'a RoaringBitmap) -> &mut Self {
+        self.filter = Some(match self.filter.take() {
+            Some(filter) => Cow::Owned(filter.as_ref() & documents),
+            None => Cow::Borrowed(documents),
+        });
+        self
+    }
+
+    /// Run the search on the index, same as [`Index::execute`].
+    pub fn execute(&self, index: &Index) -> SearchResults {
+        index.execute(self)
+    }
+
+    /// Customize the number of results you want to get back, by default it's the limit of the index, see [`Index::default_search`]
+    pub fn with_limit(&mut self, limit: usize) -> &mut Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// Customize the ranking rules and their order, by default it's the ranking rules of the index, `[Word, Typo, Exact]` unless
+    /// they were stored with [`IndexBuilder::with_default_ranking_rules`].
+    /// Any ranking rule can be used alone or in any position. A built-in ranking rule is only applied at its first position,
+    /// the custom ranking rules can be repeated. Without ranking rules every document matching
+    /// a word of the query is returned in a single bucket, see [`Search::with_tie_break`] for their order.
+    pub fn with_ranking_rules(&mut self, ranking_rules: Vec<RankingRule>) -> &mut Self {
+        self.ranking_rules = Some(ranking_rules);
+        self
+    }
+
+    /// Remove all the documents containing one of these terms from the results.
+    /// It's the same as writing `-term` in the query.
+    pub fn with_excluded_terms(&mut self, terms: &[impl AsRef<str>]) -> &mut Self {
+        self.excluded = terms.iter().map(|term| term.as_ref().to_string()).collect();
+        self
+    }
+
+    /// By default only the documents containing exactly the excluded terms are removed.
+    /// When enabled, the documents containing a typo or a prefix of an excluded term are also removed.
+    pub fn with_fuzzy_exclusion(&mut self, fuzzy: bool) -> &mut Self {
+        self.fuzzy_exclusion = fuzzy;
+        self
+    }
+
+    /// Restrict the search to the documents whose id is contained in the filter.
+    pub fn with_filter(&mut self, filter: &'a RoaringBitmap) -> &mut Self {
+        self.filter = Some(Cow::Borrowed(filter));
+        self
+    }
+
+    /// Same as [`Search::with_filter`] but the search owns the filter.
+    pub fn with_owned_filter(&mut self, filter: RoaringBitmap) -> &mut Self {
+        self.filter = Some(Cow::Owned(filter));
+        self
+    }
+
+    /// Stop looking for more words in the index once a word of the query matched this many words.
+    /// The word matching exactly is always used, then the words starting with the last word of the query
+    /// come before the words matched with typos. By default it's 10 000.
+    pub fn with_max_candidates_per_word(&mut self, max: usize) -> &mut Self {
+        self.max_candidates_per_word = max;
+        self
+    }
+
+    /// The [`RankingRule::Exact`] ranking rule returns the bucket of the previous ranking rule as is
+    /// if it contains more than this many documents, since sorting it would be too slow. By default it's 1000.
+    pub fn with_max_exact_documents(&mut self, max: usize) -> &mut Self {
+        self.max_exact_documents = max;
+        self
+    }
+
+    /// When the words of the query match more than this many documents in total, the words matching the most documents
+    /// are dropped as if they were stop words, since ranking their documents would be too slow.
+    /// The word matching the fewest documents is always kept and the results are flagged as [`SearchResults::degraded`].
+    /// By default it's 1 000 000.
+    pub fn with_max_candidate_documents(&mut self, max: usize) -> &mut Self {
+        self.max_candidate_documents = max;
+        self
+    }
+
+    /// Only look for the first words of the query and its last word, which is used as a prefix, and ignore the others.
+    /// The stop words and the words appearing multiple times in the query are not counted. By default it's 10.
+    pub fn with_max_query_words(&mut self, max: usize) -> &mut Self {
+        self.max_query_words = max;
+        self
+    }
+
+    /// The last word of the query is also used as a prefix only if it contains at least this many characters.
+    /// By default it's 1, thus the first keystroke streams every word starting with its letter up to
+    /// [`Search::with_max_candidates_per_word`]. Set it to 2 to only match the words equal to a one-letter word.
+    pub fn with_min_prefix_len(&mut self, len: usize) -> &mut Self {
+        self.min_prefix_len = len;
+        self
+    }
+
+    /// Only return the first document of the documents having the same text once normalized, like `Paris` and `paris`.
+    /// The duplicates returned by the same ranking rule are ranked by id thus the one with the lowest id is kept.
+    /// It has no effect on an index constructed without its documents, see [`IndexBuilder::with_documents`].
+    pub fn with_distinct(&mut self, distinct: bool) -> &mut Self {
+        self.distinct = distinct;
+        self
+    }
+
+    /// Stop returning documents once the word ranking rule dropped so many words of the query
+    /// that fewer than `words` of them are still required, instead of filling the results with documents
+    /// matching a single frequent word. A query with fewer words requires all of them. By default it's 0.
+    ///
+    /// ```
+    /// use zearch::{Index, Search};
+    ///
+    /// let index = Index::new_in_memory(&["kefir le chien", "kefir le chat", "kefir", "chien"]).unwrap();
+    /// let mut search = Search::new("kefir chien");
+    /// assert_eq!(index.search(&search), [0, 3]);
+    /// search.with_minimum_words_matched(2);
+    /// assert_eq!(index.search(&search), [0]);
+    /// ```
+    pub fn with_minimum_words_matched(&mut self, words: usize) -> &mut Self {
+        self.minimum_words_matched = words;
+        self
+    }
+
+    /// Give a weight to every term and phrase of the query, in their order and without the excluded terms.
+    /// They replace the weights written in the query like `paris^2`.
+    /// The word ranking rule drops the words with the lowest weight first, whatever their frequency,
+    /// and the score of a document is weighted by the words it matched. By default every word weighs 1.
+    /// A term split by the tokenizer, like `l'avion`, gives its weight to all its parts.
+    ///
+    /// ```
+    /// use zearch::{Index, Search};
+    ///
+    /// let index = Index::new_in_memory(&["hotel cheap", "hotel paris", "paris", "paris cheap"]).unwrap();
+    /// let mut search = Search::new("hotel paris cheap");
+    /// // `paris` is the most frequent word thus the first one dropped
+    /// assert_eq!(index.search(&search), [0, 1]);
+    /// search.with_word_weights(&[1.0, 3.0, 0.5]).unwrap();
+    /// assert_eq!(index.search(&search), [1, 2, 3]);
+    /// assert!(search.with_word_weights(&[1.0, 3.0]).is_err());
+    /// ```
+    pub fn with_word_weights(&mut self, weights: &[f32]) -> Result<&mut Self, Error> {
+        let words = self.query.positives().count();
+        let valid = |weight: &f32| weight.is_finite() && *weight > 0.0;
+        if weights.len() != words || !weights.iter().all(valid) {
+            return Err(Error::InvalidWordWeights {
+                words,
+                weights: weights.to_vec(),
+            });
+        }
+        self.word_weights = weights.to_vec();
+        Ok(self)
+    }
+
+    /// Customize the order of the documents inside a bucket, by default it's [`TieBreak::DocIdAsc`].
+    /// See [`Index::search`] for the guarantee.
+    ///
+    /// ```
+    /// use zearch::{Index, RankingRule, Search, TieBreak};
+    ///
+    /// let index = Index::new_in_memory(&["kefir", "tamo", "kefir le chien"]).unwrap();
+    /// let mut search = Search::new("kefir");
+    /// search.with_ranking_rules(vec![RankingRule::Word]);
+    /// assert_eq!(index.search(&search), [0, 2]);
+    /// search.with_tie_break(TieBreak::DocIdDesc);
+    /// assert_eq!(index.search(&search), [2, 0]);
+    /// ```
+    pub fn with_tie_break(&mut self, tie_break: TieBreak) -> &mut Self {
+        self.tie_break = tie_break;
+        self
+    }
+
+    /// Sort the documents by a score instead of the ranking rules, by default it's [`Scoring::BucketSort`].
+    /// With [`Scoring::Bm25`] every document matching a word of the query is scored, the ranking rules and
+    /// the sort by distance are ignored and the documents sharing a score are ordered by the tie break.
+    /// The index should be constructed with [`IndexBuilder::with_term_frequencies`] and [`IndexBuilder::with_word_counts`].
+    ///
+    /// ```
+    /// use zearch::{Index, IndexBuilder, Scoring, Search};
+    ///
+    /// let mut bytes = Vec::new();
+    /// IndexBuilder::new()
+    ///     .with_term_frequencies(true)
+    ///     .with_word_counts(true)
+    ///     .construct(&["kefir le chien", "kefir kefir"], &mut bytes)
+    ///     .unwrap();
+    /// let index = Index::from_bytes(&bytes).unwrap();
+    /// let mut search = Search::new("kefir");
+    /// assert_eq!(index.search(&search), [0, 1]);
+    /// search.with_scoring(Scoring::Bm25 { k1: 1.2, b: 0.75 });
+    /// assert_eq!(index.search(&search), [1, 0]);
+    /// ```
+    pub fn with_scoring(&mut self, scoring: Scoring) -> &mut Self {
+        self.scoring = scoring;
+        self
+    }
+
+    /// Replace the exact words of the index for this search, see [`IndexBuilder::with_exact_words`].
+    /// An empty list lets every word be matched with typos.
+    ///
+    /// ```
+    /// use zearch::{Index, Search};
+    ///
+    /// let index = Index::new_in_memory(&["paris", "parisot"]).unwrap();
+    /// let mut search = Search::new("pariso");
+    /// assert_eq!(index.search(&search), [1, 0]);
+    /// search.with_exact_words(&["paris"]);
+    /// assert_eq!(index.search(&search), [1]);
+    /// ```
+    pub fn with_exact_words(&mut self, exact_words: &[&str]) -> &mut Self {
+        self.exact_words = Some(exact_words.iter().map(|word| word.to_string()).collect());
+        self
+    }
+
+    /// Customize how the typos are counted, see [`TypoTolerance`].
+    ///
+    /// ```
+    /// use zearch::{Index, Search, TypoTolerance};
+    ///
+    /// let index = Index::new_in_memory(&["samo", "tamp"]).unwrap();
+    /// let mut search = Search::new("tamo");
+    /// search.with_ranking_rules(vec![zearch::RankingRule::Typo]);
+    /// assert_eq!(index.search(&search), [1, 0]);
+    /// search.with_typo_tolerance(TypoTolerance { first_letter_penalty: false });
+    /// assert_eq!(index.search(&search), [0, 1]);
+    /// ```
+    pub fn with_typo_tolerance(&mut self, tolerance: TypoTolerance) -> &mut Self {
+        self.typo_tolerance = tolerance;
+        self
+    }
+
+    /// Replace the query by words tokenized beforehand, for an index constructed with [`IndexBuilder::construct_pretokenized`].
+    /// The words skip the syntax and the tokenizer, they're only lowercased like the tokens of the documents.
+    /// They're looked for with typos and the last one is still the beginning of a word.
+    /// The weights given by [`Search::with_word_weights`] are dropped.
+    pub fn with_pretokenized_query(&mut self, words: &[&str]) -> &mut Self {
+        self.query = Query {
+            items: words
+                .iter()
+                .map(|word| QueryItem::Term(Term::new(*word)))
+                .collect(),
+        };
+        self.input = Cow::Owned(words.join(" "));
+        self.word_weights.clear();
+        self.pretokenized = true;
+        self
+    }
+
+    /// Also look for the synonyms of the words of the query, `st` can match `saint` for example.
+    /// The keys are compared to the normalized words of the query, thus they must be lowercase and without accents.
+    /// A synonym made of multiple words like `nyc` and `new york` matches the documents containing all of them.
+    /// The documents matched by a synonym are ranked as if they contained one typo.
+    pub fn with_synonyms(&mut self, synonyms: &'a HashMap<String, Vec<String>>) -> &mut Self {
+        self.synonyms = Some(Cow::Borrowed(synonyms));
+        self
+    }
+
+    /// Same as [`Search::with_synonyms`] but the search owns the synonyms.
+    pub fn with_owned_synonyms(&mut self, synonyms: HashMap<String, Vec<String>>) -> &mut Self {
+        self.synonyms = Some(Cow::Owned(synonyms));
+        self
+    }
+
+    /// Once the ranking rules are applied, sort the documents of every bucket by their distance to the point,
+    /// given by its latitude and longitude in degrees. The documents without coordinates come last,
+    /// see [`IndexBuilder::with_coordinates`].
+    ///
+    /// ```
+    /// use zearch::{Index, IndexBuilder, Search};
+    ///
+    /// let mut bytes = Vec::new();
+    /// IndexBuilder::new()
+    ///     .with_coordinates(&[(48.8566, 2.3522), (45.764, 4.8357)])
+    ///     .construct(&["gare de Paris", "gare de Lyon", "gare de nulle part"], &mut bytes)
+    ///     .unwrap();
+    /// let index = Index::from_bytes(&bytes).unwrap();
+    /// let mut search = Search::new("gare");
+    /// // from Marseille
+    /// search.with_sort_by_distance(43.2965, 5.3698);
+    /// assert_eq!(index.search(&search), [1, 0, 2]);
+    /// ```
+    pub fn with_sort_by_distance(&mut self, lat: f32, lng: f32) -> &mut Self {
+        self.sort_by_distance = Some((lat, lng));
+        self
+    }
+
+    /// Stop looking for more words and sorting the documents once the search ran for this long,
+    /// the documents already sorted are returned. See [`SearchResults::degraded`].
+    /// The budget starts when the search is ran, not when it's set.
+    pub fn with_time_budget(&mut self, budget: Duration) -> &mut Self {
+        self.time_budget = Some(budget);
+        self
+    }
+
+    /// Once `interrupt` is set to `true` the search stops as soon as possible like with [`Search::with_time_budget`].
+    pub fn with_interrupt(&mut self, interrupt: Arc<AtomicBool>) -> &mut Self {
+        self.interrupt = Some(interrupt);
+        self
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    pub(crate) const SMALL_CORPUS: &[&str] = &[
+        "Tamo le plus beau",
+        "kefir le bon petit chien",
+        "kefir le beau chien",
+        "tamo est très beau aussi",
+        "le plus beau c'est kefir",
+        "mais il est un peu con",
+        "le petit kefir",
+        "kefirounet se prends pour un poney",
+        "kefirounet a un gros nez",
+        "kefir est un demi poney",
+        "le double kef",
+        "les keftas c'est bon aussi",
+    ];
+
+    pub(crate) fn create_small_index() -> Index<'static> {
+        Index::new_in_memory(SMALL_CORPUS).unwrap()
+    }
+
+    /// Returns a generator of numbers below the given maximum, the same ones for the same seed
+    /// so a failing case of a randomized test can be replayed.
+    pub(crate) fn seeded_random(mut seed: u64) -> impl FnMut(usize) -> usize {
+        move |max| {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1);
+            (seed >> 33) as usize % max
+        }
+    }
+
+    fn search_documents<'a>(index: &'a Index, search: &Search) -> Vec<&'a str> {
+        index
+            .search(search)
+            .into_iter()
+            .map(|id| index.get_document(id).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_search_with_only_word() {
+        let index = create_small_index();
+        let mut search = Search::new("tamo");
+        search.ranking_rules = Some(vec![RankingRule::Word]);
+
+        insta::assert_debug_snapshot!(search_documents(&index, &search), @r###"
+        [
+            "Tamo le plus beau",
+            "tamo est très beau aussi",
+        ]
+        "###);
+
+        // "tamo est" was matched first and then tamo alone
+        let mut search = Search::new("tamo est");
+        search.ranking_rules = Some(vec![RankingRule::Word]);
+        insta::assert_debug_snapshot!(search_documents(&index, &search), @r###"
+        [
+            "tamo est très beau aussi",
+            "Tamo le plus beau",
+        ]
+        "###);
+        insta::assert_debug_snapshot!(index.search_explain(&search), @r###"
+        [
+            word: tamo est => [3],
+            word: tamo => [0],
+        ]
+        "###);
+
+        // "kefir" was removed right after we found no matches for both matches
+        // and thus no prefix search was ran and we missed kefirounet
+        let mut search = Search::new("beau kefir");
+        search.ranking_rules = Some(vec![RankingRule::Word]);
+        insta::assert_debug_snapshot!(search_documents(&index, &search), @r###"
+        [
+            "kefir le beau chien",
+            "le plus beau c'est kefir",
+            "Tamo le plus beau",
+            "tamo est très beau aussi",
+        ]
+        "###);
+    }
+
+    #[test]
+    fn test_search_with_proximity() {
+        let names = [
+            "york of new hampshire",
+            "new jersey is far from york",
+            "new york city",
+        ];
+        let mut bytes = Vec::new();
+        IndexBuilder::new()
+            .with_positions(true)
+            .construct(names.as_slice(), &mut bytes)
+            .unwrap();
+        let index = Index::from_bytes(&bytes).unwrap();
+
+        let mut search = Search::new("new york");
+        search.with_ranking_rules(vec![RankingRule::Word, RankingRule::Proximity]);
+        insta::assert_debug_snapshot!(search_documents(&index, &search), @r###"
+        [
+            "new york city",
+            "york of new hampshire",
+            "new jersey is far from york",
+        ]
+        "###);
+
+        // without the positions we can't do anything and return the documents in the order of their ids
+        let index = Index::new_in_memory(names.as_slice()).unwrap();
+        insta::assert_debug_snapshot!(search_documents(&index, &search), @r###"
+        [
+            "york of new hampshire",
+            "new jersey is far from york",
+            "new york city",
+        ]
+        "###);
+    }
+
+    #[test]
+    fn test_search_with_position() {
         let names = [
+            "Hôtel de la Gare de Paris",
+            "Gare de Lyon, Paris",
+            "Paris, France",
+            "la gare",
+            "Lyon",
+        ];
+        let mut builder = IndexBuilder::new();
+        builder
+            .with_positions(true)
+            .with_stop_words(&["de", "la"])
+            .with_default_ranking_rules(vec![RankingRule::Word, RankingRule::Position]);
+        let mut bytes = Vec::new();
+        builder.construct(&names, &mut bytes).unwrap();
+        let index = Index::from_bytes(&bytes).unwrap();
+        assert_eq!(
+            index.default_search().ranking_rules,
+            [RankingRule::Word, RankingRule::Position]
+        );
+
+        // the stop words aren't counted, `paris` is the third word of the first two documents
+        insta::assert_debug_snapshot!(index.search_explain(&Search::new("paris")), @r###"
+        [
+            word: paris > position: position 0 => [2],
+            word: paris > position: position 2 => [0, 1],
+        ]
+        "###);
+        // the first query word found in the document counts, whatever its position in the query
+        insta::assert_debug_snapshot!(index.search_explain(&Search::new("paris gare")), @r###"
+        [
+            word: paris gare > position: position 0 => [1],
+            word: paris gare > position: position 1 => [0],
+            word: paris > position: position 0 => [2],
+        ]
+        "###);
+        // the typos and the prefixes are matches too
+        insta::assert_debug_snapshot!(index.search_explain(&Search::new("lyo")), @r###"
+        [
+            word: lyo > position: position 0 => [4],
+            word: lyo > position: position 1 => [1],
+        ]
+        "###);
+
+        // a word further than the 255th one shares the last bucket
+        let far = format!("{} paris", "kefir ".repeat(300));
+        let further = format!("{} paris", "kefir ".repeat(400));
+        let mut bytes = Vec::new();
+        builder
+            .construct(&[far.as_str(), further.as_str(), "paris"], &mut bytes)
+            .unwrap();
+        let index = Index::from_bytes(&bytes).unwrap();
+        insta::assert_debug_snapshot!(index.search_explain(&Search::new("paris")), @r###"
+        [
+            word: paris > position: position 0 => [2],
+            word: paris > position: position 255 => [0, 1],
+        ]
+        "###);
+
+        // without the positions every document is in the same bucket
+        let index = Index::new_in_memory(&names).unwrap();
+        let mut search = Search::new("paris");
+        search.with_ranking_rules(vec![RankingRule::Position]);
+        insta::assert_debug_snapshot!(index.search_explain(&search), @r###"
+        [
+            position: no positions => [0, 1, 2],
+        ]
+        "###);
+    }
+
+    #[test]
+    fn test_search_with_word_count() {
+        let mut builder = IndexBuilder::new();
+        builder.with_word_counts(true).with_stop_words(&["le"]);
+        let mut bytes = Vec::new();
+        builder.construct(SMALL_CORPUS, &mut bytes).unwrap();
+        let index = Index::from_bytes(&bytes).unwrap();
+        assert_eq!(index.to_bytes().unwrap(), bytes);
+        assert_eq!(index.size_stats().total, bytes.len());
+        let mut streamed = Vec::new();
+        builder.construct_to(&mut streamed, SMALL_CORPUS).unwrap();
+        assert_eq!(streamed, bytes);
+
+        let mut search = Search::new("kefir");
+        search.with_ranking_rules(vec![
+            RankingRule::Word,
+            RankingRule::Typo,
+            RankingRule::Exact,
+            RankingRule::WordCount,
+        ]);
+        insta::assert_debug_snapshot!(search_documents(&index, &search), @r###"
+        [
+            "le petit kefir",
+            "kefir le beau chien",
+            "kefir le bon petit chien",
+            "le plus beau c'est kefir",
+            "kefir est un demi poney",
+            "kefirounet a un gros nez",
+            "kefirounet se prends pour un poney",
+        ]
+        "###);
+        // the stop words aren't counted
+        insta::assert_debug_snapshot!(index.search_explain(&search), @r###"
+        [
+            word: kefir > typo: 0 typos > exact: byte exact > word_count: 2 words => [6],
+            word: kefir > typo: 0 typos > exact: byte exact > word_count: 3 words => [2],
+            word: kefir > typo: 0 typos > exact: byte exact > word_count: 4 words => [1],
+            word: kefir > typo: 0 typos > exact: byte exact > word_count: 5 words => [4, 9],
+            word: kefir > typo: 0 typos > exact: distance 3 > word_count: 5 words => [8],
+            word: kefir > typo: 0 typos > exact: distance 3 > word_count: 6 words => [7],
+        ]
+        "###);
+
+        // it can be anywhere in the ranking rules, the documents returned by a bucket are never returned again
+        search.with_ranking_rules(vec![RankingRule::WordCount, RankingRule::Exact]);
+        insta::assert_debug_snapshot!(index.search_explain(&search), @r###"
+        [
+            word_count: 2 words > exact: byte exact => [6],
+            word_count: 3 words > exact: byte exact => [2],
+            word_count: 4 words > exact: byte exact => [1],
+            word_count: 5 words > exact: byte exact => [4, 9],
+            word_count: 5 words > exact: distance 3 => [8],
+            word_count: 6 words > exact: distance 3 => [7],
+        ]
+        "###);
+
+        // the new documents are counted too, and the merged index keeps the counts
+        let mut index = index.move_in_memory();
+        index.add_documents(&["kefir"]).unwrap();
+        assert_eq!(index.search(&search)[0], 12);
+        let mut merged = Vec::new();
+        Index::merge(&[&bytes, &index.to_bytes().unwrap()], &mut merged).unwrap();
+        let merged = Index::from_bytes(&merged).unwrap();
+        assert_eq!(merged.search(&search)[0], 24);
+
+        // without the word counts every document is in the same bucket
+        let index = create_small_index();
+        assert_eq!(index.word_count_of(0), None);
+        assert!(index.average_document_length().is_nan());
+        search.with_ranking_rules(vec![RankingRule::WordCount]);
+        insta::assert_debug_snapshot!(index.search_explain(&search), @r###"
+        [
+            word_count: no word counts => [1, 2, 4, 6, 7, 8, 9],
+        ]
+        "###);
+    }
+
+    #[test]
+    fn test_word_counts() {
+        let mut bytes = Vec::new();
+        IndexBuilder::new()
+            .with_word_counts(true)
+            .construct(SMALL_CORPUS, &mut bytes)
+            .unwrap();
+        let mut index = Index::from_bytes(&bytes).unwrap().move_in_memory();
+        let counts: Vec<Option<u16>> = (0..SMALL_CORPUS.len() as u32)
+            .map(|id| index.word_count_of(id))
+            .collect();
+        // `c'est` is two words
+        let expected = [4, 5, 4, 5, 6, 6, 3, 6, 5, 5, 3, 6];
+        assert_eq!(counts, expected.map(Some));
+        assert_eq!(index.word_count_of(12), None);
+        assert_eq!(index.average_document_length(), 58.0 / 12.0);
+
+        // the deleted documents aren't counted in the average
+        index.delete_documents(&RoaringBitmap::from_iter(0..11));
+        assert_eq!(index.word_count_of(0), Some(4));
+        assert_eq!(index.average_document_length(), 6.0);
+        index.delete_documents(&RoaringBitmap::from_iter([11]));
+        assert!(index.average_document_length().is_nan());
+    }
+
+    #[test]
+    fn test_bm25() {
+        let documents = [
+            "kefir dort sur le canapé du salon avec tamo le chat",
+            "chien chien chien",
+            "kefir le chien",
+            "tamo le chat",
+            "kefir kefir",
+        ];
+        let mut builder = IndexBuilder::new();
+        builder.with_term_frequencies(true).with_word_counts(true);
+        let mut bytes = Vec::new();
+        builder.construct(&documents, &mut bytes).unwrap();
+        let index = Index::from_bytes(&bytes).unwrap();
+
+        let mut search = Search::new("kefir chien");
+        let bucket_sort = index.execute(&search);
+        search.with_scoring(Scoring::Bm25 { k1: 1.2, b: 0.75 });
+        let bm25 = index.execute(&search);
+        // the word ranking rule drops `kefir`, the most frequent word, and only returns the documents containing `chien`,
+        // while BM25 scores every document and ranks up the repetitions of the words and the short documents
+        insta::assert_debug_snapshot!((&bucket_sort.ids, &bm25.ids), @r###"
+        (
+            [
+                2,
+                1,
+            ],
+            [
+                2,
+                1,
+                4,
+                0,
+            ],
+        )
+        "###);
+        insta::assert_debug_snapshot!(bm25.scores, @r###"
+        [
+            1.626131,
+            1.4764004,
+            0.8754171,
+            0.33402598,
+        ]
+        "###);
+        assert_eq!(bm25.total_candidates, 4);
+        let explained = index.search_explain(&search);
+        let first = format!("score {:.3}", bm25.scores[0]);
+        assert_eq!(explained.buckets[0].rules, [("bm25", first)]);
+        assert_eq!(explained.buckets[0].ids, [bm25.ids[0]]);
+
+        // the documents must match at least the minimum of words
+        search.with_minimum_words_matched(2).with_limit(2);
+        assert_eq!(index.search(&search), [2]);
+        search.with_minimum_words_matched(0);
+        assert_eq!(index.search(&search), &bm25.ids[..2]);
+
+        // the section is the same whatever the construction
+        let mut external = Vec::new();
+        builder.construct_to(&mut external, documents).unwrap();
+        assert_eq!(external, bytes);
+        let (first, second) = documents.split_at(3);
+        let (mut first_bytes, mut second_bytes) = (Vec::new(), Vec::new());
+        builder.construct(first, &mut first_bytes).unwrap();
+        builder.construct(second, &mut second_bytes).unwrap();
+        let mut merged = Vec::new();
+        Index::merge(&[&first_bytes, &second_bytes], &mut merged).unwrap();
+        let merged = Index::from_bytes(&merged).unwrap();
+        assert_eq!(merged.term_frequencies, index.term_frequencies);
+        let mut added = Index::from_bytes(&first_bytes).unwrap();
+        added.add_documents(second).unwrap();
+        assert_eq!(added.term_frequencies, index.term_frequencies);
+        assert_eq!(added.search(&search), index.search(&search));
+
+        // without the sections every word appears once and the length of the documents is ignored
+        let plain = Index::new_in_memory(&documents).unwrap();
+        search.with_limit(10);
+        let results = plain.execute(&search);
+        // the long document and `kefir kefir` share a score, they're ordered by their ids
+        assert_eq!(results.ids, [2, 1, 0, 4]);
+        insta::assert_debug_snapshot!(results.scores, @r###"
+        [
+            1.4144653,
+            0.8754688,
+            0.5389965,
+            0.5389965,
+        ]
+        "###);
+    }
+
+    #[test]
+    fn test_multi_search() {
+        let index = create_small_index();
+        let mut limited = Search::new("kefir");
+        limited.with_limit(2);
+        let searches = [
+            Search::new("kefir chien"),
+            Search::new("tamo beau"),
+            limited,
+        ];
+
+        let results = index.multi_search(&searches);
+        let expected: Vec<Vec<u32>> = searches.iter().map(|search| index.search(search)).collect();
+        assert_eq!(results, expected);
+        insta::assert_debug_snapshot!(results, @r###"
+        [
+            [
+                1,
+                2,
+            ],
+            [
+                3,
+                0,
+            ],
+            [
+                1,
+                2,
+            ],
+        ]
+        "###);
+        assert!(index.multi_search(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_execute() {
+        let index = create_small_index();
+        let results = |search: &Search| {
+            let results = index.execute(search);
+            assert_eq!(results.ids, index.search(search));
+            (results.ids, results.exhausted, results.total_candidates)
+        };
+
+        let mut search = Search::new("kefir");
+        insta::assert_debug_snapshot!(results(&search), @r###"
+        (
+            [
+                1,
+                2,
+                4,
+                6,
+                9,
+                7,
+                8,
+            ],
+            true,
+            7,
+        )
+        "###);
+        insta::assert_debug_snapshot!(results(search.with_limit(2)), @r###"
+        (
+            [
+                1,
+                2,
+            ],
+            false,
+            7,
+        )
+        "###);
+        // the excluded documents are not candidates
+        insta::assert_debug_snapshot!(results(&Search::new("kefir -chien")), @r###"
+        (
+            [
+                4,
+                6,
+                9,
+                7,
+                8,
+            ],
+            true,
+            5,
+        )
+        "###);
+        insta::assert_debug_snapshot!(results(&Search::new("xyzzy")), @r###"
+        (
+            [],
+            true,
+            0,
+        )
+        "###);
+    }
+
+    #[test]
+    fn test_tokenize() {
+        for text in [
+            "kefir  le   chien",
+            "kefir\tle\t\tchien",
+            "  kefir le chien\t ",
+            "kefir... le !! chien ?!",
+            "-- kefir, le - chien --",
+            "",
+            " \t ",
+            "?!",
+        ] {
+            let index = Index::new_in_memory(&[text]).unwrap();
+            let tokens = index.tokenize(text);
+            for token in &tokens {
+                assert_eq!(&text[token.byte_range.clone()], token.original, "{text:?}");
+            }
+            let mut normalized: Vec<String> =
+                tokens.into_iter().map(|token| token.normalized).collect();
+
+            // the query is made of the same words, in the same order
+            let search = Search::new(text);
+            let query: Vec<String> = index
+                .query_words(&search)
+                .words
+                .into_iter()
+                .map(|word| word.normalized)
+                .collect();
+            let mut seen = HashSet::new();
+            let expected: Vec<String> = normalized
+                .iter()
+                .filter(|word| seen.insert(word.to_string()))
+                .cloned()
+                .collect();
+            assert_eq!(query, expected, "{text:?}");
+
+            // and the document is indexed under these words
+            let mut indexed = Vec::new();
+            let mut stream = index.fst.stream();
+            while let Some((word, _id)) = stream.next() {
+                indexed.push(String::from_utf8_lossy(word).into_owned());
+            }
+            normalized.sort_unstable();
+            normalized.dedup();
+            assert_eq!(indexed, normalized, "{text:?}");
+        }
+
+        insta::assert_debug_snapshot!(create_small_index().tokenize("  Kefir,  le\tchien !"), @r###"
+        [
+            AnalyzedToken {
+                original: "Kefir,",
+                normalized: "kefir",
+                byte_range: 2..8,
+                index: 0,
+            },
+            AnalyzedToken {
+                original: "le",
+                normalized: "le",
+                byte_range: 10..12,
+                index: 1,
+            },
+            AnalyzedToken {
+                original: "chien",
+                normalized: "chien",
+                byte_range: 13..18,
+                index: 2,
+            },
+        ]
+        "###);
+    }
+
+    #[test]
+    fn test_short_words() {
+        let index = create_small_index();
+        let matched = |search: &Search| {
+            index
+                .candidates(search)
+                .iter()
+                .map(|candidate| {
+                    candidate
+                        .matched_words()
+                        .map(|(word, typos)| format!("{word}:{typos}"))
+                        .collect::<Vec<_>>()
+                })
+                .collect::<Vec<_>>()
+        };
+        // the first keystroke only matches the words starting with it, without typos
+        let search = Search::new("k");
+        insta::assert_debug_snapshot!(matched(&search), @r###"
+        [
+            [
+                "kef:0",
+                "kefir:0",
+                "kefirounet:0",
+                "keftas:0",
+            ],
+        ]
+        "###);
+        for id in index.search(&search) {
+            let document = index.get_document(id).unwrap().to_lowercase();
+            assert!(
+                document
+                    .split_whitespace()
+                    .any(|word| word.starts_with('k')),
+                "{document}"
+            );
+        }
+
+        // a short word that isn't the last one must be matched exactly
+        insta::assert_debug_snapshot!(matched(&Search::new("le k")), @r###"
+        [
+            [
+                "le:0",
+            ],
+            [
+                "kef:0",
+                "kefir:0",
+                "kefirounet:0",
+                "keftas:0",
+            ],
+        ]
+        "###);
+        insta::assert_debug_snapshot!(matched(&Search::new("el k")), @r###"
+        [
+            [],
+            [
+                "kef:0",
+                "kefir:0",
+                "kefirounet:0",
+                "keftas:0",
+            ],
+        ]
+        "###);
+
+        let mut search = Search::new("b");
+        search.with_max_candidates_per_word(1);
+        insta::assert_debug_snapshot!(matched(&search), @r###"
+        [
+            [
+                "beau:0",
+            ],
+        ]
+        "###);
+    }
+
+    #[test]
+    fn test_candidates() {
+        let index = create_small_index();
+        let words = |search: &Search| {
+            index
+                .candidates(search)
+                .iter()
+                .map(|candidate| {
+                    let typos: Vec<Vec<u32>> = (0..4)
+                        .map(|n| candidate.docs_with_n_typos(n).unwrap().iter().collect())
+                        .collect();
+                    (
+                        candidate.query_index(),
+                        candidate.normalized().to_string(),
+                        typos,
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+        insta::assert_debug_snapshot!(words(&Search::new("kefir chein")), @r###"
+        [
+            (
+                0,
+                "kefir",
+                [
+                    [
+                        1,
+                        2,
+                        4,
+                        6,
+                        9,
+                    ],
+                    [],
+                    [],
+                    [],
+                ],
+            ),
+            (
+                1,
+                "chein",
+                [
+                    [],
+                    [
+                        1,
+                        2,
+                    ],
+                    [],
+                    [],
+                ],
+            ),
+        ]
+        "###);
+
+        for query in ["kefir", "kefir -chien", "tamo beau", "kef", "xyzzy"] {
+            let mut search = Search::new(query);
+            let universe = index.candidate_universe(&search);
+            assert_eq!(
+                universe.len(),
+                index.execute(&search).total_candidates,
+                "{query}"
+            );
+            // the word ranking rule never drops the last word, the documents only matching `beau` are not returned
+            search.with_limit(usize::MAX);
+            let results: RoaringBitmap = index.search(&search).into_iter().collect();
+            assert!(results.is_subset(&universe), "{query}");
+            assert_eq!(results == universe, query != "tamo beau", "{query}");
+        }
+
+        // the filter is applied
+        let filter = RoaringBitmap::from_iter([1, 2, 3]);
+        let mut search = Search::new("kefir");
+        search.with_filter(&filter);
+        assert_eq!(
+            index.candidate_universe(&search),
+            RoaringBitmap::from_iter([1, 2])
+        );
+    }
+
+    #[test]
+    fn test_refine() {
+        let index = create_small_index();
+        let first = Search::new("kefir").execute(&index);
+        assert_eq!(first.universe.len(), first.total_candidates);
+
+        let second = first.refine(Search::new("beau")).execute(&index);
+        insta::assert_debug_snapshot!(second.ids, @r###"
+        [
+            2,
+            4,
+        ]
+        "###);
+        assert!(second.universe.is_subset(&first.universe));
+        // `tamo le plus beau` matched `beau` but not `kefir`
+        assert!(!second.universe.contains(0));
+
+        let third = second.refine(Search::new("chien")).execute(&index);
+        insta::assert_debug_snapshot!(third.ids, @r###"
+        [
+            2,
+        ]
+        "###);
+        assert!(third.universe.is_subset(&second.universe));
+        assert!(third.universe.len() < second.universe.len());
+
+        // the filter is kept, `tamo le plus beau` is still not returned
+        let filter = RoaringBitmap::from_iter([0, 2]);
+        let mut search = Search::new("beau");
+        search.with_filter(&filter);
+        assert_eq!(first.refine(search).execute(&index).ids, [2]);
+    }
+
+    #[test]
+    fn test_exact_case() {
+        let index = Index::new_in_memory(&["Kefir", "KEFIR", "kefir", "kefri"]).unwrap();
+        // the case isn't a typo, it only ranks up the document written as the query
+        insta::assert_debug_snapshot!(index.search_explain(&Search::new("kefir")), @r###"
+        [
+            word: kefir > typo: 0 typos > exact: byte exact => [2],
+            word: kefir > typo: 0 typos > exact: distance 0 => [0, 1],
+            word: kefir > typo: 1 typos > exact: distance 1 => [3],
+        ]
+        "###);
+        insta::assert_debug_snapshot!(index.search_explain(&Search::new("KEFIR")), @r###"
+        [
+            word: kefir > typo: 0 typos > exact: byte exact => [1],
+            word: kefir > typo: 0 typos > exact: distance 0 => [0, 2],
+            word: kefir > typo: 1 typos > exact: distance 1 => [3],
+        ]
+        "###);
+    }
+
+    #[cfg(feature = "unicode")]
+    #[test]
+    fn test_exact_accents() {
+        let mut bytes = Vec::new();
+        IndexBuilder::new()
+            .with_normalizer(NormalizerKind::Unicode)
+            .construct(
+                &["EPINAL", "épinal", "Épinal", "Epinal", "epinaal"],
+                &mut bytes,
+            )
+            .unwrap();
+        let index = Index::from_bytes(&bytes).unwrap();
+        // the accents aren't typos either
+        insta::assert_debug_snapshot!(index.search_explain(&Search::new("Épinal")), @r###"
+        [
+            word: epinal > typo: 0 typos > exact: byte exact => [2],
+            word: epinal > typo: 0 typos > exact: distance 0 => [0, 1, 3],
+            word: epinal > typo: 1 typos > exact: distance 1 => [4],
+        ]
+        "###);
+        insta::assert_debug_snapshot!(index.search_explain(&Search::new("epinal")), @r###"
+        [
+            word: epinal > typo: 0 typos > exact: distance 0 => [0, 1, 2, 3],
+            word: epinal > typo: 1 typos > exact: distance 1 => [4],
+        ]
+        "###);
+    }
+
+    #[test]
+    fn test_word_weights() {
+        let index = create_small_index();
+        let mut search = Search::new("le beau kefir");
+        // by default the most frequent words are dropped first
+        insta::assert_debug_snapshot!(index.search_explain(&search), @r###"
+        [
+            word: le beau kefir > typo: 0 typos > exact: byte exact => [2, 4],
+            word: le beau > typo: 0 typos > exact: byte exact => [0],
+            word: beau > typo: 0 typos > exact: byte exact => [3],
+        ]
+        "###);
+
+        search.with_word_weights(&[0.5, 1.0, 2.0]).unwrap();
+        insta::assert_debug_snapshot!(index.search_explain(&search), @r###"
+        [
+            word: le beau kefir > typo: 0 typos > exact: byte exact => [2, 4],
+            word: kefir > typo: 0 typos > exact: byte exact => [1, 6, 9],
+            word: kefir > typo: 0 typos > exact: distance 3 => [7, 8],
+        ]
+        "###);
+        let results = index.execute(&search);
+        let scores: Vec<String> = results
+            .ids
+            .iter()
+            .zip(&results.scores)
+            .map(|(id, score)| format!("{score:.3} {}", index.get_document(*id).unwrap()))
+            .collect();
+        insta::assert_debug_snapshot!(scores, @r###"
+        [
+            "1.000 kefir le beau chien",
+            "1.000 le plus beau c'est kefir",
+            "0.571 kefir le bon petit chien",
+            "0.571 le petit kefir",
+            "0.571 kefir est un demi poney",
+            "0.457 kefirounet se prends pour un poney",
+            "0.457 kefirounet a un gros nez",
+        ]
+        "###);
+
+        // the same weights as the default ones give the same results
+        let mut uniform = Search::new("le beau kefir");
+        uniform.with_word_weights(&[1.0, 1.0, 1.0]).unwrap();
+        let default = index.execute(&Search::new("le beau kefir"));
+        let results = index.execute(&uniform);
+        assert_eq!(results.ids, default.ids);
+        assert_eq!(results.scores, default.scores);
+
+        // the excluded terms don't have a weight
+        let mut search = Search::new("le beau -chien kefir");
+        search.with_word_weights(&[0.5, 1.0, 2.0]).unwrap();
+        insta::assert_debug_snapshot!(search.with_word_weights(&[0.5, 1.0, 2.0, 1.0]).err(), @r###"
+        Some(
+            InvalidWordWeights {
+                words: 3,
+                weights: [
+                    0.5,
+                    1.0,
+                    2.0,
+                    1.0,
+                ],
+            },
+        )
+        "###);
+        insta::assert_debug_snapshot!(search.with_word_weights(&[0.5, -1.0, 2.0]).err(), @r###"
+        Some(
+            InvalidWordWeights {
+                words: 3,
+                weights: [
+                    0.5,
+                    -1.0,
+                    2.0,
+                ],
+            },
+        )
+        "###);
+        assert!(search.with_word_weights(&[0.5, f32::NAN, 2.0]).is_err());
+        assert!(search.with_word_weights(&[0.5, 0.0, 2.0]).is_err());
+
+        // the session gives the weights of the query to the words it keeps
+        let mut session = TypeaheadSession::new(&index);
+        let mut search = Search::new("le beau kefir");
+        session.search(&search);
+        search.with_word_weights(&[0.5, 1.0, 2.0]).unwrap();
+        assert_eq!(session.search(&search), index.search(&search));
+    }
+
+    #[test]
+    fn test_query_syntax() {
+        let index = create_small_index();
+        // the words of a phrase are matched without typos nor prefix
+        assert!(!index.search(&Search::new("kefri")).is_empty());
+        assert!(index.search(&Search::new("\"kefri\"")).is_empty());
+        insta::assert_debug_snapshot!(search_documents(&index, &Search::new("\"kef\"")), @r###"
+        [
+            "le double kef",
+        ]
+        "###);
+        insta::assert_debug_snapshot!(search_documents(&index, &Search::new("\"le chien\" -petit")), @r###"
+        [
+            "kefir le beau chien",
+        ]
+        "###);
+
+        // the weights written in the query are the same as the ones given to the search
+        let ranked = |search: &Search| {
+            let results = index.execute(search);
+            (results.ids, results.scores)
+        };
+        let mut search = Search::new("le beau kefir");
+        search.with_word_weights(&[0.5, 1.0, 2.0]).unwrap();
+        assert_eq!(ranked(&Search::new("le^0.5 beau kefir^2")), ranked(&search));
+        let mut search = Search::new("le^0.5 beau kefir^2");
+        search.with_word_weights(&[1.0, 1.0, 1.0]).unwrap();
+        assert_eq!(ranked(&search), ranked(&Search::new("le beau kefir")));
+
+        // the query can be built without its syntax
+        let query = Query {
+            items: vec![
+                QueryItem::Phrase {
+                    words: vec![String::from("le"), String::from("chien")],
+                    weight: None,
+                },
+                QueryItem::Negation(String::from("petit")),
+            ],
+        };
+        let search = Search::from_query(query);
+        assert_eq!(
+            index.search(&search),
+            index.search(&Search::new("\"le chien\" -petit"))
+        );
+
+        // the session doesn't refine the phrases
+        let mut session = TypeaheadSession::new(&index);
+        for query in [
+            "\"le chien\" k",
+            "\"le chien\" ke",
+            "\"le chien\" kef",
+            "le chien kef",
+            "\"le chien\" kef",
+        ] {
+            let search = Search::new(query);
+            assert_eq!(session.search(&search), index.search(&search), "{query}");
+        }
+    }
+
+    #[test]
+    fn test_minimum_words_matched() {
+        let index = create_small_index();
+        let mut search = Search::new("le beau kefir");
+        insta::assert_debug_snapshot!(index.search_explain(&search), @r###"
+        [
+            word: le beau kefir > typo: 0 typos > exact: byte exact => [2, 4],
+            word: le beau > typo: 0 typos > exact: byte exact => [0],
+            word: beau > typo: 0 typos > exact: byte exact => [3],
+        ]
+        "###);
+
+        search.with_minimum_words_matched(2);
+        // the documents matching a single word are never returned, even if the limit isn't reached
+        insta::assert_debug_snapshot!(index.search_explain(&search), @r###"
+        [
+            word: le beau kefir > typo: 0 typos > exact: byte exact => [2, 4],
+            word: le beau > typo: 0 typos > exact: byte exact => [0],
+        ]
+        "###);
+        let results = index.execute(&search);
+        assert!(results.exhausted);
+
+        // a shorter query requires all its words
+        let mut search = Search::new("kefir");
+        search.with_minimum_words_matched(2);
+        assert_eq!(
+            search.execute(&index).ids,
+            index.search(&Search::new("kefir"))
+        );
+    }
+
+    #[test]
+    fn test_scores() {
+        let index = create_small_index();
+        let scores = |search: &Search| {
+            let results = index.execute(search);
+            assert_eq!(results.ids.len(), results.scores.len());
+            assert!(results.scores.windows(2).all(|w| w[0] >= w[1]));
+            results
+                .ids
+                .iter()
+                .zip(&results.scores)
+                .map(|(id, score)| format!("{score:.3} {}", index.get_document(*id).unwrap()))
+                .collect::<Vec<_>>()
+        };
+        insta::assert_debug_snapshot!(scores(&Search::new("kefir")), @r###"
+        [
+            "1.000 kefir le bon petit chien",
+            "1.000 kefir le beau chien",
+            "1.000 le plus beau c'est kefir",
+            "1.000 le petit kefir",
+            "1.000 kefir est un demi poney",
+            "0.800 kefirounet se prends pour un poney",
+            "0.800 kefirounet a un gros nez",
+        ]
+        "###);
+        // `chein` has a typo, `kefirounet` and `keftas` only match `kef` as a prefix
+        insta::assert_debug_snapshot!(scores(&Search::new("petit chein kef")), @r###"
+        [
+            "0.850 kefir le bon petit chien",
+            "0.333 le petit kefir",
+        ]
+        "###);
+
+        // the sort has no rank, only the word ranking rule is scored
+        let mut search = Search::new("kefir chien");
+        search.with_ranking_rules(vec![
+            RankingRule::Sort { descending: false },
+            RankingRule::Word,
+        ]);
+        insta::assert_debug_snapshot!(scores(&search), @r###"
+        [
+            "1.000 kefir le bon petit chien",
+            "1.000 kefir le beau chien",
+        ]
+        "###);
+        assert!(index.execute(&Search::new("xyzzy")).scores.is_empty());
+    }
+
+    #[test]
+    fn test_search_grouped() {
+        let index = create_small_index();
+        let mut search = Search::new("le beau kefir");
+        insta::assert_debug_snapshot!(index.search_grouped(&search), @r###"
+        [
+            MatchGroup {
+                words_matched: 3,
+                ids: [
+                    2,
+                    4,
+                ],
+            },
+            MatchGroup {
+                words_matched: 2,
+                ids: [
+                    0,
+                ],
+            },
+            MatchGroup {
+                words_matched: 1,
+                ids: [
+                    3,
+                ],
+            },
+        ]
+        "###);
+        let ids: Vec<u32> = index
+            .search_grouped(&search)
+            .into_iter()
+            .flat_map(|group| group.ids)
+            .collect();
+        assert_eq!(ids, index.search(&search));
+
+        // the limit applies to all the groups
+        search.with_limit(3);
+        insta::assert_debug_snapshot!(index.search_grouped(&search), @r###"
+        [
+            MatchGroup {
+                words_matched: 3,
+                ids: [
+                    2,
+                    4,
+                ],
+            },
+            MatchGroup {
+                words_matched: 2,
+                ids: [
+                    0,
+                ],
+            },
+        ]
+        "###);
+
+        // without the word ranking rule every document must match all the words
+        search.with_ranking_rules(vec![RankingRule::Typo, RankingRule::Exact]);
+        insta::assert_debug_snapshot!(index.search_grouped(&search), @r###"
+        [
+            MatchGroup {
+                words_matched: 3,
+                ids: [
+                    2,
+                    4,
+                ],
+            },
+        ]
+        "###);
+        assert_eq!(index.search_grouped(&Search::new("xyzzy")), []);
+    }
+
+    #[test]
+    fn test_max_candidate_documents() {
+        let documents: Vec<String> = (0..100_000)
+            .map(|id| match id % 1000 {
+                0 => String::from("le kefir"),
+                _ => String::from("le chien"),
+            })
+            .collect();
+        let index = Index::new_in_memory(&documents.iter().map(String::as_str).collect::<Vec<_>>())
+            .unwrap();
+        let dropped = |results: &SearchResults| -> Vec<(String, DropReason)> {
+            results
+                .processed_query
+                .iter()
+                .filter_map(|word| Some((word.normalized.clone(), word.reason?)))
+                .collect()
+        };
+
+        let mut search = Search::new("le chien kefir");
+        search.with_limit(3);
+        let results = index.execute(&search);
+        assert!(!results.degraded);
+        assert_eq!(results.ids, [0, 1000, 2000]);
+        assert_eq!(results.total_candidates, 100_000);
+
+        // the most frequent words are ignored as if they were stop words, `le` first
+        search.with_max_candidate_documents(50_000);
+        let results = index.execute(&search);
+        insta::assert_debug_snapshot!((&results.ids, results.total_candidates, results.degraded, dropped(&results)), @r###"
+        (
+            [
+                0,
+                1000,
+                2000,
+            ],
+            100,
+            true,
+            [
+                (
+                    "le",
+                    TooFrequent,
+                ),
+                (
+                    "chien",
+                    TooFrequent,
+                ),
+            ],
+        )
+        "###);
+        search.with_max_candidate_documents(150_000);
+        let results = index.execute(&search);
+        insta::assert_debug_snapshot!((&results.ids, results.total_candidates, results.degraded, dropped(&results)), @r###"
+        (
+            [
+                0,
+                1000,
+                2000,
+            ],
+            100000,
+            true,
+            [
+                (
+                    "le",
+                    TooFrequent,
+                ),
+            ],
+        )
+        "###);
+
+        // the least frequent word is always kept
+        let mut search = Search::new("le");
+        search.with_limit(3).with_max_candidate_documents(10);
+        let results = index.execute(&search);
+        assert_eq!(results.ids, [0, 1, 2]);
+        assert!(!results.degraded);
+    }
+
+    #[test]
+    fn test_time_budget() {
+        let index = create_small_index();
+        let mut search = Search::new("kefir");
+        let expected = index.search(&search);
+
+        // with enough time we get the same results
+        search.with_time_budget(Duration::from_secs(3600));
+        let results = index.execute(&search);
+        assert_eq!(results.ids, expected);
+        assert!(!results.degraded);
+        assert!(results.exhausted);
+
+        // the budget is spent before the ranking rules can return anything
+        search.with_time_budget(Duration::ZERO);
+        let results = index.execute(&search);
+        insta::assert_debug_snapshot!((results.ids, results.exhausted, results.degraded), @r###"
+        (
+            [],
+            false,
+            true,
+        )
+        "###);
+        assert_eq!(index.search(&search), Vec::<u32>::new());
+
+        let interrupt = Arc::new(AtomicBool::new(false));
+        let mut search = Search::new("kefir");
+        search.with_interrupt(interrupt.clone());
+        assert_eq!(index.search(&search), expected);
+        interrupt.store(true, std::sync::atomic::Ordering::Relaxed);
+        assert!(index.execute(&search).degraded);
+        assert!(index.search(&search).is_empty());
+
+        // the buckets returned before the interruption are kept
+        struct InterruptAfterFirstBucket(Arc<AtomicBool>);
+        impl RankingRuleImpl for InterruptAfterFirstBucket {
+            fn name(&self) -> &'static str {
+                "interrupt"
+            }
+            fn next(
+                &mut self,
+                _prev: Option<&dyn RankingRuleImpl>,
+                words: &mut Vec<WordCandidate>,
+                _index: &Index,
+            ) -> ControlFlow<RoaringBitmap, ()> {
+                self.0.store(true, std::sync::atomic::Ordering::Relaxed);
+                let first = self.current_results(words).min().unwrap();
+                ControlFlow::Break(RoaringBitmap::from_iter([first]))
+            }
+            fn current_results(&self, words: &[WordCandidate]) -> RoaringBitmap {
+                words
+                    .iter()
+                    .flat_map(|word| &word.typos)
+                    .fold(RoaringBitmap::new(), |acc, typo| acc | typo)
+            }
+        }
+        interrupt.store(false, std::sync::atomic::Ordering::Relaxed);
+        let cloned = interrupt.clone();
+        search.with_ranking_rules(vec![RankingRule::custom(move || {
+            InterruptAfterFirstBucket(cloned.clone())
+        })]);
+        let results = index.execute(&search);
+        insta::assert_debug_snapshot!((results.ids, results.exhausted, results.degraded), @r###"
+        (
+            [
+                1,
+            ],
+            false,
+            true,
+        )
+        "###);
+    }
+
+    #[test]
+    fn test_search_with_excluded_terms() {
+        let index = create_small_index();
+        let search = Search::new("kefir -poney");
+        insta::assert_debug_snapshot!(search_documents(&index, &search), @r###"
+        [
+            "kefir le bon petit chien",
+            "kefir le beau chien",
+            "le plus beau c'est kefir",
+            "le petit kefir",
+            "kefirounet a un gros nez",
+        ]
+        "###);
+
+        let mut search = Search::new("kefir");
+        search.with_excluded_terms(&["poney", "chien"]);
+        insta::assert_debug_snapshot!(search_documents(&index, &search), @r###"
+        [
+            "le plus beau c'est kefir",
+            "le petit kefir",
+            "kefirounet a un gros nez",
+        ]
+        "###);
+
+        // by default only the exact word is excluded
+        let search = Search::new("kefir -kefirounet");
+        insta::assert_debug_snapshot!(search_documents(&index, &search), @r###"
+        [
+            "kefir le bon petit chien",
+            "kefir le beau chien",
+            "le plus beau c'est kefir",
+            "le petit kefir",
+            "kefir est un demi poney",
+        ]
+        "###);
+
+        // but we can also exclude its typos and prefixes, here "kefir" excludes "kefirounet" as well
+        let mut search = Search::new("beau -kefir");
+        search.with_fuzzy_exclusion(true);
+        insta::assert_debug_snapshot!(search_documents(&index, &search), @r###"
+        [
+            "Tamo le plus beau",
+            "tamo est très beau aussi",
+        ]
+        "###);
+    }
+
+    #[test]
+    fn test_search_with_filter() {
+        let index = Index::new_in_memory(&["kefir"; 10]).unwrap();
+        let filter = RoaringBitmap::from_sorted_iter([2, 5, 7]).unwrap();
+        let mut search = Search::new("kefir");
+        search.with_limit(20).with_filter(&filter);
+        insta::assert_debug_snapshot!(index.search(&search), @r###"
+        [
+            2,
+            5,
+            7,
+        ]
+        "###);
+
+        // nothing left after the filter
+        let filter = RoaringBitmap::from_sorted_iter([12, 13]).unwrap();
+        search.with_filter(&filter);
+        insta::assert_debug_snapshot!(index.search(&search), @"[]");
+    }
+
+    #[test]
+    fn test_search_with_payloads() {
+        let cities = [("Paris", 2_100_000), ("Paris", 3_000), ("Paris", 25_000)];
+        let mut bytes = Vec::new();
+        Index::construct_with_payloads(cities.as_slice(), &mut bytes).unwrap();
+        let index = Index::from_bytes(&bytes).unwrap();
+        insta::assert_debug_snapshot!(index.get_payload(2), @r###"
+        Some(
+            25000,
+        )
+        "###);
+
+        let mut search = Search::new("paris");
+        insta::assert_debug_snapshot!(index.search(&search), @r###"
+        [
+            0,
+            1,
+            2,
+        ]
+        "###);
+        search.with_ranking_rules(vec![
+            RankingRule::Word,
+            RankingRule::Typo,
+            RankingRule::Exact,
+            RankingRule::Sort { descending: true },
+        ]);
+        insta::assert_debug_snapshot!(index.search(&search), @r###"
+        [
+            0,
+            2,
+            1,
+        ]
+        "###);
+
+        let filter = index.filter_by_payload(..1_000_000);
+        search.with_filter(&filter);
+        insta::assert_debug_snapshot!(index.search(&search), @r###"
+        [
+            2,
+            1,
+        ]
+        "###);
+
+        // an index without payloads still works
+        let index = Index::new_in_memory(&["Paris", "Paris"]).unwrap();
+        insta::assert_debug_snapshot!(index.get_payload(0), @"None");
+        let mut search = Search::new("paris");
+        search.with_ranking_rules(vec![RankingRule::Sort { descending: true }]);
+        insta::assert_debug_snapshot!(index.search(&search), @r###"
+        [
+            0,
+            1,
+        ]
+        "###);
+    }
+
+    #[test]
+    fn test_hits() {
+        let index = create_small_index();
+        let without_documents = {
+            let mut bytes = Vec::new();
+            IndexBuilder::new()
+                .with_documents(false)
+                .construct(SMALL_CORPUS, &mut bytes)
+                .unwrap();
+            Index::from_bytes(&bytes).unwrap().move_in_memory()
+        };
+        let mut search = Search::new("kefir chien");
+        search.with_limit(3);
+
+        // the hits outlive the results they come from
+        let hits: Vec<Hit> = {
+            let results = search.execute(&index);
+            results.hits(&index).collect()
+        };
+        insta::assert_debug_snapshot!(hits, @r###"
+        [
+            Hit {
+                id: 1,
+                text: Some(
+                    "kefir le bon petit chien",
+                ),
+            },
+            Hit {
+                id: 2,
+                text: Some(
+                    "kefir le beau chien",
+                ),
+            },
+        ]
+        "###);
+        let results = search.execute(&without_documents);
+        assert!(results
+            .hits(&without_documents)
+            .map(|hit| hit.id)
+            .eq(hits.iter().map(|hit| hit.id)));
+        assert!(results
+            .hits(&without_documents)
+            .all(|hit| hit.text.is_none()));
+    }
+
+    #[test]
+    fn test_warm_up() {
+        let mut bytes = Vec::new();
+        Index::construct(SMALL_CORPUS, &mut bytes).unwrap();
+        let index = Index::from_bytes(&bytes).unwrap();
+        index.warm_up();
+        assert!(Index::is_warm());
+        // warming up the index doesn't decode the bitmaps
+        assert!(matches!(index.bitmaps, Bitmaps::Serialized { .. }));
+        assert_eq!(
+            index.search(&Search::new("kefir")),
+            create_small_index().search(&Search::new("kefir"))
+        );
+    }
+
+    #[test]
+    fn test_owned_search() {
+        // nothing the search is built from outlives the function
+        fn build_search(query: &str) -> Search<'static> {
+            let query = format!("{query} chien -bon");
+            let mut search = Search::owned(query);
+            search
+                .with_limit(5)
+                .with_owned_filter(RoaringBitmap::from_iter(0..7))
+                .with_excluded_terms(&[String::from("petit")])
+                .with_owned_synonyms(HashMap::from([(
+                    String::from("toutou"),
+                    vec![String::from("chien")],
+                )]));
+            search
+        }
+
+        let index = create_small_index();
+        let search = build_search("kefir");
+        // the search can be sent to another thread and ran there
+        let results = std::thread::spawn(move || index.search(&search))
+            .join()
+            .unwrap();
+        insta::assert_debug_snapshot!(results, @r###"
+        [
+            2,
+        ]
+        "###);
+
+        let index = create_small_index();
+        let owned = build_search("toutou");
+        let filter = RoaringBitmap::from_iter(0..7);
+        let synonyms = HashMap::from([(String::from("toutou"), vec![String::from("chien")])]);
+        let mut borrowed = Search::new("toutou chien -bon");
+        borrowed
+            .with_limit(5)
+            .with_filter(&filter)
+            .with_excluded_terms(&["petit"])
+            .with_synonyms(&synonyms);
+        assert_eq!(index.search(&owned), index.search(&borrowed));
+        assert_eq!(search_documents(&index, &owned), ["kefir le beau chien"]);
+    }
+
+    #[test]
+    fn test_search_with_max_query_words() {
+        let index = create_small_index();
+        let words = |search: &Search| {
+            let (candidates, processed) =
+                index.get_candidates(search, &mut SearchContext::default(), &Deadline::never());
+            let words: Vec<String> = candidates
+                .iter()
+                .map(|candidate| candidate.normalized().to_string())
+                .collect();
+            let truncated = processed
+                .iter()
+                .any(|word| word.reason == Some(DropReason::TooManyWords));
+            (words, truncated)
+        };
+
+        // the duplicated words are only looked for once
+        insta::assert_debug_snapshot!(words(&Search::new("beau Kefir le beau kéfir")), @r###"
+        (
+            [
+                "beau",
+                "kefir",
+                "le",
+            ],
+            false,
+        )
+        "###);
+
+        let mut search = Search::new("kefir le plus beau chien");
+        search.with_max_query_words(3);
+        insta::assert_debug_snapshot!(words(&search), @r###"
+        (
+            [
+                "kefir",
+                "le",
+                "chien",
+            ],
+            true,
+        )
+        "###);
+        assert!(index.execute(&search).query_truncated);
+        search.with_max_query_words(5);
+        assert!(!index.execute(&search).query_truncated);
+    }
+
+    #[test]
+    fn test_processed_query() {
+        let mut bytes = Vec::new();
+        IndexBuilder::new()
+            .with_stop_words(&["le"])
+            .construct(SMALL_CORPUS, &mut bytes)
+            .unwrap();
+        let index = Index::from_bytes(&bytes).unwrap();
+        let processed = |search: &Search| -> Vec<String> {
+            index
+                .execute(search)
+                .processed_query
+                .iter()
+                .map(|word| {
+                    format!(
+                        "{:?} {:?} {:?}",
+                        word.original, word.normalized, word.reason
+                    )
+                })
+                .collect()
+        };
+
+        let mut search = Search::new("Le BEAU ?! kefir -chat beau petit chien");
+        search.with_max_query_words(3);
+        insta::assert_debug_snapshot!(processed(&search), @r###"
+        [
+            "\"Le\" \"le\" Some(StopWord)",
+            "\"BEAU\" \"beau\" None",
+            "\"?!\" \"\" Some(Empty)",
+            "\"kefir\" \"kefir\" None",
+            "\"chat\" \"chat\" Some(Negated)",
+            "\"beau\" \"beau\" Some(Duplicate)",
+            "\"petit\" \"petit\" Some(TooManyWords)",
+            "\"chien\" \"chien\" None",
+        ]
+        "###);
+        // the last word is kept even if it's a stop word, a duplicated last word becomes the prefix of its first occurrence
+        insta::assert_debug_snapshot!(processed(&Search::new("kefir le")), @r###"
+        [
+            "\"kefir\" \"kefir\" None",
+            "\"le\" \"le\" None",
+        ]
+        "###);
+        insta::assert_debug_snapshot!(processed(&Search::new("kefir Kefir")), @r###"
+        [
+            "\"kefir\" \"kefir\" None",
+            "\"Kefir\" \"kefir\" Some(Duplicate)",
+        ]
+        "###);
+        assert_eq!(processed(&Search::new("")), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_documents_containing() {
+        let mut bytes = Vec::new();
+        IndexBuilder::new()
+            .with_stop_words(&["le"])
+            .construct(SMALL_CORPUS, &mut bytes)
+            .unwrap();
+        let mut index = Index::from_bytes(&bytes).unwrap().move_in_memory();
+        let documents = |word: &str| {
+            index
+                .documents_containing(word)
+                .map(|documents| documents.iter().collect::<Vec<_>>())
+        };
+
+        // normalized like a query but without typos nor prefix
+        assert_eq!(documents("Kefir,"), documents("kefir"));
+        insta::assert_debug_snapshot!(documents("KEFIR"), @r###"
+        Some(
+            [
+                1,
+                2,
+                4,
+                6,
+                9,
+            ],
+        )
+        "###);
+        assert_eq!(documents("kefi"), None);
+        assert_eq!(documents("kefirr"), None);
+        // absent from the corpus, a stop word, nothing left after the normalization or multiple words
+        assert_eq!(documents("xyzzy"), None);
+        assert_eq!(documents("le"), None);
+        assert_eq!(documents("?!"), None);
+        assert_eq!(documents("kefir chien"), None);
+        assert!(index.contains_word("chien"));
+        assert!(!index.contains_word("xyzzy"));
+
+        index.delete_documents(&index.documents_containing("tamo").unwrap());
+        assert!(!index.contains_word("tamo"));
+    }
+
+    #[cfg(feature = "unicode")]
+    #[test]
+    fn test_documents_containing_accents() {
+        let mut bytes = Vec::new();
+        IndexBuilder::new()
+            .with_normalizer(NormalizerKind::Unicode)
+            .construct(&["Épinal", "epinal", "Besançon"], &mut bytes)
+            .unwrap();
+        let index = Index::from_bytes(&bytes).unwrap();
+        assert_eq!(
+            index.documents_containing("ÉPINAL"),
+            Some(RoaringBitmap::from_iter([0, 1]))
+        );
+        assert!(index.contains_word("besancon"));
+        assert!(!index.contains_word("besan"));
+    }
+
+    #[test]
+    fn test_documents() {
+        let documents = ["kefir", "tamo", "", "Kéfir le chien"];
+        let mut bytes = Vec::new();
+        Index::construct(&documents, &mut bytes).unwrap();
+        let index = Index::from_bytes(&bytes).unwrap();
+        assert_eq!(index.len(), 4);
+        assert!(!index.is_empty());
+        insta::assert_debug_snapshot!(index.get_documents(&[3, 0, 42, 0]), @r###"
+        [
+            Some(
+                "Kéfir le chien",
+            ),
+            Some(
+                "kefir",
+            ),
+            None,
+            Some(
+                "kefir",
+            ),
+        ]
+        "###);
+
+        let mut index = index.move_in_memory();
+        index.delete_documents(&RoaringBitmap::from_iter([1]));
+        insta::assert_debug_snapshot!(index.documents().collect::<Vec<_>>(), @r###"
+        [
+            (
+                0,
+                "kefir",
+            ),
+            (
+                2,
+                "",
+            ),
+            (
+                3,
+                "Kéfir le chien",
+            ),
+        ]
+        "###);
+        assert_eq!(index.len(), 3);
+        insta::assert_debug_snapshot!(index.get_documents(&[1, 2]), @r###"
+        [
+            None,
+            Some(
+                "",
+            ),
+        ]
+        "###);
+
+        let index = Index::new_in_memory(&[]).unwrap();
+        assert!(index.is_empty());
+        assert_eq!(index.documents().count(), 0);
+    }
+
+    #[test]
+    fn test_dedup_documents() {
+        let documents = [
+            "Kefir le chien",
+            "tamo",
+            "kefir, le CHIEN !",
+            "!!!",
+            "le chien kefir",
+            "!!!",
+            "tamo",
+        ];
+        let payloads: Vec<(&str, u64)> = documents.iter().copied().zip(0..).collect();
+        let matching = |index: &Index, query: &str| {
+            let mut ids = index.search(&Search::new(query));
+            ids.sort_unstable();
+            ids
+        };
+        let mut builder = IndexBuilder::new();
+        builder.with_boosts(&[1.0, 2.0, 3.0, 4.0, 5.0]);
+        assert_eq!(builder.document_ids(&documents), [0, 1, 2, 3, 4, 5, 6]);
+        let mut bytes = Vec::new();
+        builder
+            .construct_with_payloads(&payloads, &mut bytes)
+            .unwrap();
+        let index = Index::from_bytes(&bytes).unwrap();
+        assert_eq!(matching(&index, "kefir chien"), [0, 2, 4]);
+        assert_eq!(matching(&index, "tamo"), [1, 6]);
+
+        // the words must be the same and in the same order, the documents without words are all kept
+        builder.with_dedup_documents(true);
+        assert_eq!(builder.document_ids(&documents), [0, 1, 0, 2, 3, 4, 1]);
+        let mut bytes = Vec::new();
+        builder
+            .construct_with_payloads(&payloads, &mut bytes)
+            .unwrap();
+        let index = Index::from_bytes(&bytes).unwrap();
+        assert_eq!(index.len(), 5);
+        insta::assert_debug_snapshot!(index.documents().map(|(id, document)| (document, index.get_payload(id), index.get_boost(id))).collect::<Vec<_>>(), @r###"
+        [
+            (
+                "Kefir le chien",
+                Some(
+                    0,
+                ),
+                1.0,
+            ),
+            (
+                "tamo",
+                Some(
+                    1,
+                ),
+                2.0,
+            ),
+            (
+                "!!!",
+                Some(
+                    3,
+                ),
+                4.0,
+            ),
+            (
+                "le chien kefir",
+                Some(
+                    4,
+                ),
+                5.0,
+            ),
+            (
+                "!!!",
+                Some(
+                    5,
+                ),
+                1.0,
+            ),
+        ]
+        "###);
+        assert_eq!(matching(&index, "kefir chien"), [0, 3]);
+        assert_eq!(matching(&index, "tamo"), [1]);
+
+        let mut sharded = Vec::new();
+        builder
+            .construct_sharded(&documents, 2, &mut sharded)
+            .unwrap();
+        let sharded = ShardedIndex::from_bytes(&sharded).unwrap();
+        assert_eq!(sharded.len(), 5);
+        let search = Search::new("kefir chien");
+        assert_eq!(sharded.search(&search), index.search(&search));
+    }
+
+    #[test]
+    fn test_pretokenized() {
+        // the stored texts and their lemmas, with a stop word, an uppercase and an accented token
+        let documents: [(&str, &[&str]); 4] = [
+            ("The mice were running", &["the", "mouse", "be", "run"]),
+            ("A mouse runs", &["a", "mouse", "run"]),
+            ("Running mice in Paris", &["run", "mouse", "in", "Paris"]),
+            ("Le café", &["le", "café", ""]),
+        ];
+        let mut bytes = Vec::new();
+        IndexBuilder::new()
+            .with_positions(true)
+            .with_stop_words(&["the", "a"])
+            .construct_pretokenized(&documents, &mut bytes)
+            .unwrap();
+        let index = Index::from_bytes(&bytes).unwrap();
+        assert_eq!(index.get_document(0), Some("The mice were running"));
+
+        let search = |words: &[&str]| {
+            let mut search = Search::new("");
+            search.with_pretokenized_query(words);
+            index.search(&search)
+        };
+        // the byte exact bucket looks for the words in the stored texts
+        assert_eq!(search(&["mouse"]), [1, 0, 2]);
+        // the tokens are lowercased but not normalized
+        assert_eq!(search(&["PARIS"]), [2]);
+        assert_eq!(search(&["café"]), [3]);
+        assert!(index.fst.get("café").is_some());
+        assert!(index.fst.get("cafe").is_none());
+        // the stop words are skipped and the words of the stored texts aren't indexed
+        assert!(search(&["the"]).is_empty());
+        assert!(index.search(&Search::new("mice")).is_empty());
+        assert_eq!(index.search(&Search::new("mouse")), [1, 0, 2]);
+        // the proximity is computed on the tokens, `be` separates `mouse` and `run` in the first document
+        let mut proximity = Search::new("");
+        proximity
+            .with_pretokenized_query(&["mouse", "run"])
+            .with_ranking_rules(vec![RankingRule::Proximity]);
+        assert_eq!(index.search(&proximity), [1, 0, 2]);
+        proximity.with_pretokenized_query(&["run", "mouse"]);
+        assert_eq!(index.search(&proximity), [2, 1, 0]);
+
+        // a token containing a whitespace isn't split
+        let mut bytes = Vec::new();
+        IndexBuilder::new()
+            .construct_pretokenized(&[("NYC", &["new york"])], &mut bytes)
+            .unwrap();
+        let index = Index::from_bytes(&bytes).unwrap();
+        let mut pretokenized = Search::new("");
+        pretokenized.with_pretokenized_query(&["new york"]);
+        let mut cache = SearchCache::new(2);
+        assert_eq!(index.search_cached(&pretokenized, &mut cache), [0]);
+        pretokenized.with_pretokenized_query(&["new", "york"]);
+        assert!(index.search_cached(&pretokenized, &mut cache).is_empty());
+        let search = Search::new("new york");
+        assert!(index.search_cached(&search, &mut cache).is_empty());
+    }
+
+    #[test]
+    fn test_unsearchable_documents() {
+        // the documents without words are stored and retrievable, and the corpora without words are searchable
+        let corpora: [&[&str]; 3] = [
+            &[],
+            &["!!!", "🦀🦀", "", " - "],
+            &["!!!", "kefir", "🦀🦀", "le chien", ""],
+        ];
+        let rules = [
+            RankingRule::Word,
+            RankingRule::Typo,
+            RankingRule::Exact,
+            RankingRule::Proximity,
+            RankingRule::Attribute,
+            RankingRule::Boost,
+            RankingRule::Position,
+            RankingRule::WordCount,
+            RankingRule::Sort { descending: true },
+        ];
+        for documents in corpora {
+            let mut bytes = Vec::new();
+            IndexBuilder::new()
+                .with_positions(true)
+                .construct(documents, &mut bytes)
+                .unwrap();
+            let index = Index::from_bytes(&bytes).unwrap();
+            assert_eq!(index.to_bytes().unwrap(), bytes);
+            assert_eq!(index.len(), documents.len());
+            for (id, document) in documents.iter().enumerate() {
+                assert_eq!(index.get_document(id as u32), Some(*document));
+            }
+
+            for query in ["", "!!!", "🦀", "kefir", "le chien", "k", "-kefir"] {
+                // every ranking rule in every position
+                for position in 0..rules.len() {
+                    let mut rules = rules.to_vec();
+                    rules.rotate_left(position);
+                    let mut search = Search::new(query);
+                    search.with_ranking_rules(rules);
+                    let ids = index.search(&search);
+                    assert!(
+                        ids.iter()
+                            .all(|id| documents[*id as usize].contains(char::is_alphanumeric)),
+                        "{documents:?} {query:?} {ids:?}"
+                    );
+                    assert_eq!(index.execute(&search).ids, ids);
+                    assert_eq!(index.search_with_highlights(&search).len(), ids.len());
+                    assert_eq!(TypeaheadSession::new(&index).search(&search), ids);
+                }
+                assert_eq!(index.suggest(query), None);
+            }
+
+            let mut sharded = Vec::new();
+            IndexBuilder::new()
+                .construct_sharded(documents, 3, &mut sharded)
+                .unwrap();
+            let sharded = ShardedIndex::from_bytes(&sharded).unwrap();
+            assert_eq!(
+                sharded.search(&Search::new("kefir")),
+                index.search(&Search::new("kefir"))
+            );
+
+            // the documents added later are searchable
+            let mut index = index.move_in_memory();
+            index.add_documents(&["kefir", "?"]).unwrap();
+            assert_eq!(
+                index.search(&Search::new("kefir")).last(),
+                Some(&(documents.len() as u32))
+            );
+        }
+
+        let index = Index::new_in_memory(&["!!!", "kefir", "🦀🦀", "le chien", ""]).unwrap();
+        assert_eq!(index.search(&Search::new("kefir")), [1]);
+        assert_eq!(index.search(&Search::new("le chien")), [3]);
+        assert_eq!(index.get_document(2), Some("🦀🦀"));
+        assert_eq!(index.documents_containing("🦀🦀"), None);
+    }
+
+    #[test]
+    fn test_search_with_distinct() {
+        let index = Index::new_in_memory(&[
+            "Paris",
+            "Paris-Plage",
+            "paris",
+            "Parisot",
+            "Paris",
+            "PARIS",
+            "Paris-Plage",
+        ])
+        .unwrap();
+        let mut search = Search::new("paris");
+        search.with_limit(3);
+        insta::assert_debug_snapshot!(index.search(&search), @r###"
+        [
+            2,
+            0,
+            4,
+        ]
+        "###);
+
+        // the limit is reached with the next buckets
+        search.with_distinct(true);
+        insta::assert_debug_snapshot!(index.search(&search), @r###"
+        [
+            2,
+            3,
+            1,
+        ]
+        "###);
+        search.with_limit(10);
+        insta::assert_debug_snapshot!(search_documents(&index, &search), @r###"
+        [
+            "paris",
+            "Parisot",
+            "Paris-Plage",
+        ]
+        "###);
+    }
+
+    #[test]
+    fn test_ranking_rules_alone() {
+        let index = create_small_index();
+        let mut search = Search::new("kefir chein");
+        search.with_ranking_rules(Vec::new());
+        insta::assert_debug_snapshot!(index.search_explain(&search), @r###"
+        [
+             => [1, 2, 4, 6, 9],
+        ]
+        "###);
+        // the distinct and the limit still apply and every document gets the best score
+        search.with_distinct(true).with_limit(3);
+        let results = index.execute(&search);
+        insta::assert_debug_snapshot!((results.ids, results.scores), @r###"
+        (
+            [
+                1,
+                2,
+                4,
+            ],
+            [
+                1.0,
+                1.0,
+                1.0,
+            ],
+        )
+        "###);
+
+        search.with_distinct(false).with_limit(10);
+        search.with_ranking_rules(vec![RankingRule::Word]);
+        insta::assert_debug_snapshot!(index.search_explain(&search), @r###"
+        [
+            word: kefir chein => [1, 2],
+        ]
+        "###);
+        search.with_ranking_rules(vec![RankingRule::Typo]);
+        insta::assert_debug_snapshot!(index.search_explain(&search), @r###"
+        [
+            typo: 1 typos => [1, 2],
+        ]
+        "###);
+        search.with_ranking_rules(vec![RankingRule::Exact]);
+        insta::assert_debug_snapshot!(index.search_explain(&search), @r###"
+        [
+            exact: distance 1 => [1, 2],
+        ]
+        "###);
+
+        // nothing matched
+        let mut search = Search::new("xyzzy");
+        search.with_ranking_rules(Vec::new());
+        assert!(index.search(&search).is_empty());
+    }
+
+    #[test]
+    fn test_duplicate_ranking_rules() {
+        let index = create_small_index();
+        let mut search = Search::new("kefir le chien");
+        search.with_ranking_rules(vec![RankingRule::Word, RankingRule::Typo]);
+        let expected = index.search_explain(&search);
+        search.with_ranking_rules(vec![
+            RankingRule::Word,
+            RankingRule::Word,
+            RankingRule::Typo,
+            RankingRule::Word,
+        ]);
+        assert_eq!(
+            format!("{:?}", index.search_explain(&search)),
+            format!("{expected:?}")
+        );
+
+        // the custom ranking rules are kept even when they're clones of each other
+        let custom = RankingRule::custom(Attribute::new);
+        search.with_ranking_rules(vec![RankingRule::Word, custom.clone(), custom]);
+        insta::assert_debug_snapshot!(index.search_explain(&search), @r###"
+        [
+            word: kefir le chien > attribute: no fields > attribute: no fields => [1, 2],
+        ]
+        "###);
+    }
+
+    #[test]
+    fn test_single_word_search() {
+        let index = create_small_index();
+        // every word of the corpus with its prefixes and a typo
+        let mut queries: Vec<String> = SMALL_CORPUS
+            .iter()
+            .flat_map(|document| document.split_whitespace())
+            .flat_map(|word| {
+                let word = word.to_lowercase();
+                let prefixes: Vec<String> = word
+                    .char_indices()
+                    .skip(1)
+                    .map(|(end, _)| word[..end].to_string())
+                    .collect();
+                let typo = word.replacen(|c: char| c.is_ascii_alphabetic(), "x", 1);
+                prefixes.into_iter().chain([typo, word])
+            })
+            .collect();
+        queries.sort_unstable();
+        queries.dedup();
+
+        let rules = [
+            vec![RankingRule::Word, RankingRule::Typo, RankingRule::Exact],
+            vec![RankingRule::Word, RankingRule::Typo],
+            vec![RankingRule::Typo],
+            vec![RankingRule::Typo, RankingRule::Exact],
+        ];
+        for query in &queries {
+            for rules in &rules {
+                for limit in [1, 3, 10] {
+                    let mut search = Search::new(query);
+                    search
+                        .with_ranking_rules(rules.clone())
+                        .with_limit(limit)
+                        .with_max_exact_documents(limit);
+                    for excluded in [None, Some("chien")] {
+                        search.with_excluded_terms(excluded.as_slice());
+                        // the explain always goes through the ranking rules
+                        let expected: Vec<u32> = index
+                            .search_explain(&search)
+                            .buckets
+                            .into_iter()
+                            .flat_map(|bucket| bucket.ids)
+                            .collect();
+                        assert_eq!(
+                            index.search(&search),
+                            expected,
+                            "{query:?} {rules:?} {limit} {excluded:?}"
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_tie_break() {
+        // random queries made of the words of the corpus, each of them possibly cut to a prefix or with a typo
+        let words: Vec<&str> = SMALL_CORPUS
+            .iter()
+            .flat_map(|document| document.split_whitespace())
+            .collect();
+        let mut random = seeded_random(42);
+        let index = create_small_index();
+        for _ in 0..200 {
+            let query: Vec<String> = (0..1 + random(3))
+                .map(|_| {
+                    let word = words[random(words.len())].to_lowercase();
+                    match random(3) {
+                        0 => word.chars().take(1 + random(word.len())).collect(),
+                        1 => word.replacen(|c: char| c.is_ascii_alphabetic(), "x", 1),
+                        _ => word,
+                    }
+                })
+                .collect();
+            let query = query.join(" ");
+            let mut search = Search::new(&query);
+            search.with_limit(1 + random(SMALL_CORPUS.len()));
+            if random(2) == 0 {
+                search.with_distinct(true);
+            }
+
+            let mut previous: Option<Vec<Vec<u32>>> = None;
+            for tie_break in [
+                TieBreak::DocIdAsc,
+                TieBreak::DocIdDesc,
+                TieBreak::InsertionOrder,
+            ] {
+                search.with_tie_break(tie_break);
+                let buckets: Vec<Vec<u32>> = index
+                    .search_explain(&search)
+                    .buckets
+                    .into_iter()
+                    .map(|bucket| bucket.ids)
+                    .collect();
+                for bucket in &buckets {
+                    let ordered = match tie_break {
+                        TieBreak::DocIdAsc | TieBreak::InsertionOrder => {
+                            bucket.windows(2).all(|ids| ids[0] < ids[1])
+                        }
+                        TieBreak::DocIdDesc => bucket.windows(2).all(|ids| ids[0] > ids[1]),
+                    };
+                    assert!(ordered, "{query:?} {tie_break:?} {bucket:?}");
+                }
+                assert_eq!(
+                    index.search(&search),
+                    buckets.concat(),
+                    "{query:?} {tie_break:?}"
+                );
+                // the tie break only reorders the buckets, except the last one which is cut at the limit
+                let mut sorted = buckets.clone();
+                sorted.iter_mut().for_each(|bucket| bucket.sort_unstable());
+                sorted.pop();
+                if let Some(previous) = &previous {
+                    assert_eq!(previous, &sorted, "{query:?} {tie_break:?}");
+                }
+                previous = Some(sorted);
+            }
+        }
+    }
+
+    #[test]
+    fn test_invariants() {
+        // A stand-in for property tests: proptest isn't available in the offline build, thus the cases come from
+        // `seeded_random` with a fixed seed. The same cases run every time and a failing one isn't shrunk,
+        // the test should move to proptest once the dependency can be fetched.
+        // random corpora with accents, punctuation, empty documents and repeated words, searched with random queries
+        let vocabulary = [
+            "kefir", "Kéfir", "KEFIR", "kef", "chien", "chein", "chat", "café", "cafe", "été",
+            "le", "l'avion", "Tamo", "beau", "petit", "ñandú", "?!", "--", "", "poney",
+        ];
+        let mut random = seeded_random(42);
+        for _ in 0..100 {
+            let documents: Vec<String> = (0..1 + random(20))
+                .map(|_| {
+                    let words: Vec<&str> = (0..random(6))
+                        .map(|_| vocabulary[random(vocabulary.len())])
+                        .collect();
+                    words.join(" ")
+                })
+                .collect();
+            let mut bytes = Vec::new();
+            Index::construct(&documents, &mut bytes).unwrap();
+            let index = Index::from_bytes(&bytes).unwrap();
+            // the index is written back as it was read
+            assert_eq!(index.to_bytes().unwrap(), bytes, "{documents:?}");
+
+            let normalized = |text: &str| -> HashSet<String> {
+                index
+                    .tokenize(text)
+                    .into_iter()
+                    .map(|token| token.normalized)
+                    .collect()
+            };
+            let words: Vec<HashSet<String>> = documents
+                .iter()
+                .map(|document| normalized(document))
+                .collect();
+            for _ in 0..20 {
+                let query: Vec<&str> = (0..1 + random(3))
+                    .map(|_| vocabulary[random(vocabulary.len())])
+                    .collect();
+                let query = query.join(" ");
+                let mut search = Search::new(&query);
+                search.with_limit(documents.len());
+                let ids = index.search(&search);
+
+                // every id is a document and is returned once
+                assert!(
+                    ids.iter().all(|id| index.get_document(*id).is_some()),
+                    "{query:?} {ids:?}"
+                );
+                let unique: HashSet<u32> = ids.iter().copied().collect();
+                assert_eq!(unique.len(), ids.len(), "{query:?} {ids:?}");
+
+                // a document containing all the words of the query comes before the documents containing only some of them
+                let query = normalized(&query);
+                let contains_all = |id: u32| query.is_subset(&words[id as usize]);
+                if let Some(last) = ids.iter().rposition(|id| contains_all(*id)) {
+                    let contains_some =
+                        |id: u32| !contains_all(id) && !query.is_disjoint(&words[id as usize]);
+                    assert!(
+                        !ids[..last].iter().any(|id| contains_some(*id)),
+                        "{query:?} {ids:?} {documents:?}"
+                    );
+                }
+                for (id, words) in words.iter().enumerate() {
+                    if !query.is_empty() && query.is_subset(words) {
+                        assert!(
+                            ids.contains(&(id as u32)),
+                            "{query:?} {ids:?} {documents:?}"
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_duplicated_words() {
+        let index = create_small_index();
+        assert_eq!(
+            index.search(&Search::new("kefir kefir")),
+            index.search(&Search::new("kefir"))
+        );
+        assert_eq!(
+            index.search_explain(&Search::new("kefir kefir")),
+            index.search_explain(&Search::new("kefir"))
+        );
+
+        // the first occurrence of the last word is used as a prefix
+        insta::assert_debug_snapshot!(index.search_explain(&Search::new("kefir le kefir")), @r###"
+        [
+            word: kefir le > typo: 0 typos > exact: byte exact => [1, 2, 4, 6],
+            word: le > typo: 0 typos > exact: byte exact => [0, 10],
+        ]
+        "###);
+        assert_eq!(
+            index.search(&Search::new("kefir le kefir")),
+            index.search(&Search::new("le kefir"))
+        );
+    }
+
+    #[test]
+    fn test_matched_words() {
+        let index = create_small_index();
+        let (candidates, _) = index.get_candidates(
+            &Search::new("kefir kefi"),
+            &mut SearchContext::default(),
+            &Deadline::never(),
+        );
+        let matched: Vec<Vec<(&str, u8)>> = candidates
+            .iter()
+            .map(|candidate| candidate.matched_words().collect())
+            .collect();
+        insta::assert_debug_snapshot!(matched, @r###"
+        [
+            [
+                (
+                    "kefir",
+                    0,
+                ),
+            ],
+            [
+                (
+                    "kefir",
+                    0,
+                ),
+                (
+                    "kefirounet",
+                    0,
+                ),
+                (
+                    "kef",
+                    1,
+                ),
+                (
+                    "keftas",
+                    1,
+                ),
+            ],
+        ]
+        "###);
+
+        // the exact rule prefers the documents containing the word as it was typed
+        let mut search = Search::new("kef");
+        search.with_ranking_rules(vec![RankingRule::Exact]);
+        insta::assert_debug_snapshot!(search_documents(&index, &search), @r###"
+        [
+            "le double kef",
+            "kefir le bon petit chien",
+            "kefir le beau chien",
+            "le plus beau c'est kefir",
+            "le petit kefir",
+            "kefir est un demi poney",
+            "kefirounet se prends pour un poney",
+            "kefirounet a un gros nez",
+            "les keftas c'est bon aussi",
+        ]
+        "###);
+
+        // but not if there are too many documents to sort
+        search.with_max_exact_documents(8);
+        insta::assert_debug_snapshot!(search_documents(&index, &search), @r###"
+        [
+            "kefir le bon petit chien",
+            "kefir le beau chien",
+            "le plus beau c'est kefir",
+            "le petit kefir",
+            "kefirounet se prends pour un poney",
+            "kefirounet a un gros nez",
+            "kefir est un demi poney",
+            "le double kef",
+            "les keftas c'est bon aussi",
+        ]
+        "###);
+    }
+
+    #[test]
+    fn test_search_with_max_candidates_per_word() {
+        let index = create_small_index();
+        let mut search = Search::new("kef");
+        insta::assert_debug_snapshot!(search_documents(&index, &search), @r###"
+        [
+            "le double kef",
+            "kefir le bon petit chien",
+            "kefir le beau chien",
+            "le plus beau c'est kefir",
+            "le petit kefir",
+            "kefir est un demi poney",
+            "kefirounet se prends pour un poney",
+            "kefirounet a un gros nez",
+            "les keftas c'est bon aussi",
+        ]
+        "###);
+
+        // the exact match is always kept
+        search.with_max_candidates_per_word(1);
+        insta::assert_debug_snapshot!(search_documents(&index, &search), @r###"
+        [
+            "le double kef",
+        ]
+        "###);
+
+        search.with_max_candidates_per_word(2);
+        insta::assert_debug_snapshot!(search_documents(&index, &search), @r###"
+        [
+            "le double kef",
+            "kefir le bon petit chien",
+            "kefir le beau chien",
+            "le plus beau c'est kefir",
+            "le petit kefir",
+            "kefir est un demi poney",
+        ]
+        "###);
+
+        let mut search = Search::new("k");
+        insta::assert_debug_snapshot!(search_documents(&index, &search).len(), @"9");
+        // now "k" is too short to be used as a prefix and no word is exactly "k"
+        search.with_min_prefix_len(2);
+        insta::assert_debug_snapshot!(search_documents(&index, &search), @"[]");
+
+        // the words starting with the query come before the words with a typo, even if they're greater
+        let index = Index::new_in_memory(&["kafir", "kefirounet"]).unwrap();
+        let mut search = Search::new("kefi");
+        assert_eq!(index.search(&search), [1, 0]);
+        search.with_max_candidates_per_word(1);
+        assert_eq!(index.search(&search), [1]);
+    }
+
+    #[test]
+    fn test_search_with_context() {
+        let index = create_small_index();
+        let mut ctx = SearchContext::default();
+        let queries = [
+            "kefir",
+            "le beau kefir",
+            "tamo est",
+            "kefir",
+            "poney",
+            "",
+            "beau",
+        ];
+
+        for query in queries {
+            let search = Search::new(query);
+            assert_eq!(
+                index.search_with_context(&search, &mut ctx),
+                index.search(&search),
+                "{query}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_search_with_lazy_bitmaps() {
+        let mut bytes = Vec::new();
+        Index::construct(SMALL_CORPUS, &mut bytes).unwrap();
+        // the bitmaps stay serialized until a search needs them
+        let lazy = Index::from_bytes(&bytes).unwrap();
+        let eager = create_small_index();
+
+        for query in [
+            "kefir",
+            "le beau kefir",
+            "tamo est",
+            "kefir",
+            "poney -demi",
+            "beau",
+        ] {
+            let search = Search::new(query);
+            assert_eq!(lazy.search(&search), eager.search(&search), "{query}");
+        }
+    }
+
+    #[test]
+    fn test_format_header() {
+        let mut bytes = Vec::new();
+        Index::construct(SMALL_CORPUS, &mut bytes).unwrap();
+        insta::assert_debug_snapshot!(std::str::from_utf8(&bytes[..4]), @r###"
+        Ok(
+            "ZRCH",
+        )
+        "###);
+        insta::assert_debug_snapshot!(u16::from_be_bytes([bytes[4], bytes[5]]), @"6");
+        insta::assert_debug_snapshot!(TokenizerKind::from_id(bytes[6]), @r###"
+        Some(
+            Apostrophes,
+        )
+        "###);
+        insta::assert_debug_snapshot!(NormalizerKind::from_id(bytes[7]), @r###"
+        Some(
+            Ascii,
+        )
+        "###);
+        insta::assert_debug_snapshot!(Stemmer::from_id(bytes[8]), @r###"
+        Some(
+            None,
+        )
+        "###);
+
+        let index = Index::from_bytes(&bytes).unwrap();
+        insta::assert_debug_snapshot!(search_documents(&index, &Search::new("tamo")), @r###"
+        [
+            "tamo est très beau aussi",
+            "Tamo le plus beau",
+        ]
+        "###);
+        let index = Index::from_reader(bytes.as_slice()).unwrap();
+        insta::assert_debug_snapshot!(search_documents(&index, &Search::new("tamo")), @r###"
+        [
+            "tamo est très beau aussi",
             "Tamo le plus beau",
+        ]
+        "###);
+
+        let png = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR";
+        insta::assert_debug_snapshot!(Index::from_bytes(png).err(), @r###"
+        Some(
+            NotAnIndex,
+        )
+        "###);
+        insta::assert_snapshot!(Index::from_bytes(png).err().unwrap(), @"Not a zearch index, the magic bytes are missing.");
+        insta::assert_debug_snapshot!(Index::from_bytes(&bytes[..5]).err(), @r###"
+        Some(
+            Corrupted,
+        )
+        "###);
+
+        bytes[4..6].copy_from_slice(&42_u16.to_be_bytes());
+        insta::assert_debug_snapshot!(Index::from_bytes(&bytes).err(), @r###"
+        Some(
+            UnsupportedVersion(
+                42,
+            ),
+        )
+        "###);
+        insta::assert_snapshot!(Index::from_bytes(&bytes).err().unwrap(), @"Unsupported index version 42, only the version 6 is supported.");
+    }
+
+    #[test]
+    fn test_checksum() {
+        let mut bytes = Vec::new();
+        IndexBuilder::new()
+            .with_positions(true)
+            .construct_with_payloads(&[("Paris", 1), ("Lyon", 2), ("Paris Nord", 3)], &mut bytes)
+            .unwrap();
+        Index::validate(&bytes).unwrap();
+
+        // flipping any byte after the header, in any section or in the checksum itself, must be detected
+        for i in MAGIC.len() + std::mem::size_of_val(&VERSION)..bytes.len() {
+            let mut corrupted = bytes.clone();
+            corrupted[i] ^= 0b0010_0000;
+            assert!(
+                matches!(Index::validate(&corrupted), Err(Error::ChecksumMismatch)),
+                "byte {i} was flipped"
+            );
+            assert!(
+                matches!(Index::from_bytes(&corrupted), Err(Error::ChecksumMismatch)),
+                "byte {i} was flipped"
+            );
+        }
+
+        // a truncated download
+        insta::assert_debug_snapshot!(Index::validate(&bytes[..bytes.len() - 10]), @r###"
+        Err(
+            ChecksumMismatch,
+        )
+        "###);
+
+        // the verification can be skipped
+        let mut corrupted = bytes.clone();
+        // replace the `P` of the first `Paris` by a `p`
+        let i = bytes.iter().position(|b| *b == b'P').unwrap();
+        corrupted[i] = b'p';
+        let index = Index::from_bytes_unverified(&corrupted).unwrap();
+        insta::assert_debug_snapshot!(index.get_document(0), @r###"
+        Some(
+            "paris",
+        )
+        "###);
+    }
+
+    #[test]
+    fn test_documents_out_of_range() {
+        let mut builder = IndexBuilder::new();
+        builder
+            .with_word_counts(true)
+            .with_term_frequencies(true)
+            .with_boosts(&[1.0, 2.0]);
+        let mut bytes = Vec::new();
+        builder
+            .construct_with_payloads(&[("kefir", 1), ("kefir chien", 2)], &mut bytes)
+            .unwrap();
+
+        // a crafted index whose checksum is valid but whose bitmap of `kefir` contains a document that doesn't exist
+        let mut index = Index::from_bytes(&bytes).unwrap().move_in_memory();
+        let id = index.fst.get("kefir").unwrap();
+        let Bitmaps::Decoded(bitmaps) = &mut index.bitmaps else {
+            unreachable!("the index was moved in memory")
+        };
+        bitmaps[id as usize].insert(1000);
+        let mut crafted = Vec::new();
+        index.write_to(&mut crafted).unwrap();
+
+        let mut searches = Vec::new();
+        for ranking_rules in [
+            vec![RankingRule::Sort { descending: true }],
+            vec![RankingRule::Boost],
+        ] {
+            let mut search = Search::new("kefir");
+            search.with_ranking_rules(ranking_rules);
+            searches.push(search);
+        }
+        let mut search = Search::new("kefir");
+        search.with_scoring(Scoring::Bm25 { k1: 1.2, b: 0.75 });
+        searches.push(search);
+
+        let original = Index::from_bytes(&bytes).unwrap();
+        let lazy = Index::from_bytes(&crafted).unwrap();
+        let decoded = Index::from_bytes_unverified(&crafted)
+            .unwrap()
+            .move_in_memory();
+        for index in [lazy, decoded] {
+            for search in &searches {
+                assert_eq!(index.search(search), original.search(search));
+            }
+        }
+    }
+
+    #[test]
+    fn test_adversarial_lengths() {
+        let header = || {
+            let analysis = [
+                TokenizerKind::default() as u8,
+                NormalizerKind::default() as u8,
+                Stemmer::default() as u8,
+            ];
+            [MAGIC.as_slice(), &VERSION.to_be_bytes(), &analysis].concat()
+        };
+
+        // a 16 bytes file claiming to contain 4 billion documents
+        let mut bytes = header();
+        bytes.extend_from_slice(&u32::MAX.to_be_bytes());
+        bytes.extend_from_slice(&[0; 6]);
+        insta::assert_debug_snapshot!(Index::from_bytes_unverified(&bytes).err(), @r###"
+        Some(
+            Corrupted,
+        )
+        "###);
+
+        // no documents but 4 billion bitmaps
+        let mut bytes = header();
+        bytes.extend_from_slice(&0_u32.to_be_bytes());
+        bytes.extend_from_slice(&u32::MAX.to_be_bytes());
+        bytes.extend_from_slice(&[0; 4]);
+        insta::assert_debug_snapshot!(Index::from_bytes_unverified(&bytes).err(), @r###"
+        Some(
+            Corrupted,
+        )
+        "###);
+
+        // a document bigger than the file
+        let mut bytes = header();
+        bytes.extend_from_slice(&1_u32.to_be_bytes());
+        bytes.extend_from_slice(&u32::MAX.to_be_bytes());
+        bytes.extend_from_slice(b"kefir");
+        bytes.extend_from_slice(&[0; 4]);
+        insta::assert_debug_snapshot!(Index::from_bytes_unverified(&bytes).err(), @r###"
+        Some(
+            Corrupted,
+        )
+        "###);
+
+        // a truncated index must never be loaded with missing parts
+        let mut bytes = Vec::new();
+        IndexBuilder::new()
+            .with_positions(true)
+            .construct_with_payloads(&[("Paris", 1), ("Lyon", 2)], &mut bytes)
+            .unwrap();
+        for len in 0..bytes.len() - 4 {
+            let mut truncated = bytes[..len].to_vec();
+            // keep a checksum at the end to make sure we don't read past the truncation
+            truncated.extend_from_slice(&[0; 4]);
+            // we can only lose whole optional sections, and the payloads is the last one
+            if let Ok(index) = Index::from_bytes_unverified(&truncated) {
+                assert_eq!(index.get_payload(0), None, "{len}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_write_size_too_large() {
+        struct NoWriter;
+        impl std::io::Write for NoWriter {
+            fn write(&mut self, _buf: &[u8]) -> std::io::Result<usize> {
+                panic!("nothing should be written");
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let err = Index::write_size(&mut NoWriter, u32::MAX as usize + 1).unwrap_err();
+        insta::assert_snapshot!(err, @"Cannot store a size of 4294967296 in the index, the maximum is 4294967295.");
+
+        let mut bytes = Vec::new();
+        Index::write_size(&mut bytes, u32::MAX as usize).unwrap();
+        insta::assert_debug_snapshot!(bytes, @r###"
+        [
+            255,
+            255,
+            255,
+            255,
+        ]
+        "###);
+    }
+
+    #[test]
+    fn test_construct_error() {
+        struct FullWriter;
+        impl std::io::Write for FullWriter {
+            fn write(&mut self, _buf: &[u8]) -> std::io::Result<usize> {
+                Err(std::io::ErrorKind::WriteZero.into())
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let err = Index::construct(SMALL_CORPUS, &mut FullWriter).unwrap_err();
+        insta::assert_debug_snapshot!(err, @r###"
+        Io(
+            Kind(
+                WriteZero,
+            ),
+        )
+        "###);
+    }
+
+    #[test]
+    fn test_write_to_round_trip() {
+        let mut plain = Vec::new();
+        Index::construct(SMALL_CORPUS, &mut plain).unwrap();
+        let mut full = Vec::new();
+        IndexBuilder::new()
+            .with_positions(true)
+            .construct_with_payloads(&[("Paris", 1), ("Lyon", 2), ("Paris Nord", 3)], &mut full)
+            .unwrap();
+
+        for bytes in [plain, full] {
+            let index = Index::from_bytes(&bytes).unwrap();
+            assert_eq!(index.to_bytes().unwrap(), bytes);
+            // once the bitmaps are decoded we must re-encode them the same way
+            let index = index.move_in_memory();
+            assert_eq!(index.to_bytes().unwrap(), bytes);
+        }
+    }
+
+    #[test]
+    fn test_add_documents() {
+        let new_documents = ["kefir le magnifique", "un nouveau poney", "tamo"];
+        let all: Vec<&str> = SMALL_CORPUS.iter().chain(&new_documents).copied().collect();
+        let expected = Index::new_in_memory(&all).unwrap();
+
+        let mut bytes = Vec::new();
+        IndexBuilder::new()
+            .with_positions(true)
+            .construct(SMALL_CORPUS, &mut bytes)
+            .unwrap();
+        let mut index = Index::from_bytes(&bytes).unwrap();
+        insta::assert_debug_snapshot!(search_documents(&index, &Search::new("nouveau")), @"[]");
+
+        let ids = index.add_documents(&new_documents).unwrap();
+        insta::assert_debug_snapshot!(ids, @r###"
+        [
+            12,
+            13,
+            14,
+        ]
+        "###);
+        insta::assert_debug_snapshot!(search_documents(&index, &Search::new("nouveau")), @r###"
+        [
+            "un nouveau poney",
+        ]
+        "###);
+        insta::assert_debug_snapshot!(search_documents(&index, Search::new("kefir magnifique").with_limit(3)), @r###"
+        [
+            "kefir le magnifique",
+        ]
+        "###);
+
+        for query in ["kefir", "le beau kefir", "tamo", "poney", "magni", "un"] {
+            let mut search = Search::new(query);
+            search.with_limit(20);
+            assert_eq!(index.search(&search), expected.search(&search), "{query}");
+        }
+
+        // the new words are written like any other one
+        let index = Index::from_bytes(&index.to_bytes().unwrap())
+            .unwrap()
+            .move_in_memory();
+        let mut search = Search::new("nouveau poney");
+        search.with_ranking_rules(vec![RankingRule::Word, RankingRule::Proximity]);
+        insta::assert_debug_snapshot!(search_documents(&index, &search), @r###"
+        [
+            "un nouveau poney",
+        ]
+        "###);
+    }
+
+    #[test]
+    fn test_delete_documents() {
+        let mut index = create_small_index();
+        let search = Search::new("kefir");
+        insta::assert_debug_snapshot!(search_documents(&index, &search), @r###"
+        [
             "kefir le bon petit chien",
             "kefir le beau chien",
+            "le plus beau c'est kefir",
+            "le petit kefir",
+            "kefir est un demi poney",
+            "kefirounet se prends pour un poney",
+            "kefirounet a un gros nez",
+        ]
+        "###);
+
+        index.delete_documents(&RoaringBitmap::from_iter([1, 2, 6, 100]));
+        insta::assert_debug_snapshot!(index.get_document(1), @"None");
+        insta::assert_debug_snapshot!(index.search(&search), @r###"
+        [
+            4,
+            9,
+            7,
+            8,
+        ]
+        "###);
+
+        // the tombstones are persisted
+        let bytes = index.to_bytes().unwrap();
+        let index = Index::from_bytes(&bytes).unwrap();
+        insta::assert_debug_snapshot!(index.search(&search), @r###"
+        [
+            4,
+            9,
+            7,
+            8,
+        ]
+        "###);
+
+        let mut index = index.move_in_memory();
+        let mapping = index.compact().unwrap();
+        insta::assert_debug_snapshot!(mapping, @r###"
+        [
+            Some(
+                0,
+            ),
+            None,
+            None,
+            Some(
+                1,
+            ),
+            Some(
+                2,
+            ),
+            Some(
+                3,
+            ),
+            None,
+            Some(
+                4,
+            ),
+            Some(
+                5,
+            ),
+            Some(
+                6,
+            ),
+            Some(
+                7,
+            ),
+            Some(
+                8,
+            ),
+        ]
+        "###);
+        insta::assert_debug_snapshot!(search_documents(&index, &search), @r###"
+        [
+            "le plus beau c'est kefir",
+            "kefir est un demi poney",
+            "kefirounet se prends pour un poney",
+            "kefirounet a un gros nez",
+        ]
+        "###);
+        insta::assert_debug_snapshot!(index.search(&search), @r###"
+        [
+            2,
+            6,
+            4,
+            5,
+        ]
+        "###);
+    }
+
+    #[test]
+    fn test_merge() {
+        let north = ["Paris", "Lille", "Paris Nord", "Le Havre"];
+        let south = ["Marseille", "Nice", "Paris Sud", "Le Puy-en-Velay"];
+
+        let construct = |documents: &[&str]| {
+            let mut bytes = Vec::new();
+            IndexBuilder::new()
+                .with_positions(true)
+                .construct_with_payloads(
+                    &documents
+                        .iter()
+                        .map(|document| (*document, document.len() as u64))
+                        .collect::<Vec<_>>(),
+                    &mut bytes,
+                )
+                .unwrap();
+            bytes
+        };
+        let (north, south, all) = (
+            construct(&north),
+            construct(&south),
+            construct(&[north, south].concat()),
+        );
+
+        let mut merged = Vec::new();
+        Index::merge(&[&north, &south], &mut merged).unwrap();
+        // merging gives exactly the same index as constructing everything at once
+        assert_eq!(merged, all);
+
+        let (north, south) = (
+            Index::from_bytes(&north).unwrap(),
+            Index::from_bytes(&south).unwrap(),
+        );
+        let merged = Index::from_bytes(&merged).unwrap();
+        for query in ["paris", "le", "nice", "lile"] {
+            let search = Search::new(query);
+            let mut expected = north.search(&search);
+            expected.extend(south.search(&search).iter().map(|id| id + 4));
+            expected.sort_unstable();
+            let mut got = merged.search(&search);
+            got.sort_unstable();
+            assert_eq!(got, expected, "{query}");
+        }
+        insta::assert_debug_snapshot!(search_documents(&merged, &Search::new("paris")), @r###"
+        [
+            "Paris",
+            "Paris Nord",
+            "Paris Sud",
+        ]
+        "###);
+    }
+
+    #[test]
+    fn test_fields() {
+        // the name of the city followed by its aliases
+        let north: &[&[&str]] = &[
+            &["Paris", "Lutèce", "Ville Lumière"],
+            &["Lille", "Rijsel"],
+            &["Lutterbach", "Paris"],
+        ];
+        let south: &[&[&str]] = &[&["Marseille", "Massalia", "Cité phocéenne"], &["Lunel"]];
+        let construct = |documents: &[&[&str]]| {
+            let mut bytes = Vec::new();
+            Index::construct_with_fields(documents, &mut bytes).unwrap();
+            bytes
+        };
+        let bytes = construct(north);
+        let index = Index::from_bytes(&bytes).unwrap();
+        assert_eq!(index.to_bytes().unwrap(), bytes);
+        assert_eq!(index.size_stats().total, bytes.len());
+        assert_eq!(index.get_fields(1), Some(vec!["Lille", "Rijsel"]));
+
+        let mut search = Search::new("lu");
+        search.with_ranking_rules(vec![RankingRule::Typo, RankingRule::Attribute]);
+        // the alias of the first document comes after the name of the third one
+        insta::assert_debug_snapshot!(index.search_explain(&search), @r###"
+        [
+            typo: 0 typos > attribute: field 0 => [2],
+            typo: 0 typos > attribute: field 1 => [0],
+        ]
+        "###);
+
+        // the newlines separate the fields of the new documents
+        let mut index = index.move_in_memory();
+        index.add_documents(&["Lunéville\nParis"]).unwrap();
+        assert_eq!(index.search(&search), [2, 3, 0]);
+        index.delete_documents(&RoaringBitmap::from_iter([0]));
+        index.compact().unwrap();
+        assert_eq!(index.search(&search), [1, 2]);
+        assert_eq!(index.get_fields(2), Some(vec!["Lunéville", "Paris"]));
+
+        let mut merged = Vec::new();
+        Index::merge(&[&bytes, &construct(south)], &mut merged).unwrap();
+        // merging gives exactly the same index as constructing everything at once
+        assert_eq!(merged, construct(&[north, south].concat()));
+
+        // without fields every document ends up in the same bucket
+        let index = Index::new_in_memory(&["Lunel", "Paris\nLunel"]).unwrap();
+        assert_eq!(index.get_fields(1), Some(vec!["Paris\nLunel"]));
+        insta::assert_debug_snapshot!(index.search_explain(&search), @r###"
+        [
+            typo: 0 typos > attribute: no fields => [0, 1],
+        ]
+        "###);
+    }
+
+    #[test]
+    fn test_boosts() {
+        let documents = [
+            "kefir le chien",
+            "kefir le chien",
+            "kefir et tamo",
+            "le chat",
+        ];
+        let mut builder = IndexBuilder::new();
+        builder.with_boosts(&[1.0, 2.0, 0.5]);
+        let mut bytes = Vec::new();
+        builder.construct(&documents, &mut bytes).unwrap();
+        let index = Index::from_bytes(&bytes).unwrap();
+        assert_eq!(index.to_bytes().unwrap(), bytes);
+        assert_eq!(index.size_stats().total, bytes.len());
+        // the documents after the last boost are not boosted
+        assert_eq!(index.get_boost(3), 1.0);
+
+        let mut search = Search::new("kefir");
+        search.with_ranking_rules(vec![RankingRule::Word, RankingRule::Boost]);
+        // only the boost separates the two first documents
+        insta::assert_debug_snapshot!(index.search_explain(&search), @r###"
+        [
+            word: kefir > boost: boost 2 => [1],
+            word: kefir > boost: boost 1 => [0],
+            word: kefir > boost: boost 0.5 => [2],
+        ]
+        "###);
+
+        let mut streamed = Vec::new();
+        builder.construct_to(&mut streamed, documents).unwrap();
+        assert_eq!(streamed, bytes);
+
+        // the boosts follow the documents
+        let mut index = index.move_in_memory();
+        index.add_documents(&["kefir"]).unwrap();
+        index.delete_documents(&RoaringBitmap::from_iter([0]));
+        index.compact().unwrap();
+        assert_eq!(index.search(&search), [0, 3, 1]);
+
+        let mut merged = Vec::new();
+        let other = Index::new_in_memory(&["kefir"])
+            .unwrap()
+            .to_bytes()
+            .unwrap();
+        Index::merge(&[&other, &bytes], &mut merged).unwrap();
+        let merged = Index::from_bytes(&merged).unwrap();
+        assert_eq!(merged.search(&search), [2, 0, 1, 3]);
+
+        // an index without boosts has a single bucket
+        let index = Index::new_in_memory(&documents).unwrap();
+        assert_eq!(index.get_boost(1), 1.0);
+        insta::assert_debug_snapshot!(index.search_explain(&search), @r###"
+        [
+            word: kefir > boost: no boosts => [0, 1, 2],
+        ]
+        "###);
+    }
+
+    #[test]
+    fn test_sort_by_distance() {
+        let documents = [
+            "la gare de Lille",
+            "la gare de Paris",
+            "la gare de Marseille",
+            "la gare",
+        ];
+        let mut builder = IndexBuilder::new();
+        builder.with_coordinates(&[(50.6292, 3.0573), (48.8566, 2.3522), (43.2965, 5.3698)]);
+        let mut bytes = Vec::new();
+        builder.construct(&documents, &mut bytes).unwrap();
+        let index = Index::from_bytes(&bytes).unwrap();
+        assert_eq!(index.to_bytes().unwrap(), bytes);
+        assert_eq!(index.size_stats().total, bytes.len());
+        assert_eq!(index.get_coordinates(1), Some((48.8566, 2.3522)));
+        // the documents after the last coordinates have none
+        assert_eq!(index.get_coordinates(3), None);
+
+        // from Lyon, Marseille is closer than Paris
+        let mut search = Search::new("gare");
+        search.with_sort_by_distance(45.764, 4.8357);
+        insta::assert_debug_snapshot!(index.search_explain(&search), @r###"
+        [
+            word: gare > typo: 0 typos > exact: byte exact > geo: 277619m => [2],
+            word: gare > typo: 0 typos > exact: byte exact > geo: 391500m => [1],
+            word: gare > typo: 0 typos > exact: byte exact > geo: 556768m => [0],
+            word: gare > typo: 0 typos > exact: byte exact > geo: no coordinates => [3],
+        ]
+        "###);
+
+        let mut from_lille = Search::new("gare");
+        from_lille.with_sort_by_distance(50.6292, 3.0573);
+        assert_eq!(index.search(&from_lille), [0, 1, 2, 3]);
+
+        let mut streamed = Vec::new();
+        builder.construct_to(&mut streamed, documents).unwrap();
+        assert_eq!(streamed, bytes);
+
+        // the coordinates follow the documents
+        let mut index = index.move_in_memory();
+        index.add_documents(&["la gare de Nantes"]).unwrap();
+        index.delete_documents(&RoaringBitmap::from_iter([1]));
+        index.compact().unwrap();
+        assert_eq!(index.get_coordinates(1), Some((43.2965, 5.3698)));
+        assert_eq!(index.search(&search), [1, 0, 2, 3]);
+
+        let mut merged = Vec::new();
+        let other = Index::new_in_memory(&["la gare de Brest"])
+            .unwrap()
+            .to_bytes()
+            .unwrap();
+        Index::merge(&[&other, &bytes], &mut merged).unwrap();
+        let merged = Index::from_bytes(&merged).unwrap();
+        assert_eq!(merged.get_coordinates(0), None);
+        assert_eq!(merged.search(&search), [3, 2, 1, 0, 4]);
+    }
+
+    #[test]
+    fn test_search_defaults() {
+        let documents = ["kefir le chein", "le chien", "le chat"];
+        let mut builder = IndexBuilder::new();
+        builder
+            .with_default_limit(2)
+            .with_default_ranking_rules(vec![RankingRule::Typo, RankingRule::Word]);
+        let mut bytes = Vec::new();
+        builder.construct(&documents, &mut bytes).unwrap();
+        let index = Index::from_bytes(&bytes).unwrap();
+        assert_eq!(index.to_bytes().unwrap(), bytes);
+        assert_eq!(index.size_stats().total, bytes.len());
+        assert_eq!(
+            index.default_search(),
+            SearchDefaults {
+                limit: 2,
+                ranking_rules: vec![RankingRule::Typo, RankingRule::Word],
+            }
+        );
+
+        // the ranking rules of the index are used and only two of the three documents are returned
+        let search = Search::new("le");
+        insta::assert_debug_snapshot!(index.search_explain(&search), @r###"
+        [
+            typo: 0 typos > word: le => [0, 1],
+        ]
+        "###);
+        // every setting of the search overrides the index
+        let mut custom = Search::new("le");
+        custom
+            .with_limit(3)
+            .with_ranking_rules(vec![RankingRule::Word]);
+        insta::assert_debug_snapshot!(index.search_explain(&custom), @r###"
+        [
+            word: le => [0, 1, 2],
+        ]
+        "###);
+        // so does a configuration, but only for the settings it sets
+        let mut config = SearchConfig::default();
+        assert_eq!(index.search(&config.to_search("le")), [0, 1]);
+        config.limit = Some(3);
+        assert_eq!(index.search(&config.to_search("le")), [0, 1, 2]);
+
+        let mut streamed = Vec::new();
+        builder.construct_to(&mut streamed, documents).unwrap();
+        assert_eq!(streamed, bytes);
+
+        // the defaults survive the updates
+        let mut index = index.move_in_memory();
+        index.add_documents(&["le chien chien"]).unwrap();
+        index.delete_documents(&RoaringBitmap::from_iter([0]));
+        index.compact().unwrap();
+        assert_eq!(index.default_search().limit, 2);
+
+        // an index without defaults uses the defaults of the search
+        let index = Index::new_in_memory(&documents).unwrap();
+        assert_eq!(index.default_search(), SearchDefaults::default());
+        let mut merged = Vec::new();
+        Index::merge(&[&index.to_bytes().unwrap(), &bytes], &mut merged).unwrap();
+        assert_eq!(
+            Index::from_bytes(&merged).unwrap().default_search().limit,
+            2
+        );
+
+        let mut builder = IndexBuilder::new();
+        builder.with_default_ranking_rules(vec![RankingRule::custom(Attribute::new)]);
+        assert!(matches!(
+            builder.construct(&documents, &mut Vec::new()),
+            Err(Error::CustomRankingRule)
+        ));
+    }
+
+    #[test]
+    fn test_exact_words() {
+        let documents = ["paris", "lyon", "la gare de Paris", "parisien"];
+        let mut builder = IndexBuilder::new();
+        builder.with_exact_words(&["Paris"]);
+        let mut bytes = Vec::new();
+        builder.construct(&documents, &mut bytes).unwrap();
+        let index = Index::from_bytes(&bytes).unwrap();
+        assert_eq!(index.exact_words, ["paris"]);
+        assert_eq!(index.to_bytes().unwrap(), bytes);
+        assert_eq!(index.size_stats().total, bytes.len());
+
+        // `paris` is never matched with a typo, unlike `parisien`
+        assert_eq!(index.search(&Search::new("pariss")), [3]);
+        // an exact word of the query only matches itself, not even as a prefix
+        assert_eq!(index.search(&Search::new("paris")), [0, 2]);
+        assert_eq!(index.search(&Search::new("pari")), [0, 2, 3]);
+        // the other words keep their typos
+        assert_eq!(index.search(&Search::new("lyonn")), [1]);
+        assert_eq!(index.search(&Search::new("parisienn")), [3]);
+
+        // the search replaces the exact words of the index
+        let mut search = Search::new("pariss");
+        search.with_exact_words(&[]);
+        assert_eq!(index.search(&search), [0, 2, 3]);
+        let mut search = Search::new("lyonn");
+        search.with_exact_words(&["Lyon"]);
+        assert!(index.search(&search).is_empty());
+
+        let mut streamed = Vec::new();
+        builder.construct_to(&mut streamed, documents).unwrap();
+        assert_eq!(streamed, bytes);
+
+        let mut index = index.move_in_memory();
+        index.add_documents(&["paris plage"]).unwrap();
+        index.compact().unwrap();
+        assert_eq!(index.exact_words, ["paris"]);
+        let mut merged = Vec::new();
+        let other = Index::new_in_memory(&["parisse"])
+            .unwrap()
+            .to_bytes()
+            .unwrap();
+        Index::merge(&[&other, &bytes], &mut merged).unwrap();
+        let merged = Index::from_bytes(&merged).unwrap();
+        assert_eq!(merged.search(&Search::new("pariss")), [0, 4]);
+    }
+
+    #[test]
+    fn test_first_letter_typo() {
+        let index = Index::new_in_memory(&["le chat samo", "le chat tamp"]).unwrap();
+        let mut search = Search::new("tamo");
+        search.with_ranking_rules(vec![RankingRule::Typo]);
+        insta::assert_debug_snapshot!(index.search_explain(&search), @r###"
+        [
+            typo: 1 typos => [1],
+            typo: 2 typos => [0],
+        ]
+        "###);
+        search.with_typo_tolerance(TypoTolerance {
+            first_letter_penalty: false,
+        });
+        insta::assert_debug_snapshot!(index.search_explain(&search), @r###"
+        [
+            typo: 1 typos => [0, 1],
+        ]
+        "###);
+
+        // a word with two typos keeps them, a third one would remove it from the results
+        let index = Index::new_in_memory(&["sefiro chien", "kefiro chien"]).unwrap();
+        let none = TypoTolerance {
+            first_letter_penalty: false,
+        };
+        for query in ["kefirr", "kefirr chien"] {
+            let mut search = Search::new(query);
+            assert_eq!(index.search(&search), [1, 0], "{query}");
+            search.with_typo_tolerance(none);
+            assert_eq!(index.search(&search), [1, 0], "{query}");
+        }
+    }
+
+    #[test]
+    fn test_single_document_words() {
+        // every name is unique while the cities are shared
+        let name = |i: usize| [b'a' + (i / 26) as u8, b'a' + (i % 26) as u8];
+        let documents: Vec<String> = (0..600)
+            .map(|i| {
+                let name = name(i);
+                format!(
+                    "{} {}",
+                    std::str::from_utf8(&name).unwrap(),
+                    ["Paris", "Lille"][i % 2]
+                )
+            })
+            .collect();
+        let mut bytes = Vec::new();
+        Index::construct(&documents, &mut bytes).unwrap();
+        let mut index = Index::from_bytes(&bytes).unwrap();
+        // only the two cities have a bitmap, the others are an empty slice
+        let stats = index.size_stats();
+        assert!(stats.bitmaps < 603 * 4 + 2 * 700, "{}", stats.bitmaps);
+        let single = index.fst.get("ab").unwrap();
+        assert_eq!(single >> 32, 2);
+        assert!(index.bitmaps.get(single as u32 as u64).is_empty());
+
+        insta::assert_debug_snapshot!(index.search(&Search::new("ab lille")), @r###"
+        [
+            1,
+        ]
+        "###);
+        insta::assert_debug_snapshot!(index.search(&Search::new("wb")), @r###"
+        [
+            573,
+        ]
+        "###);
+        insta::assert_debug_snapshot!(index.search(&Search::new("c")), @r###"
+        [
+            52,
+            53,
+            54,
+            55,
+            56,
+            57,
+            58,
+            59,
+            60,
+            61,
+        ]
+        "###);
+        assert_eq!(index.document_frequency("ab"), 1);
+        assert_eq!(index.document_frequency("lille"), 300);
+
+        // a word in a single document gets a bitmap once it's added to another one
+        let mut in_memory = Index::from_bytes(&bytes).unwrap().move_in_memory();
+        let ids = in_memory.add_documents(&["ab Nice", "Nice"]).unwrap();
+        insta::assert_debug_snapshot!(ids, @r###"
+        [
+            600,
+            601,
+        ]
+        "###);
+        assert_eq!(in_memory.fst.get("ab").unwrap() >> 32, 0);
+        assert_eq!(in_memory.fst.get("nice").unwrap() >> 32, 0);
+        insta::assert_debug_snapshot!(in_memory.search(&Search::new("ab")), @r###"
+        [
+            1,
+            600,
+        ]
+        "###);
+        let ids = in_memory.add_documents(&["tamo"]).unwrap();
+        assert_eq!(in_memory.fst.get("tamo").unwrap() >> 32, ids[0] as u64 + 1);
+        insta::assert_debug_snapshot!(in_memory.search(&Search::new("tamo")), @r###"
+        [
+            602,
+        ]
+        "###);
+
+        // same when merging the index with itself
+        let mut merged = Vec::new();
+        Index::merge(&[&bytes, &bytes], &mut merged).unwrap();
+        index = Index::from_bytes(&merged).unwrap();
+        assert_eq!(index.fst.get("ab").unwrap() >> 32, 0);
+        insta::assert_debug_snapshot!(index.search(&Search::new("ab")), @r###"
+        [
+            1,
+            601,
+        ]
+        "###);
+    }
+
+    #[test]
+    fn test_optimize() {
+        // a word in every document is stored as a single run
+        let documents: Vec<String> = (0..10_000).map(|i| format!("kefir {i}")).collect();
+        let mut bytes = Vec::new();
+        Index::construct(&documents, &mut bytes).unwrap();
+        let mut index = Index::from_bytes(&bytes).unwrap().move_in_memory();
+        assert_eq!(index.optimize(), 0);
+
+        let Bitmaps::Decoded(bitmaps) = &mut index.bitmaps else {
+            unreachable!("the index was moved in memory")
+        };
+        for bitmap in bitmaps.iter_mut() {
+            bitmap.remove_run_compression();
+        }
+        let mut unoptimized = Vec::new();
+        index.write_to(&mut unoptimized).unwrap();
+        assert!(bytes.len() + 8000 < unoptimized.len());
+
+        assert!(index.optimize() >= 8000);
+        let mut optimized = Vec::new();
+        index.write_to(&mut optimized).unwrap();
+        assert_eq!(optimized, bytes);
+        assert_eq!(index.search(&Search::new("kefir")).len(), 10);
+    }
+
+    #[test]
+    fn test_without_documents() {
+        let mut with = Vec::new();
+        IndexBuilder::new()
+            .with_positions(true)
+            .construct(SMALL_CORPUS, &mut with)
+            .unwrap();
+        let mut without = Vec::new();
+        IndexBuilder::new()
+            .with_positions(true)
+            .with_documents(false)
+            .construct(SMALL_CORPUS, &mut without)
+            .unwrap();
+        assert!(without.len() < with.len());
+
+        let with = Index::from_bytes(&with).unwrap();
+        let mut index = Index::from_bytes(&without).unwrap();
+        assert_eq!(index.len(), SMALL_CORPUS.len());
+        assert_eq!(index.get_document(0), None);
+        assert_eq!(index.documents().count(), 0);
+
+        // the ranking rules don't need the text of the documents,
+        // except the exact ranking rule which reads it to break the ties of the documents without typos
+        for query in ["tamo", "kefir chien", "kefri", "beau ch"] {
+            let mut search = Search::new(query);
+            search.with_ranking_rules(vec![
+                RankingRule::Word,
+                RankingRule::Typo,
+                RankingRule::Proximity,
+            ]);
+            assert_eq!(index.search(&search), with.search(&search), "{query}");
+            search.with_ranking_rules(vec![
+                RankingRule::Word,
+                RankingRule::Typo,
+                RankingRule::Proximity,
+                RankingRule::Exact,
+            ]);
+            let mut without_text = index.search(&search);
+            let mut with_text = with.search(&search);
+            without_text.sort_unstable();
+            with_text.sort_unstable();
+            assert_eq!(without_text, with_text, "{query}");
+        }
+        insta::assert_debug_snapshot!(index.search_with_highlights(&Search::new("tamo")), @r###"
+        [
+            (
+                0,
+                [],
+            ),
+            (
+                3,
+                [],
+            ),
+        ]
+        "###);
+
+        // writing it again keeps the count of documents
+        let mut bytes = Vec::new();
+        index.write_to(&mut bytes).unwrap();
+        assert_eq!(bytes, without);
+
+        let ids = index.add_documents(&["kefir le petit chat"]).unwrap();
+        insta::assert_debug_snapshot!(ids, @r###"
+        [
+            12,
+        ]
+        "###);
+        insta::assert_debug_snapshot!(index.search(&Search::new("petit")), @r###"
+        [
+            1,
+            6,
+            12,
+        ]
+        "###);
+        assert_eq!(index.get_document(ids[0]), None);
+        assert!(matches!(index.compact(), Err(Error::MissingDocuments)));
+
+        // the documents are only kept if all the merged indexes have them
+        let mut stored = Vec::new();
+        Index::construct(&["kefir"], &mut stored).unwrap();
+        let mut merged = Vec::new();
+        Index::merge(&[&without, &stored], &mut merged).unwrap();
+        let merged = Index::from_bytes(&merged).unwrap();
+        assert_eq!(merged.len(), SMALL_CORPUS.len() + 1);
+        assert_eq!(merged.get_document(SMALL_CORPUS.len() as u32), None);
+    }
+
+    #[test]
+    #[cfg(feature = "parallel")]
+    fn test_parallel_construction() {
+        let documents: Vec<String> = (0..500)
+            .map(|i| format!("document {} about {} and kefir{}", i, i * 7919 % 501, i % 7))
+            .collect();
+
+        let mut serial = Vec::new();
+        IndexBuilder::new()
+            .with_threads(1)
+            .with_positions(true)
+            .construct(&documents, &mut serial)
+            .unwrap();
+        let mut parallel = Vec::new();
+        IndexBuilder::new()
+            .with_threads(4)
+            .with_positions(true)
+            .construct(&documents, &mut parallel)
+            .unwrap();
+
+        assert_eq!(serial, parallel);
+    }
+
+    #[test]
+    fn test_construct_from_iter() {
+        let mut expected = Vec::new();
+        Index::construct(SMALL_CORPUS, &mut expected).unwrap();
+
+        let mut bytes = Vec::new();
+        Index::construct_from_iter(
+            SMALL_CORPUS.iter().map(|name| name.to_uppercase()),
+            &mut bytes,
+        )
+        .unwrap();
+        let index = Index::from_bytes(&bytes).unwrap();
+        insta::assert_debug_snapshot!(search_documents(&index, &Search::new("tamo")), @r###"
+        [
+            "TAMO LE PLUS BEAU",
+            "TAMO EST TRÈS BEAU AUSSI",
+        ]
+        "###);
+
+        let mut bytes = Vec::new();
+        Index::construct_from_iter(SMALL_CORPUS, &mut bytes).unwrap();
+        assert_eq!(bytes, expected);
+    }
+
+    #[test]
+    fn test_repeated_words_are_only_sorted_once() {
+        let documents = [
+            "new york new york city new york",
+            "york york york",
+            "new city",
+            "Kefir kefir KEFIR kefir",
+        ];
+        let analyzer = IndexBuilder::new().analyzer(&[]);
+        let words = tokenize(&documents, 0, analyzer, &Reporter::silent()).unwrap();
+        insta::assert_debug_snapshot!(words.len(), @"7");
+
+        // what we used to build by inserting every occurrence of every word
+        let mut every_word: Vec<(Id, String)> = documents
+            .iter()
+            .enumerate()
+            .flat_map(|(id, document)| {
+                document
+                    .split_whitespace()
+                    .map(move |word| (id as Id, Ascii.normalize(word)))
+            })
+            .collect();
+        sort_words(&mut every_word);
+        let nb_words = every_word.len();
+        let (fst, bitmaps) = build_words(every_word, nb_words, &Reporter::silent()).unwrap();
+        let expected = Index {
+            documents: Documents::Stored(
+                documents
+                    .iter()
+                    .map(|document| Cow::Borrowed(*document))
+                    .collect(),
+            ),
+            bitmaps: Bitmaps::Decoded(bitmaps),
+            fst: Map::new(Cow::Owned(fst)).unwrap(),
+            positions: None,
+            word_counts: None,
+            term_frequencies: None,
+            payloads: None,
+            fields: None,
+            boosts: None,
+            coordinates: None,
+            search_defaults: None,
+            deleted: RoaringBitmap::new(),
+            stop_words: Vec::new(),
+            exact_words: Vec::new(),
+            common_words: RoaringBitmap::new(),
+            tokenizer: TokenizerKind::default(),
+            normalizer: NormalizerKind::default(),
+            stemmer: Stemmer::default(),
+            generation: 0,
+        };
+
+        let mut bytes = Vec::new();
+        Index::construct(&documents, &mut bytes).unwrap();
+        assert_eq!(bytes, expected.to_bytes().unwrap());
+    }
+
+    #[test]
+    fn test_stop_words() {
+        let index = create_small_index();
+        let mut bytes = Vec::new();
+        IndexBuilder::new()
+            .with_stop_words(&["le", "LES"])
+            .construct(SMALL_CORPUS, &mut bytes)
+            .unwrap();
+        let without_stop_words = Index::from_bytes(&bytes).unwrap();
+
+        assert_eq!(without_stop_words.stop_words, ["le", "les"]);
+        assert_eq!(index.fst.len() - 2, without_stop_words.fst.len());
+        let le = index.fst.get("le").unwrap();
+        insta::assert_debug_snapshot!(index.bitmaps.get(le).iter().collect::<Vec<_>>(), @r###"
+        [
+            0,
+            1,
+            2,
+            4,
+            6,
+            10,
+        ]
+        "###);
+        assert_eq!(without_stop_words.fst.get("le"), None);
+
+        for query in ["petit kefir", "le petit kefir", "les petit le kefir"] {
+            let search = Search::new(query);
+            assert_eq!(
+                search_documents(&index, &Search::new("petit kefir"))[..2],
+                search_documents(&without_stop_words, &search)[..2],
+                "{query:?}"
+            );
+        }
+
+        // the last word is still looked up as a prefix
+        let mut bytes = Vec::new();
+        IndexBuilder::new()
+            .with_stop_words(&["de", "la"])
+            .construct(
+                &[
+                    "rue de la paix",
+                    "rue des lilas",
+                    "rue demi-lune",
+                    "rue de paris",
+                ],
+                &mut bytes,
+            )
+            .unwrap();
+        let index = Index::from_bytes(&bytes).unwrap();
+        insta::assert_debug_snapshot!(search_documents(&index, &Search::new("rue de")), @r###"
+        [
+            "rue des lilas",
+            "rue demi-lune",
+        ]
+        "###);
+        insta::assert_debug_snapshot!(search_documents(&index, &Search::new("rue de la p")), @r###"
+        [
+            "rue de la paix",
+            "rue de paris",
+        ]
+        "###);
+    }
+
+    #[test]
+    fn test_common_words() {
+        // a tiny corpus doesn't have enough documents to flag any word
+        let mut bytes = Vec::new();
+        IndexBuilder::new()
+            .with_max_document_frequency(0.3)
+            .construct(SMALL_CORPUS, &mut bytes)
+            .unwrap();
+        let index = Index::from_bytes(&bytes).unwrap();
+        assert!(index.common_words().is_empty());
+
+        let documents: Vec<String> = (0..100)
+            .map(|i| match i % 5 {
+                0 => format!("{i} rue de la paix"),
+                1 | 2 => format!("{i} rue de paris"),
+                _ => format!("{i} avenue de la gare"),
+            })
+            .collect();
+        let mut bytes = Vec::new();
+        IndexBuilder::new()
+            .with_max_document_frequency(0.5)
+            .construct(&documents, &mut bytes)
+            .unwrap();
+        let mut index = Index::from_bytes(&bytes).unwrap().move_in_memory();
+        insta::assert_debug_snapshot!(index.common_words(), @r###"
+        [
+            "de",
+            "la",
+            "rue",
+        ]
+        "###);
+        assert_eq!(index.search(&Search::new("de")).len(), 10);
+
+        index.delete_documents(&(0..90).collect());
+        index.compact().unwrap();
+        assert_eq!(index.common_words(), ["de", "la", "rue"]);
+    }
+
+    #[test]
+    fn test_tokenizer() {
+        let documents = [
+            "Aix-en-Provence",
+            "Saint-Germain-en-Laye",
+            "Aixe-sur-Vienne",
+        ];
+        let index = Index::new_in_memory(&documents).unwrap();
+        insta::assert_debug_snapshot!(search_documents(&index, &Search::new("aix")), @r###"
+        [
+            "Aix-en-Provence",
+            "Aixe-sur-Vienne",
+        ]
+        "###);
+        insta::assert_debug_snapshot!(search_documents(&index, &Search::new("provence")), @"[]");
+
+        let mut bytes = Vec::new();
+        IndexBuilder::new()
+            .with_tokenizer(TokenizerKind::Separators)
+            .construct(&documents, &mut bytes)
+            .unwrap();
+        let index = Index::from_bytes(&bytes).unwrap();
+        assert_eq!(index.tokenizer, TokenizerKind::Separators);
+        insta::assert_debug_snapshot!(search_documents(&index, &Search::new("aix")), @r###"
+        [
+            "Aix-en-Provence",
+            "Aixe-sur-Vienne",
+        ]
+        "###);
+        insta::assert_debug_snapshot!(search_documents(&index, &Search::new("provence")), @r###"
+        [
+            "Aix-en-Provence",
+        ]
+        "###);
+        insta::assert_debug_snapshot!(search_documents(&index, &Search::new("en -saint-germain")), @r###"
+        [
+            "Aix-en-Provence",
+        ]
+        "###);
+
+        let mut other = Vec::new();
+        Index::construct(&documents, &mut other).unwrap();
+        insta::assert_debug_snapshot!(Index::merge(&[&bytes, &other], &mut Vec::new()).err(), @r###"
+        Some(
+            IncompatibleIndexes,
+        )
+        "###);
+    }
+
+    #[test]
+    fn test_apostrophes() {
+        let index = create_small_index();
+        insta::assert_debug_snapshot!(search_documents(&index, &Search::new("est")), @r###"
+        [
             "tamo est très beau aussi",
             "le plus beau c'est kefir",
             "mais il est un peu con",
-            "le petit kefir",
-            "kefirounet se prends pour un poney",
-            "kefirounet a un gros nez",
             "kefir est un demi poney",
-            "le double kef",
             "les keftas c'est bon aussi",
+        ]
+        "###);
+
+        let mut bytes = Vec::new();
+        IndexBuilder::new()
+            .with_tokenizer(TokenizerKind::FrenchElision)
+            .construct(
+                &["l'avion", "d'Artagnan", "c'est l'heure", "jusqu'ici"],
+                &mut bytes,
+            )
+            .unwrap();
+        let index = Index::from_bytes(&bytes).unwrap();
+        insta::assert_debug_snapshot!(search_documents(&index, &Search::new("avion")), @r###"
+        [
+            "l'avion",
+        ]
+        "###);
+        insta::assert_debug_snapshot!(search_documents(&index, &Search::new("artagnan")), @r###"
+        [
+            "d'Artagnan",
+        ]
+        "###);
+        insta::assert_debug_snapshot!(search_documents(&index, &Search::new("l'heure")), @r###"
+        [
+            "c'est l'heure",
+        ]
+        "###);
+        // the elided words are not indexed
+        assert_eq!(index.fst.get("l"), None);
+        assert!(index.fst.get("jusqu").is_some());
+    }
+
+    #[cfg(feature = "unicode")]
+    #[test]
+    fn test_unicode_normalizer() {
+        let documents = [
+            "Besançon",
+            "Müller",
+            "Œuvre complète",
+            "São Paulo",
+            "Straße",
         ];
-        Index::new_in_memory(names.as_slice()).unwrap()
+        let mut bytes = Vec::new();
+        IndexBuilder::new()
+            .with_normalizer(NormalizerKind::Unicode)
+            .construct(&documents, &mut bytes)
+            .unwrap();
+        let index = Index::from_bytes(&bytes).unwrap();
+        assert_eq!(index.normalizer, NormalizerKind::Unicode);
+        for query in [
+            "besancon",
+            "muller",
+            "oeuvre",
+            "sao paulo",
+            "strasse",
+            "Œuvre",
+        ] {
+            insta::allow_duplicates! {
+                insta::assert_debug_snapshot!(search_documents(&index, &Search::new(query)).len(), @"1");
+            }
+        }
+
+        let mut other = Vec::new();
+        Index::construct(&documents, &mut other).unwrap();
+        insta::assert_debug_snapshot!(Index::merge(&[&bytes, &other], &mut Vec::new()).err(), @r###"
+        Some(
+            IncompatibleIndexes,
+        )
+        "###);
     }
 
+    #[cfg(feature = "stemming")]
     #[test]
-    fn test_search_with_only_word() {
-        let index = create_small_index();
-        let mut search = Search::new("tamo");
-        search.ranking_rules = vec![RankingRule::Word];
+    fn test_stemmer() {
+        let documents = [
+            "cheval blanc",
+            "chevaux blancs",
+            "le journal du soir",
+            "les journaux du soir",
+            "un chien",
+        ];
+        fn search<'a>(index: &'a Index, query: &str) -> Vec<&'a str> {
+            search_documents(index, &Search::new(query))
+        }
 
-        insta::assert_debug_snapshot!(index.search(&search), @r###"
+        // without stemming the plural is another word, excluding it keeps the singular
+        let index = Index::new_in_memory(&documents).unwrap();
+        insta::assert_debug_snapshot!(search(&index, "blanc -chevaux"), @r###"
         [
-            "Tamo le plus beau",
-            "tamo est très beau aussi",
+            "cheval blanc",
+        ]
+        "###);
+        insta::assert_debug_snapshot!(search(&index, "soir -journal"), @r###"
+        [
+            "les journaux du soir",
         ]
         "###);
 
-        // "tamo est" was matched first and then tamo alone
-        let mut search = Search::new("tamo est");
-        search.ranking_rules = vec![RankingRule::Word];
-        insta::assert_debug_snapshot!(index.search(&search), @r###"
+        let mut bytes = Vec::new();
+        IndexBuilder::new()
+            .with_stemmer(Stemmer::French)
+            .construct(&documents, &mut bytes)
+            .unwrap();
+        let index = Index::from_bytes(&bytes).unwrap();
+        assert_eq!(index.stemmer, Stemmer::French);
+        insta::assert_debug_snapshot!(search(&index, "blanc -chevaux"), @"[]");
+        insta::assert_debug_snapshot!(search(&index, "soir -journal"), @"[]");
+        // both words are the same stem in the index, only the text of the documents tells them apart
+        insta::assert_debug_snapshot!(search(&index, "chevaux"), @r###"
         [
-            "tamo est très beau aussi",
-            "Tamo le plus beau",
+            "chevaux blancs",
+            "cheval blanc",
+        ]
+        "###);
+        insta::assert_debug_snapshot!(search(&index, "chiens"), @r###"
+        [
+            "un chien",
         ]
         "###);
 
-        // "kefir" was removed right after we found no matches for both matches
-        // and thus no prefix search was ran and we missed kefirounet
-        let mut search = Search::new("beau kefir");
-        search.ranking_rules = vec![RankingRule::Word];
-        insta::assert_debug_snapshot!(index.search(&search), @r###"
+        let mut other = Vec::new();
+        Index::construct(&documents, &mut other).unwrap();
+        insta::assert_debug_snapshot!(Index::merge(&[&bytes, &other], &mut Vec::new()).err(), @r###"
+        Some(
+            IncompatibleIndexes,
+        )
+        "###);
+    }
+
+    #[test]
+    fn test_synonyms() {
+        let index = Index::new_in_memory(&[
+            "saint denis",
+            "st denis",
+            "denis",
+            "new york city",
+            "york",
+            "nyc",
+        ])
+        .unwrap();
+        let synonyms = HashMap::from([
+            (String::from("st"), vec![String::from("saint")]),
+            (String::from("nyc"), vec![String::from("New York")]),
+        ]);
+        let search = |query| search_documents(&index, Search::new(query).with_synonyms(&synonyms));
+
+        insta::assert_debug_snapshot!(search_documents(&index, &Search::new("st denis")), @r###"
         [
-            "kefir le beau chien",
-            "le plus beau c'est kefir",
-            "Tamo le plus beau",
-            "tamo est très beau aussi",
+            "st denis",
+        ]
+        "###);
+        // the literal word is ranked before its synonym
+        insta::assert_debug_snapshot!(search("st denis"), @r###"
+        [
+            "st denis",
+            "saint denis",
+        ]
+        "###);
+        insta::assert_debug_snapshot!(search("nyc"), @r###"
+        [
+            "nyc",
+            "new york city",
+        ]
+        "###);
+
+        let mut session = TypeaheadSession::new(&index);
+        for query in ["s", "st", "st d", "st de", "st denis"] {
+            let mut search = Search::new(query);
+            search.with_synonyms(&synonyms);
+            assert_eq!(session.search(&search), index.search(&search), "{query}");
+        }
+    }
+
+    #[test]
+    fn test_compound_words() {
+        let index = Index::new_in_memory(&[
+            "new york",
+            "newyork",
+            "new delhi",
+            "baseball",
+            "base ball",
+            "ball",
+        ])
+        .unwrap();
+        insta::assert_debug_snapshot!(search_documents(&index, &Search::new("newyork")), @r###"
+        [
+            "newyork",
+            "new york",
+        ]
+        "###);
+        insta::assert_debug_snapshot!(search_documents(&index, &Search::new("new york")), @r###"
+        [
+            "new york",
+            "newyork",
         ]
         "###);
+        insta::assert_debug_snapshot!(search_documents(&index, &Search::new("baseball")), @r###"
+        [
+            "baseball",
+            "base ball",
+        ]
+        "###);
+        insta::assert_debug_snapshot!(search_documents(&index, &Search::new("base ball")), @r###"
+        [
+            "base ball",
+            "baseball",
+        ]
+        "###);
+
+        let mut session = TypeaheadSession::new(&index);
+        for query in ["n", "ne", "new", "new y", "new yo", "new york"] {
+            let search = Search::new(query);
+            assert_eq!(session.search(&search), index.search(&search), "{query}");
+        }
     }
 }
@@ -0,0 +1,143 @@
+//! Normalize the words before storing them in the fst so `Kéfir` can be found with `kefir`.
+//! Like the tokenizers, the same normalizer must be used to construct and to search an index,
+//! thus only the built-in normalizers can be used and their id is stored in the index.
+
+pub trait Normalizer {
+    fn normalize(&self, word: &str) -> String;
+}
+
+/// Lowercase the ASCII letters, fold the accents of the common vowels and remove everything else.
+/// `Besançon` becomes `besanon`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Ascii;
+
+impl Normalizer for Ascii {
+    fn normalize(&self, word: &str) -> String {
+        word.chars()
+            .filter_map(|c| match c.to_ascii_lowercase() {
+                'á' | 'â' | 'à' | 'ä' => Some('a'),
+                'é' | 'ê' | 'è' | 'ë' => Some('e'),
+                'í' | 'î' | 'ì' | 'ï' => Some('i'),
+                'ó' | 'ô' | 'ò' | 'ö' => Some('o'),
+                'ú' | 'û' | 'ù' | 'ü' => Some('u'),
+                c if c.is_ascii_punctuation() || !c.is_ascii_graphic() || c.is_ascii_control() => {
+                    None
+                }
+                c => Some(c),
+            })
+            .collect()
+    }
+}
+
+/// Lowercase the word, decompose it with the NFKD normalization and remove the combining marks.
+/// The letters that can't be decomposed like `œ` or `ß` are replaced by their ASCII equivalent,
+/// the other letters and digits are kept, like the Cyrillic or Greek ones.
+/// `Besançon` becomes `besancon` and `Œuvre` becomes `oeuvre`.
+#[cfg(feature = "unicode")]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Unicode;
+
+#[cfg(feature = "unicode")]
+impl Normalizer for Unicode {
+    fn normalize(&self, word: &str) -> String {
+        use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
+
+        let mut normalized = String::with_capacity(word.len());
+        for c in word.chars().flat_map(char::to_lowercase).nfkd() {
+            match c {
+                'œ' => normalized.push_str("oe"),
+                'æ' => normalized.push_str("ae"),
+                'ß' => normalized.push_str("ss"),
+                'þ' => normalized.push_str("th"),
+                'ø' => normalized.push('o'),
+                'ł' => normalized.push('l'),
+                'đ' | 'ð' => normalized.push('d'),
+                c if is_combining_mark(c) => (),
+                c if c.is_alphanumeric() => normalized.push(c),
+                _ => (),
+            }
+        }
+        normalized
+    }
+}
+
+/// The normalizers that can be stored in an index, see [`crate::IndexBuilder::with_normalizer`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum NormalizerKind {
+    /// See [`Ascii`].
+    #[default]
+    Ascii = 0,
+    /// See [`Unicode`], requires the `unicode` feature to construct or load the index.
+    #[cfg(feature = "unicode")]
+    Unicode = 1,
+}
+
+impl NormalizerKind {
+    pub(crate) fn from_id(id: u8) -> Option<Self> {
+        match id {
+            0 => Some(Self::Ascii),
+            #[cfg(feature = "unicode")]
+            1 => Some(Self::Unicode),
+            _ => None,
+        }
+    }
+}
+
+impl Normalizer for NormalizerKind {
+    fn normalize(&self, word: &str) -> String {
+        match self {
+            Self::Ascii => Ascii.normalize(word),
+            #[cfg(feature = "unicode")]
+            Self::Unicode => Unicode.normalize(word),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_ascii() {
+        let words = ["Kéfir", "Besançon", "Müller", "C'est", "São"];
+        insta::assert_debug_snapshot!(words.map(|word| Ascii.normalize(word)), @r###"
+        [
+            "kefir",
+            "besanon",
+            "muller",
+            "cest",
+            "so",
+        ]
+        "###);
+    }
+
+    #[cfg(feature = "unicode")]
+    #[test]
+    fn test_unicode() {
+        let words = [
+            "Kéfir",
+            "Besançon",
+            "Müller",
+            "Œuvre",
+            "São",
+            "Straße",
+            "Ærø",
+            "C'est",
+            "Москва",
+        ];
+        insta::assert_debug_snapshot!(words.map(|word| Unicode.normalize(word)), @r###"
+        [
+            "kefir",
+            "besancon",
+            "muller",
+            "oeuvre",
+            "sao",
+            "strasse",
+            "aero",
+            "cest",
+            "москва",
+        ]
+        "###);
+    }
+}
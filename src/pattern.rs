@@ -0,0 +1,303 @@
+//! Look for the words of the index matching a wildcard pattern, for the tools administrating an index
+//! rather than the end users: `*ville` matches all the words ending in `ville`.
+//!
+//! A `*` matches any sequence of characters, including none, and a `?` matches exactly one character.
+//! The rest of the pattern is normalized like the documents, but it's compared to the stemmed words
+//! when the index was constructed with a stemmer.
+
+use fst::{Automaton, IntoStreamer, Streamer};
+use roaring::RoaringBitmap;
+
+use crate::normalizer::Normalizer;
+use crate::{Error, Index};
+
+/// The maximum length in bytes of a normalized pattern, every byte is a state of the automaton.
+pub const MAX_PATTERN_LEN: usize = 256;
+/// The maximum number of words matched by a pattern, the next ones are ignored.
+pub const MAX_PATTERN_WORDS: usize = 10_000;
+
+/// Returned by [`Index::search_pattern`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PatternMatches {
+    /// The documents containing one of the words, in the order of their ids.
+    pub ids: Vec<u32>,
+    /// The words of the index matching the pattern, sorted.
+    pub words: Vec<String>,
+    /// `true` if more than [`MAX_PATTERN_WORDS`] words matched, the other ones were ignored.
+    pub truncated: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Token {
+    Byte(u8),
+    /// `?`
+    AnyChar,
+    /// `*`
+    AnyString,
+}
+
+/// A nondeterministic automaton matching the bytes of the words, its state is the set of the positions
+/// it can be at in the pattern, along with the number of bytes left to read in the character matched by a `?`.
+#[derive(Debug, Clone)]
+struct Wildcard {
+    tokens: Vec<Token>,
+}
+
+impl Wildcard {
+    fn new(pattern: &str, normalizer: &impl Normalizer) -> Result<Self, Error> {
+        let invalid = |reason: &str| Error::InvalidPattern {
+            pattern: pattern.to_string(),
+            reason: reason.to_string(),
+        };
+
+        let mut tokens = Vec::new();
+        let mut literal = String::new();
+        for c in pattern.chars() {
+            let wildcard = match c {
+                '*' => Token::AnyString,
+                '?' => Token::AnyChar,
+                c => {
+                    literal.push(c);
+                    continue;
+                }
+            };
+            // the text between the wildcards is normalized as a whole
+            tokens.extend(normalizer.normalize(&literal).bytes().map(Token::Byte));
+            literal.clear();
+            // `**` is the same as `*`
+            if wildcard != Token::AnyString || tokens.last() != Some(&Token::AnyString) {
+                tokens.push(wildcard);
+            }
+        }
+        tokens.extend(normalizer.normalize(&literal).bytes().map(Token::Byte));
+
+        if tokens.is_empty() {
+            return Err(invalid("nothing is left once normalized"));
+        }
+        if tokens.len() > MAX_PATTERN_LEN {
+            return Err(invalid(&format!(
+                "longer than {MAX_PATTERN_LEN} bytes once normalized"
+            )));
+        }
+        Ok(Self { tokens })
+    }
+
+    /// Add the position to the state, and the next ones since a `*` can match nothing.
+    fn insert(&self, state: &mut Vec<(usize, u8)>, mut position: usize) {
+        loop {
+            if !state.contains(&(position, 0)) {
+                state.push((position, 0));
+            }
+            match self.tokens.get(position) {
+                Some(Token::AnyString) => position += 1,
+                _ => return,
+            }
+        }
+    }
+}
+
+impl Automaton for Wildcard {
+    type State = Vec<(usize, u8)>;
+
+    fn start(&self) -> Self::State {
+        let mut state = Vec::new();
+        self.insert(&mut state, 0);
+        state
+    }
+
+    fn is_match(&self, state: &Self::State) -> bool {
+        state.contains(&(self.tokens.len(), 0))
+    }
+
+    fn can_match(&self, state: &Self::State) -> bool {
+        !state.is_empty()
+    }
+
+    fn accept(&self, state: &Self::State, byte: u8) -> Self::State {
+        let mut next = Vec::new();
+        for &(position, remaining) in state {
+            if remaining > 0 {
+                // the continuation bytes of the character matched by a `?`
+                match (byte & 0xC0 == 0x80, remaining) {
+                    (false, _) => (),
+                    (true, 1) => self.insert(&mut next, position + 1),
+                    (true, remaining) => next.push((position, remaining - 1)),
+                }
+                continue;
+            }
+            match self.tokens.get(position) {
+                Some(Token::Byte(expected)) if *expected == byte => {
+                    self.insert(&mut next, position + 1)
+                }
+                Some(Token::AnyChar) => match byte.leading_ones() {
+                    0 => self.insert(&mut next, position + 1),
+                    remaining @ 2..=4 => next.push((position, remaining as u8 - 1)),
+                    _ => (),
+                },
+                Some(Token::AnyString) => self.insert(&mut next, position),
+                _ => (),
+            }
+        }
+        next
+    }
+}
+
+impl Index<'_> {
+    /// Returns the first `limit` documents containing a word matching the wildcard pattern,
+    /// a `*` matches any sequence of characters and a `?` matches exactly one character.
+    /// The rest of the pattern is normalized like the documents, it must match the whole word.
+    /// The pattern is invalid if nothing is left once normalized or if it's longer than [`MAX_PATTERN_LEN`].
+    ///
+    /// ```
+    /// use zearch::Index;
+    ///
+    /// let index = Index::new_in_memory(&["Deauville", "Villeneuve", "Belleville", "Vichy"]).unwrap();
+    /// let matches = index.search_pattern("*VILLE", 10).unwrap();
+    /// assert_eq!(matches.words, ["belleville", "deauville"]);
+    /// assert_eq!(matches.ids, [0, 2]);
+    /// assert_eq!(index.search_pattern("vi?h*", 10).unwrap().ids, [3]);
+    /// assert!(index.search_pattern("!!", 10).is_err());
+    /// ```
+    pub fn search_pattern(&self, pattern: &str, limit: usize) -> Result<PatternMatches, Error> {
+        let automaton = Wildcard::new(pattern, &self.normalizer)?;
+        let mut documents = RoaringBitmap::new();
+        let mut words = Vec::new();
+        let mut stream = self.fst.search(automaton).into_stream();
+        while let Some((word, id)) = stream.next() {
+            if words.len() == MAX_PATTERN_WORDS {
+                return Ok(self.pattern_matches(documents, words, true, limit));
+            }
+            documents |= &*self.bitmaps.get(id);
+            words.push(String::from_utf8_lossy(word).into_owned());
+        }
+        Ok(self.pattern_matches(documents, words, false, limit))
+    }
+
+    fn pattern_matches(
+        &self,
+        documents: RoaringBitmap,
+        words: Vec<String>,
+        truncated: bool,
+        limit: usize,
+    ) -> PatternMatches {
+        PatternMatches {
+            ids: (documents - &self.deleted).iter().take(limit).collect(),
+            words,
+            truncated,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::normalizer::Ascii;
+    use crate::test::SMALL_CORPUS;
+
+    fn matches(index: &Index, pattern: &str) -> (Vec<String>, Vec<u32>) {
+        let matches = index.search_pattern(pattern, 100).unwrap();
+        assert!(!matches.truncated);
+        (matches.words, matches.ids)
+    }
+
+    #[test]
+    fn test_patterns() {
+        let index = Index::new_in_memory(SMALL_CORPUS).unwrap();
+        // suffix
+        insta::assert_debug_snapshot!(matches(&index, "*ir"), @r###"
+        (
+            [
+                "kefir",
+            ],
+            [
+                1,
+                2,
+                4,
+                6,
+                9,
+            ],
+        )
+        "###);
+        // infix
+        insta::assert_debug_snapshot!(matches(&index, "*ef*"), @r###"
+        (
+            [
+                "kef",
+                "kefir",
+                "kefirounet",
+                "keftas",
+            ],
+            [
+                1,
+                2,
+                4,
+                6,
+                7,
+                8,
+                9,
+                10,
+                11,
+            ],
+        )
+        "###);
+        insta::assert_debug_snapshot!(matches(&index, "p?*t"), @r###"
+        (
+            [
+                "petit",
+            ],
+            [
+                1,
+                6,
+            ],
+        )
+        "###);
+        // the pattern is normalized like the documents and must match the whole word
+        assert_eq!(matches(&index, "K?FIR"), matches(&index, "kefir"));
+        assert_eq!(matches(&index, "kefi").0, Vec::<String>::new());
+        assert_eq!(matches(&index, "**kefir**"), matches(&index, "*kefir*"));
+
+        let limited = index.search_pattern("*", 2).unwrap();
+        assert_eq!(limited.ids, [0, 1]);
+        assert_eq!(limited.words.len(), index.fst.len());
+    }
+
+    #[test]
+    fn test_utf8() {
+        // the `?` matches a character whatever its length in bytes
+        let automaton = Wildcard {
+            tokens: vec![Token::Byte(b'a'), Token::AnyChar, Token::Byte(b'b')],
+        };
+        let matches = |word: &str| {
+            let state = word.bytes().fold(automaton.start(), |state, byte| {
+                automaton.accept(&state, byte)
+            });
+            automaton.is_match(&state)
+        };
+        // a combining mark is a character on its own
+        for word in ["axb", "aéb", "a€b", "a😀b", "a\u{301}b"] {
+            assert!(matches(word), "{word}");
+        }
+        for word in ["ab", "axxb", "aéxb", "ae\u{301}b"] {
+            assert!(!matches(word), "{word}");
+        }
+    }
+
+    #[test]
+    fn test_invalid_patterns() {
+        let index = Index::new_in_memory(SMALL_CORPUS).unwrap();
+        insta::assert_snapshot!(index.search_pattern("", 10).unwrap_err(), @r###"
+        Invalid pattern "": nothing is left once normalized.
+        "###);
+        insta::assert_snapshot!(index.search_pattern("-!", 10).unwrap_err(), @r###"
+        Invalid pattern "-!": nothing is left once normalized.
+        "###);
+        // the punctuation around a wildcard is ignored
+        assert_eq!(matches(&index, "-?-"), matches(&index, "?"));
+        let long = "a".repeat(MAX_PATTERN_LEN + 1);
+        assert!(matches!(
+            index.search_pattern(&long, 10),
+            Err(Error::InvalidPattern { .. })
+        ));
+        assert!(Wildcard::new(&"a".repeat(MAX_PATTERN_LEN), &Ascii).is_ok());
+    }
+}
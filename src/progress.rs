@@ -0,0 +1,222 @@
+//! Reports the progress of the construction of an index and lets the user cancel it.
+//! The documents can be tokenized from multiple threads, thus the counters are atomic
+//! and the callback is only called with counts greater than the last one it has seen.
+
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use crate::Error;
+
+/// When we don't know how many items there are we report every time this many were processed.
+const REPORT_EVERY: usize = 1024;
+
+/// The steps of the construction of an index, in the order they happen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    /// Splitting the documents in words, counted in documents.
+    Tokenizing,
+    /// Sorting the words, counted in words.
+    Sorting,
+    /// Inserting the words in the fst and their documents in the bitmaps, counted in words.
+    BuildingFst,
+    /// Counted in documents.
+    WritingDocuments,
+    /// Counted in bitmaps.
+    WritingBitmaps,
+}
+
+/// Given to the callback of [`IndexBuilder::with_progress`](crate::IndexBuilder::with_progress).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Progress {
+    pub phase: Phase,
+    pub processed: usize,
+    /// `None` if we can't know it before the end of the phase.
+    pub total: Option<usize>,
+}
+
+#[derive(Clone)]
+pub(crate) struct ProgressCallback(pub Arc<dyn Fn(Progress) + Send + Sync>);
+
+impl fmt::Debug for ProgressCallback {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ProgressCallback")
+    }
+}
+
+/// Counts what was processed in the current phase and calls the callback at most about a hundred times per phase.
+pub(crate) struct Reporter<'b> {
+    callback: Option<&'b ProgressCallback>,
+    cancel: Option<&'b AtomicBool>,
+    processed: AtomicUsize,
+    // how many items must be processed between two reports of the current phase
+    step: AtomicUsize,
+    // `usize::MAX` when unknown
+    total: AtomicUsize,
+    // the current phase and the last progress given to the callback
+    last: Mutex<Option<Progress>>,
+}
+
+impl<'b> Reporter<'b> {
+    pub fn new(callback: Option<&'b ProgressCallback>, cancel: Option<&'b AtomicBool>) -> Self {
+        Self {
+            callback,
+            cancel,
+            processed: AtomicUsize::new(0),
+            step: AtomicUsize::new(REPORT_EVERY),
+            total: AtomicUsize::new(usize::MAX),
+            last: Mutex::new(None),
+        }
+    }
+
+    /// Returns a reporter that never reports anything and can't be cancelled.
+    pub fn silent() -> Self {
+        Self::new(None, None)
+    }
+
+    /// Start a new phase.
+    pub fn phase(&self, phase: Phase, total: Option<usize>) -> Result<(), Error> {
+        self.check()?;
+        self.processed.store(0, Ordering::Relaxed);
+        let step = total.map_or(REPORT_EVERY, |total| total.div_ceil(100).max(1));
+        self.step.store(step, Ordering::Relaxed);
+        self.total
+            .store(total.unwrap_or(usize::MAX), Ordering::Relaxed);
+        let progress = Progress {
+            phase,
+            processed: 0,
+            total,
+        };
+        *self.lock() = Some(progress);
+        if let Some(callback) = self.callback {
+            (callback.0)(progress);
+        }
+        Ok(())
+    }
+
+    /// `count` more items of the current phase were processed.
+    pub fn advance(&self, count: usize) -> Result<(), Error> {
+        self.check()?;
+        let Some(callback) = self.callback else {
+            return Ok(());
+        };
+        let before = self.processed.fetch_add(count, Ordering::Relaxed);
+        let processed = before + count;
+        let step = self.step.load(Ordering::Relaxed);
+        let total = self.total.load(Ordering::Relaxed);
+        // we only lock when we crossed a step so the threads don't fight over it
+        if before / step == processed / step && processed != total {
+            return Ok(());
+        }
+
+        let mut last = self.lock();
+        let Some(progress) = last.as_mut() else {
+            return Ok(());
+        };
+        if processed > progress.processed {
+            progress.processed = processed;
+            (callback.0)(*progress);
+        }
+        Ok(())
+    }
+
+    /// Returns [`Error::Cancelled`] if the construction was cancelled.
+    pub fn check(&self) -> Result<(), Error> {
+        match self.cancel {
+            Some(cancel) if cancel.load(Ordering::Relaxed) => Err(Error::Cancelled),
+            _ => Ok(()),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Option<Progress>> {
+        self.last
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::IndexBuilder;
+
+    fn documents() -> Vec<String> {
+        (0..1000)
+            .map(|i| format!("document {i} about kefir{}", i % 13))
+            .collect()
+    }
+
+    #[test]
+    fn test_progress_only_increases() {
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let mut builder = IndexBuilder::new();
+        let cloned = reports.clone();
+        builder.with_progress(move |progress| cloned.lock().unwrap().push(progress));
+        builder.construct(&documents(), &mut Vec::new()).unwrap();
+
+        let reports = reports.lock().unwrap();
+        let mut phases: Vec<Phase> = reports.iter().map(|progress| progress.phase).collect();
+        phases.dedup();
+        assert_eq!(
+            phases,
+            [
+                Phase::Tokenizing,
+                Phase::Sorting,
+                Phase::BuildingFst,
+                Phase::WritingDocuments,
+                Phase::WritingBitmaps
+            ]
+        );
+
+        for phase in phases {
+            let reports: Vec<&Progress> = reports
+                .iter()
+                .filter(|progress| progress.phase == phase)
+                .collect();
+            assert_eq!(reports[0].processed, 0, "{phase:?}");
+            assert!(reports.len() <= 102, "{phase:?} {}", reports.len());
+            assert!(
+                reports
+                    .windows(2)
+                    .all(|window| window[0].processed < window[1].processed),
+                "{phase:?}"
+            );
+            let last = reports.last().unwrap();
+            assert_eq!(Some(last.processed), last.total, "{phase:?}");
+        }
+    }
+
+    #[test]
+    fn test_cancellation_stops_early() {
+        let cancel = Arc::new(AtomicBool::new(false));
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let mut builder = IndexBuilder::new();
+        let (cloned_cancel, cloned_reports) = (cancel.clone(), reports.clone());
+        builder
+            .with_cancellation(cancel.clone())
+            .with_progress(move |progress| {
+                if progress.phase == Phase::BuildingFst && progress.processed > 0 {
+                    cloned_cancel.store(true, Ordering::Relaxed);
+                }
+                cloned_reports.lock().unwrap().push(progress);
+            });
+
+        let mut bytes = Vec::new();
+        let error = builder.construct(&documents(), &mut bytes).unwrap_err();
+        assert!(matches!(error, Error::Cancelled), "{error}");
+        assert!(bytes.is_empty());
+        let last = *reports.lock().unwrap().last().unwrap();
+        assert_eq!(last.phase, Phase::BuildingFst);
+        assert!(last.total.is_some_and(|total| last.processed < total));
+
+        // cancelled before starting
+        let mut bytes = Vec::new();
+        let error = builder.construct_to(&mut bytes, documents()).unwrap_err();
+        assert!(matches!(error, Error::Cancelled), "{error}");
+        assert!(bytes.is_empty());
+    }
+}
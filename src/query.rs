@@ -0,0 +1,401 @@
+//! The syntax of the queries parsed by [`crate::Search::new`], see [`Query`] for the grammar.
+
+use std::fmt;
+
+/// The parsed query of a search, it can also be built directly with [`crate::Search::from_query`].
+///
+/// ```text
+/// query    = { whitespace } { item { whitespace } }
+/// item     = negation | phrase | term
+/// negation = "-" ( phrase | word )
+/// phrase   = '"' { any character but '"' } '"' [ weight ]
+/// term     = word [ weight ]
+/// weight   = "^" number
+/// word     = any characters but the whitespaces
+/// ```
+///
+/// - A term is looked for with typos and, if it's the last one, as the beginning of a word.
+/// - The words of a phrase are matched without typos and never as a prefix, `"le chien"`.
+/// - The documents containing a negated word are excluded, `-chat`. A negated phrase only excludes
+///   the documents containing all its words, `-"le chat"`.
+/// - The weight is a positive number given to the word ranking rule, `paris^2`, see [`crate::Search::with_word_weights`].
+///
+/// A query never fails to parse: an unclosed quote, an empty phrase or an invalid weight are plain text.
+/// [`crate::Search::parse_strict`] reports them instead, for the tools that want to tell the user.
+/// A lone `-` and the quotes in the middle of a word are always plain text.
+///
+/// ```
+/// use zearch::{Query, QueryItem, Search, Term};
+///
+/// let search = Search::new(r#"hotel^2 "saint malo" -cher"#);
+/// assert_eq!(
+///     search.query(),
+///     &Query {
+///         items: vec![
+///             QueryItem::Term(Term { text: String::from("hotel"), weight: Some(2.0) }),
+///             QueryItem::Phrase { words: vec![String::from("saint"), String::from("malo")], weight: None },
+///             QueryItem::Negation(String::from("cher")),
+///         ],
+///     }
+/// );
+/// assert_eq!(search.query().to_string(), r#"hotel^2 "saint malo" -cher"#);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Query {
+    pub items: Vec<QueryItem>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum QueryItem {
+    Term(Term),
+    /// Words matched without typos nor prefix, they share the weight.
+    Phrase {
+        words: Vec<String>,
+        weight: Option<f32>,
+    },
+    /// The documents containing all the words of the text are excluded.
+    Negation(String),
+}
+
+/// A word of the query as it was typed, the tokenizer can still split it like `l'avion`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Term {
+    pub text: String,
+    /// `None` to weigh 1 or the weight given by [`crate::Search::with_word_weights`].
+    pub weight: Option<f32>,
+}
+
+impl Term {
+    pub fn new(text: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            weight: None,
+        }
+    }
+}
+
+/// Returned by [`crate::Search::parse_strict`], see [`Query`] for the grammar.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[error("{kind} at byte {position}.")]
+pub struct ParseError {
+    /// The position in bytes of the offending character in the input.
+    pub position: usize,
+    pub kind: ParseErrorKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum ParseErrorKind {
+    #[error("The quote is never closed")]
+    UnclosedQuote,
+    #[error("The phrase doesn't contain any word")]
+    EmptyPhrase,
+    #[error("The weight must be a positive number")]
+    InvalidWeight,
+}
+
+impl Query {
+    /// Parse the query, the invalid syntax is plain text.
+    pub fn parse(input: &str) -> Self {
+        Parser {
+            input,
+            position: 0,
+            strict: false,
+        }
+        .parse()
+        .unwrap_or_default()
+    }
+
+    /// Parse the query and return the first invalid syntax.
+    pub fn parse_strict(input: &str) -> Result<Self, ParseError> {
+        Parser {
+            input,
+            position: 0,
+            strict: true,
+        }
+        .parse()
+    }
+
+    /// The items that are looked for in the documents, in their order: all but the negations.
+    pub(crate) fn positives(&self) -> impl Iterator<Item = &QueryItem> {
+        self.items
+            .iter()
+            .filter(|item| !matches!(item, QueryItem::Negation(_)))
+    }
+
+    /// The text of the negations.
+    pub(crate) fn negations(&self) -> impl Iterator<Item = &str> {
+        self.items.iter().filter_map(|item| match item {
+            QueryItem::Negation(text) => Some(text.as_str()),
+            _ => None,
+        })
+    }
+}
+
+struct Parser<'i> {
+    input: &'i str,
+    position: usize,
+    strict: bool,
+}
+
+impl<'i> Parser<'i> {
+    fn parse(mut self) -> Result<Query, ParseError> {
+        let mut items = Vec::new();
+        loop {
+            let rest = &self.input[self.position..];
+            self.position += rest.len() - rest.trim_start().len();
+            let rest = &self.input[self.position..];
+            if rest.is_empty() {
+                return Ok(Query { items });
+            }
+
+            if let Some(phrase) = rest.strip_prefix("-\"") {
+                if let Some(end) = phrase.find('"') {
+                    items.push(QueryItem::Negation(phrase[..end].to_string()));
+                    self.position += 2 + end + 1;
+                    continue;
+                }
+                self.fail(self.position + 1, ParseErrorKind::UnclosedQuote)?;
+            } else if rest.starts_with('"') && self.phrase(&mut items)? {
+                continue;
+            }
+
+            let word = self.word();
+            if let Some(text) = word.strip_prefix('-').filter(|text| !text.is_empty()) {
+                items.push(QueryItem::Negation(text.to_string()));
+                continue;
+            }
+            let (text, weight) = match word.rsplit_once('^') {
+                Some((text, weight)) => {
+                    let position = self.position - weight.len() - 1;
+                    match parse_weight(weight).filter(|_| !text.is_empty()) {
+                        Some(weight) => (text, Some(weight)),
+                        None => {
+                            self.fail(position, ParseErrorKind::InvalidWeight)?;
+                            (word, None)
+                        }
+                    }
+                }
+                None => (word, None),
+            };
+            items.push(QueryItem::Term(Term {
+                text: text.to_string(),
+                weight,
+            }));
+        }
+    }
+
+    /// Returns the error in strict mode, in the other mode the caller continues with the text as is.
+    fn fail(&self, position: usize, kind: ParseErrorKind) -> Result<(), ParseError> {
+        match self.strict {
+            true => Err(ParseError { position, kind }),
+            false => Ok(()),
+        }
+    }
+
+    /// Consume the characters until the next whitespace.
+    fn word(&mut self) -> &'i str {
+        let rest: &'i str = &self.input[self.position..];
+        let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        self.position += end;
+        &rest[..end]
+    }
+
+    /// Consume the phrase starting at the current position, returns `false` if the quote must be read as plain text.
+    /// An empty phrase is consumed without adding an item.
+    fn phrase(&mut self, items: &mut Vec<QueryItem>) -> Result<bool, ParseError> {
+        let start = self.position;
+        let Some(end) = self.input[start + 1..].find('"') else {
+            self.fail(start, ParseErrorKind::UnclosedQuote)?;
+            return Ok(false);
+        };
+        let words: Vec<String> = self.input[start + 1..start + 1 + end]
+            .split_whitespace()
+            .map(str::to_string)
+            .collect();
+        self.position = start + 1 + end + 1;
+
+        let mut weight = None;
+        if self.input[self.position..].starts_with('^') {
+            let position = self.position;
+            let rest = &self.input[position + 1..];
+            let len = rest
+                .find(|c: char| c.is_whitespace() || c == '"')
+                .unwrap_or(rest.len());
+            weight = parse_weight(&rest[..len]);
+            match weight {
+                Some(_) => self.position = position + 1 + len,
+                // the next item is read from the caret
+                None => self.fail(position, ParseErrorKind::InvalidWeight)?,
+            }
+        }
+
+        if words.is_empty() {
+            self.fail(start, ParseErrorKind::EmptyPhrase)?;
+        } else {
+            items.push(QueryItem::Phrase { words, weight });
+        }
+        Ok(true)
+    }
+}
+
+fn parse_weight(weight: &str) -> Option<f32> {
+    weight
+        .parse::<f32>()
+        .ok()
+        .filter(|weight| weight.is_finite() && *weight > 0.0)
+}
+
+impl fmt::Display for Query {
+    /// Write the query with the syntax of the module, parsing it gives back the same query
+    /// as long as the text of the terms doesn't contain whitespaces, quotes or a leading `-`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (position, item) in self.items.iter().enumerate() {
+            if position > 0 {
+                f.write_str(" ")?;
+            }
+            match item {
+                QueryItem::Term(term) => {
+                    f.write_str(&term.text)?;
+                    write_weight(f, term.weight)?;
+                }
+                QueryItem::Phrase { words, weight } => {
+                    write!(f, "\"{}\"", words.join(" "))?;
+                    write_weight(f, *weight)?;
+                }
+                QueryItem::Negation(text) if text.contains(char::is_whitespace) => {
+                    write!(f, "-\"{text}\"")?
+                }
+                QueryItem::Negation(text) => write!(f, "-{text}")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+fn write_weight(f: &mut fmt::Formatter<'_>, weight: Option<f32>) -> fmt::Result {
+    match weight {
+        Some(weight) => write!(f, "^{weight}"),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        insta::assert_debug_snapshot!(Query::parse(r#"  kefir^2	"le  chien" -chat -"petit chat" "#), @r###"
+        Query {
+            items: [
+                Term(
+                    Term {
+                        text: "kefir",
+                        weight: Some(
+                            2.0,
+                        ),
+                    },
+                ),
+                Phrase {
+                    words: [
+                        "le",
+                        "chien",
+                    ],
+                    weight: None,
+                },
+                Negation(
+                    "chat",
+                ),
+                Negation(
+                    "petit chat",
+                ),
+            ],
+        }
+        "###);
+        // the whitespaces and the items written without a whitespace between them
+        insta::assert_debug_snapshot!(Query::parse(r#""le chien"kefir abc"def - "#), @r###"
+        Query {
+            items: [
+                Phrase {
+                    words: [
+                        "le",
+                        "chien",
+                    ],
+                    weight: None,
+                },
+                Term(
+                    Term {
+                        text: "kefir",
+                        weight: None,
+                    },
+                ),
+                Term(
+                    Term {
+                        text: "abc\"def",
+                        weight: None,
+                    },
+                ),
+                Term(
+                    Term {
+                        text: "-",
+                        weight: None,
+                    },
+                ),
+            ],
+        }
+        "###);
+        insta::assert_debug_snapshot!(Query::parse(""), @r###"
+        Query {
+            items: [],
+        }
+        "###);
+    }
+
+    #[test]
+    fn test_invalid_syntax() {
+        let inputs = [
+            r#"le "petit chien"#,
+            r#"le -"petit chien"#,
+            r#"le "" chien"#,
+            r#"kefir^ chien"#,
+            r#"kefir^-1"#,
+            r#"kefir^0"#,
+            r#"^2"#,
+            r#""le chien"^abc kefir"#,
+        ];
+        let results: Vec<String> = inputs
+            .iter()
+            .map(|input| {
+                let error = Query::parse_strict(input).unwrap_err();
+                format!("{input:<24} {error} {}", Query::parse(input))
+            })
+            .collect();
+        // the strict parser reports the error, the other one reads the syntax as plain text
+        insta::assert_debug_snapshot!(results, @r###"
+        [
+            "le \"petit chien          The quote is never closed at byte 3. le \"petit chien",
+            "le -\"petit chien         The quote is never closed at byte 4. le -\"petit chien",
+            "le \"\" chien              The phrase doesn't contain any word at byte 3. le chien",
+            "kefir^ chien             The weight must be a positive number at byte 5. kefir^ chien",
+            "kefir^-1                 The weight must be a positive number at byte 5. kefir^-1",
+            "kefir^0                  The weight must be a positive number at byte 5. kefir^0",
+            "^2                       The weight must be a positive number at byte 0. ^2",
+            "\"le chien\"^abc kefir     The weight must be a positive number at byte 10. \"le chien\" ^abc kefir",
+        ]
+        "###);
+    }
+
+    #[test]
+    fn test_display() {
+        for input in [
+            "kefir",
+            "kefir^2.5 le chien",
+            r#""le chien"^3 -chat -"petit chat""#,
+            r#"l'avion c'est-a-dire"#,
+        ] {
+            let query = Query::parse_strict(input).unwrap();
+            assert_eq!(query.to_string(), input);
+            assert_eq!(Query::parse_strict(&query.to_string()).unwrap(), query);
+        }
+    }
+}
@@ -0,0 +1,84 @@
+//! The attribute ranking rule ranks up the documents matching the query in their first fields,
+//! a match on the name of a place comes before a match on one of its aliases.
+//! A document is put in the bucket of the first field containing one of the words of the query.
+//! If the index was constructed without fields every document ends up in the same bucket.
+use std::ops::ControlFlow;
+
+use roaring::RoaringBitmap;
+
+use crate::{Index, WordCandidate};
+
+use super::{previous_results, Buckets, RankingRuleImpl};
+
+pub struct Attribute {
+    buckets: Buckets,
+}
+
+impl Attribute {
+    pub fn new() -> Self {
+        Self {
+            buckets: Buckets::default(),
+        }
+    }
+}
+
+impl RankingRuleImpl for Attribute {
+    fn name(&self) -> &'static str {
+        "attribute"
+    }
+
+    fn next(
+        &mut self,
+        prev: Option<&dyn RankingRuleImpl>,
+        words: &mut Vec<WordCandidate>,
+        index: &Index,
+    ) -> ControlFlow<RoaringBitmap, ()> {
+        if self.buckets.is_exhausted() {
+            let mut remaining = previous_results(prev, words);
+
+            match &index.fields {
+                Some(fields) => {
+                    let mut buckets = Vec::with_capacity(fields.count + 1);
+                    for field in 0..fields.count {
+                        let mut bucket = RoaringBitmap::new();
+                        for id in words.iter().flat_map(|word| word.word_ids.iter()) {
+                            if let Some(documents) = fields.get(id, field) {
+                                bucket |= documents & &remaining;
+                            }
+                        }
+                        remaining -= &bucket;
+                        buckets.push(bucket);
+                    }
+                    // every document contains one of the matched words, but we never want to lose a document
+                    buckets.push(remaining);
+                    self.buckets.fill(buckets);
+                }
+                None => self.buckets.fill([remaining]),
+            }
+        }
+
+        self.buckets.next()
+    }
+
+    fn current_results(&self, _words: &[WordCandidate]) -> RoaringBitmap {
+        self.buckets.current()
+    }
+
+    fn explain(&self, _words: &[WordCandidate], index: &Index) -> String {
+        match self.buckets.position() {
+            Some(_) if index.fields.is_none() => String::from("no fields"),
+            Some(field) => format!("field {field}"),
+            None => String::new(),
+        }
+    }
+
+    /// The field, the documents without fields come last.
+    fn rank(&self, _words: &[WordCandidate], index: &Index) -> Option<(usize, usize)> {
+        let fields = index.fields.as_ref()?;
+        Some((self.buckets.position()?, fields.count))
+    }
+
+    fn cleanup(&mut self, used: &RoaringBitmap) {
+        self.buckets.cleanup(used);
+    }
+}
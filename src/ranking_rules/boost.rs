@@ -0,0 +1,89 @@
+//! The boost ranking rule ranks up the documents that were given a larger boost
+//! when constructing the index, see [`crate::IndexBuilder::with_boosts`].
+//! All the documents sharing the same boost are returned in the same bucket.
+//! If the index has no boosts every document ends up in the same bucket.
+use std::ops::ControlFlow;
+
+use roaring::RoaringBitmap;
+
+use crate::{Index, WordCandidate};
+
+use super::{previous_results, Buckets, RankingRuleImpl};
+
+pub struct Boost {
+    buckets: Buckets,
+    // the boost of every bucket
+    boosts: Vec<f32>,
+}
+
+impl Boost {
+    pub fn new() -> Self {
+        Self {
+            buckets: Buckets::default(),
+            boosts: Vec::new(),
+        }
+    }
+}
+
+impl RankingRuleImpl for Boost {
+    fn name(&self) -> &'static str {
+        "boost"
+    }
+
+    fn next(
+        &mut self,
+        prev: Option<&dyn RankingRuleImpl>,
+        words: &mut Vec<WordCandidate>,
+        index: &Index,
+    ) -> ControlFlow<RoaringBitmap, ()> {
+        if self.buckets.is_exhausted() {
+            let current = previous_results(prev, words);
+            self.boosts.clear();
+
+            match &index.boosts {
+                Some(boosts) => {
+                    let mut ids: Vec<u32> = current.iter().collect();
+                    ids.sort_by(|left, right| {
+                        boosts[*right as usize].total_cmp(&boosts[*left as usize])
+                    });
+                    let buckets = ids
+                        .chunk_by(|left, right| boosts[*left as usize] == boosts[*right as usize]);
+                    let buckets: Vec<RoaringBitmap> = buckets
+                        .map(|bucket| {
+                            self.boosts.push(boosts[bucket[0] as usize]);
+                            bucket.iter().copied().collect()
+                        })
+                        .collect();
+                    self.buckets.fill(buckets);
+                }
+                None => self.buckets.fill([current]),
+            }
+        }
+
+        self.buckets.next()
+    }
+
+    fn current_results(&self, _words: &[WordCandidate]) -> RoaringBitmap {
+        self.buckets.current()
+    }
+
+    fn explain(&self, _words: &[WordCandidate], _index: &Index) -> String {
+        match self.buckets.position() {
+            Some(position) => match self.boosts.get(position) {
+                Some(boost) => format!("boost {boost}"),
+                None => String::from("no boosts"),
+            },
+            None => String::new(),
+        }
+    }
+
+    /// The opposite of the boost, the largest boost first.
+    fn sort_value(&self, _words: &[WordCandidate], _index: &Index) -> Option<f64> {
+        let boost = self.boosts.get(self.buckets.position()?)?;
+        Some(-*boost as f64)
+    }
+
+    fn cleanup(&mut self, used: &RoaringBitmap) {
+        self.buckets.cleanup(used);
+    }
+}
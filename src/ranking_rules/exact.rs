@@ -1,34 +1,151 @@
 //! The role of the exact ranking rule is to come back
 //! over all the results we're going to return and rank
-//! up the one that have 0 or almost no typos on their **original string**.
-//! Using the original string is way slower than going through the fst
-//! but it also greatly improve the user experience since typing a name
-//! with an accent and getting the misspeled version first make you want
-//! to kill someone for example.
+//! up the one that have 0 or almost no typos with the words **as they were typed**.
+//! Instead of going through the text of every document we compare the normalized words of the query
+//! with the words of the fst they matched, which are recorded in the candidates,
+//! and then use the bitmap of these words to find the documents.
+//! Thus the case and the accents don't count as typos, they only break the ties of the documents without typos:
+//! the ones containing the words byte for byte as they were typed come first, `Épinal` before `EPINAL`.
 //! Since it's the last ranking rule, its bucket shouldn't be that big
-//! thus it's not a problem to spend a lot of time going through all
-//! the IDs of the previous ranking rule.
+//! thus it's not a problem to decode the bitmap of all the matched words.
+//! When it's not the case the bucket is returned as is, see [`crate::Search::with_max_exact_documents`].
 use std::ops::ControlFlow;
 
-use roaring::RoaringBitmap;
-use text_distance::DamerauLevenshtein;
+use crate::{distance::DamerauLevenshtein, Index, Tokenizer, WordCandidate};
+use roaring::{MultiOps, RoaringBitmap};
 
-use crate::{Index, WordCandidate};
-
-use super::RankingRuleImpl;
+use super::{previous_results, Buckets, RankingRuleImpl};
 
 pub struct Exact {
-    buckets: Vec<RoaringBitmap>,
+    buckets: Buckets,
+    distance: DamerauLevenshtein,
+    max_documents: u64,
+    // the current bucket was too big to be sorted
+    skipped: bool,
+    // the index of the words a document must still contain to be byte exact, see `Exact::is_byte_exact`
+    pending: Vec<usize>,
 }
 
 impl Exact {
-    pub fn new() -> Self {
+    pub fn new(max_documents: usize) -> Self {
         Self {
-            buckets: Vec::new(),
+            buckets: Buckets::default(),
+            distance: DamerauLevenshtein::default(),
+            max_documents: max_documents as u64,
+            skipped: false,
+            pending: Vec::new(),
+        }
+    }
+
+    /// Returns the documents of `current` containing the word, by distance to the normalized word.
+    fn distances(
+        &mut self,
+        word: &WordCandidate,
+        current: &RoaringBitmap,
+        index: &Index,
+    ) -> [RoaringBitmap; 4] {
+        let mut distances: [RoaringBitmap; 4] = Default::default();
+        let normalized = &word.normalized;
+        for (matched, _, id) in word.matches.iter() {
+            // a prefix is compared to the whole word, `kef` is closer to `kefir` than to `kefirounet`
+            let mut end = matched.len().min(normalized.len());
+            while !normalized.is_char_boundary(end) {
+                end -= 1;
+            }
+            let distance = self.distance.distance(&normalized[..end], matched).min(3);
+            distances[distance] |= &*index.bitmaps.get(*id) & current;
+        }
+
+        // the documents whose words weren't recorded keep their number of typos
+        let mut seen: RoaringBitmap = distances.iter().union();
+        for (typos, docs) in word.typos.iter().enumerate() {
+            let docs = (docs & current) - &seen;
+            seen |= &docs;
+            distances[typos.min(3)] |= docs;
+        }
+        distances
+    }
+
+    /// Returns true if the document contains all the words of the query it matched without typos
+    /// byte for byte as they were typed, or as the beginning of one of its words.
+    /// `originals` are the words of the query without their punctuation, the document is tokenized once.
+    fn is_byte_exact(
+        &mut self,
+        originals: &[&str],
+        distances: &[[RoaringBitmap; 4]],
+        id: u32,
+        index: &Index,
+    ) -> bool {
+        let Some(document) = index.get_document(id) else {
+            return false;
+        };
+        self.pending.clear();
+        self.pending
+            .extend((0..originals.len()).filter(|word| distances[*word][0].contains(id)));
+        for token in index.tokenizer.tokenize(document) {
+            if self.pending.is_empty() {
+                break;
+            }
+            let token = trim_punctuation(token.text);
+            self.pending
+                .retain(|word| !token.starts_with(originals[*word]));
+        }
+        self.pending.is_empty()
+    }
+
+    /// Split the documents by the distance of their words to the query, see [`Index::search`]
+    /// for the single word queries which don't go through [`Exact::next`].
+    /// The documents without typos are split again between the ones containing the words as they were typed
+    /// and the others, there are 5 buckets. The bucket is returned as is when it's too big.
+    pub(crate) fn split(
+        &mut self,
+        words: &[WordCandidate],
+        current: RoaringBitmap,
+        index: &Index,
+    ) -> Vec<RoaringBitmap> {
+        self.skipped = current.len() > self.max_documents;
+        if self.skipped {
+            return vec![current];
         }
+        let distances: Vec<[RoaringBitmap; 4]> = words
+            .iter()
+            .map(|word| self.distances(word, &current, index))
+            .collect();
+        let originals: Vec<&str> = words
+            .iter()
+            .map(|word| trim_punctuation(&word.original))
+            .collect();
+
+        // the byte exact documents come before the 4 distances
+        let mut buckets = vec![RoaringBitmap::new(); 5];
+        for id in current.iter() {
+            let mut distance = 0;
+            // a word that the document doesn't contain isn't our business
+            for distances in distances.iter() {
+                distance += distances
+                    .iter()
+                    .position(|docs| docs.contains(id))
+                    .unwrap_or(0);
+                // it's the last bucket anyway
+                if distance >= 3 {
+                    break;
+                }
+            }
+            if distance == 0 && self.is_byte_exact(&originals, &distances, id, index) {
+                buckets[0].insert(id);
+            } else {
+                buckets[distance.min(3) + 1].insert(id);
+            }
+        }
+        buckets
     }
 }
 
+/// The punctuation around the words is ignored when they're compared byte for byte.
+fn trim_punctuation(word: &str) -> &str {
+    word.trim_matches(|c: char| !c.is_alphanumeric())
+}
+
 impl RankingRuleImpl for Exact {
     fn name(&self) -> &'static str {
         "exact"
@@ -40,64 +157,37 @@ impl RankingRuleImpl for Exact {
         words: &mut Vec<WordCandidate>,
         index: &Index,
     ) -> ControlFlow<RoaringBitmap, ()> {
-        // We're the last ranking rule, we should always break
-
-        if self.buckets.is_empty() {
-            let current = prev.unwrap().current_results(words);
-            let mut words: Vec<&WordCandidate> = words.iter().collect();
-
-            words.sort_by_key(|word| word.index);
-
-            // we won't generate more than 4 buckets
-            self.buckets = vec![RoaringBitmap::new(); 4];
-
-            for id in current.iter() {
-                let mut distance = 0;
-
-                let mut words = words.iter().peekable();
-                for (id, word) in index.documents[id as usize].split_whitespace().enumerate() {
-                    match words.peek() {
-                        Some(WordCandidate {
-                            original, index, ..
-                        }) if *index == id => {
-                            distance += DamerauLevenshtein {
-                                // Since we're merging two string it's possible that the smallest one falls in the middle of a character of the second one
-                                src: String::from_utf8_lossy(
-                                    &original.as_bytes()[0..word.len().min(original.len())],
-                                )
-                                .into_owned(),
-                                tar: word.to_string(),
-                                restricted: true,
-                            }
-                            .distance();
-                        }
-                        // we're not looking at the same word
-                        Some(_) => continue,
-                        None => break,
-                    }
-                }
-
-                let idx = distance.min(3);
-                self.buckets[idx].insert(id);
-            }
-            self.buckets.retain(|bucket| !bucket.is_empty());
-            self.buckets.reverse();
+        if self.buckets.is_exhausted() {
+            let current = previous_results(prev, words);
+            let buckets = self.split(words, current, index);
+            self.buckets.fill(buckets);
         }
 
-        match self.buckets.pop() {
-            Some(bucket) => ControlFlow::Break(bucket),
-            // we have nothing to return and the previous ranking rule doesn't either
-            None => ControlFlow::Break(RoaringBitmap::new()),
-        }
+        self.buckets.next()
     }
 
     fn current_results(&self, _words: &[WordCandidate]) -> RoaringBitmap {
-        self.buckets.first().cloned().unwrap_or_default()
+        self.buckets.current()
     }
 
-    fn cleanup(&mut self, used: &RoaringBitmap) {
-        for bucket in self.buckets.iter_mut() {
-            *bucket -= used;
+    fn explain(&self, _words: &[WordCandidate], _index: &Index) -> String {
+        match self.buckets.position() {
+            Some(_) if self.skipped => String::from("too many documents"),
+            Some(0) => String::from("byte exact"),
+            Some(position) => format!("distance {}", position - 1),
+            None => String::new(),
         }
     }
+
+    /// The distance, there are 5 buckets with the byte exact one.
+    fn rank(&self, _words: &[WordCandidate], _index: &Index) -> Option<(usize, usize)> {
+        self.buckets
+            .position()
+            .filter(|_| !self.skipped)
+            .map(|position| (position, 4))
+    }
+
+    fn cleanup(&mut self, used: &RoaringBitmap) {
+        self.buckets.cleanup(used);
+    }
 }
@@ -0,0 +1,105 @@
+//! The geo ranking rule returns the documents nearest to a point first, see [`crate::Search::with_sort_by_distance`].
+//! It's always the last ranking rule thus every document is returned in its own bucket,
+//! except the documents without coordinates which are returned last in a single bucket.
+use std::ops::ControlFlow;
+
+use roaring::RoaringBitmap;
+
+use crate::{Index, WordCandidate};
+
+use super::{previous_results, Buckets, RankingRuleImpl};
+
+/// The mean radius of the Earth in meters.
+const EARTH_RADIUS: f64 = 6_371_008.8;
+
+/// Returns the distance in meters between two points given by their latitude and longitude in degrees.
+pub(crate) fn haversine((lat1, lng1): (f32, f32), (lat2, lng2): (f32, f32)) -> f64 {
+    let (lat1, lng1) = ((lat1 as f64).to_radians(), (lng1 as f64).to_radians());
+    let (lat2, lng2) = ((lat2 as f64).to_radians(), (lng2 as f64).to_radians());
+    let a = ((lat2 - lat1) / 2.).sin().powi(2)
+        + lat1.cos() * lat2.cos() * ((lng2 - lng1) / 2.).sin().powi(2);
+    2. * EARTH_RADIUS * a.sqrt().asin()
+}
+
+pub struct Geo {
+    point: (f32, f32),
+    buckets: Buckets,
+    // the distance of every bucket, the documents without coordinates don't have one
+    distances: Vec<f64>,
+}
+
+impl Geo {
+    pub fn new(point: (f32, f32)) -> Self {
+        Self {
+            point,
+            buckets: Buckets::default(),
+            distances: Vec::new(),
+        }
+    }
+}
+
+impl RankingRuleImpl for Geo {
+    fn name(&self) -> &'static str {
+        "geo"
+    }
+
+    fn next(
+        &mut self,
+        prev: Option<&dyn RankingRuleImpl>,
+        words: &mut Vec<WordCandidate>,
+        index: &Index,
+    ) -> ControlFlow<RoaringBitmap, ()> {
+        if self.buckets.is_exhausted() {
+            let current = previous_results(prev, words);
+            let mut located = Vec::new();
+            let mut unknown = RoaringBitmap::new();
+            for id in current.iter() {
+                match index.get_coordinates(id) {
+                    Some(coordinates) => located.push((haversine(self.point, coordinates), id)),
+                    None => {
+                        unknown.insert(id);
+                    }
+                }
+            }
+            located.sort_by(|(left, _), (right, _)| left.total_cmp(right));
+
+            self.distances = located.iter().map(|(distance, _)| *distance).collect();
+            let buckets = located
+                .into_iter()
+                .map(|(_, id)| RoaringBitmap::from_iter([id]))
+                .chain([unknown]);
+            self.buckets.fill(buckets);
+        }
+
+        self.buckets.next()
+    }
+
+    fn current_results(&self, _words: &[WordCandidate]) -> RoaringBitmap {
+        self.buckets.current()
+    }
+
+    fn explain(&self, _words: &[WordCandidate], _index: &Index) -> String {
+        match self.buckets.position() {
+            Some(position) => match self.distances.get(position) {
+                Some(distance) => format!("{distance:.0}m"),
+                None => String::from("no coordinates"),
+            },
+            None => String::new(),
+        }
+    }
+
+    /// The distance in meters, the documents without coordinates come last.
+    fn sort_value(&self, _words: &[WordCandidate], _index: &Index) -> Option<f64> {
+        let position = self.buckets.position()?;
+        Some(
+            self.distances
+                .get(position)
+                .copied()
+                .unwrap_or(f64::INFINITY),
+        )
+    }
+
+    fn cleanup(&mut self, used: &RoaringBitmap) {
+        self.buckets.cleanup(used);
+    }
+}
@@ -1,29 +1,251 @@
-use std::ops::ControlFlow;
+use std::{
+    fmt,
+    hash::{Hash, Hasher},
+    ops::{ControlFlow, Range},
+    sync::Arc,
+};
 
-use roaring::RoaringBitmap;
+use roaring::{MultiOps, RoaringBitmap};
 
 use crate::{Index, WordCandidate};
 
+pub mod attribute;
+pub mod boost;
 pub mod exact;
+pub mod geo;
+pub mod position;
+pub mod proximity;
+pub mod sort;
 pub mod typo;
 pub mod word;
+pub mod word_count;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
 pub enum RankingRule {
     Word,
     Typo,
     Exact,
+    /// Requires the index to be constructed with the positions, see [`crate::IndexBuilder::with_positions`].
+    Proximity,
+    /// Rank up the documents matching the query in their first fields,
+    /// requires the index to be constructed with fields, see [`crate::Index::construct_with_fields`].
+    Attribute,
+    /// Rank up the documents with the largest boost, see [`crate::IndexBuilder::with_boosts`].
+    Boost,
+    /// Rank up the documents matching the query in their first words, whatever the position of the other words.
+    /// Requires the index to be constructed with the positions, see [`crate::IndexBuilder::with_positions`].
+    Position,
+    /// Rank up the shortest documents, the ones with the fewest words.
+    /// Requires the index to be constructed with the word counts, see [`crate::IndexBuilder::with_word_counts`].
+    WordCount,
+    /// Sort the documents by their payload, see [`crate::Index::construct_with_payloads`].
+    Sort {
+        descending: bool,
+    },
+    /// Your own ranking rule, see [`RankingRule::custom`].
+    /// It can't be serialized, the code creating it is not part of the configuration.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    Custom(CustomRankingRule),
 }
 
+impl RankingRule {
+    /// Since a ranking rule keeps a state while a search is running, a new one
+    /// is created by calling `new_rule` at the beginning of every search.
+    pub fn custom<R: RankingRuleImpl + 'static>(
+        new_rule: impl Fn() -> R + Send + Sync + 'static,
+    ) -> Self {
+        RankingRule::Custom(CustomRankingRule(Arc::new(move || Box::new(new_rule()))))
+    }
+}
+
+/// Creates the user defined ranking rules, two of them are equal only if they're clones of each other.
+#[derive(Clone)]
+pub struct CustomRankingRule(Arc<dyn Fn() -> Box<dyn RankingRuleImpl> + Send + Sync>);
+
+impl CustomRankingRule {
+    pub(crate) fn build(&self) -> Box<dyn RankingRuleImpl> {
+        (self.0)()
+    }
+}
+
+impl fmt::Debug for CustomRankingRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("CustomRankingRule")
+    }
+}
+
+impl PartialEq for CustomRankingRule {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for CustomRankingRule {}
+
+impl Hash for CustomRankingRule {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        Arc::as_ptr(&self.0).cast::<()>().hash(state);
+    }
+}
+
+/// The order of the documents inside a bucket, once the ranking rules can't tell them apart.
+/// See [`crate::Search::with_tie_break`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum TieBreak {
+    /// The smallest document id first.
+    #[default]
+    DocIdAsc,
+    /// The biggest document id first.
+    DocIdDesc,
+    /// The document added first to the index first. The ids are given in the order the documents
+    /// are added and [`crate::Index::compact`] keeps it, thus it's the same as [`TieBreak::DocIdAsc`] today.
+    InsertionOrder,
+}
+
+impl TieBreak {
+    /// Iterate over the documents of a bucket in this order.
+    pub(crate) fn iter(self, bucket: &RoaringBitmap) -> TieBreakIter<'_> {
+        match self {
+            TieBreak::DocIdAsc | TieBreak::InsertionOrder => TieBreakIter::Asc(bucket.iter()),
+            TieBreak::DocIdDesc => TieBreakIter::Desc(bucket.iter().rev()),
+        }
+    }
+
+    /// Keep the first `len` documents of the bucket in this order.
+    pub(crate) fn truncate(self, bucket: &mut RoaringBitmap, len: u64) {
+        let extra = bucket.len().saturating_sub(len);
+        match self {
+            TieBreak::DocIdAsc | TieBreak::InsertionOrder => bucket.remove_biggest(extra),
+            TieBreak::DocIdDesc => bucket.remove_smallest(extra),
+        }
+    }
+}
+
+/// Returned by [`TieBreak::iter`].
+pub(crate) enum TieBreakIter<'a> {
+    Asc(roaring::bitmap::Iter<'a>),
+    Desc(std::iter::Rev<roaring::bitmap::Iter<'a>>),
+}
+
+impl Iterator for TieBreakIter<'_> {
+    type Item = u32;
+
+    fn next(&mut self) -> Option<u32> {
+        match self {
+            TieBreakIter::Asc(iter) => iter.next(),
+            TieBreakIter::Desc(iter) => iter.next(),
+        }
+    }
+}
+
+/// The bucket sort calls the ranking rules one after the other, every ranking rule
+/// refines the results of the previous one by splitting them into smaller buckets.
+///
+/// Here is a ranking rule that returns the results of the previous ranking rule
+/// one document at a time, the biggest id first:
+/// ```
+/// use std::ops::ControlFlow;
+///
+/// use roaring::RoaringBitmap;
+/// use zearch::{Index, RankingRule, RankingRuleImpl, Search, WordCandidate};
+///
+/// #[derive(Default)]
+/// struct Reverse {
+///     remaining: Option<Vec<u32>>,
+///     current: RoaringBitmap,
+/// }
+///
+/// impl RankingRuleImpl for Reverse {
+///     fn name(&self) -> &'static str {
+///         "reverse"
+///     }
+///
+///     fn next(
+///         &mut self,
+///         prev: Option<&dyn RankingRuleImpl>,
+///         words: &mut Vec<WordCandidate>,
+///         _index: &Index,
+///     ) -> ControlFlow<RoaringBitmap, ()> {
+///         let remaining = self.remaining.get_or_insert_with(|| {
+///             let prev = prev.expect("must be called after another ranking rule");
+///             prev.current_results(words).iter().collect()
+///         });
+///         match remaining.pop() {
+///             Some(id) => {
+///                 self.current = RoaringBitmap::from_iter([id]);
+///                 ControlFlow::Continue(())
+///             }
+///             None => {
+///                 self.remaining = None;
+///                 ControlFlow::Break(RoaringBitmap::new())
+///             }
+///         }
+///     }
+///
+///     fn current_results(&self, _words: &[WordCandidate]) -> RoaringBitmap {
+///         self.current.clone()
+///     }
+///
+///     fn cleanup(&mut self, used: &RoaringBitmap) {
+///         if let Some(remaining) = self.remaining.as_mut() {
+///             remaining.retain(|id| !used.contains(*id));
+///         }
+///     }
+/// }
+///
+/// let index = Index::new_in_memory(&["kefir", "kefir le chien", "le kefir"]).unwrap();
+/// let mut search = Search::new("kefir");
+/// search.with_ranking_rules(vec![RankingRule::Word, RankingRule::custom(Reverse::default)]);
+/// assert_eq!(index.search(&search), vec![2, 1, 0]);
+/// ```
 pub trait RankingRuleImpl {
-    /// For debugging/logging purposes
-    #[allow(unused)]
+    /// For debugging/logging purposes, see [`Index::search_explain`].
     fn name(&self) -> &'static str;
 
-    /// 1. Do your shit with the words candidates
-    /// 2. Let me know if I should pass the word candidates to the next ranking rules:
-    ///    - ControlFlow::Continue(()) means yes
-    ///    - ControlFlow::Break(_) means no and I should insert your results to the bucket sort + call you again
+    /// Describes the bucket you're currently returning, see [`Index::search_explain`].
+    fn explain(&self, _words: &[WordCandidate], _index: &Index) -> String {
+        String::new()
+    }
+
+    /// The position of the bucket you're currently returning and the position of the worst bucket you could return,
+    /// `None` if your buckets can't be compared between two searches. Used to compute the score of the documents,
+    /// see [`crate::SearchResults::scores`].
+    fn rank(&self, _words: &[WordCandidate], _index: &Index) -> Option<(usize, usize)> {
+        None
+    }
+
+    /// The part of the score range between 0 and 1 taken by the bucket you're currently returning.
+    /// By default the range is split evenly between the buckets given by [`RankingRuleImpl::rank`],
+    /// the ranges of the buckets must not overlap and the best bucket must take the highest one.
+    fn score_range(&self, words: &[WordCandidate], index: &Index) -> Option<Range<f64>> {
+        let (rank, max) = self.rank(words, index)?;
+        let size = 1.0 / (max + 1) as f64;
+        let start = (max - rank.min(max)) as f64 * size;
+        Some(start..start + size)
+    }
+
+    /// The value shared by the documents of the bucket you're currently returning, the best bucket having the smallest one,
+    /// `None` if it can't be compared with the buckets of another index. Used to merge the buckets of the shards,
+    /// see [`crate::ShardedIndex`]. By default it's the position given by [`RankingRuleImpl::rank`].
+    fn sort_value(&self, words: &[WordCandidate], index: &Index) -> Option<f64> {
+        self.rank(words, index).map(|(rank, _)| rank as f64)
+    }
+
+    /// 1. Refine the results with the words candidates, the previous ranking rule is `None` if you're the first one.
+    /// 2. Let the bucket sort know if it should pass the word candidates to the next ranking rule:
+    ///    - `ControlFlow::Continue(())` means yes, it'll then use your `current_results`
+    ///    - `ControlFlow::Break(bucket)` means no, the bucket is inserted in the results and you'll be called again.
+    ///      Returning an empty bucket means you have nothing left to return and the previous ranking rule must move on.
     fn next(
         &mut self,
         prev: Option<&dyn RankingRuleImpl>,
@@ -31,10 +253,107 @@ pub trait RankingRuleImpl {
         index: &Index,
     ) -> ControlFlow<RoaringBitmap, ()>;
 
-    /// Can be called if you returned a `Continue` right before, but there is no ranking rules after you
-    /// so we're simply going to insert your results in the bucket sort and call you again.
+    /// Can be called if you returned a `Continue` right before, by the next ranking rule or if there
+    /// is no ranking rules after you, then we're simply going to insert your results in the bucket sort and call you again.
     fn current_results(&self, words: &[WordCandidate]) -> RoaringBitmap;
 
     /// If your ranking rule uses any kind of caches then it should remove the `used` elements from it.
     fn cleanup(&mut self, _used: &RoaringBitmap) {}
 }
+
+/// Returns the results of the previous ranking rule or all the documents
+/// matching every words if there is no previous ranking rule.
+pub(crate) fn previous_results(
+    prev: Option<&dyn RankingRuleImpl>,
+    words: &[WordCandidate],
+) -> RoaringBitmap {
+    match prev {
+        Some(prev) => prev.current_results(words),
+        None => words
+            .iter()
+            .map(|word| word.typos.as_slice().union())
+            .intersection(),
+    }
+}
+
+/// Returns the score between 0 and 1 of the current bucket of the ranking rules.
+/// Like the buckets, the ranks are compared in the order of the ranking rules: every ranking rule
+/// splits the score range of the previous one in as many parts as it has buckets.
+pub(crate) fn score(
+    ranking_rules: &[Box<dyn RankingRuleImpl>],
+    words: &[WordCandidate],
+    index: &Index,
+) -> f32 {
+    let mut score = 0.0;
+    // the score range of the current bucket
+    let mut range = 1.0;
+    for bucket in ranking_rules
+        .iter()
+        .filter_map(|rr| rr.score_range(words, index))
+    {
+        score += range * bucket.start;
+        range *= bucket.end - bucket.start;
+    }
+    (score + range) as f32
+}
+
+/// Used by the ranking rules that split the results of the previous ranking rule into
+/// multiple buckets and return them one by one.
+#[derive(Default)]
+pub(crate) struct Buckets {
+    // the buckets we still have to return with their position in the original buckets, the best one is last
+    remaining: Vec<(usize, RoaringBitmap)>,
+    // the bucket we're currently returning, `None` if we need to fetch new results from the previous ranking rule
+    current: Option<(usize, RoaringBitmap)>,
+}
+
+impl Buckets {
+    /// Returns `true` if all the buckets were returned and new ones must be computed.
+    pub fn is_exhausted(&self) -> bool {
+        self.current.is_none()
+    }
+
+    /// The buckets must be sorted from the best to the worst one, the empty ones are skipped.
+    pub fn fill(&mut self, buckets: impl IntoIterator<Item = RoaringBitmap>) {
+        self.remaining = buckets
+            .into_iter()
+            .enumerate()
+            .filter(|(_, bucket)| !bucket.is_empty())
+            .collect();
+        self.remaining.reverse();
+    }
+
+    /// Moves to the next bucket and returns `Continue`, or `Break` with an empty
+    /// bucket once they have all been returned.
+    pub fn next(&mut self) -> ControlFlow<RoaringBitmap, ()> {
+        match self.remaining.pop() {
+            Some(bucket) => {
+                self.current = Some(bucket);
+                ControlFlow::Continue(())
+            }
+            None => {
+                // we can reset ourselves, if we're called again it'll be from the previous ranking rule
+                self.current = None;
+                ControlFlow::Break(RoaringBitmap::new())
+            }
+        }
+    }
+
+    pub fn current(&self) -> RoaringBitmap {
+        self.current
+            .as_ref()
+            .map(|(_, bucket)| bucket.clone())
+            .unwrap_or_default()
+    }
+
+    /// The position of the current bucket in the buckets given to [`Buckets::fill`], empty ones included.
+    pub fn position(&self) -> Option<usize> {
+        self.current.as_ref().map(|(position, _)| *position)
+    }
+
+    pub fn cleanup(&mut self, used: &RoaringBitmap) {
+        for (_, bucket) in self.remaining.iter_mut().chain(self.current.as_mut()) {
+            *bucket -= used;
+        }
+    }
+}
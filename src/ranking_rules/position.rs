@@ -0,0 +1,90 @@
+//! The position ranking rule ranks up the documents where the query matches one of their first words,
+//! "Paris, France" should come before "Hôtel de la Gare de Paris" for `paris`.
+//! A document is put in the bucket of the first of its words matching any word of the query,
+//! the stop words are not counted and the words after the 255th one share the last bucket.
+//! It requires the positions to be stored in the index, if they're missing every document ends up in the same bucket.
+use std::ops::ControlFlow;
+
+use roaring::RoaringBitmap;
+
+use crate::{Index, WordCandidate};
+
+use super::{previous_results, Buckets, RankingRuleImpl};
+
+/// Past this position we consider that the word is too far in the document to matter.
+const MAX_POSITION: usize = 255;
+
+pub struct Position {
+    buckets: Buckets,
+}
+
+impl Position {
+    pub fn new() -> Self {
+        Self {
+            buckets: Buckets::default(),
+        }
+    }
+
+    /// Returns the position of the first word of the document matching the query, capped to [`MAX_POSITION`].
+    fn first_match(document: &[u32], words: &[WordCandidate]) -> usize {
+        document
+            .iter()
+            .take(MAX_POSITION)
+            .position(|id| words.iter().any(|word| word.word_ids.contains(*id)))
+            .unwrap_or(MAX_POSITION)
+    }
+}
+
+impl RankingRuleImpl for Position {
+    fn name(&self) -> &'static str {
+        "position"
+    }
+
+    fn next(
+        &mut self,
+        prev: Option<&dyn RankingRuleImpl>,
+        words: &mut Vec<WordCandidate>,
+        index: &Index,
+    ) -> ControlFlow<RoaringBitmap, ()> {
+        if self.buckets.is_exhausted() {
+            let current = previous_results(prev, words);
+
+            match &index.positions {
+                Some(positions) => {
+                    let mut buckets = vec![RoaringBitmap::new(); MAX_POSITION + 1];
+                    for id in current.iter() {
+                        let position = Self::first_match(&positions[id as usize], words);
+                        buckets[position].insert(id);
+                    }
+                    self.buckets.fill(buckets);
+                }
+                // without positions we cannot rank anything
+                None => self.buckets.fill([current]),
+            }
+        }
+
+        self.buckets.next()
+    }
+
+    fn current_results(&self, _words: &[WordCandidate]) -> RoaringBitmap {
+        self.buckets.current()
+    }
+
+    fn explain(&self, _words: &[WordCandidate], index: &Index) -> String {
+        match self.buckets.position() {
+            Some(_) if index.positions.is_none() => String::from("no positions"),
+            Some(position) => format!("position {position}"),
+            None => String::new(),
+        }
+    }
+
+    /// The position of the first match, the documents matching after the last position come last.
+    fn rank(&self, _words: &[WordCandidate], index: &Index) -> Option<(usize, usize)> {
+        index.positions.as_ref()?;
+        Some((self.buckets.position()?, MAX_POSITION))
+    }
+
+    fn cleanup(&mut self, used: &RoaringBitmap) {
+        self.buckets.cleanup(used);
+    }
+}
@@ -0,0 +1,123 @@
+//! The proximity ranking rule rank up the documents where the
+//! query words are close to each other and in the same order.
+//! "new york" should return "new york city" before "york of new hampshire".
+//! It requires the positions to be stored in the index, if they're
+//! missing every document ends up in the same bucket.
+use std::ops::ControlFlow;
+
+use roaring::RoaringBitmap;
+
+use crate::{Index, WordCandidate};
+
+use super::{previous_results, Buckets, RankingRuleImpl};
+
+/// Past this distance we consider that two words have nothing to do with each other
+const MAX_DISTANCE: u32 = 8;
+
+pub struct Proximity {
+    buckets: Buckets,
+    // the distance of the first bucket
+    best: u32,
+}
+
+impl Proximity {
+    pub fn new() -> Self {
+        Self {
+            buckets: Buckets::default(),
+            best: 0,
+        }
+    }
+
+    /// Returns the sum of the distances between every consecutive query words in the document.
+    /// Two words next to each other in the right order have a distance of 1.
+    fn distance(document: &[u32], words: &[&WordCandidate]) -> u32 {
+        let positions: Vec<Vec<u32>> = words
+            .iter()
+            .map(|word| {
+                document
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, id)| word.word_ids.contains(**id))
+                    .map(|(position, _)| position as u32)
+                    .collect()
+            })
+            .collect();
+
+        positions
+            .windows(2)
+            .map(|pair| {
+                pair[0]
+                    .iter()
+                    .flat_map(|left| {
+                        pair[1].iter().map(move |right| {
+                            // swapped words costs one more than words in the right order
+                            left.abs_diff(*right) + (right <= left) as u32
+                        })
+                    })
+                    .min()
+                    .unwrap_or(MAX_DISTANCE)
+                    .min(MAX_DISTANCE)
+            })
+            .sum()
+    }
+}
+
+impl RankingRuleImpl for Proximity {
+    fn name(&self) -> &'static str {
+        "proximity"
+    }
+
+    fn next(
+        &mut self,
+        prev: Option<&dyn RankingRuleImpl>,
+        words: &mut Vec<WordCandidate>,
+        index: &Index,
+    ) -> ControlFlow<RoaringBitmap, ()> {
+        if self.buckets.is_exhausted() {
+            let current = previous_results(prev, words);
+            let mut words: Vec<&WordCandidate> = words.iter().collect();
+            words.sort_by_key(|word| word.index);
+
+            // the best possible distance is one per pair of words
+            let best = words.len().saturating_sub(1) as u32;
+            self.best = best;
+            let mut buckets = vec![RoaringBitmap::new(); (best * (MAX_DISTANCE - 1) + 1) as usize];
+
+            match &index.positions {
+                Some(positions) => {
+                    for id in current.iter() {
+                        let distance = Self::distance(&positions[id as usize], &words);
+                        buckets[(distance - best) as usize].insert(id);
+                    }
+                }
+                // without positions we cannot rank anything
+                None => buckets[0] = current,
+            }
+            self.buckets.fill(buckets);
+        }
+
+        self.buckets.next()
+    }
+
+    fn current_results(&self, _words: &[WordCandidate]) -> RoaringBitmap {
+        self.buckets.current()
+    }
+
+    fn explain(&self, _words: &[WordCandidate], index: &Index) -> String {
+        match self.buckets.position() {
+            Some(_) if index.positions.is_none() => String::from("no positions"),
+            Some(position) => format!("distance {}", self.best + position as u32),
+            None => String::new(),
+        }
+    }
+
+    /// The distance between the words.
+    fn sort_value(&self, _words: &[WordCandidate], index: &Index) -> Option<f64> {
+        index.positions.as_ref()?;
+        Some((self.best + self.buckets.position()? as u32) as f64)
+    }
+
+    fn cleanup(&mut self, used: &RoaringBitmap) {
+        self.buckets.cleanup(used);
+    }
+}
@@ -0,0 +1,85 @@
+//! The sort ranking rule orders the documents by the payload
+//! that was associated to them when constructing the index.
+//! All the documents sharing the same payload are returned in the same bucket.
+//! If the index has no payloads every document ends up in the same bucket.
+use std::{collections::BTreeMap, ops::ControlFlow};
+
+use roaring::RoaringBitmap;
+
+use crate::{Index, WordCandidate};
+
+use super::{previous_results, Buckets, RankingRuleImpl};
+
+pub struct Sort {
+    descending: bool,
+    buckets: Buckets,
+}
+
+impl Sort {
+    pub fn new(descending: bool) -> Self {
+        Self {
+            descending,
+            buckets: Buckets::default(),
+        }
+    }
+}
+
+impl RankingRuleImpl for Sort {
+    fn name(&self) -> &'static str {
+        "sort"
+    }
+
+    fn next(
+        &mut self,
+        prev: Option<&dyn RankingRuleImpl>,
+        words: &mut Vec<WordCandidate>,
+        index: &Index,
+    ) -> ControlFlow<RoaringBitmap, ()> {
+        if self.buckets.is_exhausted() {
+            let current = previous_results(prev, words);
+
+            match &index.payloads {
+                Some(payloads) => {
+                    let mut buckets: BTreeMap<u64, RoaringBitmap> = BTreeMap::new();
+                    for id in current.iter() {
+                        buckets.entry(payloads[id as usize]).or_default().insert(id);
+                    }
+                    if self.descending {
+                        self.buckets.fill(buckets.into_values().rev());
+                    } else {
+                        self.buckets.fill(buckets.into_values());
+                    }
+                }
+                // without payloads we cannot sort anything
+                None => self.buckets.fill(Some(current)),
+            }
+        }
+
+        self.buckets.next()
+    }
+
+    fn current_results(&self, _words: &[WordCandidate]) -> RoaringBitmap {
+        self.buckets.current()
+    }
+
+    /// All the documents of the bucket share the same payload.
+    fn explain(&self, _words: &[WordCandidate], index: &Index) -> String {
+        let Some(id) = self.buckets.current().min() else {
+            return String::new();
+        };
+        match index.get_payload(id) {
+            Some(payload) => format!("payload {payload}"),
+            None => String::from("no payloads"),
+        }
+    }
+
+    /// The payload, or its opposite when descending. The payloads above 2^53 are rounded.
+    fn sort_value(&self, _words: &[WordCandidate], index: &Index) -> Option<f64> {
+        let payload = index.get_payload(self.buckets.current().min()?)? as f64;
+        Some(if self.descending { -payload } else { payload })
+    }
+
+    fn cleanup(&mut self, used: &RoaringBitmap) {
+        self.buckets.cleanup(used);
+    }
+}
@@ -60,4 +60,16 @@ impl RankingRuleImpl for Typo {
             .map(|word| word.typos.iter().take(self.typo_allowed).union())
             .intersection()
     }
+
+    /// The maximum number of typos per word.
+    fn explain(&self, _words: &[WordCandidate], _index: &Index) -> String {
+        format!("{} typos", self.typo_allowed.saturating_sub(1))
+    }
+
+    fn rank(&self, _words: &[WordCandidate], _index: &Index) -> Option<(usize, usize)> {
+        Some((
+            self.typo_allowed.saturating_sub(1),
+            self.max_typos.saturating_sub(1),
+        ))
+    }
 }
@@ -1,4 +1,4 @@
-use std::ops::ControlFlow;
+use std::ops::{ControlFlow, Range};
 
 use roaring::{MultiOps, RoaringBitmap};
 
@@ -8,20 +8,43 @@ use super::RankingRuleImpl;
 
 pub struct Word {
     first_iteration: bool,
+    // the share of the total weight of the words still required after dropping as many words as the position
+    kept: Vec<f64>,
 }
 
 impl Word {
-    pub fn new(words: &mut [WordCandidate]) -> Self {
+    pub fn new(words: &mut [WordCandidate], common_words: &RoaringBitmap) -> Self {
         // Since the default strategy is to pop the words from
         // the biggest frequency to the lowest we're going to
         // sort all the words by frequency in advance.
         // Later on we'll simply be able to pop the last one.
+        // The words that only matched common words are popped before all the others.
 
         // We're also going to cache the key as making the union of all typos is not that fast
-        words.sort_by_cached_key(|candidates| candidates.typos.as_slice().union().len());
+        words.sort_by_cached_key(|candidates| {
+            let common =
+                !candidates.word_ids.is_empty() && candidates.word_ids.is_subset(common_words);
+            (common, candidates.typos.as_slice().union().len())
+        });
+        // The words with the lowest weight are popped first whatever their frequency,
+        // the sort is stable thus the words of the same weight stay sorted by frequency.
+        words.sort_by(|left, right| right.weight.total_cmp(&left.weight));
+
+        let total: f64 = words.iter().map(|word| word.weight as f64).sum();
+        let mut kept: Vec<f64> = (0..=words.len())
+            .map(|len| {
+                words[..len]
+                    .iter()
+                    .map(|word| word.weight as f64)
+                    .sum::<f64>()
+                    / total
+            })
+            .collect();
+        kept.reverse();
 
         Self {
             first_iteration: true,
+            kept,
         }
     }
 }
@@ -57,6 +80,30 @@ impl RankingRuleImpl for Word {
             .map(|word| word.typos.as_slice().union())
             .intersection()
     }
+
+    /// The words that are still required, in the order of the query.
+    fn explain(&self, words: &[WordCandidate], _index: &Index) -> String {
+        let mut words: Vec<&WordCandidate> = words.iter().collect();
+        words.sort_by_key(|word| word.index);
+        let words: Vec<&str> = words.iter().map(|word| word.normalized()).collect();
+        words.join(" ")
+    }
+
+    /// Between the share of the weight of the words still required and the share without the next word to drop,
+    /// with the default weights it's the number of words that were dropped.
+    fn score_range(&self, words: &[WordCandidate], _index: &Index) -> Option<Range<f64>> {
+        let dropped = self.kept.len() - 1 - words.len();
+        match self.kept.get(dropped..=dropped + 1) {
+            Some(&[end, start]) => Some(start..end),
+            _ => None,
+        }
+    }
+
+    /// The share of the weight of the words that were dropped.
+    fn sort_value(&self, words: &[WordCandidate], _index: &Index) -> Option<f64> {
+        let dropped = self.kept.len().checked_sub(1 + words.len())?;
+        Some(1.0 - self.kept[dropped])
+    }
 }
 
 #[cfg(test)]
@@ -67,7 +114,7 @@ mod test {
 
     #[test]
     fn test_words_rr() {
-        let index = Index::construct(Vec::new());
+        let index = Index::new_in_memory(&[]).unwrap();
 
         // let's say we're working with "le beau chien"
         let mut words = vec![
@@ -76,31 +123,43 @@ mod test {
                 original: String::from("le"),
                 normalized: String::from("le"),
                 index: 0,
+                weight: 1.0,
                 typos: vec![RoaringBitmap::from_sorted_iter(0..1000).unwrap()],
+                word_ids: RoaringBitmap::new(),
+                matches: Vec::new(),
+                distance: Default::default(),
             },
             // "beau" is present in a bunch of documents but only 4 overlaps with "le"
             WordCandidate {
                 original: String::from("beau"),
                 normalized: String::from("beau"),
                 index: 1,
+                weight: 1.0,
                 // where I shove my stuff must not matter
                 typos: vec![
                     RoaringBitmap::from_sorted_iter(0..2).unwrap(),
                     RoaringBitmap::from_sorted_iter(100..102).unwrap(),
                     RoaringBitmap::from_sorted_iter(1000..1030).unwrap(),
                 ],
+                word_ids: RoaringBitmap::new(),
+                matches: Vec::new(),
+                distance: Default::default(),
             },
             WordCandidate {
                 original: String::from("chien"),
                 normalized: String::from("chien"),
                 index: 2,
+                weight: 1.0,
                 typos: vec![RoaringBitmap::from_sorted_iter(
                     (1..3).chain(98..101).chain(1028..1030),
                 )
                 .unwrap()],
+                word_ids: RoaringBitmap::new(),
+                matches: Vec::new(),
+                distance: Default::default(),
             },
         ];
-        let mut rr = Word::new(&mut words);
+        let mut rr = Word::new(&mut words, &RoaringBitmap::new());
         // after calling new, the words should be sorted from the less frequent to the most frequent one:
         let ordering: Vec<_> = words
             .iter()
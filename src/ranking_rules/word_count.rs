@@ -0,0 +1,86 @@
+//! The word count ranking rule ranks up the shortest documents, all else being equal
+//! "le petit kefir" is a better match for `kefir` than "kefir le bon petit chien".
+//! A document is put in the bucket of its number of words, the stop words are not counted.
+//! It requires the word counts to be stored in the index, if they're missing every document ends up in the same bucket.
+use std::{collections::BTreeMap, ops::ControlFlow};
+
+use roaring::RoaringBitmap;
+
+use crate::{Index, WordCandidate};
+
+use super::{previous_results, Buckets, RankingRuleImpl};
+
+pub struct WordCount {
+    buckets: Buckets,
+    // the number of words of the documents of every bucket
+    counts: Vec<u16>,
+}
+
+impl WordCount {
+    pub fn new() -> Self {
+        Self {
+            buckets: Buckets::default(),
+            counts: Vec::new(),
+        }
+    }
+}
+
+impl RankingRuleImpl for WordCount {
+    fn name(&self) -> &'static str {
+        "word_count"
+    }
+
+    fn next(
+        &mut self,
+        prev: Option<&dyn RankingRuleImpl>,
+        words: &mut Vec<WordCandidate>,
+        index: &Index,
+    ) -> ControlFlow<RoaringBitmap, ()> {
+        if self.buckets.is_exhausted() {
+            let current = previous_results(prev, words);
+
+            match &index.word_counts {
+                Some(word_counts) => {
+                    let mut buckets: BTreeMap<u16, RoaringBitmap> = BTreeMap::new();
+                    for id in current.iter() {
+                        buckets
+                            .entry(word_counts[id as usize])
+                            .or_default()
+                            .insert(id);
+                    }
+                    self.counts = buckets.keys().copied().collect();
+                    self.buckets.fill(buckets.into_values());
+                }
+                // without word counts we cannot rank anything
+                None => {
+                    self.counts.clear();
+                    self.buckets.fill([current]);
+                }
+            }
+        }
+
+        self.buckets.next()
+    }
+
+    fn current_results(&self, _words: &[WordCandidate]) -> RoaringBitmap {
+        self.buckets.current()
+    }
+
+    fn explain(&self, _words: &[WordCandidate], index: &Index) -> String {
+        match self.buckets.position() {
+            Some(_) if index.word_counts.is_none() => String::from("no word counts"),
+            Some(position) => format!("{} words", self.counts[position]),
+            None => String::new(),
+        }
+    }
+
+    /// The number of words of the documents.
+    fn sort_value(&self, _words: &[WordCandidate], index: &Index) -> Option<f64> {
+        index.word_counts.as_ref()?;
+        Some(*self.counts.get(self.buckets.position()?)? as f64)
+    }
+
+    fn cleanup(&mut self, used: &RoaringBitmap) {
+        self.buckets.cleanup(used);
+    }
+}
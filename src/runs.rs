@@ -0,0 +1,84 @@
+//! The words of the documents can be sorted in multiple runs, for example in parallel,
+//! and then merged back in a single stream sorted by word.
+
+use std::{cmp::Reverse, collections::BinaryHeap};
+
+use crate::Id;
+
+/// Merge runs of `(id, word)` sorted by word into a single stream sorted by word.
+/// When multiple runs contains the same word, the first run comes first.
+pub(crate) struct KWayMerge<I> {
+    runs: Vec<I>,
+    heap: BinaryHeap<Reverse<(String, usize, Id)>>,
+}
+
+impl<I: Iterator<Item = (Id, String)>> KWayMerge<I> {
+    pub fn new(mut runs: Vec<I>) -> Self {
+        let mut heap = BinaryHeap::with_capacity(runs.len());
+        for (run, iter) in runs.iter_mut().enumerate() {
+            if let Some((id, word)) = iter.next() {
+                heap.push(Reverse((word, run, id)));
+            }
+        }
+        Self { runs, heap }
+    }
+}
+
+impl<I: Iterator<Item = (Id, String)>> Iterator for KWayMerge<I> {
+    type Item = (Id, String);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let Reverse((word, run, id)) = self.heap.pop()?;
+        if let Some((next_id, next_word)) = self.runs[run].next() {
+            self.heap.push(Reverse((next_word, run, next_id)));
+        }
+        Some((id, word))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_merge_runs() {
+        let runs = vec![
+            vec![(0, "beau"), (1, "kefir"), (0, "tamo")],
+            vec![],
+            vec![(2, "chien"), (3, "kefir"), (2, "poney")],
+        ];
+        let runs = runs
+            .into_iter()
+            .map(|run| run.into_iter().map(|(id, word)| (id, word.to_string())))
+            .collect();
+        let merged: Vec<_> = KWayMerge::new(runs).collect();
+        insta::assert_debug_snapshot!(merged, @r###"
+        [
+            (
+                0,
+                "beau",
+            ),
+            (
+                2,
+                "chien",
+            ),
+            (
+                1,
+                "kefir",
+            ),
+            (
+                3,
+                "kefir",
+            ),
+            (
+                2,
+                "poney",
+            ),
+            (
+                0,
+                "tamo",
+            ),
+        ]
+        "###);
+    }
+}
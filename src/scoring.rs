@@ -0,0 +1,160 @@
+//! Rank the documents by a single score instead of the buckets of the ranking rules, see [`Scoring`].
+//!
+//! BM25 sums, for every word of the query found in a document, the rarity of the word in the corpus
+//! weighted by how many times it appears in the document relative to the length of the document.
+//! The frequencies and lengths come from the optional sections of the index:
+//! - the number of documents containing a word is the length of the bitmaps it matched,
+//! - the number of occurrences of a word in a document, see [`crate::IndexBuilder::with_term_frequencies`],
+//!   a word found in a document appears once without them,
+//! - the number of words of a document, see [`crate::IndexBuilder::with_word_counts`],
+//!   the length of the documents is ignored without them.
+
+use std::collections::HashSet;
+
+use roaring::RoaringBitmap;
+
+use crate::{BucketRank, ExplainedBucket, Index, Search, WordCandidate};
+
+/// How the documents matching a search are ordered, see [`crate::Search::with_scoring`].
+#[derive(Debug, Default, Clone, Copy)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum Scoring {
+    /// The documents are sorted by the ranking rules, one after the other.
+    #[default]
+    BucketSort,
+    /// The documents are sorted by their BM25 score, the ranking rules are ignored.
+    /// `k1` is how fast the repetitions of a word stop mattering and `b` how much the long documents are penalized,
+    /// between 0 and 1. The usual values are `1.2` and `0.75`, the negative values are replaced by 0 and `b` is capped to 1.
+    Bm25 { k1: f32, b: f32 },
+}
+
+/// The parameters are compared by their bits, thus a scoring is always equal to itself.
+impl PartialEq for Scoring {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::BucketSort, Self::BucketSort) => true,
+            (Self::Bm25 { k1, b }, Self::Bm25 { k1: k1_2, b: b_2 }) => {
+                k1.to_bits() == k1_2.to_bits() && b.to_bits() == b_2.to_bits()
+            }
+            _ => false,
+        }
+    }
+}
+
+impl Eq for Scoring {}
+
+/// The statistics of the whole corpus, used by BM25 instead of the ones of the index when it's a shard,
+/// see [`crate::ShardedIndex`].
+#[derive(Debug, Clone)]
+pub(crate) struct CorpusStats {
+    pub documents: u64,
+    pub average_length: f32,
+    /// The number of documents matched by every word of the query, by its position in the query.
+    pub frequencies: Vec<u64>,
+}
+
+impl Index<'_> {
+    /// Push the documents of the universe in `res` by decreasing BM25 score, the documents sharing a score in the same bucket,
+    /// until the limit of the search is reached. Does nothing if the search isn't scored with BM25.
+    /// `explain` and `ranks` are filled like [`Index::bucket_sort`] does.
+    pub(crate) fn bm25_sort(
+        &self,
+        search: &Search,
+        candidates: &[WordCandidate],
+        universe: &RoaringBitmap,
+        res: &mut Vec<RoaringBitmap>,
+        mut explain: Option<&mut Vec<ExplainedBucket>>,
+        mut ranks: Option<&mut Vec<BucketRank>>,
+    ) {
+        let Scoring::Bm25 { k1, b } = search.scoring else {
+            return;
+        };
+        // `max` also replaces NaN, unlike `clamp`
+        let (k1, b) = (k1.max(0.0), b.max(0.0).clamp(0.0, 1.0));
+        let (total, average) = match &search.corpus {
+            Some(corpus) => (corpus.documents as f32, corpus.average_length),
+            None => (self.document_count() as f32, self.average_document_length()),
+        };
+
+        // the scores and the number of words matched of the documents, in the order of the universe
+        let mut scores = vec![0.0f32; universe.len() as usize];
+        let mut matched = vec![0usize; universe.len() as usize];
+        for candidate in candidates {
+            let documents = roaring::MultiOps::union(&candidate.typos);
+            let frequency = match &search.corpus {
+                Some(corpus) => corpus.frequencies[candidate.index] as f32,
+                None => documents.len() as f32,
+            };
+            let idf = (1.0 + (total - frequency + 0.5) / (frequency + 0.5)).ln();
+            for id in documents.iter() {
+                let tf = self.term_frequency(id, &candidate.word_ids) as f32;
+                let length = match &self.word_counts {
+                    Some(word_counts) if average > 0.0 => word_counts[id as usize] as f32 / average,
+                    _ => 1.0,
+                };
+                let position = universe.rank(id) as usize - 1;
+                scores[position] +=
+                    candidate.weight * idf * tf * (k1 + 1.0) / (tf + k1 * (1.0 - b + b * length));
+                matched[position] += 1;
+            }
+        }
+
+        let minimum_words = search.minimum_words_matched.min(candidates.len());
+        let mut order: Vec<(u32, f32)> = universe
+            .iter()
+            .zip(scores)
+            .zip(matched)
+            .filter(|(_, matched)| *matched >= minimum_words)
+            .map(|(document, _)| document)
+            .collect();
+        // the sort is stable, the documents sharing a score stay in the order of their ids
+        order.sort_by(|(_, left), (_, right)| right.total_cmp(left));
+
+        let mut distinct = search.distinct.then(HashSet::new);
+        let limit = self.limit(search) as u64;
+        let mut returned = 0;
+        for bucket in order.chunk_by(|(_, left), (_, right)| left == right) {
+            if returned >= limit {
+                break;
+            }
+            let score = bucket[0].1;
+            let mut bucket: RoaringBitmap = bucket.iter().map(|(id, _)| *id).collect();
+            if let Some(seen) = distinct.as_mut() {
+                self.remove_duplicates(&mut bucket, seen, search.tie_break);
+            }
+            if let Some(explain) = explain.as_mut() {
+                explain.push(ExplainedBucket {
+                    rules: vec![("bm25", format!("score {score:.3}"))],
+                    ids: search.tie_break.iter(&bucket).collect(),
+                });
+            }
+            if let Some(ranks) = ranks.as_mut() {
+                ranks.push(BucketRank {
+                    score,
+                    words: candidates.len(),
+                    values: vec![Some(-score as f64)],
+                });
+            }
+            returned += bucket.len();
+            res.push(bucket);
+        }
+    }
+
+    /// Returns how many times the words of the fst matched by a word of the query appear in the document,
+    /// at least 1 since the document contains one of them.
+    fn term_frequency(&self, id: u32, word_ids: &RoaringBitmap) -> u32 {
+        let Some(term_frequencies) = &self.term_frequencies else {
+            return 1;
+        };
+        let frequency: u32 = term_frequencies[id as usize]
+            .iter()
+            .filter(|(word, _)| word_ids.contains(*word))
+            .map(|(_, count)| *count as u32)
+            .sum();
+        frequency.max(1)
+    }
+}
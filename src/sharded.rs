@@ -0,0 +1,474 @@
+//! A large corpus can be split into multiple independent indexes, the shards, stored in a single file.
+//! They're faster to construct and load, and every search runs on all the shards at once.
+//!
+//! The documents are distributed round-robin: the document `id` is the document `id / count` of the shard `id % count`,
+//! thus the ids returned by a search are the ids of the documents in the corpus.
+//!
+//! The file starts with its own magic bytes and the number of shards, followed by the size of every shard,
+//! and then the shards themselves, each of them a complete index with its own header and checksum.
+
+use std::{cmp::Ordering, collections::HashSet};
+
+use roaring::{MultiOps, RoaringBitmap};
+
+use crate::deadline::Deadline;
+use crate::scoring::CorpusStats;
+use crate::{BucketRank, Error, Index, IndexBuilder, Scoring, Search, SearchContext};
+
+const MAGIC: &[u8; 4] = b"ZSHD";
+
+/// An index split into shards, see [`IndexBuilder::construct_sharded`].
+///
+/// The buckets of the shards are merged by the values of the ranking rules that returned them, see
+/// [`crate::RankingRuleImpl::sort_value`], the documents of the buckets sharing their values returned in the order of their ids.
+/// The filter is translated to the ids of every shard, the duplicates are removed across the shards and BM25 uses
+/// the statistics of all the shards. The results are the same as an unsharded index except when a shard decides alone:
+/// the word ranking rule drops the most frequent words of the shard first, [`Search::with_max_exact_documents`]
+/// and [`Search::with_max_candidate_documents`] count the documents of the shard, and the buckets of the custom
+/// ranking rules without values are merged.
+///
+/// ```
+/// use zearch::{IndexBuilder, Search, ShardedIndex};
+///
+/// let mut bytes = Vec::new();
+/// IndexBuilder::new()
+///     .construct_sharded(&["kefir", "le chien", "kefir le chien"], 2, &mut bytes)
+///     .unwrap();
+/// let index = ShardedIndex::from_bytes(&bytes).unwrap();
+/// assert_eq!(index.shard_count(), 2);
+/// assert_eq!(index.search(&Search::new("kefir")), [0, 2]);
+/// assert_eq!(index.get_document(2), Some("kefir le chien"));
+/// ```
+pub struct ShardedIndex<'a> {
+    shards: Vec<Index<'a>>,
+}
+
+impl IndexBuilder {
+    /// Construct a [`ShardedIndex`] made of `shards` indexes with the settings of this builder.
+    /// The boosts and coordinates follow their documents in the shards.
+    pub fn construct_sharded(
+        &self,
+        documents: &[impl AsRef<str>],
+        shards: usize,
+        writer: &mut impl std::io::Write,
+    ) -> Result<(), Error> {
+        if self.dedup_documents {
+            let documents: Vec<&str> = documents.iter().map(AsRef::as_ref).collect();
+            let (builder, documents, _) = self.remove_duplicates(documents, None);
+            return builder.construct_sharded(&documents, shards, writer);
+        }
+        let shards = shards.max(1);
+        let mut table = Vec::with_capacity(shards);
+        let mut bytes = Vec::new();
+        for shard in 0..shards {
+            let mut builder = self.clone();
+            builder.boosts = self
+                .boosts
+                .iter()
+                .copied()
+                .skip(shard)
+                .step_by(shards)
+                .collect();
+            builder.coordinates = self
+                .coordinates
+                .iter()
+                .copied()
+                .skip(shard)
+                .step_by(shards)
+                .collect();
+            let documents: Vec<&str> = documents
+                .iter()
+                .skip(shard)
+                .step_by(shards)
+                .map(AsRef::as_ref)
+                .collect();
+            let start = bytes.len();
+            builder.construct(&documents, &mut bytes)?;
+            table.push(bytes.len() - start);
+        }
+
+        writer.write_all(MAGIC)?;
+        Index::write_size(writer, shards)?;
+        for size in table {
+            Index::write_size(writer, size)?;
+        }
+        writer.write_all(&bytes)?;
+        Ok(())
+    }
+}
+
+impl<'a> ShardedIndex<'a> {
+    /// Load the shards without copying the bytes, the checksum of every shard is verified.
+    pub fn from_bytes(bytes: &'a [u8]) -> Result<Self, Error> {
+        let mut rest = bytes.strip_prefix(MAGIC).ok_or(Error::NotAnIndex)?;
+        let count = Index::read_count_from_bytes(&mut rest, std::mem::size_of::<u32>())
+            .ok_or(Error::Corrupted)?;
+        let sizes = (0..count)
+            .map(|_| Index::read_size_from_bytes(&mut rest))
+            .collect::<Option<Vec<u32>>>()
+            .ok_or(Error::Corrupted)?;
+        if count == 0 || sizes.iter().map(|size| *size as usize).sum::<usize>() != rest.len() {
+            return Err(Error::Corrupted);
+        }
+
+        let mut shards = Vec::with_capacity(count as usize);
+        for size in sizes {
+            let (shard, next) = rest.split_at(size as usize);
+            shards.push(Index::from_bytes(shard)?);
+            rest = next;
+        }
+        Ok(Self { shards })
+    }
+
+    /// Copy the shards in memory, see [`Index::move_in_memory`].
+    pub fn move_in_memory(self) -> ShardedIndex<'static> {
+        ShardedIndex {
+            shards: self.shards.into_iter().map(Index::move_in_memory).collect(),
+        }
+    }
+
+    pub fn shard_count(&self) -> usize {
+        self.shards.len()
+    }
+
+    /// The number of documents in all the shards.
+    pub fn len(&self) -> usize {
+        self.shards.iter().map(Index::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the shard containing the document and its id in this shard.
+    fn locate(&self, id: u32) -> (&Index<'a>, u32) {
+        let count = self.shards.len() as u32;
+        (&self.shards[(id % count) as usize], id / count)
+    }
+
+    /// Returns the text of the document, see [`Index::get_document`].
+    pub fn get_document(&self, id: u32) -> Option<&str> {
+        let (shard, id) = self.locate(id);
+        shard.get_document(id)
+    }
+
+    /// Search every shard and merge their buckets, see [`ShardedIndex`].
+    /// With the `parallel` feature the shards are searched on the rayon thread pool.
+    pub fn search(&self, search: &Search) -> Vec<u32> {
+        let searches = self.shard_searches(search);
+        #[cfg(feature = "parallel")]
+        let buckets: Vec<Vec<(RoaringBitmap, BucketRank)>> = {
+            use rayon::prelude::*;
+
+            self.shards
+                .par_iter()
+                .zip(&searches)
+                .map_init(SearchContext::default, |ctx, (shard, search)| {
+                    shard.ranked_buckets(search, ctx)
+                })
+                .collect()
+        };
+        #[cfg(not(feature = "parallel"))]
+        let buckets: Vec<Vec<(RoaringBitmap, BucketRank)>> = {
+            let mut ctx = SearchContext::default();
+            self.shards
+                .iter()
+                .zip(&searches)
+                .map(|(shard, search)| shard.ranked_buckets(search, &mut ctx))
+                .collect()
+        };
+
+        let count = self.shards.len() as u32;
+        let mut remaining: Vec<&[(RoaringBitmap, BucketRank)]> =
+            buckets.iter().map(Vec::as_slice).collect();
+        // every shard was constructed with the same builder thus stores the same limit
+        let limit = self.shards[0].limit(search);
+        let mut distinct = search.distinct.then(HashSet::new);
+        let mut ret = Vec::new();
+        while ret.len() < limit {
+            let Some(best) = remaining
+                .iter()
+                .filter_map(|buckets| buckets.first())
+                .map(|(_, rank)| &rank.values)
+                .reduce(|best, values| match compare(values, best) {
+                    Ordering::Less => values,
+                    _ => best,
+                })
+            else {
+                break;
+            };
+            let mut bucket = RoaringBitmap::new();
+            for (shard, buckets) in remaining.iter_mut().enumerate() {
+                if let Some(((first, rank), rest)) = buckets.split_first() {
+                    if compare(&rank.values, best).is_eq() {
+                        bucket.extend(first.iter().map(|id| id * count + shard as u32));
+                        *buckets = rest;
+                    }
+                }
+            }
+            for id in search.tie_break.iter(&bucket) {
+                if ret.len() >= limit {
+                    break;
+                }
+                // the shards only removed their own duplicates
+                if let Some(seen) = distinct.as_mut() {
+                    let (shard, local) = self.locate(id);
+                    if shard
+                        .distinct_text(local)
+                        .is_some_and(|text| !seen.insert(text))
+                    {
+                        continue;
+                    }
+                }
+                ret.push(id);
+            }
+        }
+        ret
+    }
+
+    /// Returns the search of every shard: the filter is translated to the ids of the shard
+    /// and BM25 uses the statistics of all the shards.
+    fn shard_searches<'s>(&self, search: &Search<'s>) -> Vec<Search<'s>> {
+        let count = self.shards.len();
+        let mut searches = vec![search.clone(); count];
+        if let Some(filter) = &search.filter {
+            let mut filters = vec![RoaringBitmap::new(); count];
+            for id in filter.iter() {
+                filters[id as usize % count].insert(id / count as u32);
+            }
+            for (search, filter) in searches.iter_mut().zip(filters) {
+                search.with_owned_filter(filter);
+            }
+        }
+
+        if search.scoring != Scoring::BucketSort {
+            let mut frequencies = Vec::new();
+            for (shard, search) in self.shards.iter().zip(&searches) {
+                for candidate in shard.candidates(search) {
+                    if frequencies.len() <= candidate.index {
+                        frequencies.resize(candidate.index + 1, 0);
+                    }
+                    frequencies[candidate.index] += candidate.typos.union().len();
+                }
+            }
+            let documents = self
+                .shards
+                .iter()
+                .map(|shard| shard.document_count() as u64);
+            let lengths = self.shards.iter().map(Index::document_lengths).try_fold(
+                (0, 0),
+                |(total, documents), lengths| {
+                    lengths.map(|(words, count)| (total + words, documents + count))
+                },
+            );
+            let corpus = CorpusStats {
+                documents: documents.sum(),
+                average_length: match lengths {
+                    Some((total, documents)) => (total as f64 / documents as f64) as f32,
+                    None => f32::NAN,
+                },
+                frequencies,
+            };
+            for search in searches.iter_mut() {
+                search.corpus = Some(corpus.clone());
+            }
+        }
+        searches
+    }
+}
+
+/// Compares the sort values of two buckets ranking rule by ranking rule, see [`crate::RankingRuleImpl::sort_value`].
+/// The values missing in one of them are ignored, the two buckets are then merged.
+fn compare(left: &[Option<f64>], right: &[Option<f64>]) -> Ordering {
+    left.iter()
+        .zip(right)
+        .filter_map(|(left, right)| Some(left.as_ref()?.total_cmp(right.as_ref()?)))
+        .find(|ordering| ordering.is_ne())
+        .unwrap_or(Ordering::Equal)
+}
+
+impl Index<'_> {
+    /// Run the search like [`Index::search_buckets`] but returns the rank of every bucket,
+    /// the buckets are not cut at the limit.
+    fn ranked_buckets(
+        &self,
+        search: &Search,
+        ctx: &mut SearchContext,
+    ) -> Vec<(RoaringBitmap, BucketRank)> {
+        let deadline = Deadline::new(search);
+        let (mut candidates, _) = self.get_candidates(search, ctx, &deadline);
+        let mut buckets = Vec::new();
+        let mut ranks = Vec::new();
+        self.bucket_sort(
+            search,
+            &mut candidates,
+            &mut buckets,
+            &deadline,
+            None,
+            Some(&mut ranks),
+        );
+        ctx.candidates.extend(candidates);
+        buckets
+            .into_iter()
+            .zip(ranks)
+            .filter(|(bucket, _)| !bucket.is_empty())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test::SMALL_CORPUS;
+    use crate::RankingRule;
+
+    #[test]
+    fn test_same_results() {
+        let index = Index::new_in_memory(SMALL_CORPUS).unwrap();
+        let mut bytes = Vec::new();
+        IndexBuilder::new()
+            .construct_sharded(SMALL_CORPUS, 3, &mut bytes)
+            .unwrap();
+        let sharded = ShardedIndex::from_bytes(&bytes).unwrap();
+        assert_eq!(sharded.len(), index.len());
+        for id in 0..SMALL_CORPUS.len() as u32 {
+            assert_eq!(sharded.get_document(id), index.get_document(id));
+        }
+
+        for query in [
+            "tamo",
+            "kefir",
+            "kef",
+            "chien",
+            "chat",
+            "petit",
+            "kefir chien",
+            "kefir kefir",
+            "tamo est",
+            "tamo beau",
+            "petit kefir",
+            "le kefir",
+            "kefir magnifique",
+            "kefir -chien",
+            "toutou chien -bon",
+            "chein",
+            "xyzzy",
+        ] {
+            let mut search = Search::new(query);
+            assert_eq!(sharded.search(&search), index.search(&search), "{query}");
+            search.with_limit(2);
+            assert_eq!(sharded.search(&search), index.search(&search), "{query}");
+        }
+    }
+
+    #[test]
+    fn test_search_options() {
+        let documents = [
+            "kefir le chien",
+            "kefir",
+            "le chien kefir kefir",
+            "kefir kefir",
+            "chien",
+            "kefir le beau chien",
+            "Kefir !",
+        ];
+        let mut builder = IndexBuilder::new();
+        builder
+            .with_word_counts(true)
+            .with_term_frequencies(true)
+            .with_boosts(&[1.0, 3.0, 2.0, 1.0, 0.0, 3.0, 2.0])
+            .with_coordinates(&[(4.0, 4.0), (1.0, 1.0), (3.0, 3.0), (2.0, 2.0), (0.5, 0.5)]);
+        let mut bytes = Vec::new();
+        builder.construct(&documents, &mut bytes).unwrap();
+        let index = Index::from_bytes(&bytes).unwrap();
+        let mut sharded = Vec::new();
+        builder
+            .construct_sharded(&documents, 3, &mut sharded)
+            .unwrap();
+        let sharded = ShardedIndex::from_bytes(&sharded).unwrap();
+
+        let filter = RoaringBitmap::from_iter([4, 5]);
+        let mut searches = Vec::new();
+        let mut search = Search::new("kefir");
+        search.with_sort_by_distance(0.0, 0.0);
+        searches.push(search.clone());
+        search = Search::new("kefir chien");
+        search.with_scoring(Scoring::Bm25 { k1: 1.2, b: 0.75 });
+        searches.push(search.clone());
+        search.with_distinct(true);
+        searches.push(search);
+        search = Search::new("kefir");
+        search.with_ranking_rules(vec![RankingRule::Boost]);
+        searches.push(search.clone());
+        search.with_ranking_rules(vec![RankingRule::Word, RankingRule::WordCount]);
+        searches.push(search.clone());
+        search.with_distinct(true);
+        searches.push(search);
+        search = Search::new("chien");
+        search.with_filter(&filter);
+        searches.push(search.clone());
+        search.within(&filter);
+        searches.push(search);
+
+        for (i, search) in searches.iter().enumerate() {
+            assert_eq!(sharded.search(search), index.search(search), "{i}");
+        }
+        // the first document of the shards is `kefir`, only the first one of the corpus is returned
+        assert_eq!(sharded.search(&searches[5]), [1, 3, 0, 2, 5]);
+    }
+
+    #[test]
+    fn test_word_frequencies() {
+        let index = Index::new_in_memory(SMALL_CORPUS).unwrap();
+        let mut bytes = Vec::new();
+        IndexBuilder::new()
+            .construct_sharded(SMALL_CORPUS, 3, &mut bytes)
+            .unwrap();
+        let sharded = ShardedIndex::from_bytes(&bytes).unwrap();
+
+        // `le` is rarer than `kefir` in the corpus but not in every shard, the word ranking rule
+        // drops the most frequent word first thus some shards return the documents containing `kefir` alone
+        let search = Search::new("kefir le kefir");
+        insta::assert_debug_snapshot!(index.search(&search), @r###"
+        [
+            1,
+            2,
+            4,
+            6,
+            0,
+            10,
+        ]
+        "###);
+        insta::assert_debug_snapshot!(sharded.search(&search), @r###"
+        [
+            1,
+            2,
+            4,
+            6,
+            9,
+            7,
+        ]
+        "###);
+    }
+
+    #[test]
+    fn test_corrupted() {
+        let mut bytes = Vec::new();
+        IndexBuilder::new()
+            .construct_sharded(&["kefir", "tamo"], 4, &mut bytes)
+            .unwrap();
+        let sharded = ShardedIndex::from_bytes(&bytes).unwrap();
+        // the shards without documents are still searched
+        assert_eq!(sharded.search(&Search::new("tamo")), [1]);
+        assert_eq!(sharded.get_document(3), None);
+
+        assert!(matches!(
+            ShardedIndex::from_bytes(&bytes[..bytes.len() - 1]),
+            Err(Error::Corrupted)
+        ));
+        assert!(matches!(
+            ShardedIndex::from_bytes(b"ZRCH"),
+            Err(Error::NotAnIndex)
+        ));
+    }
+}
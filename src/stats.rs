@@ -0,0 +1,381 @@
+//! Statistics about the documents and the words of an index, to analyze the queries or build a list of stop words,
+//! and about its size to choose between loading it in memory or from a mmap.
+
+use std::{borrow::Cow, fmt};
+
+use fst::{map::Stream, Streamer};
+use roaring::RoaringBitmap;
+
+use crate::{documents::Documents, Index, MAGIC, VERSION};
+
+/// The size of a `u32` prefixing every slice and count of the format.
+const SIZE: usize = std::mem::size_of::<u32>();
+/// The kind and the size of an optional section.
+const SECTION: usize = 1 + SIZE;
+
+impl<'a> Index<'a> {
+    /// Returns the number of documents in the index, the deleted documents are not counted.
+    pub fn document_count(&self) -> u32 {
+        (self.documents.len() as u64 - self.deleted.len()) as u32
+    }
+
+    /// Returns the number of distinct words stored in the index.
+    /// The words only contained in deleted documents are counted until the index is compacted.
+    pub fn word_count(&self) -> usize {
+        self.fst.len()
+    }
+
+    /// Returns the number of documents containing the word, it's normalized and stemmed like the words of the query.
+    /// The deleted documents are not counted.
+    ///
+    /// ```
+    /// use zearch::Index;
+    ///
+    /// let index = Index::new_in_memory(&["kefir", "Kéfir le chien", "le chat"]).unwrap();
+    /// assert_eq!(index.document_frequency("KEFIR"), 2);
+    /// assert_eq!(index.document_frequency("kef"), 0);
+    /// ```
+    pub fn document_frequency(&self, word: &str) -> u64 {
+        let word = self.analyzer().normalize(word);
+        match self.fst.get(word) {
+            Some(id) => self.bitmaps.get(id).difference_len(&self.deleted),
+            None => 0,
+        }
+    }
+
+    /// Returns all the words of the index in the lexicographic order with their document frequency.
+    /// Like in [`Index::word_count`], the words only contained in deleted documents have a frequency of 0.
+    pub fn words(&self) -> Words<'_, 'a> {
+        Words {
+            index: self,
+            stream: self.fst.stream(),
+        }
+    }
+
+    /// Returns the size of every part of the index once written and an estimate of the memory it uses.
+    /// It's computed from the index itself, thus it's the size [`Index::write_to`] would write.
+    ///
+    /// ```
+    /// use zearch::Index;
+    ///
+    /// let index = Index::new_in_memory(&["kefir", "Kéfir le chien", "le chat"]).unwrap();
+    /// let stats = index.size_stats();
+    /// let mut bytes = Vec::new();
+    /// index.write_to(&mut bytes).unwrap();
+    /// assert_eq!(stats.total, bytes.len());
+    /// println!("{stats}");
+    /// ```
+    pub fn size_stats(&self) -> IndexStats {
+        let bitmaps = self.bitmaps.serialized_size();
+        let fst = self.fst.as_fst().as_bytes().len();
+        let mut stats = IndexStats {
+            bitmaps: SIZE + self.bitmaps.len() * SIZE + bitmaps,
+            fst: SIZE + fst,
+            memory: bitmaps + fst,
+            ..IndexStats::default()
+        };
+
+        stats.documents = SIZE;
+        match &self.documents {
+            Documents::Stored(documents) => {
+                let text: usize = documents.iter().map(|document| document.len()).sum();
+                stats.documents += documents.len() * SIZE + text;
+                stats.memory += documents.len() * std::mem::size_of::<Cow<str>>() + text;
+            }
+            Documents::Missing(_) => stats.documents += SECTION + SIZE,
+            Documents::Compressed {
+                first_ids, blocks, ..
+            } => {
+                let compressed: usize = blocks.iter().map(|block| block.len()).sum();
+                stats.documents += SECTION + 2 * SIZE + first_ids.len() * 2 * SIZE + compressed;
+                // the blocks that were decompressed aren't counted
+                stats.memory += first_ids.len() * SIZE + compressed;
+            }
+        }
+
+        if let Some(positions) = &self.positions {
+            let ids: usize = positions.iter().map(Vec::len).sum();
+            stats.positions = SECTION + (positions.len() + ids) * SIZE;
+            stats.memory += positions.len() * std::mem::size_of::<Vec<u32>>() + ids * SIZE;
+        }
+        if let Some(payloads) = &self.payloads {
+            let size = payloads.len() * std::mem::size_of::<u64>();
+            stats.payloads = SECTION + size;
+            stats.memory += size;
+        }
+
+        if let Some(word_counts) = &self.word_counts {
+            let size = word_counts.len() * std::mem::size_of::<u16>();
+            stats.other += SECTION + size;
+            stats.memory += size;
+        }
+        if let Some(term_frequencies) = &self.term_frequencies {
+            let entries: usize = term_frequencies.iter().map(Vec::len).sum();
+            let entry = std::mem::size_of::<(u32, u16)>();
+            stats.other += SECTION
+                + term_frequencies.len() * SIZE
+                + entries * (SIZE + std::mem::size_of::<u16>());
+            stats.memory +=
+                term_frequencies.len() * std::mem::size_of::<Vec<(u32, u16)>>() + entries * entry;
+        }
+        if let Some(boosts) = &self.boosts {
+            let size = boosts.len() * std::mem::size_of::<f32>();
+            stats.other += SECTION + size;
+            stats.memory += size;
+        }
+        if let Some(coordinates) = &self.coordinates {
+            let size = coordinates.len() * std::mem::size_of::<(f32, f32)>();
+            stats.other += SECTION + size;
+            stats.memory += size;
+        }
+        if let Some(defaults) = &self.search_defaults {
+            let size = defaults.serialized_size();
+            stats.other += SECTION + size;
+            stats.memory += size;
+        }
+        if let Some(fields) = &self.fields {
+            let size = fields.serialized_size();
+            stats.other += SECTION + size;
+            stats.memory += size;
+        }
+
+        let bitmap = |bitmap: &RoaringBitmap| match bitmap.is_empty() {
+            true => 0,
+            false => SECTION + bitmap.serialized_size(),
+        };
+        let words =
+            |words: &[String]| -> usize { words.iter().map(|word| SIZE + word.len()).sum() };
+        let (stop_words, exact_words) = (words(&self.stop_words), words(&self.exact_words));
+        let section = |size: usize| if size == 0 { 0 } else { SECTION + size };
+        stats.other += bitmap(&self.deleted)
+            + bitmap(&self.common_words)
+            + section(stop_words)
+            + section(exact_words);
+        stats.memory += self.deleted.serialized_size()
+            + self.common_words.serialized_size()
+            + stop_words
+            + exact_words;
+
+        // the header and the checksum
+        let header = MAGIC.len() + std::mem::size_of_val(&VERSION) + 3;
+        stats.total = header
+            + stats.documents
+            + stats.bitmaps
+            + stats.fst
+            + stats.positions
+            + stats.payloads
+            + stats.other
+            + SIZE;
+        stats
+    }
+}
+
+/// The sizes in bytes returned by [`Index::size_stats`].
+/// The `Display` implementation prints them as a table.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct IndexStats {
+    /// The documents, or only their count if they're not stored.
+    pub documents: usize,
+    pub bitmaps: usize,
+    pub fst: usize,
+    /// `0` if the positions are not stored, same for the payloads.
+    pub positions: usize,
+    pub payloads: usize,
+    /// The deleted documents, the stop words, the common words, the fields, the word counts, the boosts, the coordinates
+    /// and the search defaults.
+    pub other: usize,
+    /// The size of the whole index, header and checksum included.
+    pub total: usize,
+    /// An estimate of the memory used by the index once moved in memory, see [`Index::move_in_memory`].
+    /// The bitmaps are counted with their serialized size, which is close to their size once decoded.
+    pub memory: usize,
+}
+
+impl fmt::Display for IndexStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let rows = [
+            ("documents", self.documents),
+            ("bitmaps", self.bitmaps),
+            ("fst", self.fst),
+            ("positions", self.positions),
+            ("payloads", self.payloads),
+            ("other", self.other),
+            ("total", self.total),
+            ("in memory", self.memory),
+        ];
+        for (name, size) in rows {
+            let percent = match self.total {
+                0 => 0.,
+                total => size as f64 * 100. / total as f64,
+            };
+            writeln!(f, "{name:<10} {:>10} {percent:>6.1}%", HumanSize(size))?;
+        }
+        Ok(())
+    }
+}
+
+/// Writes a size in bytes with the largest unit under which it stays above 1, like `1.5 KiB`.
+struct HumanSize(usize);
+
+impl fmt::Display for HumanSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+        if self.0 < 1024 {
+            return f.pad(&format!("{} B", self.0));
+        }
+        let mut size = self.0 as f64 / 1024.;
+        let mut unit = 0;
+        while size >= 1024. && unit < UNITS.len() - 1 {
+            size /= 1024.;
+            unit += 1;
+        }
+        f.pad(&format!("{size:.1} {}", UNITS[unit]))
+    }
+}
+
+/// The iterator returned by [`Index::words`].
+pub struct Words<'i, 'a> {
+    index: &'i Index<'a>,
+    stream: Stream<'i>,
+}
+
+impl Iterator for Words<'_, '_> {
+    type Item = (String, u64);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (word, id) = self.stream.next()?;
+        let frequency = self
+            .index
+            .bitmaps
+            .get(id)
+            .difference_len(&self.index.deleted);
+        Some((String::from_utf8_lossy(word).into_owned(), frequency))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use roaring::RoaringBitmap;
+
+    use crate::{test::create_small_index, Index, IndexBuilder};
+
+    #[test]
+    fn test_stats() {
+        let mut index = create_small_index();
+        insta::assert_debug_snapshot!((index.document_count(), index.word_count()), @r###"
+        (
+            12,
+            30,
+        )
+        "###);
+        insta::assert_debug_snapshot!(
+            ["kefir", "Kéfir", "kef", "chiens", "xyzzy"].map(|word| index.document_frequency(word)),
+            @r###"
+        [
+            5,
+            5,
+            1,
+            0,
+            0,
+        ]
+        "###
+        );
+        let words: Vec<(String, u64)> = index.words().collect();
+        assert_eq!(words.len(), index.word_count());
+        insta::assert_debug_snapshot!(&words[..5], @r###"
+        [
+            (
+                "a",
+                1,
+            ),
+            (
+                "aussi",
+                2,
+            ),
+            (
+                "beau",
+                4,
+            ),
+            (
+                "bon",
+                2,
+            ),
+            (
+                "c",
+                2,
+            ),
+        ]
+        "###);
+
+        // the deleted documents are not counted
+        index.delete_documents(&RoaringBitmap::from_iter([1, 2]));
+        insta::assert_debug_snapshot!((index.document_count(), index.word_count()), @r###"
+        (
+            10,
+            30,
+        )
+        "###);
+        assert_eq!(
+            index.document_frequency("kefir"),
+            index.words().find(|(word, _)| word == "kefir").unwrap().1
+        );
+        insta::assert_debug_snapshot!(index.document_frequency("kefir"), @"3");
+    }
+
+    #[test]
+    fn test_size_stats() {
+        let index = create_small_index();
+        let stats = index.size_stats();
+        let mut bytes = Vec::new();
+        index.write_to(&mut bytes).unwrap();
+        assert_eq!(stats.total, bytes.len());
+        assert!(stats.documents > 0 && stats.bitmaps > 0 && stats.fst > 0);
+        // an in memory index takes roughly the size of its serialized form
+        assert!(stats.memory > stats.total / 2 && stats.memory < stats.total * 2);
+        insta::assert_snapshot!(stats, @r###"
+        documents       317 B   27.1%
+        bitmaps         432 B   37.0%
+        fst             407 B   34.8%
+        positions         0 B    0.0%
+        payloads          0 B    0.0%
+        other             0 B    0.0%
+        total         1.1 KiB  100.0%
+        in memory     1.2 KiB  109.5%
+        "###);
+
+        // every section is counted
+        let documents = ["kefir le chien", "tamo le chat", "kefir"];
+        let mut bytes = Vec::new();
+        IndexBuilder::new()
+            .with_positions(true)
+            .with_stop_words(&["le"])
+            .construct_with_payloads(&documents.map(|document| (document, 1)), &mut bytes)
+            .unwrap();
+        let mut index = Index::from_bytes(&bytes).unwrap();
+        index.delete_documents(&RoaringBitmap::from_iter([1]));
+        let stats = index.size_stats();
+        let mut bytes = Vec::new();
+        index.write_to(&mut bytes).unwrap();
+        assert_eq!(stats.total, bytes.len());
+        insta::assert_debug_snapshot!(stats, @r###"
+        IndexStats {
+            documents: 47,
+            bitmaps: 40,
+            fst: 96,
+            positions: 37,
+            payloads: 29,
+            other: 34,
+            total: 296,
+            memory: 363,
+        }
+        "###);
+
+        let mut bytes = Vec::new();
+        IndexBuilder::new()
+            .with_documents(false)
+            .construct(&documents, &mut bytes)
+            .unwrap();
+        let stats = Index::from_bytes(&bytes).unwrap().size_stats();
+        assert_eq!(stats.total, bytes.len());
+    }
+}
@@ -0,0 +1,166 @@
+//! Reduce the words to their stem after normalizing them so `chiens` can be found with `chien`.
+//! Like the normalizers, the stemmer is stored in the index since the documents
+//! and the queries must be stemmed in the same language.
+
+use std::borrow::Cow;
+
+/// The language of the stemmer, see [`crate::IndexBuilder::with_stemmer`].
+/// Every language except [`Stemmer::None`] requires the `stemming` feature to construct or load the index.
+/// The stemmers of the languages that aren't written in the latin alphabet are useless with the
+/// [`crate::NormalizerKind::Ascii`] normalizer since it removes their letters.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Stemmer {
+    /// The words are kept as they are.
+    #[default]
+    None = 0,
+    #[cfg(feature = "stemming")]
+    Arabic = 1,
+    #[cfg(feature = "stemming")]
+    Danish = 2,
+    #[cfg(feature = "stemming")]
+    Dutch = 3,
+    #[cfg(feature = "stemming")]
+    English = 4,
+    #[cfg(feature = "stemming")]
+    Finnish = 5,
+    #[cfg(feature = "stemming")]
+    French = 6,
+    #[cfg(feature = "stemming")]
+    German = 7,
+    #[cfg(feature = "stemming")]
+    Greek = 8,
+    #[cfg(feature = "stemming")]
+    Hungarian = 9,
+    #[cfg(feature = "stemming")]
+    Italian = 10,
+    #[cfg(feature = "stemming")]
+    Norwegian = 11,
+    #[cfg(feature = "stemming")]
+    Portuguese = 12,
+    #[cfg(feature = "stemming")]
+    Romanian = 13,
+    #[cfg(feature = "stemming")]
+    Russian = 14,
+    #[cfg(feature = "stemming")]
+    Spanish = 15,
+    #[cfg(feature = "stemming")]
+    Swedish = 16,
+    #[cfg(feature = "stemming")]
+    Tamil = 17,
+    #[cfg(feature = "stemming")]
+    Turkish = 18,
+}
+
+impl Stemmer {
+    pub(crate) fn from_id(id: u8) -> Option<Self> {
+        #[cfg(feature = "stemming")]
+        const LANGUAGES: [Stemmer; 18] = [
+            Stemmer::Arabic,
+            Stemmer::Danish,
+            Stemmer::Dutch,
+            Stemmer::English,
+            Stemmer::Finnish,
+            Stemmer::French,
+            Stemmer::German,
+            Stemmer::Greek,
+            Stemmer::Hungarian,
+            Stemmer::Italian,
+            Stemmer::Norwegian,
+            Stemmer::Portuguese,
+            Stemmer::Romanian,
+            Stemmer::Russian,
+            Stemmer::Spanish,
+            Stemmer::Swedish,
+            Stemmer::Tamil,
+            Stemmer::Turkish,
+        ];
+
+        match id {
+            0 => Some(Self::None),
+            #[cfg(feature = "stemming")]
+            id => LANGUAGES.get(id as usize - 1).copied(),
+            #[cfg(not(feature = "stemming"))]
+            _ => None,
+        }
+    }
+
+    /// Returns the stem of a normalized word.
+    pub fn stem<'w>(&self, word: &'w str) -> Cow<'w, str> {
+        #[cfg(feature = "stemming")]
+        {
+            use rust_stemmers::Algorithm;
+
+            let algorithm = match self {
+                Self::None => return Cow::Borrowed(word),
+                Self::Arabic => Algorithm::Arabic,
+                Self::Danish => Algorithm::Danish,
+                Self::Dutch => Algorithm::Dutch,
+                Self::English => Algorithm::English,
+                Self::Finnish => Algorithm::Finnish,
+                Self::French => Algorithm::French,
+                Self::German => Algorithm::German,
+                Self::Greek => Algorithm::Greek,
+                Self::Hungarian => Algorithm::Hungarian,
+                Self::Italian => Algorithm::Italian,
+                Self::Norwegian => Algorithm::Norwegian,
+                Self::Portuguese => Algorithm::Portuguese,
+                Self::Romanian => Algorithm::Romanian,
+                Self::Russian => Algorithm::Russian,
+                Self::Spanish => Algorithm::Spanish,
+                Self::Swedish => Algorithm::Swedish,
+                Self::Tamil => Algorithm::Tamil,
+                Self::Turkish => Algorithm::Turkish,
+            };
+            rust_stemmers::Stemmer::create(algorithm).stem(word)
+        }
+        #[cfg(not(feature = "stemming"))]
+        {
+            Cow::Borrowed(word)
+        }
+    }
+}
+
+#[cfg(all(test, feature = "stemming"))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_stem() {
+        let stem = |stemmer: Stemmer, words: &[&str]| {
+            words
+                .iter()
+                .map(|word| stemmer.stem(word).into_owned())
+                .collect::<Vec<_>>()
+        };
+        insta::assert_debug_snapshot!(stem(Stemmer::French, &["chiens", "chien", "chevaux", "mangeait"]), @r###"
+        [
+            "chien",
+            "chien",
+            "cheval",
+            "mang",
+        ]
+        "###);
+        insta::assert_debug_snapshot!(stem(Stemmer::English, &["running", "run", "ponies"]), @r###"
+        [
+            "run",
+            "run",
+            "poni",
+        ]
+        "###);
+        insta::assert_debug_snapshot!(stem(Stemmer::None, &["chiens", "running"]), @r###"
+        [
+            "chiens",
+            "running",
+        ]
+        "###);
+
+        for id in 0..=u8::MAX {
+            if let Some(stemmer) = Stemmer::from_id(id) {
+                assert_eq!(stemmer as u8, id);
+            }
+        }
+        assert_eq!(Stemmer::from_id(18), Some(Stemmer::Turkish));
+        assert_eq!(Stemmer::from_id(19), None);
+    }
+}
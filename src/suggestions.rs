@@ -0,0 +1,335 @@
+//! Suggest words from the fst instead of documents, to complete the word the user is typing
+//! or to correct the typos of a query.
+
+use std::{cmp::Reverse, collections::BinaryHeap};
+
+use fst::{automaton::Str, Automaton, IntoStreamer, Streamer};
+
+use crate::{
+    deadline::Deadline, dfas::Dfas, levenshtein_builders, typo_budget, Index, Matching, Normalizer,
+    QueryItem, Search, WordCandidate,
+};
+
+/// The maximum number of words of the fst looked at by a completion, the next ones are ignored.
+/// Like [`crate::Search::with_max_candidates_per_word`] it bounds the work of the short prefixes.
+pub const MAX_COMPLETION_WORDS: usize = 10_000;
+
+impl Index<'_> {
+    /// Returns up to `limit` words of the index starting with `prefix` with the number of documents containing them,
+    /// the most frequent first. The deleted documents are not counted.
+    /// Only the first [`MAX_COMPLETION_WORDS`] words starting with the prefix, in the alphabetical order, are considered.
+    ///
+    /// ```
+    /// use zearch::Index;
+    ///
+    /// let index = Index::new_in_memory(&["kefir", "kefir le chien", "keftas"]).unwrap();
+    /// assert_eq!(
+    ///     index.complete("Kef", 10),
+    ///     [(String::from("kefir"), 2), (String::from("keftas"), 1)]
+    /// );
+    /// ```
+    pub fn complete(&self, prefix: &str, limit: usize) -> Vec<(String, u64)> {
+        // a prefix can't be stemmed, it's only normalized
+        let prefix = self.normalizer.normalize(prefix);
+        self.completions(Str::new(&prefix).starts_with(), limit)
+    }
+
+    /// Same as [`Index::complete`] but tolerates as many typos in the prefix as the search does.
+    pub fn complete_fuzzy(&self, prefix: &str, limit: usize) -> Vec<(String, u64)> {
+        let prefix = self.normalizer.normalize(prefix);
+        let dfa = levenshtein_builders()[typo_budget(&prefix)].build_prefix_dfa(&prefix);
+        self.completions(&dfa, limit)
+    }
+
+    fn completions(&self, automaton: impl Automaton, limit: usize) -> Vec<(String, u64)> {
+        if limit == 0 {
+            return Vec::new();
+        }
+        // the `limit` best words seen so far, the worst one on top: the least frequent, then the last in the alphabetical order
+        let mut best = BinaryHeap::with_capacity(limit.min(MAX_COMPLETION_WORDS) + 1);
+        let mut stream = self.fst.search(automaton).into_stream();
+        let mut visited = 0;
+        while let Some((word, id)) = stream.next() {
+            if visited == MAX_COMPLETION_WORDS {
+                break;
+            }
+            visited += 1;
+            let frequency = self.bitmaps.get(id).difference_len(&self.deleted);
+            // the words come sorted from the fst, a word as frequent as the worst one can't replace it
+            let worse = |(Reverse(worst), _): &(Reverse<u64>, _)| frequency <= *worst;
+            if frequency == 0 || (best.len() == limit && best.peek().is_some_and(worse)) {
+                continue;
+            }
+            best.push((
+                Reverse(frequency),
+                String::from_utf8_lossy(word).into_owned(),
+            ));
+            if best.len() > limit {
+                best.pop();
+            }
+        }
+        best.into_sorted_vec()
+            .into_iter()
+            .map(|(Reverse(frequency), word)| (word, frequency))
+            .collect()
+    }
+
+    /// Returns the query with every word that doesn't match any document replaced by the most frequent word
+    /// of the index it could be a typo of, or `None` if every word already matches.
+    /// The query is parsed like a search, see [`crate::Query`]: the words of the phrases are corrected too
+    /// while the negations are kept as they were typed. Like in a search the last term can be a prefix,
+    /// and the suggested words are normalized.
+    ///
+    /// ```
+    /// use zearch::Index;
+    ///
+    /// let index = Index::new_in_memory(&["le beau chien", "le bon chien"]).unwrap();
+    /// assert_eq!(index.suggest("le baeu chein").as_deref(), Some("le beau chien"));
+    /// assert_eq!(index.suggest("le beau ch"), None);
+    /// ```
+    pub fn suggest(&self, query: &str) -> Option<String> {
+        let search = Search::new(query);
+        let mut suggestion = search.query().clone();
+        let mut corrected = false;
+        let mut dfas = Dfas::default();
+
+        // only the last item looked for can be a prefix
+        let last = search.query().positives().count().checked_sub(1);
+        let positives = suggestion
+            .items
+            .iter_mut()
+            .filter(|item| !matches!(item, QueryItem::Negation(_)));
+        for (position, item) in positives.enumerate() {
+            let texts = match item {
+                QueryItem::Term(term) => vec![(&mut term.text, Some(position) == last)],
+                // the words of a phrase are never a prefix
+                QueryItem::Phrase { words, .. } => {
+                    words.iter_mut().map(|word| (word, false)).collect()
+                }
+                QueryItem::Negation(_) => unreachable!("the negations were filtered out"),
+            };
+            for (text, prefix) in texts {
+                if let Some(correction) = self.correct_text(&search, text, prefix, &mut dfas) {
+                    *text = correction;
+                    corrected = true;
+                }
+            }
+        }
+
+        corrected.then(|| suggestion.to_string())
+    }
+
+    /// Returns the text with its misspelled words corrected, or `None` if they all match.
+    /// A text can contain multiple tokens like `c'est`, only the misspelled ones are replaced.
+    /// With `prefix` its last token can be the beginning of a word.
+    fn correct_text(
+        &self,
+        search: &Search,
+        text: &str,
+        prefix: bool,
+        dfas: &mut Dfas,
+    ) -> Option<String> {
+        let mut corrected = String::with_capacity(text.len());
+        let mut changed = false;
+        let mut end = 0;
+        for (token, normalized) in self.analyzer().words(text) {
+            corrected.push_str(&text[end..token.offset]);
+            end = token.offset + token.text.len();
+            let prefix = prefix && end == text.len();
+            match self.correct(search, &normalized, prefix, dfas) {
+                Some(correction) => {
+                    corrected.push_str(&correction);
+                    changed = true;
+                }
+                None => corrected.push_str(token.text),
+            }
+        }
+        corrected.push_str(&text[end..]);
+        changed.then_some(corrected)
+    }
+
+    /// Returns the most frequent word matching the normalized word with typos,
+    /// or `None` if the word matches without typos, is a stop word or doesn't match anything.
+    fn correct(
+        &self,
+        search: &Search,
+        normalized: &str,
+        prefix: bool,
+        dfas: &mut Dfas,
+    ) -> Option<String> {
+        if normalized.is_empty() || self.analyzer().is_stop_word(normalized) {
+            return None;
+        }
+        let mut candidate = WordCandidate::new(String::new(), normalized.to_string(), 0);
+        let mut matches = Vec::new();
+        let deadline = Deadline::never();
+        self.lookup_word(
+            search,
+            &mut candidate,
+            normalized,
+            Matching {
+                prefix,
+                typos: Some(dfas.get(normalized, prefix)),
+            },
+            &deadline,
+            |word, id| matches.push((word.to_string(), id)),
+        );
+        self.restrict(search, &mut candidate);
+        if !candidate.typos[0].is_empty() {
+            return None;
+        }
+
+        matches
+            .into_iter()
+            .map(|(word, id)| (self.bitmaps.get(id).difference_len(&self.deleted), word))
+            .filter(|(frequency, _)| *frequency > 0)
+            // with the same frequency we keep the first word in the alphabetical order
+            .max_by(|(left, left_word), (right, right_word)| {
+                left.cmp(right).then_with(|| right_word.cmp(left_word))
+            })
+            .map(|(_, word)| word)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use roaring::RoaringBitmap;
+
+    use super::*;
+    use crate::test::create_small_index;
+
+    #[test]
+    fn test_complete() {
+        let mut index = create_small_index();
+        insta::assert_debug_snapshot!(index.complete("kef", 10), @r###"
+        [
+            (
+                "kefir",
+                5,
+            ),
+            (
+                "kefirounet",
+                2,
+            ),
+            (
+                "kef",
+                1,
+            ),
+            (
+                "keftas",
+                1,
+            ),
+        ]
+        "###);
+        insta::assert_debug_snapshot!(index.complete("Kéf", 2), @r###"
+        [
+            (
+                "kefir",
+                5,
+            ),
+            (
+                "kefirounet",
+                2,
+            ),
+        ]
+        "###);
+        insta::assert_debug_snapshot!(index.complete("kefirz", 10), @"[]");
+        insta::assert_debug_snapshot!(index.complete_fuzzy("kafi", 10), @r###"
+        [
+            (
+                "kefir",
+                5,
+            ),
+            (
+                "kefirounet",
+                2,
+            ),
+        ]
+        "###);
+
+        // the deleted documents are not counted
+        index.delete_documents(&RoaringBitmap::from_iter([11]));
+        insta::assert_debug_snapshot!(index.complete("kef", 10), @r###"
+        [
+            (
+                "kefir",
+                5,
+            ),
+            (
+                "kefirounet",
+                2,
+            ),
+            (
+                "kef",
+                1,
+            ),
+        ]
+        "###);
+    }
+
+    #[test]
+    fn test_complete_many_words() {
+        // more words start with `k` than a completion looks at, the most frequent one comes last in the fst
+        let mut documents: Vec<String> = (0..MAX_COMPLETION_WORDS + 10)
+            .map(|i| format!("k{i:05}"))
+            .collect();
+        documents.extend(std::iter::repeat_n(String::from("kz k00002 k00001"), 3));
+        let documents: Vec<&str> = documents.iter().map(String::as_str).collect();
+        let index = Index::new_in_memory(&documents).unwrap();
+
+        // `kz` is the most frequent word but it's past the words looked at
+        insta::assert_debug_snapshot!(index.complete("k", 3), @r###"
+        [
+            (
+                "k00001",
+                4,
+            ),
+            (
+                "k00002",
+                4,
+            ),
+            (
+                "k00000",
+                1,
+            ),
+        ]
+        "###);
+        assert!(index.complete("k", 0).is_empty());
+        assert_eq!(index.complete("k", usize::MAX).len(), MAX_COMPLETION_WORDS);
+        // a longer prefix reaches the last words
+        assert_eq!(index.complete("kz", 3), [(String::from("kz"), 3)]);
+    }
+
+    #[test]
+    fn test_suggest() {
+        let index = create_small_index();
+        insta::assert_debug_snapshot!(index.suggest("kefor le bau chien"), @r###"
+        Some(
+            "kefir le beau chien",
+        )
+        "###);
+        insta::assert_debug_snapshot!(index.suggest("kefir le beau chien"), @"None");
+        // the last word is a prefix
+        insta::assert_debug_snapshot!(index.suggest("kefir le beau chi"), @"None");
+        insta::assert_debug_snapshot!(index.suggest("Tamo  c'ets -pony pooney"), @r###"
+        Some(
+            "Tamo c'est -pony poney",
+        )
+        "###);
+        insta::assert_debug_snapshot!(index.suggest("xyzzy kefir"), @"None");
+
+        // the query is parsed like a search: the words of a phrase are corrected but never as a prefix,
+        // the weights are kept and a negated phrase stays as it was typed
+        insta::assert_debug_snapshot!(index.suggest(r#""le ptit" kefor^2 -"tamo bo""#), @r###"
+        Some(
+            "\"le petit\" kefir^2 -\"tamo bo\"",
+        )
+        "###);
+        // `pet` would be the beginning of `petit` if it wasn't in a phrase
+        insta::assert_debug_snapshot!(index.suggest(r#"kefir "le pet""#), @r###"
+        Some(
+            "kefir \"le peu\"",
+        )
+        "###);
+    }
+}
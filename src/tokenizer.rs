@@ -0,0 +1,289 @@
+//! Split the documents and the queries in words before normalizing them.
+//! The same tokenizer must be used to construct and to search an index, thus
+//! only the built-in tokenizers can be used and their id is stored in the index.
+//! A tokenizer must never change once released, a new one with a new id has to be added instead.
+
+use std::ops::Range;
+
+/// A word of a text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Token<'s> {
+    pub text: &'s str,
+    /// The position of the word in the text, in bytes.
+    pub offset: usize,
+}
+
+/// A word of a text with its normalized version, as it's indexed and searched, see [`crate::Index::tokenize`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AnalyzedToken<'s> {
+    /// The word as it's written in the text.
+    pub original: &'s str,
+    /// The word normalized and stemmed, never empty.
+    pub normalized: String,
+    /// The position of the word in the text, in bytes.
+    pub byte_range: Range<usize>,
+    /// The position of the word among the words of the text.
+    pub index: usize,
+}
+
+pub trait Tokenizer {
+    fn tokenize<'s>(&self, text: &'s str) -> impl Iterator<Item = Token<'s>>;
+}
+
+/// Split the text on the whitespaces, `Aix-en-Provence` and `c'est` are a single word.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Whitespace;
+
+impl Tokenizer for Whitespace {
+    fn tokenize<'s>(&self, text: &'s str) -> impl Iterator<Item = Token<'s>> {
+        Tokens::new(text, char::is_whitespace, false)
+    }
+}
+
+/// Split the text on the whitespaces and apostrophes, `l'avion` is made of the words `l` and `avion`.
+/// With `elision` the one letter words and `qu` followed by an apostrophe are dropped,
+/// `l'avion` is then only made of `avion`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Apostrophes {
+    pub elision: bool,
+}
+
+impl Tokenizer for Apostrophes {
+    fn tokenize<'s>(&self, text: &'s str) -> impl Iterator<Item = Token<'s>> {
+        Tokens::new(text, is_apostrophe_or_whitespace, self.elision)
+    }
+}
+
+/// Split the text on the whitespaces, hyphens, apostrophes and underscores,
+/// `Aix-en-Provence` is made of the words `Aix`, `en` and `Provence`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Separators;
+
+impl Tokenizer for Separators {
+    fn tokenize<'s>(&self, text: &'s str) -> impl Iterator<Item = Token<'s>> {
+        Tokens::new(text, is_separator, false)
+    }
+}
+
+fn is_apostrophe(c: char) -> bool {
+    matches!(c, '\'' | '’')
+}
+
+fn is_apostrophe_or_whitespace(c: char) -> bool {
+    c.is_whitespace() || is_apostrophe(c)
+}
+
+fn is_separator(c: char) -> bool {
+    c.is_whitespace() || is_apostrophe(c) || matches!(c, '-' | '_')
+}
+
+/// The tokenizers that can be stored in an index, see [`crate::IndexBuilder::with_tokenizer`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum TokenizerKind {
+    /// See [`Whitespace`], the default of the indexes constructed before the apostrophes were split.
+    Whitespace = 0,
+    /// See [`Separators`].
+    Separators = 1,
+    /// See [`Apostrophes`].
+    #[default]
+    Apostrophes = 2,
+    /// See [`Apostrophes`] with the elision.
+    FrenchElision = 3,
+}
+
+impl TokenizerKind {
+    pub(crate) fn from_id(id: u8) -> Option<Self> {
+        match id {
+            0 => Some(Self::Whitespace),
+            1 => Some(Self::Separators),
+            2 => Some(Self::Apostrophes),
+            3 => Some(Self::FrenchElision),
+            _ => None,
+        }
+    }
+}
+
+impl Tokenizer for TokenizerKind {
+    fn tokenize<'s>(&self, text: &'s str) -> impl Iterator<Item = Token<'s>> {
+        match self {
+            Self::Whitespace => Tokens::new(text, char::is_whitespace, false),
+            Self::Separators => Tokens::new(text, is_separator, false),
+            Self::Apostrophes => Tokens::new(text, is_apostrophe_or_whitespace, false),
+            Self::FrenchElision => Tokens::new(text, is_apostrophe_or_whitespace, true),
+        }
+    }
+}
+
+/// Returns the non-empty words between the separators.
+struct Tokens<'s> {
+    text: &'s str,
+    offset: usize,
+    is_separator: fn(char) -> bool,
+    elision: bool,
+}
+
+impl<'s> Tokens<'s> {
+    fn new(text: &'s str, is_separator: fn(char) -> bool, elision: bool) -> Self {
+        Self {
+            text,
+            offset: 0,
+            is_separator,
+            elision,
+        }
+    }
+
+    /// Returns `true` if the word is an elided article or pronoun like the `l` of `l'avion`.
+    fn is_elided(&self, word: &str, end: usize) -> bool {
+        let followed_by_apostrophe = self.text[end..].starts_with(is_apostrophe);
+        let is_short = word.chars().count() == 1 || word.eq_ignore_ascii_case("qu");
+        followed_by_apostrophe && is_short
+    }
+}
+
+impl<'s> Iterator for Tokens<'s> {
+    type Item = Token<'s>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let rest = &self.text[self.offset..];
+            let start = self.offset + rest.find(|c| !(self.is_separator)(c))?;
+            let end = self.text[start..]
+                .find(self.is_separator)
+                .map_or(self.text.len(), |end| start + end);
+            self.offset = end;
+
+            let text = &self.text[start..end];
+            if self.elision && self.is_elided(text, end) {
+                continue;
+            }
+            return Some(Token {
+                text,
+                offset: start,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_tokenizers() {
+        let text = "  Aix-en-Provence l'avion\tsnake_case ";
+        let words = |tokenizer: TokenizerKind| {
+            tokenizer
+                .tokenize(text)
+                .map(|token| (token.text, token.offset))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            words(TokenizerKind::Whitespace),
+            Whitespace
+                .tokenize(text)
+                .map(|token| (token.text, token.offset))
+                .collect::<Vec<_>>()
+        );
+        insta::assert_debug_snapshot!(words(TokenizerKind::Whitespace), @r###"
+        [
+            (
+                "Aix-en-Provence",
+                2,
+            ),
+            (
+                "l'avion",
+                18,
+            ),
+            (
+                "snake_case",
+                26,
+            ),
+        ]
+        "###);
+        insta::assert_debug_snapshot!(words(TokenizerKind::Apostrophes), @r###"
+        [
+            (
+                "Aix-en-Provence",
+                2,
+            ),
+            (
+                "l",
+                18,
+            ),
+            (
+                "avion",
+                20,
+            ),
+            (
+                "snake_case",
+                26,
+            ),
+        ]
+        "###);
+        insta::assert_debug_snapshot!(words(TokenizerKind::FrenchElision), @r###"
+        [
+            (
+                "Aix-en-Provence",
+                2,
+            ),
+            (
+                "avion",
+                20,
+            ),
+            (
+                "snake_case",
+                26,
+            ),
+        ]
+        "###);
+        insta::assert_debug_snapshot!(
+            Apostrophes { elision: true }
+                .tokenize("Qu’il d'Artagnan s' l'a a' jusqu'à")
+                .map(|token| token.text)
+                .collect::<Vec<_>>(),
+            @r###"
+        [
+            "il",
+            "Artagnan",
+            "a",
+            "jusqu",
+            "à",
+        ]
+        "###
+        );
+        insta::assert_debug_snapshot!(words(TokenizerKind::Separators), @r###"
+        [
+            (
+                "Aix",
+                2,
+            ),
+            (
+                "en",
+                6,
+            ),
+            (
+                "Provence",
+                9,
+            ),
+            (
+                "l",
+                18,
+            ),
+            (
+                "avion",
+                20,
+            ),
+            (
+                "snake",
+                26,
+            ),
+            (
+                "case",
+                32,
+            ),
+        ]
+        "###);
+    }
+}
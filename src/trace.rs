@@ -0,0 +1,131 @@
+//! Helpers for the `tracing` feature, they do nothing when it's disabled.
+//! The spans themselves are created with `#[cfg_attr(feature = "tracing", tracing::instrument(...))]`
+//! on the functions of every phase of the search and the construction.
+
+/// Records the time spent in the current span in its `elapsed_us` field when it's dropped.
+/// The span must declare the field, `fields(elapsed_us)`.
+#[must_use]
+pub(crate) struct Elapsed {
+    #[cfg(feature = "tracing")]
+    span: tracing::Span,
+    #[cfg(feature = "tracing")]
+    start: crate::Instant,
+}
+
+impl Elapsed {
+    pub fn start() -> Self {
+        Self {
+            #[cfg(feature = "tracing")]
+            span: tracing::Span::current(),
+            #[cfg(feature = "tracing")]
+            start: crate::Instant::now(),
+        }
+    }
+}
+
+#[cfg(feature = "tracing")]
+impl Drop for Elapsed {
+    fn drop(&mut self) {
+        self.span
+            .record("elapsed_us", self.start.elapsed().as_micros() as u64);
+    }
+}
+
+#[cfg(all(test, feature = "tracing"))]
+mod test {
+    use std::sync::{Arc, Mutex};
+
+    use tracing_subscriber::fmt::{format::FmtSpan, MakeWriter};
+
+    use crate::{Index, Search};
+
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl std::io::Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl MakeWriter<'_> for Buffer {
+        type Writer = Self;
+
+        fn make_writer(&self) -> Self::Writer {
+            self.clone()
+        }
+    }
+
+    /// Returns the lines logged by the subscriber with the timings replaced by `_`.
+    fn trace(f: impl FnOnce()) -> Vec<String> {
+        let buffer = Buffer::default();
+        let subscriber = tracing_subscriber::fmt()
+            .with_writer(buffer.clone())
+            .with_max_level(tracing::Level::TRACE)
+            .with_span_events(FmtSpan::CLOSE)
+            .without_time()
+            .with_target(false)
+            .with_level(false)
+            .finish();
+        tracing::subscriber::with_default(subscriber, f);
+
+        let logs = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        logs.lines()
+            .map(|line| {
+                let mut line = line.to_string();
+                while let Some(start) = line.find("elapsed_us=").map(|i| i + "elapsed_us=".len()) {
+                    let end = line[start..]
+                        .find(|c: char| !c.is_ascii_digit())
+                        .map_or(line.len(), |end| start + end);
+                    if end == start {
+                        break;
+                    }
+                    line.replace_range(start..end, "_");
+                }
+                line
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_spans() {
+        let logs = trace(|| {
+            let mut bytes = Vec::new();
+            Index::construct(&["kefir le chien", "tamo le chat"], &mut bytes).unwrap();
+        });
+        insta::assert_debug_snapshot!(logs, @r###"
+        [
+            "construct_to{documents=2}:build_fst{words=6 elapsed_us=_}: close",
+            "construct_to{documents=2 elapsed_us=_}: close",
+        ]
+        "###);
+
+        let index = Index::new_in_memory(&["kefir le chien", "tamo le chat"]).unwrap();
+        let logs = trace(|| {
+            index.search(&Search::new("kefir chien"));
+        });
+        // every call to a ranking rule is logged, we only keep the first ones
+        let (next, logs): (Vec<String>, Vec<String>) =
+            logs.into_iter().partition(|line| line.contains(": next "));
+        insta::assert_debug_snapshot!(logs, @r###"
+        [
+            "search{query_len=11 limit=10}:get_candidates: candidates word=\"kefir\" candidates=1",
+            "search{query_len=11 limit=10}:get_candidates: candidates word=\"chien\" candidates=1",
+            "search{query_len=11 limit=10}:get_candidates{words=2 elapsed_us=_}: close",
+            "search{query_len=11 limit=10}:bucket_sort{universe=1 buckets=1 elapsed_us=_}: close",
+            "search{query_len=11 limit=10 results=1 elapsed_us=_}: close",
+        ]
+        "###);
+        insta::assert_debug_snapshot!(&next[..3], @r###"
+        [
+            "search{query_len=11 limit=10}:bucket_sort{universe=1}: next rule=\"word\"",
+            "search{query_len=11 limit=10}:bucket_sort{universe=1}: next rule=\"typo\"",
+            "search{query_len=11 limit=10}:bucket_sort{universe=1}: next rule=\"exact\" bucket=0",
+        ]
+        "###);
+    }
+}
@@ -0,0 +1,322 @@
+//! When the user is typing, every new query is the previous one with one more letter.
+//! All the words except the last one are the same and the words matching the last one
+//! are a subset of the words that matched the shorter prefix.
+//! Instead of streaming the whole fst again we only check the words we've already found.
+
+use fst::Automaton;
+use roaring::RoaringBitmap;
+
+use crate::{
+    deadline::Deadline, dfas::Dfas, typo_budget, Index, Matching, QueryWord, QueryWords, Search,
+    TypoTolerance, WordCandidate,
+};
+
+/// Remembers the candidates of the last query to speed up the next one if it extends it.
+/// If the new query doesn't extend the previous one we fall back to a normal search.
+///
+/// ```
+/// use zearch::{Index, Search, TypeaheadSession};
+///
+/// let index = Index::new_in_memory(&["paris", "parisien", "pau"]).unwrap();
+/// let mut session = TypeaheadSession::new(&index);
+/// for query in ["p", "pa", "par", "pari", "paris"] {
+///     assert_eq!(session.search(&Search::new(query)), index.search(&Search::new(query)));
+/// }
+/// ```
+pub struct TypeaheadSession<'a> {
+    index: &'a Index<'a>,
+    // the candidates of the last query, without the filter and tombstones applied
+    words: Vec<WordCandidate>,
+    // every word of the fst that matched the last word of the query
+    last_matches: Vec<(String, u64)>,
+    // the parameters of the last query that changes the candidates
+    max_candidates_per_word: usize,
+    min_prefix_len: usize,
+    typo_tolerance: TypoTolerance,
+    // if the last word hit the `max_candidates_per_word` we can't refine it
+    truncated: bool,
+    buckets: Vec<RoaringBitmap>,
+    // the words before the last one are repeated by every query
+    dfas: Dfas,
+}
+
+impl<'a> TypeaheadSession<'a> {
+    pub fn new(index: &'a Index<'a>) -> Self {
+        Self {
+            index,
+            words: Vec::new(),
+            last_matches: Vec::new(),
+            max_candidates_per_word: 0,
+            min_prefix_len: 0,
+            typo_tolerance: TypoTolerance::default(),
+            truncated: true,
+            buckets: Vec::new(),
+            dfas: Dfas::default(),
+        }
+    }
+
+    /// Returns the same results as [`Index::search`].
+    pub fn search(&mut self, search: &Search) -> Vec<u32> {
+        let QueryWords { words, prefix, .. } = self.index.query_words(search);
+
+        let deadline = Deadline::new(search);
+        if self.extends(search, &words) {
+            self.refine(&words);
+        } else {
+            self.compute(search, &words, prefix, &deadline);
+        }
+
+        // the compounds depend on the whole query thus we never keep them in the session
+        let mut candidates = self.words.clone();
+        self.index.lookup_compounds(&mut candidates);
+        // the weights don't change the candidates, they're given to the words of every query
+        for (candidate, word) in candidates.iter_mut().zip(&words) {
+            self.index.restrict(search, candidate);
+            candidate.weight = word.weight;
+        }
+
+        self.index
+            .search_with_candidates(search, &mut candidates, &mut self.buckets, &deadline)
+    }
+
+    /// Returns `true` if the new query only added letters to the last word of the previous query.
+    fn extends(&self, search: &Search, words: &[QueryWord]) -> bool {
+        if self.truncated
+            || words.is_empty()
+            // the words of a phrase are looked for without typos
+            || words.iter().any(|word| word.exact)
+            || words.len() != self.words.len()
+            || search.max_candidates_per_word != self.max_candidates_per_word
+            || search.min_prefix_len != self.min_prefix_len
+            || search.typo_tolerance != self.typo_tolerance
+            // the synonyms of the last word are not among its matches
+            || search.synonyms.is_some()
+        {
+            return false;
+        }
+
+        let (last, others) = words.split_last().unwrap();
+        let previous = self.words.last().unwrap();
+
+        others
+            .iter()
+            .zip(&self.words)
+            .all(|(word, candidate)| word.original == candidate.original)
+            // the previous query must have been a prefix search
+            && previous.normalized.len() >= search.min_prefix_len
+            && last.normalized.starts_with(&previous.normalized)
+            // with more typos allowed we could match words we never saw
+            && typo_budget(&last.normalized) == typo_budget(&previous.normalized)
+    }
+
+    /// Only keep the words that still match the last word of the query.
+    fn refine(&mut self, words: &[QueryWord]) {
+        let last = words.last().unwrap();
+        let dfa = self.dfas.get(&last.normalized, true);
+
+        let mut candidate = WordCandidate::new(
+            last.original.to_string(),
+            last.normalized.clone(),
+            words.len() - 1,
+        );
+        self.last_matches.retain(|(word, id)| {
+            let state = word
+                .bytes()
+                .fold(dfa.start(), |state, byte| dfa.accept(&state, byte));
+            if dfa.is_match(&state) {
+                candidate.insert_with_maybe_typo(
+                    word,
+                    *id,
+                    &self.index.bitmaps.get(*id),
+                    self.typo_tolerance,
+                );
+                true
+            } else {
+                false
+            }
+        });
+
+        *self.words.last_mut().unwrap() = candidate;
+    }
+
+    /// Compute all the candidates from scratch.
+    fn compute(
+        &mut self,
+        search: &Search,
+        words: &[QueryWord],
+        prefix: Option<usize>,
+        deadline: &Deadline,
+    ) {
+        self.words.clear();
+        self.last_matches.clear();
+        self.max_candidates_per_word = search.max_candidates_per_word;
+        self.min_prefix_len = search.min_prefix_len;
+        self.typo_tolerance = search.typo_tolerance;
+        // when the last word is a duplicate the prefix is on another word that we can't refine,
+        // the last word of a phrase is never a prefix
+        self.truncated = prefix != words.len().checked_sub(1);
+
+        for (index, word) in words.iter().enumerate() {
+            let normalized = &word.normalized;
+            let mut candidates =
+                WordCandidate::new(word.original.to_string(), normalized.clone(), index);
+            let prefix = prefix == Some(index) && normalized.len() >= search.min_prefix_len;
+            if word.exact {
+                self.index.lookup_word(
+                    search,
+                    &mut candidates,
+                    normalized,
+                    Matching {
+                        prefix: false,
+                        typos: None,
+                    },
+                    deadline,
+                    |_, _| (),
+                );
+            } else if index == words.len() - 1 {
+                let last_matches = &mut self.last_matches;
+                self.truncated |= !self.index.lookup_word(
+                    search,
+                    &mut candidates,
+                    normalized,
+                    Matching {
+                        prefix,
+                        typos: Some(self.dfas.get(normalized, prefix)),
+                    },
+                    deadline,
+                    |word, id| last_matches.push((word.to_string(), id)),
+                );
+            } else {
+                self.index.lookup_word(
+                    search,
+                    &mut candidates,
+                    normalized,
+                    Matching {
+                        prefix,
+                        typos: Some(self.dfas.get(normalized, prefix)),
+                    },
+                    deadline,
+                    |_, _| (),
+                );
+            }
+            self.index
+                .lookup_synonyms(search, &mut candidates, normalized);
+            self.words.push(candidates);
+        }
+        // the words of the query that were cut by the deadline must be computed again by the next search
+        self.truncated |= deadline.was_reached();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test::seeded_random;
+
+    #[test]
+    fn test_same_results_as_a_cold_search() {
+        let index = Index::new_in_memory(&[
+            "Paris",
+            "Parisien",
+            "Pari sportif",
+            "Paris Saint-Germain",
+            "Le petit parisien",
+            "Parti",
+            "Pau",
+            "Marseille",
+            "Saint-Malo",
+            "Saint-Germain-en-Laye",
+        ])
+        .unwrap();
+        let filter: RoaringBitmap = (0..5).collect();
+
+        let mut session = TypeaheadSession::new(&index);
+        let queries = [
+            "p",
+            "pa",
+            "par",
+            "pari",
+            "paris",
+            "parisi",
+            "parisie",
+            "parisien",
+            "parisie",
+            "paris",
+            "paris ",
+            "paris s",
+            "paris sa",
+            "paris sai",
+            "paris saint",
+            "pari sai",
+            "marseille",
+            "",
+            "saint",
+            "saint-g",
+            "-paris saint",
+            "saint-germain",
+            "saint paris sai",
+            "saint paris saint",
+            "saint paris sainte",
+        ];
+        for query in queries {
+            for filter in [None, Some(&filter)] {
+                let mut search = Search::new(query);
+                if let Some(filter) = filter {
+                    search.with_filter(filter);
+                }
+                assert_eq!(
+                    session.search(&search),
+                    index.search(&search),
+                    "{query:?} filtered: {}",
+                    filter.is_some()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_refine_capped_words() {
+        let words: Vec<String> = (0..100).map(|i| format!("word{i}")).collect();
+        let words: Vec<&str> = words.iter().map(|word| word.as_str()).collect();
+        let index = Index::new_in_memory(&words).unwrap();
+
+        let mut session = TypeaheadSession::new(&index);
+        for query in ["w", "wo", "wor", "word", "word1", "word12"] {
+            let mut search = Search::new(query);
+            search.with_max_candidates_per_word(20).with_limit(100);
+            assert_eq!(session.search(&search), index.search(&search), "{query:?}");
+        }
+    }
+
+    #[test]
+    #[ignore]
+    fn bench_typeahead() {
+        // generate a lot of different words so the fst is large
+        let mut random = seeded_random(42);
+        let mut word = || {
+            (0..8)
+                .map(|_| (b'a' + random(26) as u8) as char)
+                .collect::<String>()
+        };
+        let documents: Vec<String> = (0..100_000)
+            .map(|_| format!("{} {} {}", word(), word(), word()))
+            .collect();
+        let documents: Vec<&str> = documents.iter().map(|doc| doc.as_str()).collect();
+        let index = Index::new_in_memory(&documents).unwrap();
+        let queries = ["kef", "kefi", "kefir", "kefiro", "kefirou", "kefirous"];
+
+        let now = std::time::Instant::now();
+        for query in queries {
+            index.search(&Search::new(query));
+        }
+        let cold = now.elapsed();
+
+        let mut session = TypeaheadSession::new(&index);
+        let now = std::time::Instant::now();
+        for query in queries {
+            session.search(&Search::new(query));
+        }
+        let typeahead = now.elapsed();
+        assert!(typeahead < cold, "{typeahead:?} {cold:?}");
+    }
+}
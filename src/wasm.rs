@@ -0,0 +1,113 @@
+//! JavaScript bindings generated by `wasm-bindgen`, with the `wasm` feature.
+//! The errors are thrown as JavaScript exceptions instead of aborting the wasm instance.
+//!
+//! ```js
+//! const bytes = new Uint8Array(await (await fetch("index.zearch")).arrayBuffer());
+//! const index = WasmIndex.fromBytes(bytes);
+//! for (const { id, text } of index.search("kefir", 10)) {
+//!     console.log(id, text);
+//! }
+//! ```
+
+use js_sys::{Array, Object, Reflect, Uint8Array};
+use wasm_bindgen::prelude::*;
+
+use crate::{Index, Search};
+
+#[wasm_bindgen]
+pub struct WasmIndex {
+    // the bytes are copied out of the JavaScript memory, thus the index owns them
+    index: Index<'static>,
+}
+
+#[wasm_bindgen]
+impl WasmIndex {
+    /// Load an index written by [`Index::construct`] and verify its checksum.
+    #[wasm_bindgen(js_name = fromBytes)]
+    pub fn from_bytes(bytes: &Uint8Array) -> Result<WasmIndex, JsError> {
+        let index = Index::from_reader(bytes.to_vec().as_slice())?;
+        Ok(WasmIndex { index })
+    }
+
+    /// Returns the number of documents, the deleted documents are not counted.
+    #[wasm_bindgen(js_name = documentCount)]
+    pub fn document_count(&self) -> u32 {
+        self.index.document_count()
+    }
+
+    /// Returns an array of `{ id, text }`, the text is `undefined` if the documents weren't stored.
+    pub fn search(&self, query: &str, limit: usize) -> Result<Array, JsError> {
+        let mut search = Search::new(query);
+        search.with_limit(limit);
+        self.index
+            .search(&search)
+            .into_iter()
+            .map(|id| {
+                let text = self.index.get_document(id).map(JsValue::from);
+                object(&[
+                    ("id", JsValue::from(id)),
+                    ("text", text.unwrap_or(JsValue::UNDEFINED)),
+                ])
+            })
+            .collect()
+    }
+
+    /// Returns an array of `{ word, frequency }` completing the prefix, see [`Index::complete`].
+    pub fn complete(&self, prefix: &str, limit: usize) -> Result<Array, JsError> {
+        self.index
+            .complete(prefix, limit)
+            .into_iter()
+            .map(|(word, frequency)| {
+                object(&[
+                    ("word", JsValue::from(word)),
+                    // a document frequency is below `u32::MAX`, it's exact as a JavaScript number
+                    ("frequency", JsValue::from(frequency as f64)),
+                ])
+            })
+            .collect()
+    }
+}
+
+fn object(fields: &[(&str, JsValue)]) -> Result<JsValue, JsError> {
+    let object = Object::new();
+    for (key, value) in fields {
+        Reflect::set(&object, &JsValue::from_str(key), value)
+            .map_err(|_| JsError::new("cannot set a field of the result"))?;
+    }
+    Ok(object.into())
+}
+
+#[cfg(all(test, target_arch = "wasm32"))]
+mod test {
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    use super::*;
+
+    #[wasm_bindgen_test]
+    fn test_search() {
+        let mut bytes = Vec::new();
+        Index::construct(&["kefir le chien", "tamo le chat", "kefir"], &mut bytes).unwrap();
+        let index = WasmIndex::from_bytes(&Uint8Array::from(bytes.as_slice())).unwrap();
+        assert_eq!(index.document_count(), 3);
+
+        let results = index.search("kefir", 10).unwrap();
+        let ids: Vec<_> = results
+            .iter()
+            .map(|result| {
+                Reflect::get(&result, &"id".into())
+                    .unwrap()
+                    .as_f64()
+                    .unwrap()
+            })
+            .collect();
+        assert_eq!(ids, [0.0, 2.0]);
+        let text = Reflect::get(&results.get(0), &"text".into()).unwrap();
+        assert_eq!(text.as_string().as_deref(), Some("kefir le chien"));
+
+        let completions = index.complete("ta", 10).unwrap();
+        let word = Reflect::get(&completions.get(0), &"word".into()).unwrap();
+        assert_eq!(word.as_string().as_deref(), Some("tamo"));
+
+        assert!(WasmIndex::from_bytes(&Uint8Array::from(&bytes[1..])).is_err());
+    }
+}
@@ -0,0 +1,91 @@
+//! Compile the C program of `tests/ffi/test.c` against `include/zearch.h` and the crate
+//! built as a static library, then run it on a small index.
+
+#![cfg(all(feature = "ffi", unix))]
+
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use zearch::Index;
+
+/// Build the crate as a static library and returns its path with the native libraries it must be linked with.
+fn static_library(manifest_dir: &Path, target_dir: &Path) -> (PathBuf, Vec<String>) {
+    // in its own target directory so it doesn't wait on the lock held by `cargo test`
+    let output = Command::new(std::env::var("CARGO").unwrap_or_else(|_| "cargo".into()))
+        .current_dir(manifest_dir)
+        .args([
+            "rustc",
+            "--lib",
+            "--features",
+            "ffi",
+            "--crate-type",
+            "staticlib",
+        ])
+        .arg("--target-dir")
+        .arg(target_dir)
+        .args(["--", "--print", "native-static-libs"])
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "{stderr}");
+    // the note is replayed by cargo when the library is fresh
+    let native_libs = stderr
+        .lines()
+        .find_map(|line| line.split_once("native-static-libs: "))
+        .map(|(_, libs)| libs.split_whitespace().map(String::from).collect())
+        .unwrap_or_default();
+    (target_dir.join("debug").join("libzearch.a"), native_libs)
+}
+
+/// The target we're compiled for is the host since the test is running.
+fn host() -> String {
+    let output = Command::new(std::env::var("RUSTC").unwrap_or_else(|_| "rustc".into()))
+        .arg("-vV")
+        .output()
+        .unwrap();
+    let version = String::from_utf8(output.stdout).unwrap();
+    let host = version.lines().find_map(|line| line.strip_prefix("host: "));
+    host.unwrap().to_string()
+}
+
+#[test]
+fn test_c_program() {
+    let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let target_dir = manifest_dir.join("target").join("ffi");
+    let (library, native_libs) = static_library(manifest_dir, &target_dir);
+
+    let program = target_dir.join("test_c_program");
+    let host = host();
+    let compiler = cc::Build::new()
+        .cargo_metadata(false)
+        .opt_level(0)
+        .target(&host)
+        .host(&host)
+        .get_compiler();
+    let status = compiler
+        .to_command()
+        .arg("-I")
+        .arg(manifest_dir.join("include"))
+        .arg(manifest_dir.join("tests/ffi/test.c"))
+        .arg(&library)
+        .args(&native_libs)
+        .arg("-o")
+        .arg(&program)
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let index = target_dir.join("test_c_program.zearch");
+    let mut bytes = Vec::new();
+    Index::construct(&["kefir le chien", "tamo le chat", "kefir"], &mut bytes).unwrap();
+    std::fs::write(&index, bytes).unwrap();
+
+    let output = Command::new(&program).arg(&index).output().unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
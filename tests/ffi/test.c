@@ -0,0 +1,62 @@
+// Load the index given as argument and search it through the C interface.
+// It's compiled and run by tests/ffi.rs, the index contains
+// "kefir le chien", "tamo le chat" and "kefir".
+
+#include <stdio.h>
+#include <string.h>
+
+#include "zearch.h"
+
+#define CHECK(condition)                                                       \
+  if (!(condition)) {                                                          \
+    fprintf(stderr, "%s:%d: %s\n", __FILE__, __LINE__, #condition);            \
+    return 1;                                                                  \
+  }
+
+int main(int argc, char **argv) {
+  CHECK(argc == 2);
+  FILE *file = fopen(argv[1], "rb");
+  CHECK(file != NULL);
+  uint8_t bytes[4096];
+  size_t len = fread(bytes, 1, sizeof(bytes), file);
+  CHECK(feof(file));
+  fclose(file);
+
+  ZearchIndex *index = NULL;
+  CHECK(zearch_index_from_bytes(bytes, len, &index) == ZEARCH_ERROR_OK);
+  CHECK(index != NULL);
+  // the bytes are copied by the index
+  memset(bytes, 0, sizeof(bytes));
+
+  uint32_t ids[10];
+  size_t count = 0;
+  CHECK(zearch_search(index, "kefir", 10, ids, &count) == ZEARCH_ERROR_OK);
+  CHECK(count == 2);
+  CHECK(ids[0] == 0 && ids[1] == 2);
+  CHECK(zearch_search(index, "le", 1, ids, &count) == ZEARCH_ERROR_OK);
+  CHECK(count == 1);
+
+  const char *document = NULL;
+  size_t document_len = 0;
+  CHECK(zearch_get_document(index, 0, &document, &document_len) ==
+        ZEARCH_ERROR_OK);
+  CHECK(document_len == strlen("kefir le chien"));
+  CHECK(memcmp(document, "kefir le chien", document_len) == 0);
+  CHECK(zearch_get_document(index, 3, &document, &document_len) ==
+        ZEARCH_ERROR_NOT_FOUND);
+
+  CHECK(zearch_search(index, "\xff", 10, ids, &count) ==
+        ZEARCH_ERROR_INVALID_UTF8);
+  CHECK(zearch_search(NULL, "kefir", 10, ids, &count) ==
+        ZEARCH_ERROR_NULL_POINTER);
+
+  ZearchIndex *other = NULL;
+  const uint8_t garbage[] = "not an index";
+  CHECK(zearch_index_from_bytes(garbage, sizeof(garbage), &other) ==
+        ZEARCH_ERROR_NOT_AN_INDEX);
+  CHECK(other == NULL);
+
+  zearch_index_free(index);
+  zearch_index_free(NULL);
+  return 0;
+}